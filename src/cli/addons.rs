@@ -0,0 +1,106 @@
+use dialoguer::Confirm;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::time::Duration;
+
+use crate::cli::commands::AddonAction;
+use crate::cli::display;
+use crate::ipc::protocol::{AddonAttachRequest, AddonCreateRequest, AddonResponse};
+use crate::ipc::IpcClient;
+
+pub async fn handle_addon_action(action: AddonAction) -> anyhow::Result<()> {
+    let client = IpcClient::new();
+
+    match action {
+        AddonAction::Create {
+            kind,
+            version,
+            name,
+            project,
+            env_var,
+        } => {
+            let req = AddonCreateRequest {
+                kind,
+                version,
+                name,
+                project,
+                env_var,
+            };
+
+            // Pulling the image and initialising the database can take a while
+            let spinner = ProgressBar::new_spinner();
+            spinner.set_style(
+                ProgressStyle::default_spinner()
+                    .template("{spinner:.cyan} {msg}")
+                    .unwrap(),
+            );
+            spinner.enable_steady_tick(Duration::from_millis(100));
+            spinner.set_message(format!("Criando {}...", kind));
+
+            let result = client.create_addon(&req).await;
+            spinner.finish_and_clear();
+            match result {
+                Ok(resp) => print_response(&resp),
+                Err(e) => display::print_error(&format!("{}", e)),
+            }
+        }
+        AddonAction::List => match client.list_addons().await {
+            Ok(addons) => display::print_addons(&addons),
+            Err(e) => display::print_error(&format!("{}", e)),
+        },
+        AddonAction::Attach {
+            name,
+            project,
+            env_var,
+        } => {
+            let req = AddonAttachRequest { project, env_var };
+            match client.attach_addon(&name, &req).await {
+                Ok(resp) => print_response(&resp),
+                Err(e) => display::print_error(&format!("{}", e)),
+            }
+        }
+        AddonAction::Detach { name } => match client.detach_addon(&name).await {
+            Ok(resp) => print_response(&resp),
+            Err(e) => display::print_error(&format!("{}", e)),
+        },
+        AddonAction::Destroy { name, keep_data } => {
+            let prompt = if keep_data {
+                format!("Tem certeza que deseja remover o add-on '{}'?", name)
+            } else {
+                format!(
+                    "Tem certeza que deseja remover o add-on '{}' e todos os seus dados? Esta acao e irreversivel.",
+                    name
+                )
+            };
+            let confirm = Confirm::with_theme(&dialoguer::theme::ColorfulTheme::default())
+                .with_prompt(prompt)
+                .default(false)
+                .interact()?;
+            if !confirm {
+                println!("  Cancelado.");
+                return Ok(());
+            }
+
+            match client.destroy_addon(&name, keep_data).await {
+                Ok(resp) => display::print_success(&resp.message),
+                Err(e) => display::print_error(&format!("{}", e)),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_response(resp: &AddonResponse) {
+    display::print_success(&resp.message);
+    match (&resp.project, resp.build_id) {
+        (Some(project), Some(build_id)) => println!(
+            "  Atualizando o container de '{}' (build #{}): dockyard build-log {} {} -f",
+            project, build_id, project, build_id
+        ),
+        (Some(project), None) => println!(
+            "  As variaveis de '{}' sao aplicadas no proximo deploy",
+            project
+        ),
+        _ => {}
+    }
+}
@@ -0,0 +1,85 @@
+use dialoguer::Confirm;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::time::Duration;
+
+use crate::cli::commands::BackupAction;
+use crate::cli::display;
+use crate::ipc::protocol::BackupScheduleRequest;
+use crate::ipc::IpcClient;
+
+pub async fn handle_backup_action(action: BackupAction) -> anyhow::Result<()> {
+    let client = IpcClient::new();
+
+    match action {
+        BackupAction::List { slug } => match client.list_backups(&slug).await {
+            Ok(list) => display::print_backups(&list),
+            Err(e) => display::print_error(&format!("{}", e)),
+        },
+        BackupAction::Run { slug } => {
+            let spinner = spinner(format!("Fazendo backup de '{}'...", slug));
+            let result = client.run_backup(&slug).await;
+            spinner.finish_and_clear();
+            match result {
+                Ok(backup) => display::print_success(&format!(
+                    "Backup #{} de '{}' concluido ({} arquivo(s), {})",
+                    backup.id,
+                    slug,
+                    backup.artifacts.len(),
+                    backup.location
+                )),
+                Err(e) => display::print_error(&format!("{}", e)),
+            }
+        }
+        BackupAction::Restore { slug, id } => {
+            let confirm = Confirm::with_theme(&dialoguer::theme::ColorfulTheme::default())
+                .with_prompt(format!(
+                    "Restaurar o backup #{} substitui os volumes e bancos de '{}', que sera parado durante a restauracao. Continuar?",
+                    id, slug
+                ))
+                .default(false)
+                .interact()?;
+            if !confirm {
+                println!("  Cancelado.");
+                return Ok(());
+            }
+
+            let spinner = spinner(format!("Restaurando backup #{}...", id));
+            let result = client.restore_backup(&slug, id).await;
+            spinner.finish_and_clear();
+            match result {
+                Ok(resp) => display::print_success(&resp.message),
+                Err(e) => display::print_error(&format!("{}", e)),
+            }
+        }
+        BackupAction::Schedule {
+            slug,
+            cron,
+            keep,
+            clear,
+        } => {
+            let req = BackupScheduleRequest {
+                schedule: cron,
+                clear,
+                keep,
+            };
+            match client.set_backup_schedule(&slug, &req).await {
+                Ok(resp) => display::print_success(&resp.message),
+                Err(e) => display::print_error(&format!("{}", e)),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn spinner(message: String) -> ProgressBar {
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.cyan} {msg}")
+            .unwrap(),
+    );
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner.set_message(message);
+    spinner
+}
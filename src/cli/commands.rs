@@ -1,4 +1,8 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::config::addon::AddonKind;
+use crate::config::project::{EnvUpdateMode, VolumeMount};
 
 #[derive(Parser)]
 #[command(
@@ -34,9 +38,40 @@ pub enum Commands {
         #[arg(long)]
         domain: Option<String>,
 
-        /// Container port (the port your app listens on)
+        /// Container port (the port your app listens on); detected from the build if omitted
         #[arg(long)]
         port: Option<u16>,
+
+        /// Environment variable for the container (KEY=VALUE, repeatable)
+        #[arg(short, long = "env", value_name = "KEY=VALUE")]
+        env: Vec<String>,
+
+        /// Read environment variables from a .env file
+        #[arg(long)]
+        env_file: Option<PathBuf>,
+
+        /// Internal service: no host port, reachable only from projects that link to it
+        #[arg(long, conflicts_with_all = ["public", "domain"])]
+        internal: bool,
+
+        /// Project this one may call at http://<slug>:<port> (repeatable)
+        #[arg(long = "link", value_name = "SLUG")]
+        links: Vec<String>,
+
+        /// Persistent volume (name:/path or /host/path:/path, optionally :ro; repeatable)
+        #[arg(short = 'v', long = "volume", value_name = "SOURCE:PATH")]
+        volumes: Vec<VolumeMount>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+
+        /// Number of containers to run behind the load balancer
+        #[arg(long, value_name = "N")]
+        replicas: Option<u32>,
+
+        /// Return immediately instead of following the build log
+        #[arg(long)]
+        detach: bool,
     },
 
     /// List all projects
@@ -52,6 +87,186 @@ pub enum Commands {
     Rebuild {
         /// Project slug
         slug: String,
+
+        /// Build this commit instead of the branch's latest
+        #[arg(long, value_name = "SHA", conflicts_with = "git_ref")]
+        commit: Option<String>,
+
+        /// Build this tag or branch instead of the tracked branch's latest commit
+        #[arg(long = "ref", value_name = "REF")]
+        git_ref: Option<String>,
+
+        /// Return immediately instead of following the build log
+        #[arg(long)]
+        detach: bool,
+    },
+
+    /// List the builds of a project
+    Builds {
+        /// Project slug
+        slug: String,
+    },
+
+    /// Show the output of a build
+    BuildLog {
+        /// Project slug
+        slug: String,
+
+        /// Build number (defaults to the latest build)
+        #[arg(default_value = "latest")]
+        build: String,
+
+        /// Follow the log until the build finishes
+        #[arg(short, long)]
+        follow: bool,
+    },
+
+    /// List the release history of a project
+    Releases {
+        /// Project slug
+        slug: String,
+    },
+
+    /// Show or change build args, build secrets, the target stage and the release command
+    BuildConfig {
+        /// Project slug
+        slug: String,
+
+        /// Set a build arg (KEY=VALUE, repeatable)
+        #[arg(long = "arg", value_name = "KEY=VALUE")]
+        args: Vec<String>,
+
+        /// Remove a build arg
+        #[arg(long = "unset-arg", value_name = "KEY")]
+        unset_args: Vec<String>,
+
+        /// Set a build secret (KEY=VALUE, repeatable); use `RUN --mount=type=secret,id=KEY`
+        #[arg(long = "secret", value_name = "KEY=VALUE")]
+        secrets: Vec<String>,
+
+        /// Remove a build secret
+        #[arg(long = "unset-secret", value_name = "KEY")]
+        unset_secrets: Vec<String>,
+
+        /// Build this stage of a multi-stage Dockerfile
+        #[arg(long, conflicts_with = "clear_target")]
+        target: Option<String>,
+
+        /// Build the final stage again
+        #[arg(long)]
+        clear_target: bool,
+
+        /// Run this shell command from each new image before it serves traffic; the deploy
+        /// is aborted if it fails
+        #[arg(long, value_name = "COMMAND", conflicts_with = "clear_release_command")]
+        release_command: Option<String>,
+
+        /// Deploy without a release command
+        #[arg(long)]
+        clear_release_command: bool,
+    },
+
+    /// Change a project's settings
+    Project {
+        #[command(subcommand)]
+        action: ProjectAction,
+    },
+
+    /// Show the webhook URL and secret to enter in GitHub, or replace the secret
+    WebhookSecret {
+        /// Project slug
+        slug: String,
+
+        /// Print the secret
+        #[arg(long, required_unless_present = "rotate")]
+        show: bool,
+
+        /// Generate a new secret and print it; GitHub must be updated with it
+        #[arg(long)]
+        rotate: bool,
+    },
+
+    /// Show or change which projects a project may call
+    Links {
+        /// Project slug
+        slug: String,
+
+        /// Allow calls to this project
+        #[arg(long, value_name = "SLUG")]
+        add: Vec<String>,
+
+        /// Remove a link
+        #[arg(long, value_name = "SLUG")]
+        remove: Vec<String>,
+    },
+
+    /// List persistent volumes and their size, or change a project's mounts
+    Volumes {
+        /// Project slug; lists every project when omitted
+        slug: Option<String>,
+
+        /// Mount a volume (name:/path or /host/path:/path, optionally :ro)
+        #[arg(long, value_name = "SOURCE:PATH", requires = "slug")]
+        add: Vec<VolumeMount>,
+
+        /// Unmount the volume at this container path (its data is kept)
+        #[arg(long, value_name = "PATH", requires = "slug")]
+        remove: Vec<String>,
+    },
+
+    /// Change how many containers serve a project
+    Scale {
+        /// Project slug
+        slug: String,
+
+        /// Number of replicas
+        replicas: u32,
+
+        /// Return immediately instead of following the build log
+        #[arg(long)]
+        detach: bool,
+    },
+
+    /// List, declare or remove process types (web, workers)
+    Process {
+        #[command(subcommand)]
+        action: ProcessAction,
+    },
+
+    /// Show or change CPU, memory and process limits (per replica)
+    Limits {
+        /// Project slug
+        slug: String,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+
+    /// Show the Dockerfile used to build a project, or override it
+    Dockerfile {
+        /// Project slug
+        slug: String,
+
+        /// Use this file instead of the repository's or the generated Dockerfile
+        #[arg(long, conflicts_with = "reset")]
+        set: Option<PathBuf>,
+
+        /// Remove the override
+        #[arg(long)]
+        reset: bool,
+    },
+
+    /// Redeploy an earlier release without rebuilding
+    Rollback {
+        /// Project slug
+        slug: String,
+
+        /// Release number (defaults to the previous release)
+        release: Option<u32>,
+
+        /// Return immediately instead of following the build log
+        #[arg(long)]
+        detach: bool,
     },
 
     /// View project logs
@@ -66,6 +281,78 @@ pub enum Commands {
         /// Number of lines to show
         #[arg(short = 'n', long, default_value = "100")]
         tail: u32,
+
+        /// Only show lines since a time (RFC 3339, YYYY-MM-DD, or relative like 10m, 2h)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only show lines before a time (same formats as --since)
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Prefix each line with its timestamp
+        #[arg(short = 't', long)]
+        timestamps: bool,
+
+        /// Process type to show (default: web)
+        #[arg(short, long)]
+        process: Option<String>,
+
+        /// Replica of the process to show, from 1
+        #[arg(short, long)]
+        replica: Option<u32>,
+    },
+
+    /// Run a command in a project's live container (a shell when none is given)
+    Exec {
+        /// Project slug
+        slug: String,
+
+        /// Do not allocate a TTY, even when attached to a terminal
+        #[arg(short = 'T', long)]
+        no_tty: bool,
+
+        /// Command and its arguments, after `--`
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+
+    /// Run a command in a new container from a project's current image, removed when it exits
+    Run {
+        /// Project slug
+        slug: String,
+
+        /// Do not allocate a TTY, even when attached to a terminal
+        #[arg(short = 'T', long)]
+        no_tty: bool,
+
+        /// Command and its arguments, after `--`
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+
+    /// Manage a project's environment variables
+    Env {
+        #[command(subcommand)]
+        action: EnvAction,
+    },
+
+    /// Manage database add-ons (Postgres, Redis, MySQL)
+    Addon {
+        #[command(subcommand)]
+        action: AddonAction,
+    },
+
+    /// Back up and restore a project's volumes and databases
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+
+    /// Commands run on a schedule from the project's current image
+    Cron {
+        #[command(subcommand)]
+        action: CronAction,
     },
 
     /// Stop (disable) a project
@@ -84,6 +371,10 @@ pub enum Commands {
     Delete {
         /// Project slug
         slug: String,
+
+        /// Also delete the project's named volumes and their data
+        #[arg(long)]
+        purge_volumes: bool,
     },
 
     /// Configuration management
@@ -106,4 +397,367 @@ pub enum ConfigAction {
         /// Value to set
         value: String,
     },
+
+    /// Re-encrypt all project secrets with a new master key
+    RotateKey,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum EnvAction {
+    /// List the variables of a project
+    List {
+        /// Project slug
+        slug: String,
+    },
+
+    /// Set one or more variables
+    Set {
+        /// Project slug
+        slug: String,
+
+        /// Variables to set (KEY=VALUE)
+        #[arg(required = true, value_name = "KEY=VALUE")]
+        vars: Vec<String>,
+
+        /// Store the values encrypted and hide them from list and export
+        #[arg(long)]
+        secret: bool,
+
+        #[command(flatten)]
+        apply: EnvApplyArgs,
+    },
+
+    /// Remove one or more variables
+    Unset {
+        /// Project slug
+        slug: String,
+
+        /// Variable names
+        #[arg(required = true)]
+        keys: Vec<String>,
+
+        #[command(flatten)]
+        apply: EnvApplyArgs,
+    },
+
+    /// Set variables from a .env file
+    Import {
+        /// Project slug
+        slug: String,
+
+        /// Path to the .env file
+        file: PathBuf,
+
+        /// Remove variables that are not in the file
+        #[arg(long)]
+        replace: bool,
+
+        /// Store the values encrypted and hide them from list and export
+        #[arg(long)]
+        secret: bool,
+
+        #[command(flatten)]
+        apply: EnvApplyArgs,
+    },
+
+    /// Print the variables as a .env file (secret values are left out)
+    Export {
+        /// Project slug
+        slug: String,
+
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Show or set how env changes reach the running container
+    Mode {
+        /// Project slug
+        slug: String,
+
+        /// restart (recreate in place) or blue-green (zero downtime)
+        mode: Option<EnvUpdateMode>,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum ProjectAction {
+    /// Set branch=NAME (tracked branch), submodules=true|false or lfs=true|false; applies
+    /// from the next rebuild
+    Set {
+        /// Project slug
+        slug: String,
+
+        /// Settings to change
+        #[arg(value_name = "KEY=VALUE", required = true)]
+        settings: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum ProcessAction {
+    /// Show each process type with its containers and usage
+    List {
+        /// Project slug
+        slug: String,
+    },
+
+    /// Declare a process type or change its command, replicas or port
+    Set {
+        /// Project slug
+        slug: String,
+
+        /// Process type (web or a worker name)
+        name: String,
+
+        /// Command to run (defaults to the Procfile entry)
+        #[arg(long, conflicts_with = "clear_command")]
+        command: Option<String>,
+
+        /// Use the Procfile entry again
+        #[arg(long)]
+        clear_command: bool,
+
+        /// Number of containers
+        #[arg(long)]
+        replicas: Option<u32>,
+
+        /// Port the worker listens on, checked over TCP (never published on the host)
+        #[arg(long, conflicts_with = "no_port")]
+        port: Option<u16>,
+
+        /// The worker listens on no port
+        #[arg(long)]
+        no_port: bool,
+
+        /// Return immediately instead of following the build log
+        #[arg(long)]
+        detach: bool,
+    },
+
+    /// Stop a worker process type and remove it
+    Remove {
+        /// Project slug
+        slug: String,
+
+        /// Process type
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum AddonAction {
+    /// Start a database with a persistent volume and generated credentials
+    Create {
+        /// postgres, redis or mysql
+        kind: AddonKind,
+
+        /// Image tag (defaults to the current major version)
+        #[arg(long)]
+        version: Option<String>,
+
+        /// Add-on name, also its host name (defaults to <project>-<kind>)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Project that gets the connection URL
+        #[arg(long)]
+        project: Option<String>,
+
+        /// Variable the URL is injected as (DATABASE_URL or REDIS_URL by default)
+        #[arg(long)]
+        env_var: Option<String>,
+    },
+
+    /// List add-ons
+    List,
+
+    /// Inject an add-on's connection URL into a project
+    Attach {
+        /// Add-on name
+        name: String,
+
+        /// Project slug
+        #[arg(long)]
+        project: String,
+
+        /// Variable the URL is injected as
+        #[arg(long)]
+        env_var: Option<String>,
+    },
+
+    /// Stop injecting an add-on into its project (the database keeps running)
+    Detach {
+        /// Add-on name
+        name: String,
+    },
+
+    /// Remove a detached add-on and its data
+    Destroy {
+        /// Add-on name
+        name: String,
+
+        /// Keep the data volume
+        #[arg(long)]
+        keep_data: bool,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum BackupAction {
+    /// List a project's backups and schedule
+    List {
+        /// Project slug
+        slug: String,
+    },
+
+    /// Back up a project now
+    Run {
+        /// Project slug
+        slug: String,
+    },
+
+    /// Replace a project's volumes and databases with a backup
+    Restore {
+        /// Project slug
+        slug: String,
+
+        /// Backup number (see `dockyard backup list`)
+        id: u32,
+    },
+
+    /// Set when backups run and how many are kept
+    Schedule {
+        /// Project slug
+        slug: String,
+
+        /// Cron expression in UTC, e.g. "0 3 * * *"
+        #[arg(conflicts_with = "clear")]
+        cron: Option<String>,
+
+        /// Number of backups to keep
+        #[arg(long)]
+        keep: Option<usize>,
+
+        /// Turn scheduled backups off
+        #[arg(long)]
+        clear: bool,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum CronAction {
+    /// List a project's cron jobs with their next and last run
+    List {
+        /// Project slug
+        slug: String,
+    },
+
+    /// Show past runs, or the output of one run
+    History {
+        /// Project slug
+        slug: String,
+
+        /// Only runs of this job
+        #[arg(long)]
+        job: Option<String>,
+
+        /// Show this run's exit code and output
+        #[arg(long, value_name = "ID", conflicts_with = "job")]
+        run: Option<u32>,
+    },
+
+    /// Run a job now and wait for it to finish
+    RunNow {
+        /// Project slug
+        slug: String,
+
+        /// Job name
+        name: String,
+    },
+
+    /// Declare a job or change its schedule, command or timeout
+    Set {
+        /// Project slug
+        slug: String,
+
+        /// Job name
+        name: String,
+
+        /// Cron expression in UTC, e.g. "0 3 * * *"
+        #[arg(long)]
+        schedule: Option<String>,
+
+        /// Shell command to run
+        #[arg(long)]
+        command: Option<String>,
+
+        /// Stop the job after this many seconds (default 3600)
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+    },
+
+    /// Remove a job (its run history is kept)
+    Remove {
+        /// Project slug
+        slug: String,
+
+        /// Job name
+        name: String,
+    },
+}
+
+/// How an env change is applied to the running container
+#[derive(Args, Clone)]
+pub struct EnvApplyArgs {
+    /// Apply with this mode instead of the project's (restart or blue-green)
+    #[arg(long, conflicts_with = "no_apply")]
+    pub mode: Option<EnvUpdateMode>,
+
+    /// Only save; the running container keeps its environment until the next deploy
+    #[arg(long)]
+    pub no_apply: bool,
+
+    /// Return immediately instead of following the update
+    #[arg(long)]
+    pub detach: bool,
+}
+
+/// Container resource limits; `none` removes a limit
+#[derive(Args, Clone)]
+pub struct LimitArgs {
+    /// Memory limit (e.g. 512m, 2g)
+    #[arg(long)]
+    pub memory: Option<String>,
+
+    /// Memory plus swap (e.g. 1g); -1 for unlimited swap
+    #[arg(long, allow_hyphen_values = true)]
+    pub memory_swap: Option<String>,
+
+    /// Number of CPUs (e.g. 0.5, 2)
+    #[arg(long)]
+    pub cpus: Option<String>,
+
+    /// Relative CPU weight (default 1024)
+    #[arg(long)]
+    pub cpu_shares: Option<String>,
+
+    /// Maximum number of processes
+    #[arg(long)]
+    pub pids_limit: Option<String>,
+
+    /// Ulimit (name=soft[:hard], repeatable); replaces the current list
+    #[arg(long = "ulimit", value_name = "NAME=SOFT[:HARD]")]
+    pub ulimits: Vec<String>,
+}
+
+impl LimitArgs {
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_none()
+            && self.memory_swap.is_none()
+            && self.cpus.is_none()
+            && self.cpu_shares.is_none()
+            && self.pids_limit.is_none()
+            && self.ulimits.is_empty()
+    }
 }
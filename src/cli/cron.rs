@@ -0,0 +1,102 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::time::Duration;
+
+use crate::cli::commands::CronAction;
+use crate::cli::display;
+use crate::ipc::protocol::CronJobRequest;
+use crate::ipc::IpcClient;
+
+/// Returns whether the action succeeded, including the job's exit code for `run-now`
+pub async fn handle_cron_action(action: CronAction) -> anyhow::Result<bool> {
+    let client = IpcClient::new();
+
+    match action {
+        CronAction::List { slug } => match client.list_cron_jobs(&slug).await {
+            Ok(list) => display::print_cron_jobs(&list),
+            Err(e) => {
+                display::print_error(&format!("{}", e));
+                return Ok(false);
+            }
+        },
+        CronAction::History {
+            slug,
+            run: Some(id),
+            ..
+        } => match client.get_cron_run(&slug, id).await {
+            Ok(run) => display::print_cron_run(&run),
+            Err(e) => {
+                display::print_error(&format!("{}", e));
+                return Ok(false);
+            }
+        },
+        CronAction::History {
+            slug,
+            job,
+            run: None,
+        } => match client.cron_history(&slug, job.as_deref()).await {
+            Ok(history) => display::print_cron_runs(&history.runs),
+            Err(e) => {
+                display::print_error(&format!("{}", e));
+                return Ok(false);
+            }
+        },
+        CronAction::RunNow { slug, name } => {
+            let spinner = spinner(format!("Executando '{}' de '{}'...", name, slug));
+            let result = client.run_cron_job(&slug, &name).await;
+            spinner.finish_and_clear();
+            let run = match result {
+                Ok(run) => run,
+                Err(e) => {
+                    display::print_error(&format!("{}", e));
+                    return Ok(false);
+                }
+            };
+            match client.get_cron_run(&slug, run.id).await {
+                Ok(resp) => display::print_cron_run(&resp),
+                Err(e) => display::print_error(&format!("{}", e)),
+            }
+            return Ok(run.succeeded());
+        }
+        CronAction::Set {
+            slug,
+            name,
+            schedule,
+            command,
+            timeout,
+        } => {
+            let req = CronJobRequest {
+                schedule,
+                command,
+                timeout_secs: timeout,
+            };
+            match client.set_cron_job(&slug, &name, &req).await {
+                Ok(resp) => display::print_success(&resp.message),
+                Err(e) => {
+                    display::print_error(&format!("{}", e));
+                    return Ok(false);
+                }
+            }
+        }
+        CronAction::Remove { slug, name } => match client.remove_cron_job(&slug, &name).await {
+            Ok(resp) => display::print_success(&resp.message),
+            Err(e) => {
+                display::print_error(&format!("{}", e));
+                return Ok(false);
+            }
+        },
+    }
+
+    Ok(true)
+}
+
+fn spinner(message: String) -> ProgressBar {
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.cyan} {msg}")
+            .unwrap(),
+    );
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner.set_message(message);
+    spinner
+}
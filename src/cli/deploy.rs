@@ -4,7 +4,7 @@ use indicatif::{ProgressBar, ProgressStyle};
 use std::collections::HashMap;
 use std::time::Duration;
 
-use crate::cli::display;
+use crate::cli::{display, projects};
 use crate::config::project::NetworkMode;
 use crate::ipc::protocol::DeployRequest;
 use crate::ipc::IpcClient;
@@ -23,7 +23,11 @@ pub async fn deploy_interactive() -> anyhow::Result<()> {
         .default("main".to_string())
         .interact_text()?;
 
-    let network_options = vec!["Rede local apenas", "Publico (via Cloudflare Tunnel)"];
+    let network_options = vec![
+        "Rede local apenas",
+        "Publico (via Cloudflare Tunnel)",
+        "Interno (sem porta no host, so para projetos vinculados)",
+    ];
     let network_selection =
         Select::with_theme(&dialoguer::theme::ColorfulTheme::default())
             .with_prompt("Modo de rede")
@@ -34,13 +38,18 @@ pub async fn deploy_interactive() -> anyhow::Result<()> {
     let network_mode = match network_selection {
         0 => NetworkMode::LocalOnly,
         1 => NetworkMode::Public,
+        2 => NetworkMode::Internal,
         _ => unreachable!(),
     };
 
-    let hostname: String = Input::with_theme(&dialoguer::theme::ColorfulTheme::default())
-        .with_prompt("Hostname personalizado (deixe vazio para auto)")
-        .allow_empty(true)
-        .interact_text()?;
+    let hostname: String = if network_mode == NetworkMode::Internal {
+        String::new()
+    } else {
+        Input::with_theme(&dialoguer::theme::ColorfulTheme::default())
+            .with_prompt("Hostname personalizado (deixe vazio para auto)")
+            .allow_empty(true)
+            .interact_text()?
+    };
 
     let hostname = if hostname.is_empty() {
         None
@@ -48,10 +57,18 @@ pub async fn deploy_interactive() -> anyhow::Result<()> {
         Some(hostname)
     };
 
-    let container_port: u16 = Input::with_theme(&dialoguer::theme::ColorfulTheme::default())
-        .with_prompt("Porta do container (porta que seu app escuta)")
-        .default(3000u16)
+    let container_port: String = Input::with_theme(&dialoguer::theme::ColorfulTheme::default())
+        .with_prompt("Porta do container (vazio para detectar no build)")
+        .allow_empty(true)
+        .validate_with(|input: &String| -> Result<(), &str> {
+            if input.is_empty() || input.parse::<u16>().is_ok() {
+                Ok(())
+            } else {
+                Err("Porta invalida")
+            }
+        })
         .interact_text()?;
+    let container_port = container_port.parse::<u16>().ok();
 
     println!();
 
@@ -73,6 +90,10 @@ pub async fn deploy_interactive() -> anyhow::Result<()> {
         hostname,
         container_port,
         env_vars: HashMap::new(),
+        resources: Default::default(),
+        volumes: Vec::new(),
+        links: Vec::new(),
+        replicas: None,
     };
 
     spinner.set_message("Enviando deploy...");
@@ -90,7 +111,13 @@ pub async fn deploy_interactive() -> anyhow::Result<()> {
                 style("URL:").bold(),
                 resp.url.unwrap_or_else(|| "--".to_string())
             );
-            println!("  {} {}", style("Porta host:").bold(), resp.host_port);
+            println!(
+                "  {} {}",
+                style("Porta host:").bold(),
+                resp.host_port
+                    .map(|p| p.to_string())
+                    .unwrap_or_else(|| "nenhuma (interno)".to_string())
+            );
             println!();
             println!(
                 "  {} {}",
@@ -99,8 +126,13 @@ pub async fn deploy_interactive() -> anyhow::Result<()> {
             );
             println!(
                 "  {}",
-                style("(Configure no GitHub para auto-deploy)").dim()
+                style(format!(
+                    "(Configure no GitHub para auto-deploy; segredo: dockyard webhook-secret {} --show)",
+                    resp.slug
+                ))
+                .dim()
             );
+            projects::follow_build(&client, &resp.slug, resp.build_id).await?;
             println!();
         }
         Err(e) => {
@@ -112,28 +144,9 @@ pub async fn deploy_interactive() -> anyhow::Result<()> {
     Ok(())
 }
 
-pub async fn deploy_direct(
-    repo_url: String,
-    branch: String,
-    public: bool,
-    domain: Option<String>,
-    port: Option<u16>,
-) -> anyhow::Result<()> {
+pub async fn deploy_direct(req: DeployRequest, detach: bool) -> anyhow::Result<bool> {
     let client = IpcClient::new();
 
-    let req = DeployRequest {
-        repo_url,
-        branch,
-        network_mode: if public {
-            NetworkMode::Public
-        } else {
-            NetworkMode::LocalOnly
-        },
-        hostname: domain,
-        container_port: port.unwrap_or(3000),
-        env_vars: HashMap::new(),
-    };
-
     let spinner = ProgressBar::new_spinner();
     spinner.set_style(
         ProgressStyle::default_spinner()
@@ -146,20 +159,30 @@ pub async fn deploy_direct(
     match client.deploy(&req).await {
         Ok(resp) => {
             spinner.finish_and_clear();
-            display::print_success(&format!(
-                "Deployed '{}' on port {}",
-                resp.name, resp.host_port
-            ));
+            display::print_success(&match resp.host_port {
+                Some(port) => format!(
+                    "Deploying '{}' on port {} (build #{})",
+                    resp.name, port, resp.build_id
+                ),
+                None => format!(
+                    "Deploying '{}' as an internal service (build #{})",
+                    resp.name, resp.build_id
+                ),
+            });
             if let Some(url) = resp.url {
                 println!("  URL: {}", url);
             }
             println!("  Webhook: {}", resp.webhook_url);
+            println!("  Webhook secret: dockyard webhook-secret {} --show", resp.slug);
+            if detach {
+                return Ok(true);
+            }
+            projects::follow_build(&client, &resp.slug, resp.build_id).await
         }
         Err(e) => {
             spinner.finish_and_clear();
             display::print_error(&format!("Deploy failed: {}", e));
+            Ok(false)
         }
     }
-
-    Ok(())
 }
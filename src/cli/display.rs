@@ -1,6 +1,16 @@
 use console::style;
 
-use crate::models::project::{ProjectState, ProjectStatus};
+use crate::config::backup::Backup;
+use crate::config::cron::CronRun;
+use crate::config::project::{GitConfig, ResourceLimits};
+use crate::config::release::Release;
+use crate::ipc::protocol::{
+    AddonInfo, BackupListResponse, BuildConfigResponse, CronListResponse, CronRunResponse,
+    LinksResponse, VolumesResponse, WebhookSecretResponse,
+};
+use crate::models::build::{BuildStatus, BuildSummary};
+use crate::models::log::{LogEntry, LogStream};
+use crate::models::project::{ProcessStatus, ProjectState, ProjectStatus};
 
 pub fn print_banner() {
     println!(
@@ -36,7 +46,10 @@ pub fn print_project_table(projects: &[ProjectStatus]) {
     println!("  {}", "-".repeat(89));
 
     for project in projects {
-        let status_display = format_state(&project.state);
+        let status_display = match project.phase {
+            Some(phase) => format!("{} ({})", format_state(&project.state), phase),
+            None => format_state(&project.state),
+        };
         let url = project.url.as_deref().unwrap_or("--");
         let uptime = format_uptime(project.uptime_secs);
         let memory = project
@@ -82,7 +95,7 @@ pub fn format_uptime(secs: Option<u64>) -> String {
     }
 }
 
-pub fn print_project_detail(status: &ProjectStatus, repo_url: &str, branch: &str) {
+pub fn print_project_detail(status: &ProjectStatus, repo_url: &str, branch: &str, git: &GitConfig) {
     println!();
     println!("  {} {}", style("Projeto:").bold(), status.name);
     println!("  {} {}", style("Slug:").bold(), status.slug);
@@ -91,41 +104,119 @@ pub fn print_project_detail(status: &ProjectStatus, repo_url: &str, branch: &str
         style("Status:").bold(),
         format_state(&status.state)
     );
+    if let Some(phase) = status.phase {
+        println!("  {} {}", style("Etapa:").bold(), phase);
+    }
     println!("  {} {}", style("Repositorio:").bold(), repo_url);
-    println!("  {} {}", style("Branch:").bold(), branch);
+    let mut extras = Vec::new();
+    if git.submodules {
+        extras.push("submodulos");
+    }
+    if git.lfs {
+        extras.push("LFS");
+    }
+    if extras.is_empty() {
+        println!("  {} {}", style("Branch:").bold(), branch);
+    } else {
+        println!(
+            "  {} {} (com {})",
+            style("Branch:").bold(),
+            branch,
+            extras.join(", ")
+        );
+    }
+    println!(
+        "  {} {}",
+        style("Commit:").bold(),
+        format_sha(status.commit_sha.as_deref())
+    );
     println!("  {} {}", style("Rede:").bold(), status.network_mode);
     println!(
         "  {} {}",
         style("URL:").bold(),
         status.url.as_deref().unwrap_or("--")
     );
-    println!(
-        "  {} {} -> {} (host)",
-        style("Porta:").bold(),
-        status.container_port,
-        status.host_port,
-    );
+    match status.host_port {
+        Some(host_port) => println!(
+            "  {} {} -> {} (host)",
+            style("Porta:").bold(),
+            status.container_port,
+            host_port,
+        ),
+        None => println!(
+            "  {} {} (sem porta no host)",
+            style("Porta:").bold(),
+            status.container_port,
+        ),
+    }
     println!(
         "  {} {}",
         style("Uptime:").bold(),
         format_uptime(status.uptime_secs)
     );
+    if status.replicas > 1 {
+        // Usage below is summed over the replicas; limits apply to each one
+        println!(
+            "  {} {} (limites por replica)",
+            style("Replicas:").bold(),
+            status.replicas
+        );
+    }
+    let limits = &status.resources;
     println!(
-        "  {} {}",
+        "  {} {}{}",
         style("Memoria:").bold(),
         status
             .memory_usage_mb
             .map(|m| format!("{:.1} MB", m))
-            .unwrap_or_else(|| "--".to_string())
+            .unwrap_or_else(|| "--".to_string()),
+        format_limit(limits.memory_mb.map(|m| format!("{} MB", m)))
     );
+    if let Some(swap) = limits.memory_swap_mb {
+        println!(
+            "  {} {}",
+            style("Memoria + swap:").bold(),
+            if swap < 0 {
+                "ilimitado".to_string()
+            } else {
+                format!("{} MB", swap)
+            }
+        );
+    }
     println!(
-        "  {} {}",
+        "  {} {}{}",
         style("CPU:").bold(),
         status
             .cpu_percent
             .map(|c| format!("{:.1}%", c))
-            .unwrap_or_else(|| "--".to_string())
+            .unwrap_or_else(|| "--".to_string()),
+        format_limit(limits.cpus.map(|c| format!("{} CPU", c)))
     );
+    if let Some(shares) = limits.cpu_shares {
+        println!("  {} {}", style("CPU shares:").bold(), shares);
+    }
+    if status.pids.is_some() || limits.pids_limit.is_some() {
+        println!(
+            "  {} {}{}",
+            style("Processos:").bold(),
+            status
+                .pids
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "--".to_string()),
+            format_limit(limits.pids_limit.map(|p| p.to_string()))
+        );
+    }
+    if !limits.ulimits.is_empty() {
+        let ulimits: Vec<String> = limits.ulimits.iter().map(|u| u.to_string()).collect();
+        println!("  {} {}", style("Ulimits:").bold(), ulimits.join(", "));
+    }
+    if let Some(container_id) = &status.container_id {
+        println!(
+            "  {} {}",
+            style("Container:").bold(),
+            &container_id[..12.min(container_id.len())]
+        );
+    }
     if let Some(deploy) = &status.last_deploy {
         println!(
             "  {} {}",
@@ -133,9 +224,548 @@ pub fn print_project_detail(status: &ProjectStatus, repo_url: &str, branch: &str
             deploy.format("%Y-%m-%d %H:%M:%S")
         );
     }
+    if let Some(error) = &status.last_error {
+        println!("  {} {}", style("Ultimo erro:").bold().red(), error);
+    }
+    if status.processes.len() > 1 {
+        println!();
+        println!("  {}", style("Tipos de processo:").bold());
+        print_process_rows(&status.processes);
+    }
+    println!();
+}
+
+pub fn print_processes(processes: &[ProcessStatus]) {
+    println!();
+    print_process_rows(processes);
+    println!();
+}
+
+fn print_process_rows(processes: &[ProcessStatus]) {
+    println!(
+        "  {:<16} {:<10} {:<8} {:>10} {:>8}  {}",
+        style("PROCESSO").bold(),
+        style("ATIVOS").bold(),
+        style("PORTA").bold(),
+        style("MEMORIA").bold(),
+        style("CPU").bold(),
+        style("COMANDO").bold(),
+    );
+    println!("  {}", "-".repeat(80));
+    for process in processes {
+        println!(
+            "  {:<16} {:<10} {:<8} {:>10} {:>8}  {}",
+            process.name,
+            format!("{}/{}", process.running, process.replicas),
+            process
+                .port
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "--".to_string()),
+            process
+                .memory_usage_mb
+                .map(|m| format!("{:.1} MB", m))
+                .unwrap_or_else(|| "--".to_string()),
+            process
+                .cpu_percent
+                .map(|c| format!("{:.1}%", c))
+                .unwrap_or_else(|| "--".to_string()),
+            process.command.as_deref().unwrap_or("(padrao da imagem)"),
+        );
+    }
+}
+
+/// " / <limit>" after a usage figure, or nothing when unlimited
+fn format_limit(limit: Option<String>) -> String {
+    limit
+        .map(|l| format!(" / {}", l))
+        .unwrap_or_default()
+}
+
+pub fn print_limits(limits: &ResourceLimits) {
+    let unlimited = || "ilimitado".to_string();
+    println!();
+    println!(
+        "  {} {}",
+        style("Memoria:").bold(),
+        limits
+            .memory_mb
+            .map(|m| format!("{} MB", m))
+            .unwrap_or_else(unlimited)
+    );
+    println!(
+        "  {} {}",
+        style("Memoria + swap:").bold(),
+        match limits.memory_swap_mb {
+            Some(swap) if swap >= 0 => format!("{} MB", swap),
+            Some(_) => unlimited(),
+            None if limits.memory_mb.is_some() => "2x memoria".to_string(),
+            None => unlimited(),
+        }
+    );
+    println!(
+        "  {} {}",
+        style("CPUs:").bold(),
+        limits.cpus.map(|c| c.to_string()).unwrap_or_else(unlimited)
+    );
+    println!(
+        "  {} {}",
+        style("CPU shares:").bold(),
+        limits
+            .cpu_shares
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "1024 (padrao)".to_string())
+    );
+    println!(
+        "  {} {}",
+        style("Processos:").bold(),
+        limits
+            .pids_limit
+            .map(|p| p.to_string())
+            .unwrap_or_else(unlimited)
+    );
+    println!("  {}", style("Ulimits:").bold());
+    if limits.ulimits.is_empty() {
+        println!("    {}", style("(padrao do Docker)").dim());
+    }
+    for ulimit in &limits.ulimits {
+        println!("    {}", ulimit);
+    }
+    println!();
+}
+
+pub fn print_webhook_secret(webhook: &WebhookSecretResponse) {
+    println!();
+    println!("  {} {}", style("Payload URL:").bold(), webhook.webhook_url);
+    println!("  {} application/json", style("Content type:").bold());
+    println!("  {} {}", style("Secret:").bold(), webhook.secret);
+    println!();
+}
+
+pub fn print_links(links: &LinksResponse) {
+    println!();
+    println!("  {}", style("Pode chamar:").bold());
+    if links.links.is_empty() {
+        println!("    {}", style("(nenhum projeto)").dim());
+    }
+    for link in &links.links {
+        println!(
+            "    {:<20} {}{}",
+            link.slug,
+            link.url,
+            if link.internal { " (interno)" } else { "" }
+        );
+    }
+    println!("  {}", style("Chamado por:").bold());
+    if links.linked_by.is_empty() {
+        println!("    {}", style("(nenhum projeto)").dim());
+    }
+    for slug in &links.linked_by {
+        println!("    {}", slug);
+    }
+    println!();
+}
+
+pub fn print_addons(addons: &[AddonInfo]) {
+    println!();
+    if addons.is_empty() {
+        println!("  {}", style("Nenhum add-on").dim());
+        println!();
+        return;
+    }
+
+    println!(
+        "  {:<20} {:<16} {:<12} {:<26} {:<20} {:<15}",
+        style("NOME").bold(),
+        style("TIPO").bold(),
+        style("STATUS").bold(),
+        style("ENDERECO").bold(),
+        style("PROJETO").bold(),
+        style("VARIAVEL").bold(),
+    );
+    println!("  {}", "-".repeat(114));
+    for addon in addons {
+        println!(
+            "  {:<20} {:<16} {:<12} {:<26} {:<20} {:<15}",
+            addon.name,
+            format!("{} {}", addon.kind, addon.version),
+            format_state(&addon.state),
+            addon.address,
+            addon.project.as_deref().unwrap_or("--"),
+            if addon.project.is_some() {
+                addon.env_var.as_str()
+            } else {
+                "--"
+            },
+        );
+    }
+    println!();
+}
+
+pub fn print_volumes(projects: &[VolumesResponse]) {
+    println!();
+    if projects.iter().all(|p| p.volumes.is_empty()) {
+        println!("  {}", style("Nenhum volume").dim());
+        println!();
+        return;
+    }
+
+    println!(
+        "  {:<20} {:<30} {:<30} {:>10}",
+        style("PROJETO").bold(),
+        style("ORIGEM").bold(),
+        style("CAMINHO").bold(),
+        style("TAMANHO").bold(),
+    );
+    println!("  {}", "-".repeat(93));
+    for project in projects {
+        for info in &project.volumes {
+            let (source, target) = match &info.mount {
+                Some(mount) => (
+                    mount.source.clone(),
+                    if mount.read_only {
+                        format!("{} (ro)", mount.target)
+                    } else {
+                        mount.target.clone()
+                    },
+                ),
+                None => (
+                    info.volume.clone().unwrap_or_default(),
+                    "(nao montado)".to_string(),
+                ),
+            };
+            println!(
+                "  {:<20} {:<30} {:<30} {:>10}",
+                project.slug,
+                source,
+                target,
+                info.size_bytes
+                    .map(format_size)
+                    .unwrap_or_else(|| "--".to_string()),
+            );
+        }
+    }
+    println!();
+}
+
+pub fn print_backups(list: &BackupListResponse) {
+    println!();
+    println!(
+        "  {} {}",
+        style("Agendamento:").bold(),
+        match (&list.schedule, list.next_run) {
+            (Some(schedule), Some(next)) => format!(
+                "{} (proximo: {} UTC)",
+                schedule,
+                next.format("%Y-%m-%d %H:%M")
+            ),
+            (Some(schedule), None) => schedule.clone(),
+            (None, _) => "desativado".to_string(),
+        }
+    );
+    println!("  {} {}", style("Retencao:").bold(), list.keep);
+    println!("  {} {}", style("Destino:").bold(), list.storage);
+    println!();
+
+    if list.backups.is_empty() {
+        println!("  {}", style("Nenhum backup encontrado.").dim());
+        println!();
+        return;
+    }
+
+    println!(
+        "  {:<8} {:<10} {:<20} {:<7} {:>10} {:<8} {}",
+        style("BACKUP").bold(),
+        style("ORIGEM").bold(),
+        style("DATA").bold(),
+        style("LOCAL").bold(),
+        style("TAMANHO").bold(),
+        style("DURACAO").bold(),
+        style("CONTEUDO").bold(),
+    );
+    println!("  {}", "-".repeat(90));
+    for backup in &list.backups {
+        println!(
+            "  {:<8} {:<10} {:<20} {:<7} {:>10} {:<8} {}",
+            format!("#{}", backup.id),
+            backup.trigger,
+            backup.created_at.format("%Y-%m-%d %H:%M:%S"),
+            backup.location,
+            format_size(backup.size_bytes()),
+            format!("{}s", backup.duration_secs),
+            format_artifacts(backup),
+        );
+    }
+    println!();
+}
+
+pub fn print_cron_jobs(list: &CronListResponse) {
+    println!();
+    if list.jobs.is_empty() {
+        println!("  {}", style("Nenhum job agendado").dim());
+        println!();
+        return;
+    }
+
+    for job in &list.jobs {
+        println!("  {}", style(&job.name).bold());
+        println!("    {} {}", style("Agendamento:").bold(), job.schedule);
+        println!("    {} {}", style("Comando:").bold(), job.command);
+        println!("    {} {}s", style("Timeout:").bold(), job.timeout_secs);
+        println!(
+            "    {} {}",
+            style("Proxima execucao:").bold(),
+            job.next_run
+                .map(|next| format!("{} UTC", next.format("%Y-%m-%d %H:%M")))
+                .unwrap_or_else(|| "--".to_string())
+        );
+        println!(
+            "    {} {}",
+            style("Ultima execucao:").bold(),
+            match (&job.last_run, job.running) {
+                (_, true) => style("em execucao".to_string()).yellow().to_string(),
+                (Some(run), false) => format!(
+                    "#{} em {} UTC, {}",
+                    run.id,
+                    run.started_at.format("%Y-%m-%d %H:%M"),
+                    format_cron_result(run)
+                ),
+                (None, false) => "--".to_string(),
+            }
+        );
+    }
+    println!();
+}
+
+pub fn print_cron_runs(runs: &[CronRun]) {
+    println!();
+    if runs.is_empty() {
+        println!("  {}", style("Nenhuma execucao encontrada.").dim());
+        println!();
+        return;
+    }
+
+    println!(
+        "  {:<8} {:<20} {:<10} {:<20} {:<8} {}",
+        style("EXEC").bold(),
+        style("JOB").bold(),
+        style("ORIGEM").bold(),
+        style("INICIO").bold(),
+        style("DURACAO").bold(),
+        style("RESULTADO").bold(),
+    );
+    println!("  {}", "-".repeat(90));
+    for run in runs {
+        println!(
+            "  {:<8} {:<20} {:<10} {:<20} {:<8} {}",
+            format!("#{}", run.id),
+            run.job,
+            run.trigger,
+            run.started_at.format("%Y-%m-%d %H:%M:%S"),
+            run.duration_secs
+                .map(|d| format!("{}s", d))
+                .unwrap_or_else(|| "--".to_string()),
+            format_cron_result(run),
+        );
+    }
+    println!();
+}
+
+pub fn print_cron_run(resp: &CronRunResponse) {
+    let run = &resp.run;
+    println!();
+    println!("  {} #{} ({})", style("Execucao:").bold(), run.id, run.job);
+    println!(
+        "  {} {} UTC",
+        style("Inicio:").bold(),
+        run.started_at.format("%Y-%m-%d %H:%M:%S")
+    );
+    println!(
+        "  {} {}",
+        style("Resultado:").bold(),
+        format_cron_result(run)
+    );
+    println!();
+    if resp.output.is_empty() {
+        println!("  {}", style("(sem saida)").dim());
+    } else {
+        print!("{}", resp.output);
+    }
+    println!();
+}
+
+fn format_cron_result(run: &CronRun) -> String {
+    if !run.finished() {
+        return style("em execucao").yellow().to_string();
+    }
+    match (&run.error, run.exit_code) {
+        (Some(error), _) => style(format!("falhou: {}", error)).red().to_string(),
+        _ if run.succeeded() => style("codigo 0").green().to_string(),
+        (None, Some(code)) => style(format!("codigo {}", code)).red().to_string(),
+        (None, None) => "--".to_string(),
+    }
+}
+
+fn format_artifacts(backup: &Backup) -> String {
+    backup
+        .artifacts
+        .iter()
+        .map(|a| format!("{} {}", a.kind, a.source))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+pub fn print_build_config(config: &BuildConfigResponse) {
+    println!();
+    println!(
+        "  {} {}",
+        style("Target:").bold(),
+        config.target.as_deref().unwrap_or("(ultimo estagio)")
+    );
+    println!(
+        "  {} {}",
+        style("Release command:").bold(),
+        config.release_command.as_deref().unwrap_or("nenhum")
+    );
+
+    println!("  {}", style("Build args:").bold());
+    if config.args.is_empty() {
+        println!("    {}", style("nenhum").dim());
+    }
+    let mut args: Vec<_> = config.args.iter().collect();
+    args.sort();
+    for (key, value) in args {
+        println!("    {}={}", key, value);
+    }
+
+    println!("  {}", style("Secrets:").bold());
+    if config.secrets.is_empty() {
+        println!("    {}", style("nenhum").dim());
+    }
+    for key in &config.secrets {
+        println!("    {}={}", key, style("********").dim());
+    }
     println!();
 }
 
+pub fn print_release_table(releases: &[Release], current: Option<u32>) {
+    if releases.is_empty() {
+        println!("  {}", style("Nenhuma release encontrada.").dim());
+        return;
+    }
+
+    println!(
+        "  {:<8} {:<10} {:<10} {:<20} {:<8} {:<10}",
+        style("RELEASE").bold(),
+        style("COMMIT").bold(),
+        style("ORIGEM").bold(),
+        style("DATA").bold(),
+        style("DURACAO").bold(),
+        style("IMAGEM").bold(),
+    );
+    println!("  {}", "-".repeat(71));
+
+    for release in releases.iter().rev() {
+        let id = if Some(release.id) == current {
+            style(format!("v{} *", release.id)).green().to_string()
+        } else {
+            format!("v{}", release.id)
+        };
+        let trigger = match release.source_release {
+            Some(source) => format!("{} v{}", release.trigger, source),
+            None => release.trigger.to_string(),
+        };
+        let image = if release.pruned {
+            style("removida").dim().to_string()
+        } else {
+            release
+                .image_id
+                .trim_start_matches("sha256:")
+                .chars()
+                .take(10)
+                .collect()
+        };
+
+        println!(
+            "  {:<8} {:<10} {:<10} {:<20} {:<8} {:<10}",
+            id,
+            format_sha(release.commit_sha.as_deref()),
+            trigger,
+            release.created_at.format("%Y-%m-%d %H:%M:%S"),
+            format!("{}s", release.duration_secs),
+            image,
+        );
+    }
+}
+
+pub fn print_build_table(builds: &[BuildSummary]) {
+    if builds.is_empty() {
+        println!("  {}", style("Nenhum build encontrado.").dim());
+        return;
+    }
+
+    println!(
+        "  {:<8} {:<12} {:<20} {}",
+        style("BUILD").bold(),
+        style("STATUS").bold(),
+        style("INICIO").bold(),
+        style("ERRO").bold(),
+    );
+    println!("  {}", "-".repeat(70));
+
+    for build in builds.iter().rev() {
+        let status = match build.status {
+            BuildStatus::Running => style("Running").yellow().to_string(),
+            BuildStatus::Succeeded => style("Succeeded").green().to_string(),
+            BuildStatus::Failed => style("Failed").red().to_string(),
+        };
+        let started = build
+            .started_at
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "--".to_string());
+
+        println!(
+            "  {:<8} {:<12} {:<20} {}",
+            format!("#{}", build.id),
+            status,
+            started,
+            build.error.as_deref().unwrap_or(""),
+        );
+    }
+}
+
+pub fn format_sha(sha: Option<&str>) -> String {
+    match sha {
+        Some(s) if !s.is_empty() => s.chars().take(7).collect(),
+        _ => "--".to_string(),
+    }
+}
+
+/// Print a container log line; stderr output goes to stderr
+pub fn print_log_entry(entry: &LogEntry, timestamps: bool) {
+    let prefix = match (timestamps, entry.timestamp) {
+        (true, Some(t)) => format!("{} ", style(t.format("%Y-%m-%d %H:%M:%S")).dim()),
+        _ => String::new(),
+    };
+    match entry.stream {
+        LogStream::Stdout => println!("  {}{}", prefix, entry.message),
+        LogStream::Stderr => eprintln!("  {}{}", prefix, style(&entry.message).red()),
+    }
+}
+
 pub fn print_success(msg: &str) {
     println!("  {} {}", style("OK").green().bold(), msg);
 }
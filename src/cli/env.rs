@@ -0,0 +1,168 @@
+use console::style;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::cli::commands::{EnvAction, EnvApplyArgs};
+use crate::cli::{display, projects};
+use crate::ipc::protocol::EnvUpdateRequest;
+use crate::ipc::IpcClient;
+
+/// Read the variables of a .env file
+pub fn read_env_file(path: &Path) -> anyhow::Result<HashMap<String, String>> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    let vars = crate::utils::parse_dotenv(&content)
+        .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    Ok(vars.into_iter().collect())
+}
+
+/// Returns false if the change failed or the followed update failed
+pub async fn handle_env_action(action: EnvAction) -> anyhow::Result<bool> {
+    let client = IpcClient::new();
+
+    match action {
+        EnvAction::List { slug } => match client.get_env(&slug).await {
+            Ok(resp) => {
+                println!();
+                println!(
+                    "  {} {}",
+                    style("Modo de atualizacao:").bold(),
+                    resp.update_mode
+                );
+                println!();
+                if resp.env_vars.is_empty() {
+                    println!("  {}", style("Nenhuma variavel definida").dim());
+                }
+                let mut vars: Vec<_> = resp.env_vars.iter().collect();
+                vars.sort();
+                for (key, value) in vars {
+                    if resp.secrets.contains(key) {
+                        println!(
+                            "  {}={} {}",
+                            style(key).bold(),
+                            style(value).dim(),
+                            style("(secreta)").dim()
+                        );
+                    } else {
+                        println!("  {}={}", style(key).bold(), value);
+                    }
+                }
+                println!();
+            }
+            Err(e) => display::print_error(&format!("{}", e)),
+        },
+        EnvAction::Set {
+            slug,
+            vars,
+            secret,
+            apply,
+        } => {
+            let set = vars
+                .iter()
+                .map(|pair| Ok(crate::utils::parse_key_value(pair)?))
+                .collect::<anyhow::Result<_>>()?;
+            let req = EnvUpdateRequest {
+                set,
+                secret,
+                ..Default::default()
+            };
+            return update(&client, &slug, req, apply).await;
+        }
+        EnvAction::Unset { slug, keys, apply } => {
+            let req = EnvUpdateRequest {
+                unset: keys,
+                ..Default::default()
+            };
+            return update(&client, &slug, req, apply).await;
+        }
+        EnvAction::Import {
+            slug,
+            file,
+            replace,
+            secret,
+            apply,
+        } => {
+            let req = EnvUpdateRequest {
+                set: read_env_file(&file)?,
+                replace,
+                secret,
+                ..Default::default()
+            };
+            return update(&client, &slug, req, apply).await;
+        }
+        EnvAction::Export { slug, output } => match client.get_env(&slug).await {
+            Ok(resp) => {
+                // The daemon never returns secret values, so they cannot be exported
+                let mut content = crate::utils::format_dotenv(
+                    resp.env_vars
+                        .iter()
+                        .filter(|(key, _)| !resp.secrets.contains(key)),
+                );
+                for key in &resp.secrets {
+                    content.push_str(&format!("# {}: secret, not exported\n", key));
+                }
+                match output {
+                    Some(path) => {
+                        std::fs::write(&path, content)?;
+                        display::print_success(&format!(
+                            "{} variavel(is) exportada(s) para {}",
+                            resp.env_vars.len() - resp.secrets.len(),
+                            path.display()
+                        ));
+                    }
+                    None => print!("{}", content),
+                }
+            }
+            Err(e) => display::print_error(&format!("{}", e)),
+        },
+        EnvAction::Mode { slug, mode: None } => match client.get_env(&slug).await {
+            Ok(resp) => println!("{}", resp.update_mode),
+            Err(e) => display::print_error(&format!("{}", e)),
+        },
+        EnvAction::Mode {
+            slug,
+            mode: Some(mode),
+        } => {
+            let req = EnvUpdateRequest {
+                default_mode: Some(mode),
+                no_apply: true,
+                ..Default::default()
+            };
+            match client.update_env(&slug, &req).await {
+                Ok(_) => display::print_success(&format!(
+                    "Mudancas de ambiente em '{}' agora usam {}",
+                    slug, mode
+                )),
+                Err(e) => display::print_error(&format!("{}", e)),
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+async fn update(
+    client: &IpcClient,
+    slug: &str,
+    mut req: EnvUpdateRequest,
+    apply: EnvApplyArgs,
+) -> anyhow::Result<bool> {
+    req.mode = apply.mode;
+    req.no_apply = apply.no_apply;
+
+    match client.update_env(slug, &req).await {
+        Ok(resp) => {
+            display::print_success(&resp.message);
+            match resp.build_id {
+                Some(build_id) if !apply.detach => {
+                    projects::follow_build(client, slug, build_id).await
+                }
+                _ => Ok(true),
+            }
+        }
+        Err(e) => {
+            display::print_error(&format!("{}", e));
+            Ok(false)
+        }
+    }
+}
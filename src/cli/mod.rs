@@ -1,10 +1,21 @@
+pub mod addons;
+pub mod backups;
 pub mod commands;
+pub mod cron;
 pub mod deploy;
 pub mod display;
+pub mod env;
+pub mod processes;
 pub mod projects;
+pub mod session;
 pub mod settings;
+pub mod terminal;
 
-use crate::cli::commands::Commands;
+use std::collections::HashMap;
+
+use crate::cli::commands::{Commands, LimitArgs, ProjectAction};
+use crate::config::project::{NetworkMode, ResourceLimits};
+use crate::ipc::protocol::{DeployRequest, LogSource};
 
 /// Handle a specific CLI subcommand
 pub async fn handle_command(cmd: Commands) -> anyhow::Result<()> {
@@ -16,9 +27,45 @@ pub async fn handle_command(cmd: Commands) -> anyhow::Result<()> {
             public,
             domain,
             port,
+            env,
+            env_file,
+            internal,
+            links,
+            volumes,
+            limits,
+            replicas,
+            detach,
         } => {
             if let Some(repo_url) = repo {
-                deploy::deploy_direct(repo_url, branch, public, domain, port).await?;
+                let mut env_vars = match env_file {
+                    Some(path) => env::read_env_file(&path)?,
+                    None => HashMap::new(),
+                };
+                env_vars.extend(parse_pairs(&env)?);
+                let mut resources = ResourceLimits::default();
+                apply_limits(&limits, &mut resources)?;
+
+                let req = DeployRequest {
+                    repo_url,
+                    branch,
+                    network_mode: if internal {
+                        NetworkMode::Internal
+                    } else if public {
+                        NetworkMode::Public
+                    } else {
+                        NetworkMode::LocalOnly
+                    },
+                    hostname: domain,
+                    container_port: port,
+                    env_vars,
+                    resources,
+                    volumes,
+                    links,
+                    replicas,
+                };
+                if !deploy::deploy_direct(req, detach).await? {
+                    std::process::exit(1);
+                }
             } else {
                 deploy::deploy_interactive().await?;
             }
@@ -29,11 +76,153 @@ pub async fn handle_command(cmd: Commands) -> anyhow::Result<()> {
         Commands::Status { slug } => {
             projects::show_status(&slug).await?;
         }
-        Commands::Rebuild { slug } => {
-            projects::rebuild_project(&slug).await?;
+        Commands::Rebuild {
+            slug,
+            commit,
+            git_ref,
+            detach,
+        } => {
+            let req = crate::ipc::protocol::RebuildRequest { commit, git_ref };
+            if !projects::rebuild_project(&slug, req, !detach).await? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Builds { slug } => {
+            projects::show_builds(&slug).await?;
+        }
+        Commands::BuildLog {
+            slug,
+            build,
+            follow,
+        } => {
+            projects::show_build_log(&slug, &build, follow).await?;
+        }
+        Commands::Releases { slug } => {
+            projects::show_releases(&slug).await?;
+        }
+        Commands::BuildConfig {
+            slug,
+            args,
+            unset_args,
+            secrets,
+            unset_secrets,
+            target,
+            clear_target,
+            release_command,
+            clear_release_command,
+        } => {
+            let req = crate::ipc::protocol::BuildConfigUpdateRequest {
+                set_args: parse_pairs(&args)?,
+                unset_args,
+                set_secrets: parse_pairs(&secrets)?,
+                unset_secrets,
+                target,
+                clear_target,
+                release_command,
+                clear_release_command,
+            };
+            projects::build_config(&slug, req).await?;
+        }
+        Commands::Project { action } => match action {
+            ProjectAction::Set { slug, settings } => {
+                if !projects::set_settings(&slug, &settings).await? {
+                    std::process::exit(1);
+                }
+            }
+        },
+        Commands::WebhookSecret { slug, rotate, .. } => {
+            if !projects::webhook_secret(&slug, rotate).await? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Links { slug, add, remove } => {
+            projects::links(&slug, add, remove).await?;
+        }
+        Commands::Volumes { slug, add, remove } => {
+            projects::volumes(slug.as_deref(), add, remove).await?;
+        }
+        Commands::Scale {
+            slug,
+            replicas,
+            detach,
+        } => {
+            if !projects::scale_project(&slug, replicas, !detach).await? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Process { action } => {
+            if !processes::handle_process_action(action).await? {
+                std::process::exit(1);
+            }
         }
-        Commands::Logs { slug, follow, tail } => {
-            projects::show_logs(&slug, follow, tail).await?;
+        Commands::Limits { slug, limits } => {
+            projects::limits(&slug, &limits).await?;
+        }
+        Commands::Dockerfile { slug, set, reset } => {
+            projects::dockerfile(&slug, set.as_deref(), reset).await?;
+        }
+        Commands::Rollback {
+            slug,
+            release,
+            detach,
+        } => {
+            if !projects::rollback_project(&slug, release, !detach).await? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Exec {
+            slug,
+            no_tty,
+            command,
+        } => {
+            let code = session::exec(&slug, command, no_tty).await?;
+            // Exit right away: stdin is still being read on a blocking thread
+            std::process::exit(code);
+        }
+        Commands::Run {
+            slug,
+            no_tty,
+            command,
+        } => {
+            let code = session::run(&slug, command, no_tty).await?;
+            std::process::exit(code);
+        }
+        Commands::Logs {
+            slug,
+            follow,
+            tail,
+            since,
+            until,
+            timestamps,
+            process,
+            replica,
+        } => {
+            projects::show_logs(
+                &slug,
+                &LogSource { process, replica },
+                follow,
+                tail,
+                since.as_deref(),
+                until.as_deref(),
+                timestamps,
+            )
+            .await?;
+        }
+        Commands::Env { action } => {
+            if !env::handle_env_action(action).await? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Addon { action } => {
+            addons::handle_addon_action(action).await?;
+        }
+        Commands::Backup { action } => {
+            backups::handle_backup_action(action).await?;
+        }
+        Commands::Cron { action } => {
+            if !cron::handle_cron_action(action).await? {
+                std::process::exit(1);
+            }
         }
         Commands::Stop { slug } => {
             projects::stop_project(&slug).await?;
@@ -41,8 +230,11 @@ pub async fn handle_command(cmd: Commands) -> anyhow::Result<()> {
         Commands::Start { slug } => {
             projects::start_project(&slug).await?;
         }
-        Commands::Delete { slug } => {
-            projects::delete_project(&slug).await?;
+        Commands::Delete {
+            slug,
+            purge_volumes,
+        } => {
+            projects::delete_project(&slug, purge_volumes).await?;
         }
         Commands::Config { action } => {
             settings::handle_config_action(action).await?;
@@ -51,6 +243,59 @@ pub async fn handle_command(cmd: Commands) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Parse repeated KEY=VALUE arguments
+fn parse_pairs(pairs: &[String]) -> anyhow::Result<HashMap<String, String>> {
+    pairs
+        .iter()
+        .map(|pair| Ok(crate::utils::parse_key_value(pair)?))
+        .collect()
+}
+
+/// Apply limit flags on top of `limits`; `none` removes a limit
+pub fn apply_limits(args: &LimitArgs, limits: &mut ResourceLimits) -> anyhow::Result<()> {
+    fn value<T>(
+        arg: &Option<String>,
+        parse: impl Fn(&str) -> anyhow::Result<T>,
+    ) -> anyhow::Result<Option<Option<T>>> {
+        match arg.as_deref() {
+            None => Ok(None),
+            Some("none") => Ok(Some(None)),
+            Some(v) => Ok(Some(Some(parse(v)?))),
+        }
+    }
+
+    if let Some(memory) = value(&args.memory, |v| Ok(crate::utils::parse_size_mb(v)?))? {
+        limits.memory_mb = memory;
+    }
+    if let Some(swap) = value(&args.memory_swap, |v| match v {
+        "-1" | "unlimited" => Ok(-1),
+        v => Ok(crate::utils::parse_size_mb(v)? as i64),
+    })? {
+        limits.memory_swap_mb = swap;
+    }
+    if let Some(cpus) = value(&args.cpus, |v| match v.parse::<f64>() {
+        Ok(c) if c > 0.0 => Ok(c),
+        _ => anyhow::bail!("Invalid CPU count '{}'", v),
+    })? {
+        limits.cpus = cpus;
+    }
+    if let Some(shares) = value(&args.cpu_shares, |v| Ok(v.parse()?))? {
+        limits.cpu_shares = shares;
+    }
+    if let Some(pids) = value(&args.pids_limit, |v| Ok(v.parse()?))? {
+        limits.pids_limit = pids;
+    }
+    if !args.ulimits.is_empty() {
+        limits.ulimits = args
+            .ulimits
+            .iter()
+            .filter(|u| *u != "none")
+            .map(|u| u.parse().map_err(|e: String| anyhow::anyhow!(e)))
+            .collect::<anyhow::Result<_>>()?;
+    }
+    Ok(())
+}
+
 /// Show the interactive main menu
 pub async fn interactive_menu() -> anyhow::Result<()> {
     display::print_banner();
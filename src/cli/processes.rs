@@ -0,0 +1,59 @@
+use crate::cli::commands::ProcessAction;
+use crate::cli::display;
+use crate::cli::projects::follow_build;
+use crate::ipc::protocol::ProcessUpdateRequest;
+use crate::ipc::IpcClient;
+
+/// Returns whether the action and any build it started succeeded
+pub async fn handle_process_action(action: ProcessAction) -> anyhow::Result<bool> {
+    let client = IpcClient::new();
+
+    match action {
+        ProcessAction::List { slug } => match client.get_project(&slug).await {
+            Ok(detail) => display::print_processes(&detail.status.processes),
+            Err(e) => {
+                display::print_error(&format!("{}", e));
+                return Ok(false);
+            }
+        },
+        ProcessAction::Set {
+            slug,
+            name,
+            command,
+            clear_command,
+            replicas,
+            port,
+            no_port,
+            detach,
+        } => {
+            let req = ProcessUpdateRequest {
+                command,
+                clear_command,
+                replicas,
+                port,
+                clear_port: no_port,
+            };
+            match client.set_process(&slug, &name, &req).await {
+                Ok(resp) => {
+                    display::print_success(&resp.message);
+                    if let Some(build_id) = resp.build_id.filter(|_| !detach) {
+                        return follow_build(&client, &slug, build_id).await;
+                    }
+                }
+                Err(e) => {
+                    display::print_error(&format!("{}", e));
+                    return Ok(false);
+                }
+            }
+        }
+        ProcessAction::Remove { slug, name } => match client.remove_process(&slug, &name).await {
+            Ok(resp) => display::print_success(&resp.message),
+            Err(e) => {
+                display::print_error(&format!("{}", e));
+                return Ok(false);
+            }
+        },
+    }
+
+    Ok(true)
+}
@@ -1,8 +1,16 @@
 use console::style;
 use dialoguer::{Confirm, Select};
+use std::io::Write;
 
+use crate::cli::commands::LimitArgs;
 use crate::cli::display;
+use crate::config::project::VolumeMount;
+use crate::ipc::protocol::{
+    BuildConfigUpdateRequest, LinksUpdateRequest, LogSource, ProjectSettingsRequest,
+    RebuildRequest, VolumesUpdateRequest,
+};
 use crate::ipc::IpcClient;
+use crate::models::build::BuildStatus;
 
 pub async fn projects_menu() -> anyhow::Result<()> {
     let client = IpcClient::new();
@@ -60,6 +68,8 @@ async fn project_actions_menu(client: &IpcClient, slug: &str) -> anyhow::Result<
             "Ver detalhes",
             "Ver logs",
             "Rebuildar",
+            "Ver releases",
+            "Rollback",
             "Iniciar",
             "Parar",
             "Deletar",
@@ -80,28 +90,35 @@ async fn project_actions_menu(client: &IpcClient, slug: &str) -> anyhow::Result<
                             &detail.status,
                             &detail.repo_url,
                             &detail.branch,
+                            &detail.git,
                         );
                     }
                     Err(e) => display::print_error(&format!("{}", e)),
                 }
             }
             1 => {
-                show_logs(slug, false, 50).await?;
+                show_logs(slug, &LogSource::default(), false, 50, None, None, false).await?;
             }
             2 => {
-                rebuild_project(slug).await?;
+                rebuild_project(slug, RebuildRequest::default(), true).await?;
             }
             3 => {
-                start_project(slug).await?;
+                show_releases(slug).await?;
             }
             4 => {
-                stop_project(slug).await?;
+                rollback_interactive(client, slug).await?;
             }
             5 => {
-                delete_project(slug).await?;
+                start_project(slug).await?;
+            }
+            6 => {
+                stop_project(slug).await?;
+            }
+            7 => {
+                delete_project(slug, false).await?;
                 return Ok(());
             }
-            6 => return Ok(()),
+            8 => return Ok(()),
             _ => unreachable!(),
         }
     }
@@ -124,22 +141,412 @@ pub async fn show_status(slug: &str) -> anyhow::Result<()> {
     let client = IpcClient::new();
     match client.get_project(slug).await {
         Ok(detail) => {
-            display::print_project_detail(&detail.status, &detail.repo_url, &detail.branch);
+            display::print_project_detail(
+                &detail.status,
+                &detail.repo_url,
+                &detail.branch,
+                &detail.git,
+            );
         }
         Err(e) => display::print_error(&format!("{}", e)),
     }
     Ok(())
 }
 
-pub async fn rebuild_project(slug: &str) -> anyhow::Result<()> {
+/// Queue a rebuild; returns false if it could not be queued or the followed build failed
+pub async fn rebuild_project(
+    slug: &str,
+    req: RebuildRequest,
+    follow: bool,
+) -> anyhow::Result<bool> {
     let client = IpcClient::new();
-    match client.rebuild(slug).await {
-        Ok(resp) => display::print_success(&resp.message),
+    match client.rebuild(slug, &req).await {
+        Ok(resp) => {
+            display::print_success(&resp.message);
+            if follow {
+                return follow_build(&client, slug, resp.build_id).await;
+            }
+            Ok(true)
+        }
+        Err(e) => {
+            display::print_error(&format!("{}", e));
+            Ok(false)
+        }
+    }
+}
+
+pub async fn scale_project(slug: &str, replicas: u32, follow: bool) -> anyhow::Result<bool> {
+    let client = IpcClient::new();
+    match client.scale(slug, replicas).await {
+        Ok(resp) => {
+            display::print_success(&resp.message);
+            match resp.build_id {
+                Some(build_id) if follow => follow_build(&client, slug, build_id).await,
+                _ => Ok(true),
+            }
+        }
+        Err(e) => {
+            display::print_error(&format!("{}", e));
+            Ok(false)
+        }
+    }
+}
+
+/// Print a build log as it is written; returns whether the build succeeded
+pub async fn follow_build(client: &IpcClient, slug: &str, build_id: u32) -> anyhow::Result<bool> {
+    let build = build_id.to_string();
+
+    println!();
+    client
+        .stream_build_log(slug, &build, true, |chunk| {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(chunk);
+            let _ = stdout.flush();
+        })
+        .await?;
+    println!();
+
+    let summary = client.get_build(slug, &build).await?;
+    match summary.status {
+        BuildStatus::Succeeded => {
+            display::print_success(&format!("Build #{} concluido", build_id));
+            Ok(true)
+        }
+        _ => {
+            display::print_error(&format!(
+                "Build #{} falhou: {}",
+                build_id,
+                summary
+                    .error
+                    .unwrap_or_else(|| "motivo desconhecido".to_string())
+            ));
+            Ok(false)
+        }
+    }
+}
+
+pub async fn show_builds(slug: &str) -> anyhow::Result<()> {
+    let client = IpcClient::new();
+    match client.list_builds(slug).await {
+        Ok(builds) => {
+            println!();
+            display::print_build_table(&builds);
+            println!();
+        }
         Err(e) => display::print_error(&format!("{}", e)),
     }
     Ok(())
 }
 
+pub async fn show_build_log(slug: &str, build: &str, follow: bool) -> anyhow::Result<()> {
+    let client = IpcClient::new();
+    let result = client
+        .stream_build_log(slug, build, follow, |chunk| {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(chunk);
+            let _ = stdout.flush();
+        })
+        .await;
+    if let Err(e) = result {
+        display::print_error(&format!("{}", e));
+    }
+    Ok(())
+}
+
+pub async fn show_releases(slug: &str) -> anyhow::Result<()> {
+    let client = IpcClient::new();
+    match client.list_releases(slug).await {
+        Ok(resp) => {
+            println!();
+            display::print_release_table(&resp.releases, resp.current);
+            println!();
+        }
+        Err(e) => display::print_error(&format!("{}", e)),
+    }
+    Ok(())
+}
+
+pub async fn build_config(slug: &str, req: BuildConfigUpdateRequest) -> anyhow::Result<()> {
+    let client = IpcClient::new();
+    let changed = !req.set_args.is_empty()
+        || !req.unset_args.is_empty()
+        || !req.set_secrets.is_empty()
+        || !req.unset_secrets.is_empty()
+        || req.target.is_some()
+        || req.clear_target
+        || req.release_command.is_some()
+        || req.clear_release_command;
+
+    let result = if changed {
+        client.update_build_config(slug, &req).await
+    } else {
+        client.get_build_config(slug).await
+    };
+
+    match result {
+        Ok(resp) => {
+            if changed {
+                display::print_success("Build config atualizada (vale a partir do proximo build)");
+            }
+            display::print_build_config(&resp);
+        }
+        Err(e) => display::print_error(&format!("{}", e)),
+    }
+    Ok(())
+}
+
+/// Change project settings given as KEY=VALUE; returns false if they were not changed
+pub async fn set_settings(slug: &str, settings: &[String]) -> anyhow::Result<bool> {
+    let mut req = ProjectSettingsRequest::default();
+    for setting in settings {
+        let (key, value) = crate::utils::parse_key_value(setting)?;
+        let flag = || match value.trim() {
+            "true" | "on" | "yes" => Ok(true),
+            "false" | "off" | "no" => Ok(false),
+            _ => Err(anyhow::anyhow!(
+                "Valor invalido para {}: '{}' (use true ou false)",
+                key,
+                value
+            )),
+        };
+        match key.as_str() {
+            "branch" => req.branch = Some(value.trim().to_string()),
+            "submodules" => req.submodules = Some(flag()?),
+            "lfs" => req.lfs = Some(flag()?),
+            _ => {
+                display::print_error(&format!(
+                    "Configuracao desconhecida: '{}' (use branch, submodules ou lfs)",
+                    key
+                ));
+                return Ok(false);
+            }
+        }
+    }
+
+    let client = IpcClient::new();
+    match client.update_settings(slug, &req).await {
+        Ok(resp) => {
+            display::print_success(&resp.message);
+            Ok(true)
+        }
+        Err(e) => {
+            display::print_error(&format!("{}", e));
+            Ok(false)
+        }
+    }
+}
+
+pub async fn webhook_secret(slug: &str, rotate: bool) -> anyhow::Result<bool> {
+    let client = IpcClient::new();
+    let resp = if rotate {
+        client.rotate_webhook_secret(slug).await
+    } else {
+        client.get_webhook_secret(slug).await
+    };
+    match resp {
+        Ok(resp) => {
+            if rotate {
+                display::print_success("Novo segredo gerado; atualize o webhook no GitHub");
+            }
+            display::print_webhook_secret(&resp);
+            Ok(true)
+        }
+        Err(e) => {
+            display::print_error(&format!("{}", e));
+            Ok(false)
+        }
+    }
+}
+
+pub async fn links(slug: &str, add: Vec<String>, remove: Vec<String>) -> anyhow::Result<()> {
+    let client = IpcClient::new();
+
+    if !add.is_empty() || !remove.is_empty() {
+        let req = LinksUpdateRequest { add, remove };
+        match client.update_links(slug, &req).await {
+            Ok(resp) => display::print_success(&resp.message),
+            Err(e) => {
+                display::print_error(&format!("{}", e));
+                return Ok(());
+            }
+        }
+    }
+
+    match client.get_links(slug).await {
+        Ok(links) => display::print_links(&links),
+        Err(e) => display::print_error(&format!("{}", e)),
+    }
+    Ok(())
+}
+
+pub async fn volumes(
+    slug: Option<&str>,
+    add: Vec<VolumeMount>,
+    remove: Vec<String>,
+) -> anyhow::Result<()> {
+    let client = IpcClient::new();
+
+    let Some(slug) = slug else {
+        match client.list_volumes().await {
+            Ok(all) => display::print_volumes(&all),
+            Err(e) => display::print_error(&format!("{}", e)),
+        }
+        return Ok(());
+    };
+
+    if !add.is_empty() || !remove.is_empty() {
+        let req = VolumesUpdateRequest { add, remove };
+        match client.update_volumes(slug, &req).await {
+            Ok(resp) => display::print_success(&resp.message),
+            Err(e) => {
+                display::print_error(&format!("{}", e));
+                return Ok(());
+            }
+        }
+    }
+
+    match client.get_volumes(slug).await {
+        Ok(volumes) => display::print_volumes(&[volumes]),
+        Err(e) => display::print_error(&format!("{}", e)),
+    }
+    Ok(())
+}
+
+pub async fn limits(slug: &str, args: &LimitArgs) -> anyhow::Result<()> {
+    let client = IpcClient::new();
+
+    let mut limits = match client.get_resources(slug).await {
+        Ok(limits) => limits,
+        Err(e) => {
+            display::print_error(&format!("{}", e));
+            return Ok(());
+        }
+    };
+    if args.is_empty() {
+        display::print_limits(&limits);
+        return Ok(());
+    }
+
+    crate::cli::apply_limits(args, &mut limits)?;
+    match client.update_resources(slug, &limits).await {
+        Ok(resp) => {
+            display::print_success(&resp.message);
+            display::print_limits(&resp.limits);
+        }
+        Err(e) => display::print_error(&format!("{}", e)),
+    }
+    Ok(())
+}
+
+pub async fn dockerfile(
+    slug: &str,
+    set: Option<&std::path::Path>,
+    reset: bool,
+) -> anyhow::Result<()> {
+    let client = IpcClient::new();
+
+    if set.is_some() || reset {
+        let content = match set {
+            Some(path) => Some(std::fs::read_to_string(path)?),
+            None => None,
+        };
+        match client.set_dockerfile(slug, content).await {
+            Ok(resp) => display::print_success(&resp.message),
+            Err(e) => display::print_error(&format!("{}", e)),
+        }
+        return Ok(());
+    }
+
+    match client.get_dockerfile(slug).await {
+        Ok(resp) => {
+            // Header on stderr so the Dockerfile itself can be redirected to a file
+            eprintln!();
+            eprintln!(
+                "  {} {} ({})",
+                style("Dockerfile:").bold(),
+                resp.path,
+                resp.source
+            );
+            if let Some(port) = resp.container_port {
+                eprintln!("  {} {}", style("Porta:").bold(), port);
+            }
+            eprintln!();
+            print!("{}", resp.content);
+        }
+        Err(e) => display::print_error(&format!("{}", e)),
+    }
+    Ok(())
+}
+
+pub async fn rollback_project(
+    slug: &str,
+    release: Option<u32>,
+    follow: bool,
+) -> anyhow::Result<bool> {
+    let client = IpcClient::new();
+    match client.rollback(slug, release).await {
+        Ok(resp) => {
+            display::print_success(&resp.message);
+            if follow {
+                return follow_build(&client, slug, resp.build_id).await;
+            }
+            Ok(true)
+        }
+        Err(e) => {
+            display::print_error(&format!("{}", e));
+            Ok(false)
+        }
+    }
+}
+
+async fn rollback_interactive(client: &IpcClient, slug: &str) -> anyhow::Result<()> {
+    let resp = match client.list_releases(slug).await {
+        Ok(r) => r,
+        Err(e) => {
+            display::print_error(&format!("{}", e));
+            return Ok(());
+        }
+    };
+
+    let candidates: Vec<_> = resp
+        .releases
+        .iter()
+        .rev()
+        .filter(|r| !r.pruned && Some(r.id) != resp.current)
+        .collect();
+
+    if candidates.is_empty() {
+        println!("  {}", style("Nenhuma release anterior disponivel.").dim());
+        return Ok(());
+    }
+
+    let mut options: Vec<String> = candidates
+        .iter()
+        .map(|r| {
+            format!(
+                "v{} - {} ({})",
+                r.id,
+                display::format_sha(r.commit_sha.as_deref()),
+                r.created_at.format("%Y-%m-%d %H:%M")
+            )
+        })
+        .collect();
+    options.push("Voltar".to_string());
+
+    let selection = Select::with_theme(&dialoguer::theme::ColorfulTheme::default())
+        .with_prompt("Voltar para qual release?")
+        .items(&options)
+        .default(0)
+        .interact()?;
+
+    if selection == options.len() - 1 {
+        return Ok(());
+    }
+
+    rollback_project(slug, Some(candidates[selection].id), true).await?;
+    Ok(())
+}
+
 pub async fn start_project(slug: &str) -> anyhow::Result<()> {
     let client = IpcClient::new();
     match client.start_project(slug).await {
@@ -158,12 +565,20 @@ pub async fn stop_project(slug: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub async fn delete_project(slug: &str) -> anyhow::Result<()> {
-    let confirm = Confirm::with_theme(&dialoguer::theme::ColorfulTheme::default())
-        .with_prompt(format!(
+pub async fn delete_project(slug: &str, purge_volumes: bool) -> anyhow::Result<()> {
+    let prompt = if purge_volumes {
+        format!(
+            "Tem certeza que deseja deletar '{}' e os dados dos seus volumes? Esta acao e irreversivel.",
+            slug
+        )
+    } else {
+        format!(
             "Tem certeza que deseja deletar '{}'? Esta acao e irreversivel.",
             slug
-        ))
+        )
+    };
+    let confirm = Confirm::with_theme(&dialoguer::theme::ColorfulTheme::default())
+        .with_prompt(prompt)
         .default(false)
         .interact()?;
 
@@ -173,27 +588,73 @@ pub async fn delete_project(slug: &str) -> anyhow::Result<()> {
     }
 
     let client = IpcClient::new();
-    match client.delete_project(slug).await {
+    match client.delete_project(slug, purge_volumes).await {
         Ok(resp) => display::print_success(&resp.message),
         Err(e) => display::print_error(&format!("{}", e)),
     }
     Ok(())
 }
 
-pub async fn show_logs(slug: &str, _follow: bool, tail: u32) -> anyhow::Result<()> {
+pub async fn show_logs(
+    slug: &str,
+    source: &LogSource,
+    follow: bool,
+    tail: u32,
+    since: Option<&str>,
+    until: Option<&str>,
+    timestamps: bool,
+) -> anyhow::Result<()> {
+    let (since, until) = match (
+        since.map(crate::utils::parse_time_filter).transpose(),
+        until.map(crate::utils::parse_time_filter).transpose(),
+    ) {
+        (Ok(since), Ok(until)) => (since, until),
+        (Err(e), _) | (_, Err(e)) => {
+            display::print_error(&format!("{}", e));
+            return Ok(());
+        }
+    };
+
     let client = IpcClient::new();
-    match client.get_logs(slug, tail).await {
+
+    if follow {
+        // Dropping the stream on Ctrl-C closes the connection and stops the daemon side
+        tokio::select! {
+            result = client.stream_logs(slug, source, Some(tail), since, until, |entry| {
+                display::print_log_entry(&entry, timestamps);
+            }) => {
+                if let Err(e) = result {
+                    display::print_error(&format!("{}", e));
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                println!();
+            }
+        }
+        return Ok(());
+    }
+
+    match client.get_logs(slug, source, tail, since, until).await {
         Ok(resp) => {
+            let container = match (&source.process, source.replica) {
+                (None, None) => String::new(),
+                (process, replica) => format!(
+                    " {} #{}",
+                    process.as_deref().unwrap_or("web"),
+                    replica.unwrap_or(1)
+                ),
+            };
             println!();
             println!(
-                "  {} {} (ultimas {} linhas)",
+                "  {} {}{} (ultimas {} linhas)",
                 style("Logs de").dim(),
                 style(slug).bold(),
+                container,
                 tail,
             );
             println!("  {}", "-".repeat(60));
-            for line in &resp.logs {
-                println!("  {}", line);
+            for entry in &resp.logs {
+                display::print_log_entry(entry, timestamps);
             }
             println!();
         }
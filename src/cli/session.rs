@@ -0,0 +1,119 @@
+use hyper::upgrade::Upgraded;
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+
+use crate::cli::display;
+use crate::cli::terminal::{self, RawMode};
+use crate::ipc::protocol::SessionRequest;
+use crate::ipc::session::Frame;
+use crate::ipc::IpcClient;
+
+/// Run a command in the project's live container; returns its exit code
+pub async fn exec(slug: &str, command: Vec<String>, no_tty: bool) -> anyhow::Result<i32> {
+    let req = request(command, no_tty);
+    let client = IpcClient::new();
+    match client.exec_session(slug, &req).await {
+        Ok(stream) => attach(stream, req.tty).await,
+        Err(e) => {
+            display::print_error(&format!("{}", e));
+            Ok(1)
+        }
+    }
+}
+
+/// Run a command in a new container from the project's current image; returns its exit code
+pub async fn run(slug: &str, command: Vec<String>, no_tty: bool) -> anyhow::Result<i32> {
+    let req = request(command, no_tty);
+    let client = IpcClient::new();
+    match client.run_session(slug, &req).await {
+        Ok(stream) => attach(stream, req.tty).await,
+        Err(e) => {
+            display::print_error(&format!("{}", e));
+            Ok(1)
+        }
+    }
+}
+
+fn request(command: Vec<String>, no_tty: bool) -> SessionRequest {
+    let tty = !no_tty && terminal::is_interactive();
+    let size = terminal::size().filter(|_| tty);
+    SessionRequest {
+        command,
+        tty,
+        rows: size.map(|(rows, _)| rows),
+        cols: size.map(|(_, cols)| cols),
+    }
+}
+
+/// Forward stdin and terminal resizes to the session and print its output until the
+/// command exits
+async fn attach(stream: TokioIo<Upgraded>, tty: bool) -> anyhow::Result<i32> {
+    let (mut reader, mut writer) = tokio::io::split(stream);
+
+    // Stdin and resizes both write to the connection, through one task
+    let (tx, mut rx) = mpsc::channel::<Frame>(16);
+    let sender = tokio::spawn(async move {
+        while let Some(frame) = rx.recv().await {
+            if frame.write(&mut writer).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let stdin_tx = tx.clone();
+    let stdin = tokio::spawn(async move {
+        let mut stdin = tokio::io::stdin();
+        let mut buf = [0u8; 4096];
+        loop {
+            let frame = match stdin.read(&mut buf).await {
+                Ok(0) | Err(_) => Frame::StdinClosed,
+                Ok(n) => Frame::Stdin(buf[..n].to_vec()),
+            };
+            let closed = frame == Frame::StdinClosed;
+            if stdin_tx.send(frame).await.is_err() || closed {
+                break;
+            }
+        }
+    });
+
+    let resize = tokio::spawn(async move {
+        let Ok(mut winch) = signal(SignalKind::window_change()) else {
+            return;
+        };
+        while winch.recv().await.is_some() {
+            if let Some((rows, cols)) = terminal::size() {
+                if tx.send(Frame::Resize { rows, cols }).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let raw = if tty { Some(RawMode::enable()?) } else { None };
+    let mut stdout = tokio::io::stdout();
+    let mut stderr = tokio::io::stderr();
+    let result = loop {
+        match Frame::read(&mut reader).await {
+            Ok(Some(Frame::Stdout(data))) => {
+                stdout.write_all(&data).await?;
+                stdout.flush().await?;
+            }
+            Ok(Some(Frame::Stderr(data))) => {
+                stderr.write_all(&data).await?;
+                stderr.flush().await?;
+            }
+            Ok(Some(Frame::Exit(code))) => break Ok(code.clamp(0, 255) as i32),
+            Ok(Some(_)) => {}
+            Ok(None) => break Err(anyhow::anyhow!("Conexao com o daemon encerrada")),
+            Err(e) => break Err(e.into()),
+        }
+    };
+    drop(raw);
+
+    stdin.abort();
+    resize.abort();
+    sender.abort();
+    result
+}
@@ -181,6 +181,10 @@ pub async fn handle_config_action(action: ConfigAction) -> anyhow::Result<()> {
                 Err(e) => display::print_error(&format!("{}", e)),
             }
         }
+        ConfigAction::RotateKey => match client.rotate_master_key().await {
+            Ok(resp) => display::print_success(&resp.message),
+            Err(e) => display::print_error(&format!("{}", e)),
+        },
     }
 
     Ok(())
@@ -0,0 +1,50 @@
+use std::io::IsTerminal;
+
+/// Keeps the terminal in raw mode, so keys go straight to the remote TTY, until dropped
+pub struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    pub fn enable() -> std::io::Result<Self> {
+        // SAFETY: termios is plain data filled in by tcgetattr before it is read
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let original = termios;
+            libc::cfmakeraw(&mut termios);
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(Self { original })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: restores the settings read in `enable`
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/// Whether stdin and stdout are both a terminal
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+}
+
+/// Rows and columns of the terminal on stdout
+pub fn size() -> Option<(u16, u16)> {
+    // SAFETY: winsize is plain data filled in by the ioctl
+    unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) != 0 || size.ws_col == 0 {
+            return None;
+        }
+        Some((size.ws_row, size.ws_col))
+    }
+}
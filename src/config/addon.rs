@@ -0,0 +1,237 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::config::{paths, secrets};
+use crate::error::DockyardError;
+
+/// Database engine of a managed add-on
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AddonKind {
+    Postgres,
+    Redis,
+    Mysql,
+}
+
+impl AddonKind {
+    pub fn default_version(&self) -> &'static str {
+        match self {
+            AddonKind::Postgres => "16",
+            AddonKind::Redis => "7",
+            AddonKind::Mysql => "8",
+        }
+    }
+
+    fn image(&self) -> &'static str {
+        match self {
+            AddonKind::Postgres => "postgres",
+            AddonKind::Redis => "redis",
+            AddonKind::Mysql => "mysql",
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        match self {
+            AddonKind::Postgres => 5432,
+            AddonKind::Redis => 6379,
+            AddonKind::Mysql => 3306,
+        }
+    }
+
+    /// Where the engine keeps its data inside the container
+    pub fn data_path(&self) -> &'static str {
+        match self {
+            AddonKind::Postgres => "/var/lib/postgresql/data",
+            AddonKind::Redis => "/data",
+            AddonKind::Mysql => "/var/lib/mysql",
+        }
+    }
+
+    /// Variable the connection URL is injected as unless another one is chosen
+    pub fn default_env_var(&self) -> &'static str {
+        match self {
+            AddonKind::Postgres | AddonKind::Mysql => "DATABASE_URL",
+            AddonKind::Redis => "REDIS_URL",
+        }
+    }
+}
+
+impl std::fmt::Display for AddonKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddonKind::Postgres => write!(f, "postgres"),
+            AddonKind::Redis => write!(f, "redis"),
+            AddonKind::Mysql => write!(f, "mysql"),
+        }
+    }
+}
+
+impl std::str::FromStr for AddonKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "postgres" | "postgresql" => Ok(AddonKind::Postgres),
+            "redis" => Ok(AddonKind::Redis),
+            "mysql" => Ok(AddonKind::Mysql),
+            _ => Err(format!(
+                "unknown add-on '{}' (expected postgres, redis or mysql)",
+                s
+            )),
+        }
+    }
+}
+
+/// A database container managed by dockyard, persisted as `addons/<name>.toml`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AddonConfig {
+    /// Also the host name other containers reach it at on `dockyard-network`
+    pub name: String,
+    pub kind: AddonKind,
+    /// Tag of the engine's official image
+    pub version: String,
+    /// Project whose container gets the connection URL
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Variable the connection URL is injected as
+    pub env_var: String,
+    pub user: String,
+    /// Stored encrypted
+    pub password: String,
+    pub database: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AddonConfig {
+    /// A new add-on with generated credentials
+    pub fn new(name: String, kind: AddonKind, version: String) -> Result<Self, DockyardError> {
+        Ok(Self {
+            database: name.replace('-', "_"),
+            name,
+            kind,
+            version,
+            project: None,
+            env_var: kind.default_env_var().to_string(),
+            user: "dockyard".to_string(),
+            password: secrets::seal(&crate::utils::generate_password())?,
+            created_at: Utc::now(),
+        })
+    }
+
+    pub fn container_name(&self) -> String {
+        format!("dockyard-addon-{}", self.name)
+    }
+
+    pub fn volume_name(&self) -> String {
+        format!("dockyard-addon-{}-data", self.name)
+    }
+
+    pub fn image(&self) -> String {
+        format!("{}:{}", self.kind.image(), self.version)
+    }
+
+    /// `volume:path` mount that keeps the data across container recreation
+    pub fn bind_spec(&self) -> String {
+        format!("{}:{}", self.volume_name(), self.kind.data_path())
+    }
+
+    /// Environment the engine's image reads its credentials from
+    pub fn container_env(&self) -> Result<HashMap<String, String>, DockyardError> {
+        let password = secrets::reveal(&self.password)?;
+        let vars = match self.kind {
+            AddonKind::Postgres => vec![
+                ("POSTGRES_USER", self.user.clone()),
+                ("POSTGRES_PASSWORD", password),
+                ("POSTGRES_DB", self.database.clone()),
+            ],
+            AddonKind::Mysql => vec![
+                ("MYSQL_USER", self.user.clone()),
+                ("MYSQL_PASSWORD", password),
+                ("MYSQL_DATABASE", self.database.clone()),
+                ("MYSQL_RANDOM_ROOT_PASSWORD", "yes".to_string()),
+            ],
+            // Configured through the command line instead
+            AddonKind::Redis => vec![],
+        };
+        Ok(vars
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect())
+    }
+
+    /// Command overriding the image's default, if the engine needs one
+    pub fn command(&self) -> Result<Option<Vec<String>>, DockyardError> {
+        match self.kind {
+            AddonKind::Redis => Ok(Some(vec![
+                "redis-server".to_string(),
+                "--requirepass".to_string(),
+                secrets::reveal(&self.password)?,
+                "--appendonly".to_string(),
+                "yes".to_string(),
+            ])),
+            AddonKind::Postgres | AddonKind::Mysql => Ok(None),
+        }
+    }
+
+    /// Connection URL as seen from a container on the same network
+    pub fn url(&self) -> Result<String, DockyardError> {
+        let password = secrets::reveal(&self.password)?;
+        let port = self.kind.port();
+        Ok(match self.kind {
+            AddonKind::Postgres => format!(
+                "postgres://{}:{}@{}:{}/{}",
+                self.user, password, self.name, port, self.database
+            ),
+            AddonKind::Mysql => format!(
+                "mysql://{}:{}@{}:{}/{}",
+                self.user, password, self.name, port, self.database
+            ),
+            AddonKind::Redis => format!("redis://default:{}@{}:{}/0", password, self.name, port),
+        })
+    }
+
+    /// Re-encrypt the password with the active master key
+    pub fn reseal_secrets(&mut self) -> Result<usize, DockyardError> {
+        Ok(secrets::reseal(&mut self.password)? as usize)
+    }
+
+    pub fn save(&self) -> Result<(), DockyardError> {
+        std::fs::create_dir_all(paths::addons_dir())?;
+        let content =
+            toml::to_string_pretty(self).map_err(|e| DockyardError::Config(e.to_string()))?;
+        std::fs::write(paths::addon_config_path(&self.name), content)?;
+        Ok(())
+    }
+
+    pub fn delete(name: &str) -> Result<(), DockyardError> {
+        let path = paths::addon_config_path(name);
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    pub fn load_all() -> Result<Vec<Self>, DockyardError> {
+        let dir = paths::addons_dir();
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut addons = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("toml") {
+                continue;
+            }
+            let loaded = std::fs::read_to_string(&path)
+                .map_err(DockyardError::from)
+                .and_then(|content| Ok(toml::from_str::<AddonConfig>(&content)?));
+            match loaded {
+                Ok(addon) => addons.push(addon),
+                Err(e) => tracing::warn!("Failed to load add-on {}: {}", path.display(), e),
+            }
+        }
+        addons.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(addons)
+    }
+}
@@ -0,0 +1,164 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::history::{self, Numbered};
+use crate::config::paths;
+use crate::error::DockyardError;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupTrigger {
+    Scheduled,
+    Manual,
+}
+
+impl std::fmt::Display for BackupTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupTrigger::Scheduled => write!(f, "scheduled"),
+            BackupTrigger::Manual => write!(f, "manual"),
+        }
+    }
+}
+
+/// Where the files of a backup are stored
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupLocation {
+    Local,
+    S3,
+}
+
+impl std::fmt::Display for BackupLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupLocation::Local => write!(f, "local"),
+            BackupLocation::S3 => write!(f, "s3"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactKind {
+    /// Tarball of a Docker volume's contents
+    Volume,
+    /// Logical dump of a database add-on
+    Dump,
+}
+
+impl std::fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArtifactKind::Volume => write!(f, "volume"),
+            ArtifactKind::Dump => write!(f, "dump"),
+        }
+    }
+}
+
+/// One file of a backup
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupArtifact {
+    pub kind: ArtifactKind,
+    /// Volume source as declared in the project (volumes) or add-on name (dumps)
+    pub source: String,
+    pub file: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Backup {
+    pub id: u32,
+    pub trigger: BackupTrigger,
+    pub location: BackupLocation,
+    pub created_at: DateTime<Utc>,
+    pub duration_secs: u64,
+    #[serde(default)]
+    pub artifacts: Vec<BackupArtifact>,
+}
+
+impl Numbered for Backup {
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Backup {
+    pub fn size_bytes(&self) -> u64 {
+        self.artifacts.iter().map(|a| a.size_bytes).sum()
+    }
+}
+
+/// Backups of a project, persisted next to its `project.toml`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BackupHistory {
+    #[serde(default)]
+    pub backups: Vec<Backup>,
+}
+
+impl BackupHistory {
+    pub fn load(slug: &str) -> Result<Self, DockyardError> {
+        history::load(&paths::project_backups_path(slug))
+    }
+
+    pub fn save(&self, slug: &str) -> Result<(), DockyardError> {
+        history::save(&paths::project_backups_path(slug), self)
+    }
+
+    pub fn next_id(&self) -> u32 {
+        history::next_id(&self.backups)
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Backup> {
+        history::get(&self.backups, id)
+    }
+
+    /// Drop all but the newest `keep` backups (at least one) from the history and return
+    /// them, newest first
+    pub fn prune(&mut self, keep: usize) -> Vec<Backup> {
+        let expired = history::expired(&self.backups, keep.max(1));
+        let (mut removed, kept): (Vec<Backup>, Vec<Backup>) = std::mem::take(&mut self.backups)
+            .into_iter()
+            .partition(|b| expired.contains(&b.id));
+        removed.sort_by_key(|b| std::cmp::Reverse(b.id));
+        self.backups = kept;
+        self.backups.sort_by_key(|b| b.id);
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(ids: &[u32]) -> BackupHistory {
+        BackupHistory {
+            backups: ids
+                .iter()
+                .map(|&id| Backup {
+                    id,
+                    trigger: BackupTrigger::Scheduled,
+                    location: BackupLocation::Local,
+                    created_at: Utc::now(),
+                    duration_secs: 0,
+                    artifacts: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn ids(backups: &[Backup]) -> Vec<u32> {
+        backups.iter().map(|b| b.id).collect()
+    }
+
+    #[test]
+    fn prune_removes_the_oldest_but_never_the_last_backup() {
+        let mut history = history(&[3, 1, 4, 2, 5]);
+        assert_eq!(ids(&history.prune(2)), vec![3, 2, 1]);
+        assert_eq!(ids(&history.backups), vec![4, 5]);
+
+        assert_eq!(ids(&history.prune(0)), vec![4]);
+        assert_eq!(ids(&history.backups), vec![5]);
+        assert!(BackupHistory::default().prune(3).is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::history::{self, Numbered};
+use crate::config::paths;
+use crate::error::DockyardError;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CronTrigger {
+    Scheduled,
+    Manual,
+}
+
+impl std::fmt::Display for CronTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CronTrigger::Scheduled => write!(f, "scheduled"),
+            CronTrigger::Manual => write!(f, "manual"),
+        }
+    }
+}
+
+/// One execution of a cron job; its output is kept in a log file of the same id
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CronRun {
+    pub id: u32,
+    pub job: String,
+    pub trigger: CronTrigger,
+    pub started_at: DateTime<Utc>,
+    /// None while the run is in progress, or when the daemon stopped during it
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+    /// None when the command did not exit on its own (timeout, failed start)
+    #[serde(default)]
+    pub exit_code: Option<i64>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl CronRun {
+    pub fn finished(&self) -> bool {
+        self.duration_secs.is_some() || self.error.is_some()
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+impl Numbered for CronRun {
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// Cron runs of a project, persisted next to its `project.toml`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CronHistory {
+    #[serde(default)]
+    pub runs: Vec<CronRun>,
+}
+
+impl CronHistory {
+    pub fn load(slug: &str) -> Result<Self, DockyardError> {
+        history::load(&paths::project_cron_runs_path(slug))
+    }
+
+    pub fn save(&self, slug: &str) -> Result<(), DockyardError> {
+        history::save(&paths::project_cron_runs_path(slug), self)
+    }
+
+    pub fn next_id(&self) -> u32 {
+        history::next_id(&self.runs)
+    }
+
+    pub fn get(&self, id: u32) -> Option<&CronRun> {
+        history::get(&self.runs, id)
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut CronRun> {
+        self.runs.iter_mut().find(|r| r.id == id)
+    }
+
+    /// Latest run of a job
+    pub fn last(&self, job: &str) -> Option<&CronRun> {
+        self.runs
+            .iter()
+            .filter(|r| r.job == job)
+            .max_by_key(|r| r.id)
+    }
+
+    /// Fail the runs that never finished, because the daemon that ran them stopped.
+    /// Returns their ids.
+    pub fn fail_unfinished(&mut self, reason: &str) -> Vec<u32> {
+        let mut failed = Vec::new();
+        for run in self.runs.iter_mut().filter(|r| !r.finished()) {
+            run.error = Some(reason.to_string());
+            failed.push(run.id);
+        }
+        failed
+    }
+
+    /// Drop all but the newest `keep` runs of `job` from the history and return them
+    pub fn prune(&mut self, job: &str, keep: usize) -> Vec<CronRun> {
+        let expired = history::expired(self.runs.iter().filter(|r| r.job == job), keep);
+        let (removed, kept) = std::mem::take(&mut self.runs)
+            .into_iter()
+            .partition(|r| expired.contains(&r.id));
+        self.runs = kept;
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(runs: &[(u32, &str)]) -> CronHistory {
+        CronHistory {
+            runs: runs
+                .iter()
+                .map(|&(id, job)| CronRun {
+                    id,
+                    job: job.to_string(),
+                    trigger: CronTrigger::Scheduled,
+                    started_at: Utc::now(),
+                    duration_secs: Some(1),
+                    exit_code: Some(0),
+                    error: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn running(id: u32, job: &str) -> CronRun {
+        CronRun {
+            duration_secs: None,
+            exit_code: None,
+            ..history(&[(id, job)]).runs.remove(0)
+        }
+    }
+
+    fn ids(runs: &[CronRun]) -> Vec<u32> {
+        runs.iter().map(|r| r.id).collect()
+    }
+
+    #[test]
+    fn prune_only_drops_old_runs_of_the_given_job() {
+        let mut history = history(&[(1, "sync"), (2, "mail"), (3, "sync"), (4, "sync")]);
+        assert_eq!(ids(&history.prune("sync", 2)), vec![1]);
+        assert_eq!(ids(&history.runs), vec![2, 3, 4]);
+        assert_eq!(history.last("sync").map(|r| r.id), Some(4));
+
+        assert_eq!(ids(&history.prune("mail", 0)), vec![2]);
+        assert_eq!(ids(&history.runs), vec![3, 4]);
+        assert!(history.prune("report", 0).is_empty());
+    }
+
+    #[test]
+    fn unfinished_runs_are_failed_and_finished_ones_kept() {
+        let mut history = history(&[(1, "sync")]);
+        history.runs.push(running(2, "sync"));
+        history.runs.push(running(3, "mail"));
+        assert!(!history.runs[1].finished());
+
+        assert_eq!(history.fail_unfinished("interrupted"), vec![2, 3]);
+        assert!(history.runs.iter().all(CronRun::finished));
+        assert_eq!(history.runs[0].error, None);
+        assert_eq!(history.runs[1].error.as_deref(), Some("interrupted"));
+        assert!(!history.runs[1].succeeded());
+        assert!(history.fail_unfinished("interrupted").is_empty());
+    }
+}
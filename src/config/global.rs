@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
-use crate::config::paths;
+use crate::config::{paths, secrets};
 use crate::error::DockyardError;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -14,6 +14,8 @@ pub struct GlobalConfig {
     pub daemon: DaemonConfig,
     #[serde(default)]
     pub caddy: CaddyConfig,
+    #[serde(default)]
+    pub backup: BackupStorageConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -48,6 +50,15 @@ pub struct DaemonConfig {
     pub socket_path: PathBuf,
     #[serde(default = "default_log_level")]
     pub log_level: String,
+    /// Number of release images kept per project for rollbacks
+    #[serde(default = "default_keep_releases")]
+    pub keep_releases: usize,
+    /// Seconds the previous container keeps running after a blue-green switch
+    #[serde(default = "default_drain_secs")]
+    pub drain_secs: u64,
+    /// Largest build context, in megabytes, sent to Docker
+    #[serde(default = "default_max_context_mb")]
+    pub max_context_mb: u64,
 }
 
 impl Default for DaemonConfig {
@@ -56,6 +67,9 @@ impl Default for DaemonConfig {
             webhook_port: default_webhook_port(),
             socket_path: default_socket_path(),
             log_level: default_log_level(),
+            keep_releases: default_keep_releases(),
+            drain_secs: default_drain_secs(),
+            max_context_mb: default_max_context_mb(),
         }
     }
 }
@@ -72,16 +86,32 @@ fn default_log_level() -> String {
     "info".to_string()
 }
 
+fn default_keep_releases() -> usize {
+    5
+}
+
+fn default_drain_secs() -> u64 {
+    10
+}
+
+fn default_max_context_mb() -> u64 {
+    1024
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CaddyConfig {
     #[serde(default = "default_caddy_admin")]
     pub admin_api: String,
+    /// Addresses of the `dockyard` server block created when Caddy has none
+    #[serde(default = "default_caddy_listen")]
+    pub listen: Vec<String>,
 }
 
 impl Default for CaddyConfig {
     fn default() -> Self {
         Self {
             admin_api: default_caddy_admin(),
+            listen: default_caddy_listen(),
         }
     }
 }
@@ -90,6 +120,47 @@ fn default_caddy_admin() -> String {
     "http://localhost:2019".to_string()
 }
 
+fn default_caddy_listen() -> Vec<String> {
+    vec![":443".to_string()]
+}
+
+/// Where backup files are kept
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BackupStorageConfig {
+    /// Local directory for backups; also where S3 uploads are staged
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// Upload backups to an S3-compatible bucket instead of keeping them locally
+    #[serde(default)]
+    pub s3: Option<S3Config>,
+}
+
+impl BackupStorageConfig {
+    pub fn local_dir(&self) -> PathBuf {
+        self.dir.clone().unwrap_or_else(paths::backups_dir)
+    }
+}
+
+/// An S3-compatible endpoint (AWS, MinIO, ...), addressed path-style
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct S3Config {
+    /// e.g. `https://s3.eu-west-1.amazonaws.com` or `http://localhost:9000`
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    pub access_key: String,
+    /// Encrypted by the daemon when it finds it in plain text
+    pub secret_key: String,
+    /// Key prefix for all backups
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
 impl Default for GlobalConfig {
     fn default() -> Self {
         Self {
@@ -97,6 +168,7 @@ impl Default for GlobalConfig {
             cloudflare: CloudflareConfig::default(),
             daemon: DaemonConfig::default(),
             caddy: CaddyConfig::default(),
+            backup: BackupStorageConfig::default(),
         }
     }
 }
@@ -113,6 +185,25 @@ impl GlobalConfig {
         }
     }
 
+    /// Encrypt the S3 secret key if it is still in plain text. Returns whether it changed.
+    pub fn seal_secrets(&mut self) -> Result<bool, DockyardError> {
+        match &mut self.backup.s3 {
+            Some(s3) if !secrets::is_sealed(&s3.secret_key) => {
+                s3.secret_key = secrets::seal(&s3.secret_key)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Re-encrypt the sealed values with the active master key; returns how many there were
+    pub fn reseal_secrets(&mut self) -> Result<usize, DockyardError> {
+        match &mut self.backup.s3 {
+            Some(s3) => Ok(secrets::reseal(&mut s3.secret_key)? as usize),
+            None => Ok(0),
+        }
+    }
+
     pub fn save(&self) -> Result<(), DockyardError> {
         let path = paths::global_config_path();
         if let Some(parent) = path.parent() {
@@ -124,3 +215,60 @@ impl GlobalConfig {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::secrets::testing;
+
+    fn with_s3(secret_key: String) -> GlobalConfig {
+        let mut config = GlobalConfig::default();
+        config.backup.s3 = Some(S3Config {
+            endpoint: "http://localhost:9000".to_string(),
+            bucket: "backups".to_string(),
+            region: default_s3_region(),
+            access_key: "minio".to_string(),
+            secret_key,
+            prefix: None,
+        });
+        config
+    }
+
+    #[test]
+    fn rotation_reseals_the_s3_secret_key() {
+        let _keyring = testing::keyring(false);
+        let mut config = with_s3(secrets::seal("s3cr3t").unwrap());
+
+        let new_key = secrets::begin_rotation().unwrap();
+        assert_eq!(config.reseal_secrets().unwrap(), 1);
+        secrets::finish_rotation().unwrap();
+
+        let sealed = &config.backup.s3.as_ref().unwrap().secret_key;
+        assert_eq!(testing::key_of(sealed), new_key);
+        assert_eq!(secrets::reveal(sealed).unwrap(), "s3cr3t");
+    }
+
+    #[test]
+    fn plain_s3_secret_keys_are_sealed_once() {
+        let _keyring = testing::keyring(false);
+        let mut config = with_s3("s3cr3t".to_string());
+        assert!(config.seal_secrets().unwrap());
+
+        let sealed = config.backup.s3.as_ref().unwrap().secret_key.clone();
+        assert!(secrets::is_sealed(&sealed));
+        assert_eq!(secrets::reveal(&sealed).unwrap(), "s3cr3t");
+
+        assert!(!config.seal_secrets().unwrap());
+        assert_eq!(config.backup.s3.unwrap().secret_key, sealed);
+        assert!(!GlobalConfig::default().seal_secrets().unwrap());
+    }
+
+    #[test]
+    fn plain_and_missing_s3_secrets_are_left_alone() {
+        let _keyring = testing::keyring(false);
+        let mut config = with_s3("plain".to_string());
+        assert_eq!(config.reseal_secrets().unwrap(), 0);
+        assert_eq!(config.backup.s3.unwrap().secret_key, "plain");
+        assert_eq!(GlobalConfig::default().reseal_secrets().unwrap(), 0);
+    }
+}
@@ -0,0 +1,106 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::error::DockyardError;
+
+/// An entry of a per-project history (release, backup, cron run), numbered from 1
+pub trait Numbered {
+    fn id(&self) -> u32;
+}
+
+/// Read a history file; a project without one has an empty history
+pub fn load<T: DeserializeOwned + Default>(path: &Path) -> Result<T, DockyardError> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let content = std::fs::read_to_string(path)?;
+    Ok(toml::from_str(&content)?)
+}
+
+pub fn save<T: Serialize>(path: &Path, history: &T) -> Result<(), DockyardError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let content =
+        toml::to_string_pretty(history).map_err(|e| DockyardError::Config(e.to_string()))?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// One past the highest number still in the history
+pub fn next_id<T: Numbered>(entries: &[T]) -> u32 {
+    entries.iter().map(|e| e.id()).max().unwrap_or(0) + 1
+}
+
+pub fn get<T: Numbered>(entries: &[T], id: u32) -> Option<&T> {
+    entries.iter().find(|e| e.id() == id)
+}
+
+/// Numbers of the entries beyond the newest `keep`, newest first
+pub fn expired<'a, T: Numbered + 'a>(
+    entries: impl IntoIterator<Item = &'a T>,
+    keep: usize,
+) -> Vec<u32> {
+    let mut ids: Vec<u32> = entries.into_iter().map(|e| e.id()).collect();
+    ids.sort_by_key(|id| std::cmp::Reverse(*id));
+    ids.into_iter().skip(keep).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+    struct History {
+        #[serde(default)]
+        entries: Vec<Entry>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Entry {
+        id: u32,
+    }
+
+    impl Numbered for Entry {
+        fn id(&self) -> u32 {
+            self.id
+        }
+    }
+
+    fn entries(ids: &[u32]) -> Vec<Entry> {
+        ids.iter().map(|&id| Entry { id }).collect()
+    }
+
+    #[test]
+    fn missing_file_is_an_empty_history_and_saved_ones_load_back() {
+        let dir = std::env::temp_dir().join(format!("dockyard-history-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("project").join("history.toml");
+        assert_eq!(load::<History>(&path).unwrap(), History::default());
+
+        let history = History {
+            entries: entries(&[1, 2]),
+        };
+        save(&path, &history).unwrap();
+        assert_eq!(load::<History>(&path).unwrap(), history);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn next_id_follows_the_highest_number() {
+        assert_eq!(next_id::<Entry>(&[]), 1);
+        assert_eq!(next_id(&entries(&[2, 7, 3])), 8);
+        assert_eq!(get(&entries(&[2, 7, 3]), 7), Some(&Entry { id: 7 }));
+        assert_eq!(get(&entries(&[2, 7, 3]), 4), None);
+    }
+
+    #[test]
+    fn expired_is_everything_past_the_newest_newest_first() {
+        let all = entries(&[3, 1, 4, 2, 5]);
+        assert_eq!(expired(&all, 2), vec![3, 2, 1]);
+        assert_eq!(expired(&all, 0), vec![5, 4, 3, 2, 1]);
+        assert!(expired(&all, 5).is_empty());
+        assert!(expired(&all, 9).is_empty());
+    }
+}
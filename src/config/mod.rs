@@ -1,6 +1,13 @@
+pub mod addon;
+pub mod backup;
+pub mod cron;
 pub mod global;
+pub mod history;
 pub mod paths;
 pub mod project;
+pub mod release;
+pub mod runtime;
+pub mod secrets;
 
 pub use global::GlobalConfig;
 pub use project::ProjectConfig;
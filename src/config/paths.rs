@@ -8,6 +8,11 @@ pub fn global_config_path() -> PathBuf {
     config_dir().join("config.toml")
 }
 
+/// Master key that encrypts project secrets, one hex key per line (active key first)
+pub fn master_key_path() -> PathBuf {
+    config_dir().join("master.key")
+}
+
 pub fn data_dir() -> PathBuf {
     PathBuf::from("/var/lib/dockyard")
 }
@@ -28,10 +33,53 @@ pub fn project_repo_dir(slug: &str) -> PathBuf {
     project_dir(slug).join("repo")
 }
 
+pub fn project_releases_path(slug: &str) -> PathBuf {
+    project_dir(slug).join("releases.toml")
+}
+
+pub fn project_state_path(slug: &str) -> PathBuf {
+    project_dir(slug).join("state.toml")
+}
+
+/// User-supplied Dockerfile that replaces the repository's or the generated one
+pub fn project_dockerfile_override_path(slug: &str) -> PathBuf {
+    project_dir(slug).join("Dockerfile")
+}
+
+pub fn project_backups_path(slug: &str) -> PathBuf {
+    project_dir(slug).join("backups.toml")
+}
+
+pub fn project_cron_runs_path(slug: &str) -> PathBuf {
+    project_dir(slug).join("cron.toml")
+}
+
+/// Output of a cron run
+pub fn project_cron_log_path(slug: &str, run_id: u32) -> PathBuf {
+    project_logs_dir(slug).join(format!("cron-{}.log", run_id))
+}
+
+/// Default location of backup files, one directory per project and backup
+pub fn backups_dir() -> PathBuf {
+    data_dir().join("backups")
+}
+
 pub fn project_logs_dir(slug: &str) -> PathBuf {
     project_dir(slug).join("logs")
 }
 
+pub fn project_build_log_path(slug: &str, build_id: u32) -> PathBuf {
+    project_logs_dir(slug).join(format!("build-{}.log", build_id))
+}
+
+pub fn addons_dir() -> PathBuf {
+    data_dir().join("addons")
+}
+
+pub fn addon_config_path(name: &str) -> PathBuf {
+    addons_dir().join(format!("{}.toml", name))
+}
+
 pub fn socket_path() -> PathBuf {
     PathBuf::from("/var/run/dockyard.sock")
 }
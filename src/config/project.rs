@@ -1,9 +1,9 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use uuid::Uuid;
 
-use crate::config::paths;
+use crate::config::{paths, secrets};
 use crate::error::DockyardError;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -14,9 +14,21 @@ pub struct ProjectConfig {
     pub repo_url: String,
     #[serde(default = "default_branch")]
     pub branch: String,
+    #[serde(default)]
+    pub git: GitConfig,
     pub network_mode: NetworkMode,
+    /// Projects this one may call; internal projects are only reachable through links
+    #[serde(default)]
+    pub links: Vec<String>,
     pub domain: DomainConfig,
     pub container: ContainerConfig,
+    #[serde(default)]
+    pub build: BuildConfig,
+    #[serde(default)]
+    pub backup: BackupConfig,
+    /// Commands run on a schedule, by job name
+    #[serde(default)]
+    pub cron: BTreeMap<String, CronJobConfig>,
     pub webhook: WebhookConfig,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -32,11 +44,24 @@ fn default_enabled() -> bool {
     true
 }
 
+/// What is checked out besides the repository's own files
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct GitConfig {
+    /// Check out submodules, recursively
+    #[serde(default)]
+    pub submodules: bool,
+    /// Download Git LFS files instead of leaving their pointers; needs git-lfs installed
+    #[serde(default)]
+    pub lfs: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum NetworkMode {
     LocalOnly,
     Public,
+    /// No host port; reachable only from linked projects on its own network
+    Internal,
 }
 
 impl std::fmt::Display for NetworkMode {
@@ -44,6 +69,7 @@ impl std::fmt::Display for NetworkMode {
         match self {
             NetworkMode::LocalOnly => write!(f, "Local Only"),
             NetworkMode::Public => write!(f, "Public"),
+            NetworkMode::Internal => write!(f, "Internal"),
         }
     }
 }
@@ -53,24 +79,366 @@ pub struct DomainConfig {
     pub hostname: Option<String>,
     pub container_port: u16,
     pub host_port: u16,
+    /// Take `container_port` from the build (EXPOSE or detected runtime) instead of the user
+    #[serde(default)]
+    pub auto_port: bool,
+    /// Host ports of the replicas after the first, in replica order
+    #[serde(default)]
+    pub replica_ports: Vec<u16>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ContainerConfig {
     pub image_name: String,
     pub container_name: String,
+    /// Dockerfile location, relative to the build context
     #[serde(default = "default_dockerfile")]
     pub dockerfile_path: String,
+    /// Repository subdirectory sent to Docker as the build context (monorepos)
+    #[serde(default)]
+    pub build_context: Option<String>,
+    /// Values marked secret are stored encrypted (see `config::secrets`)
     #[serde(default)]
     pub env_vars: HashMap<String, String>,
+    /// How the running container picks up changed `env_vars`
+    #[serde(default)]
+    pub env_update: EnvUpdateMode,
+    #[serde(default)]
+    pub health_check: HealthCheck,
+    #[serde(default)]
+    pub resources: ResourceLimits,
+    /// Mounts that survive rebuilds; named volumes are only removed with `delete --purge-volumes`
+    #[serde(default)]
+    pub volumes: Vec<VolumeMount>,
+    /// Containers running the release; the proxy spreads traffic across them
+    #[serde(default = "default_replicas")]
+    pub replicas: u32,
+    /// Shell command of the `web` process; the Procfile's, else the image's, when unset
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Process types besides `web` (workers, queue consumers), by name
+    #[serde(default)]
+    pub processes: BTreeMap<String, ProcessConfig>,
+}
+
+/// A process type run from the project's image next to the `web` containers.
+/// It gets no host port or route.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProcessConfig {
+    /// Shell command; taken from the repository's Procfile when unset
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default = "default_replicas")]
+    pub replicas: u32,
+    /// Port the process listens on, reachable from other containers as `<name>.<slug>`.
+    /// Its health check connects to it.
+    #[serde(default)]
+    pub port: Option<u16>,
 }
 
+/// The process type that serves HTTP traffic
+pub const WEB_PROCESS: &str = "web";
+
 fn default_dockerfile() -> String {
     "Dockerfile".to_string()
 }
 
+fn default_replicas() -> u32 {
+    1
+}
+
+/// Upper bound for `replicas`
+pub const MAX_REPLICAS: u32 = 16;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvUpdateMode {
+    /// Recreate the container on its current port; brief downtime
+    Restart,
+    /// Start a new container next to the live one and switch once it is healthy
+    #[default]
+    BlueGreen,
+}
+
+impl std::fmt::Display for EnvUpdateMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvUpdateMode::Restart => write!(f, "restart"),
+            EnvUpdateMode::BlueGreen => write!(f, "blue-green"),
+        }
+    }
+}
+
+impl std::str::FromStr for EnvUpdateMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "restart" => Ok(EnvUpdateMode::Restart),
+            "blue-green" | "blue_green" => Ok(EnvUpdateMode::BlueGreen),
+            _ => Err(format!(
+                "unknown update mode '{}' (expected restart or blue-green)",
+                s
+            )),
+        }
+    }
+}
+
+/// How the daemon decides a container is ready for traffic and still alive
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HealthCheck {
+    /// HTTP path to request; without one only a TCP connection is attempted
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Required HTTP status; any 2xx or 3xx passes when unset
+    #[serde(default)]
+    pub expected_status: Option<u16>,
+    #[serde(default = "default_health_interval")]
+    pub interval_secs: u64,
+    #[serde(default = "default_health_timeout")]
+    pub timeout_secs: u64,
+    /// Consecutive failures after which the container is unhealthy
+    #[serde(default = "default_health_retries")]
+    pub retries: u32,
+    /// Grace period after start during which failures are not counted
+    #[serde(default = "default_health_start_period")]
+    pub start_period_secs: u64,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            path: None,
+            expected_status: None,
+            interval_secs: default_health_interval(),
+            timeout_secs: default_health_timeout(),
+            retries: default_health_retries(),
+            start_period_secs: default_health_start_period(),
+        }
+    }
+}
+
+fn default_health_interval() -> u64 {
+    2
+}
+
+fn default_health_timeout() -> u64 {
+    5
+}
+
+fn default_health_retries() -> u32 {
+    3
+}
+
+fn default_health_start_period() -> u64 {
+    30
+}
+
+/// Resource limits of the project's container; unset fields mean no limit
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// Memory limit in MiB
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    /// Memory plus swap in MiB; -1 allows unlimited swap
+    #[serde(default)]
+    pub memory_swap_mb: Option<i64>,
+    /// Number of CPUs the container may use, enforced as a CFS quota (e.g. 0.5)
+    #[serde(default)]
+    pub cpus: Option<f64>,
+    /// Relative CPU weight when the host is busy (Docker's default is 1024)
+    #[serde(default)]
+    pub cpu_shares: Option<u64>,
+    /// Maximum number of processes and threads
+    #[serde(default)]
+    pub pids_limit: Option<u64>,
+    /// Applied when a container is created; changing them needs a redeploy
+    #[serde(default)]
+    pub ulimits: Vec<Ulimit>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Ulimit {
+    pub name: String,
+    pub soft: i64,
+    pub hard: i64,
+}
+
+impl std::fmt::Display for Ulimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.soft == self.hard {
+            write!(f, "{}={}", self.name, self.soft)
+        } else {
+            write!(f, "{}={}:{}", self.name, self.soft, self.hard)
+        }
+    }
+}
+
+impl std::str::FromStr for Ulimit {
+    type Err = String;
+
+    /// `name=limit` or `name=soft:hard`, as with `docker run --ulimit`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid ulimit '{}' (expected name=soft[:hard])", s);
+        let (name, values) = s.split_once('=').ok_or_else(invalid)?;
+        let (soft, hard) = match values.split_once(':') {
+            Some((soft, hard)) => (soft, hard),
+            None => (values, values),
+        };
+        let soft: i64 = soft.trim().parse().map_err(|_| invalid())?;
+        let hard: i64 = hard.trim().parse().map_err(|_| invalid())?;
+        if name.trim().is_empty() || soft > hard {
+            return Err(invalid());
+        }
+        Ok(Ulimit {
+            name: name.trim().to_string(),
+            soft,
+            hard,
+        })
+    }
+}
+
+/// A named volume or host directory mounted into the project container
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct VolumeMount {
+    /// Volume name, or an absolute host path for a bind mount
+    pub source: String,
+    /// Absolute path inside the container
+    pub target: String,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+impl VolumeMount {
+    pub fn is_bind(&self) -> bool {
+        self.source.starts_with('/')
+    }
+
+    /// Name of the Docker volume backing a named mount; volumes are per project
+    pub fn volume_name(&self, slug: &str) -> Option<String> {
+        (!self.is_bind()).then(|| format!("dockyard-{}-{}", slug, self.source))
+    }
+
+    /// `source:target[:ro]` as accepted by Docker's `Binds`
+    pub fn bind_spec(&self, slug: &str) -> String {
+        let source = self.volume_name(slug).unwrap_or_else(|| self.source.clone());
+        let mut spec = format!("{}:{}", source, self.target);
+        if self.read_only {
+            spec.push_str(":ro");
+        }
+        spec
+    }
+}
+
+impl std::fmt::Display for VolumeMount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.source, self.target)?;
+        if self.read_only {
+            write!(f, ":ro")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for VolumeMount {
+    type Err = String;
+
+    /// `name:/path` or `/host/path:/path`, optionally followed by `:ro`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |why: &str| format!("invalid volume '{}': {}", s, why);
+        let mut parts = s.split(':');
+        let (Some(source), Some(target)) = (parts.next(), parts.next()) else {
+            return Err(invalid("expected name:/path or /host/path:/path"));
+        };
+        let read_only = match parts.next() {
+            None | Some("rw") => false,
+            Some("ro") => true,
+            Some(_) => return Err(invalid("the only options are ro and rw")),
+        };
+        if parts.next().is_some() {
+            return Err(invalid("too many ':'"));
+        }
+        if !target.starts_with('/') || target.trim_end_matches('/').is_empty() {
+            return Err(invalid("the container path must be absolute and not /"));
+        }
+        let valid_name = source
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c));
+        if !source.starts_with('/') && (source.is_empty() || !valid_name) {
+            return Err(invalid(
+                "volume names may only contain letters, digits, '_', '.' and '-'",
+            ));
+        }
+        Ok(VolumeMount {
+            source: source.to_string(),
+            target: target.trim_end_matches('/').to_string(),
+            read_only,
+        })
+    }
+}
+
+/// Options passed to the Docker build, and the command run against the built image
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BuildConfig {
+    /// `--build-arg` values; visible in the image history, so not for secrets
+    #[serde(default)]
+    pub args: HashMap<String, String>,
+    /// Stage of a multi-stage Dockerfile to build
+    #[serde(default)]
+    pub target: Option<String>,
+    /// BuildKit secrets, read in the Dockerfile with `RUN --mount=type=secret,id=<NAME>`;
+    /// stored encrypted
+    #[serde(default)]
+    pub secrets: HashMap<String, String>,
+    /// Shell command run in a one-off container from each new image before it serves
+    /// traffic (migrations, say); a non-zero exit aborts the deploy
+    #[serde(default)]
+    pub release_command: Option<String>,
+}
+
+/// When the project's volumes and add-ons are backed up
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupConfig {
+    /// Cron expression (5 fields, UTC); no scheduled backups when unset
+    #[serde(default)]
+    pub schedule: Option<String>,
+    /// Number of backups kept; older ones are deleted
+    #[serde(default = "default_backup_keep")]
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            schedule: None,
+            keep: default_backup_keep(),
+        }
+    }
+}
+
+fn default_backup_keep() -> usize {
+    7
+}
+
+/// A command run on a schedule in a one-off container from the current release
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CronJobConfig {
+    /// Cron expression (5 fields, UTC)
+    pub schedule: String,
+    /// Shell command line
+    pub command: String,
+    /// The container is stopped once it has run this long
+    #[serde(default = "default_cron_timeout")]
+    pub timeout_secs: u64,
+}
+
+pub fn default_cron_timeout() -> u64 {
+    3600
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WebhookConfig {
+    /// Stored encrypted
     pub secret: String,
     pub github_webhook_id: Option<u64>,
 }
@@ -93,18 +461,33 @@ impl ProjectConfig {
             slug: slug.clone(),
             repo_url,
             branch,
+            git: GitConfig::default(),
             network_mode,
+            links: Vec::new(),
             domain: DomainConfig {
                 hostname,
                 container_port,
                 host_port,
+                auto_port: false,
+                replica_ports: Vec::new(),
             },
             container: ContainerConfig {
                 image_name: format!("dockyard/{}", slug),
                 container_name: format!("dockyard-{}", slug),
                 dockerfile_path: default_dockerfile(),
+                build_context: None,
                 env_vars: HashMap::new(),
+                env_update: EnvUpdateMode::default(),
+                health_check: HealthCheck::default(),
+                resources: ResourceLimits::default(),
+                volumes: Vec::new(),
+                replicas: default_replicas(),
+                command: None,
+                processes: BTreeMap::new(),
             },
+            build: BuildConfig::default(),
+            backup: BackupConfig::default(),
+            cron: BTreeMap::new(),
             webhook: WebhookConfig {
                 secret: crate::utils::generate_webhook_secret(),
                 github_webhook_id: None,
@@ -115,6 +498,145 @@ impl ProjectConfig {
         }
     }
 
+    pub fn is_internal(&self) -> bool {
+        self.network_mode == NetworkMode::Internal
+    }
+
+    /// Name of the container running replica `index`; the first one keeps the project's
+    /// container name. `_` cannot appear in a slug, so these never clash with a project.
+    pub fn replica_name(&self, index: usize) -> String {
+        match index {
+            0 => self.container.container_name.clone(),
+            _ => format!("{}_{}", self.container.container_name, index + 1),
+        }
+    }
+
+    /// Containers of the configured replicas plus any left over from a larger scale
+    pub fn replica_names(&self) -> Vec<String> {
+        let count = (self.container.replicas as usize).max(self.domain.replica_ports.len() + 1);
+        (0..count).map(|i| self.replica_name(i)).collect()
+    }
+
+    /// Host port of replica `index`, once one has been assigned
+    pub fn replica_port(&self, index: usize) -> Option<u16> {
+        match index {
+            0 => Some(self.domain.host_port),
+            _ => self.domain.replica_ports.get(index - 1).copied(),
+        }
+    }
+
+    pub fn set_replica_port(&mut self, index: usize, port: u16) {
+        match index {
+            0 => self.domain.host_port = port,
+            _ => {
+                let ports = &mut self.domain.replica_ports;
+                if ports.len() < index {
+                    ports.resize(index, port);
+                }
+                ports[index - 1] = port;
+            }
+        }
+    }
+
+    /// Command a process type runs: the configured one, else the Procfile's
+    pub fn process_command(
+        &self,
+        process: &str,
+        procfile: &BTreeMap<String, String>,
+    ) -> Option<String> {
+        let configured = match process {
+            WEB_PROCESS => self.container.command.clone(),
+            _ => self
+                .container
+                .processes
+                .get(process)
+                .and_then(|p| p.command.clone()),
+        };
+        configured.or_else(|| procfile.get(process).cloned())
+    }
+
+    /// Name of the container running replica `index` of a non-web process type
+    pub fn worker_name(&self, process: &str, index: usize) -> String {
+        match index {
+            0 => format!("{}_{}", self.container.container_name, process),
+            _ => format!(
+                "{}_{}_{}",
+                self.container.container_name,
+                process,
+                index + 1
+            ),
+        }
+    }
+
+    /// Every non-web container as (process type, replica index, container name)
+    pub fn worker_containers(&self) -> Vec<(String, usize, String)> {
+        self.container
+            .processes
+            .iter()
+            .flat_map(|(process, config)| {
+                (0..config.replicas as usize)
+                    .map(move |i| (process.clone(), i, self.worker_name(process, i)))
+            })
+            .collect()
+    }
+
+    /// Container of replica `index` of a process type, if the config runs that many
+    pub fn process_container(&self, process: &str, index: usize) -> Option<String> {
+        if process == WEB_PROCESS {
+            let replicas = (self.container.replicas as usize).max(1);
+            return (index < replicas).then(|| self.replica_name(index));
+        }
+        let config = self.container.processes.get(process)?;
+        (index < config.replicas as usize).then(|| self.worker_name(process, index))
+    }
+
+    /// All containers of the project, the first `web` replica first
+    pub fn all_containers(&self) -> Vec<String> {
+        let mut names = self.replica_names();
+        names.extend(
+            self.worker_containers()
+                .into_iter()
+                .map(|(_, _, name)| name),
+        );
+        names
+    }
+
+    /// Host ports the proxy balances across, one per replica
+    pub fn upstream_ports(&self) -> Vec<u16> {
+        (0..self.container.replicas as usize)
+            .filter_map(|i| self.replica_port(i))
+            .collect()
+    }
+
+    /// Encrypt the values that are always secret (webhook secret, build secrets) if they
+    /// are still in plain text. Returns whether anything changed.
+    pub fn seal_secrets(&mut self) -> Result<bool, DockyardError> {
+        let mut changed = false;
+        for value in
+            std::iter::once(&mut self.webhook.secret).chain(self.build.secrets.values_mut())
+        {
+            if !secrets::is_sealed(value) {
+                *value = secrets::seal(value)?;
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Re-encrypt every sealed value with the active master key; returns how many
+    pub fn reseal_secrets(&mut self) -> Result<usize, DockyardError> {
+        let mut count = 0;
+        for value in std::iter::once(&mut self.webhook.secret)
+            .chain(self.build.secrets.values_mut())
+            .chain(self.container.env_vars.values_mut())
+        {
+            if secrets::reseal(value)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
     pub fn load(slug: &str) -> Result<Self, DockyardError> {
         let path = paths::project_config_path(slug);
         if !path.exists() {
@@ -0,0 +1,206 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::config::history::{self, Numbered};
+use crate::config::paths;
+use crate::error::DockyardError;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseTrigger {
+    Deploy,
+    Manual,
+    Webhook,
+    Rollback,
+}
+
+impl std::fmt::Display for ReleaseTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReleaseTrigger::Deploy => write!(f, "deploy"),
+            ReleaseTrigger::Manual => write!(f, "manual"),
+            ReleaseTrigger::Webhook => write!(f, "webhook"),
+            ReleaseTrigger::Rollback => write!(f, "rollback"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Release {
+    pub id: u32,
+    pub commit_sha: Option<String>,
+    /// Tag or branch the commit was resolved from, for rebuilds of a given ref
+    #[serde(default)]
+    pub git_ref: Option<String>,
+    pub image_id: String,
+    pub image_tag: String,
+    pub trigger: ReleaseTrigger,
+    pub created_at: DateTime<Utc>,
+    pub duration_secs: u64,
+    /// Release whose image was redeployed, for rollbacks
+    #[serde(default)]
+    pub source_release: Option<u32>,
+    /// Set once the image tag has been removed by retention
+    #[serde(default)]
+    pub pruned: bool,
+    /// Process commands from the Procfile the image was built from
+    #[serde(default)]
+    pub procfile: BTreeMap<String, String>,
+}
+
+impl Numbered for Release {
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// Release history of a project, persisted next to its `project.toml`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ReleaseHistory {
+    pub current: Option<u32>,
+    #[serde(default)]
+    pub releases: Vec<Release>,
+}
+
+impl ReleaseHistory {
+    pub fn load(slug: &str) -> Result<Self, DockyardError> {
+        history::load(&paths::project_releases_path(slug))
+    }
+
+    pub fn save(&self, slug: &str) -> Result<(), DockyardError> {
+        history::save(&paths::project_releases_path(slug), self)
+    }
+
+    pub fn next_id(&self) -> u32 {
+        history::next_id(&self.releases)
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Release> {
+        history::get(&self.releases, id)
+    }
+
+    /// Append a release and mark it as the one currently running
+    pub fn record(&mut self, release: Release) {
+        self.current = Some(release.id);
+        self.releases.push(release);
+    }
+
+    /// The most recent release before the current one that still has an image
+    pub fn previous(&self) -> Option<&Release> {
+        let current = self.current?;
+        self.releases
+            .iter()
+            .filter(|r| r.id < current && !r.pruned)
+            .max_by_key(|r| r.id)
+    }
+
+    /// Mark all but the newest `keep` releases as pruned and return their image tags
+    pub fn prune(&mut self, keep: usize) -> Vec<String> {
+        let live = self.releases.iter().filter(|r| !r.pruned);
+        let expired = history::expired(live, keep.max(1));
+
+        let mut removed = Vec::new();
+        for id in expired {
+            if Some(id) == self.current {
+                continue;
+            }
+            if let Some(release) = self.releases.iter_mut().find(|r| r.id == id) {
+                release.pruned = true;
+                removed.push(release.image_tag.clone());
+            }
+        }
+        removed
+    }
+
+    /// Image tags of all releases that have not been pruned yet
+    pub fn live_tags(&self) -> Vec<String> {
+        self.releases
+            .iter()
+            .filter(|r| !r.pruned)
+            .map(|r| r.image_tag.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(ids: &[u32], current: Option<u32>) -> ReleaseHistory {
+        ReleaseHistory {
+            current,
+            releases: ids
+                .iter()
+                .map(|&id| Release {
+                    id,
+                    commit_sha: None,
+                    git_ref: None,
+                    image_id: format!("sha256:{}", id),
+                    image_tag: format!("dockyard/app:{}", id),
+                    trigger: ReleaseTrigger::Deploy,
+                    created_at: Utc::now(),
+                    duration_secs: 0,
+                    source_release: None,
+                    pruned: false,
+                    procfile: BTreeMap::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn live_ids(history: &ReleaseHistory) -> Vec<u32> {
+        history
+            .releases
+            .iter()
+            .filter(|r| !r.pruned)
+            .map(|r| r.id)
+            .collect()
+    }
+
+    #[test]
+    fn prune_keeps_the_newest_releases() {
+        let mut history = history(&[1, 2, 3, 4, 5], Some(5));
+        assert_eq!(
+            history.prune(2),
+            vec!["dockyard/app:3", "dockyard/app:2", "dockyard/app:1"]
+        );
+        assert_eq!(live_ids(&history), vec![4, 5]);
+        // Already pruned releases are not returned again
+        assert!(history.prune(2).is_empty());
+    }
+
+    #[test]
+    fn prune_keeps_the_current_release_after_a_rollback() {
+        let mut history = history(&[1, 2, 3, 4, 5], Some(2));
+        history.prune(2);
+        assert_eq!(live_ids(&history), vec![2, 4, 5]);
+    }
+
+    #[test]
+    fn prune_keeps_at_least_one_release() {
+        let mut history = history(&[1, 2], Some(2));
+        assert_eq!(history.prune(0), vec!["dockyard/app:1"]);
+        assert_eq!(live_ids(&history), vec![2]);
+    }
+
+    #[test]
+    fn previous_skips_pruned_releases() {
+        let mut history = history(&[1, 2, 3], Some(3));
+        assert_eq!(history.previous().map(|r| r.id), Some(2));
+        history.releases[1].pruned = true;
+        assert_eq!(history.previous().map(|r| r.id), Some(1));
+    }
+
+    #[test]
+    fn previous_of_the_oldest_or_no_release_is_none() {
+        assert!(history(&[1, 2, 3], Some(1)).previous().is_none());
+        assert!(history(&[1, 2, 3], None).previous().is_none());
+        assert!(history(&[], None).previous().is_none());
+    }
+
+    #[test]
+    fn previous_of_a_missing_current_release_is_the_one_before_it() {
+        assert_eq!(history(&[1, 2], Some(7)).previous().map(|r| r.id), Some(2));
+    }
+}
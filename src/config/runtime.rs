@@ -0,0 +1,117 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::paths;
+use crate::error::DockyardError;
+use crate::models::project::{DeployPhase, ProjectState};
+
+/// Daemon-owned lifecycle state of a project, persisted as `state.toml`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RuntimeState {
+    pub state: ProjectState,
+    #[serde(default)]
+    pub phase: Option<DeployPhase>,
+    #[serde(default)]
+    pub container_id: Option<String>,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    #[serde(default)]
+    pub last_deploy: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Default for RuntimeState {
+    fn default() -> Self {
+        Self {
+            state: ProjectState::Offline,
+            phase: None,
+            container_id: None,
+            last_error: None,
+            last_deploy: None,
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+impl RuntimeState {
+    pub fn load(slug: &str) -> Result<Self, DockyardError> {
+        let path = paths::project_state_path(slug);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        let state: RuntimeState = toml::from_str(&content)?;
+        Ok(state)
+    }
+
+    pub fn save(&self, slug: &str) -> Result<(), DockyardError> {
+        let dir = paths::project_dir(slug);
+        std::fs::create_dir_all(&dir)?;
+        let path = paths::project_state_path(slug);
+        let content =
+            toml::to_string_pretty(self).map_err(|e| DockyardError::Config(e.to_string()))?;
+        std::fs::write(&path, content)?;
+        Ok(())
+    }
+
+    /// Whether a deploy/rebuild job currently owns the project
+    pub fn is_busy(&self) -> bool {
+        matches!(
+            self.state,
+            ProjectState::Building | ProjectState::Rebuilding
+        )
+    }
+
+    /// Enter a job phase; `state` is Building for first deploys, Rebuilding otherwise
+    pub fn begin(&mut self, state: ProjectState, phase: DeployPhase) {
+        self.state = state;
+        self.phase = Some(phase);
+        self.updated_at = Utc::now();
+    }
+
+    pub fn succeed(&mut self, container_id: String) {
+        self.state = ProjectState::Online;
+        self.phase = None;
+        self.container_id = Some(container_id);
+        self.last_error = None;
+        self.last_deploy = Some(Utc::now());
+        self.updated_at = Utc::now();
+    }
+
+    pub fn fail(&mut self, state: ProjectState, error: String) {
+        self.state = state;
+        self.phase = None;
+        self.last_error = Some(error);
+        self.updated_at = Utc::now();
+    }
+
+    /// Leave a phase that did not deploy anything, e.g. a restore
+    pub fn finish(&mut self, observed: ProjectState) {
+        self.state = observed;
+        self.phase = None;
+        self.updated_at = Utc::now();
+    }
+
+    /// Reconcile with the container state reported by Docker
+    pub fn observe(&mut self, observed: ProjectState) {
+        if self.is_busy() || self.state == observed {
+            return;
+        }
+        // A missing container does not clear a recorded failure
+        if self.state == ProjectState::Error && observed == ProjectState::Offline {
+            return;
+        }
+        self.state = observed;
+        self.updated_at = Utc::now();
+    }
+
+    /// Jobs do not survive a daemon restart; drop their transient state
+    pub fn recover(&mut self) {
+        if self.is_busy() || self.phase.is_some() {
+            self.state = ProjectState::Offline;
+            self.phase = None;
+            self.last_error = Some("interrupted by daemon restart".to_string());
+            self.updated_at = Utc::now();
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::config::paths;
+use crate::error::DockyardError;
+
+/// Environment variable that supplies the master key instead of the key file
+pub const MASTER_KEY_ENV: &str = "DOCKYARD_MASTER_KEY";
+
+/// Prefix of values encrypted with the master key: `enc:v1:<key id>:<hex nonce + ciphertext>`
+const SEALED_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+/// Shown in place of a secret value in API responses
+pub const REDACTED: &str = "********";
+
+struct MasterKey {
+    id: String,
+    cipher: ChaCha20Poly1305,
+    hex: String,
+}
+
+impl MasterKey {
+    fn generate() -> Self {
+        Self::from_bytes(&ChaCha20Poly1305::generate_key(&mut OsRng))
+    }
+
+    fn from_hex(hex_key: &str) -> Result<Self, DockyardError> {
+        let bytes = hex::decode(hex_key.trim())
+            .ok()
+            .filter(|b| b.len() == 32)
+            .ok_or_else(|| {
+                DockyardError::Secrets("master key must be 64 hex characters".to_string())
+            })?;
+        Ok(Self::from_bytes(Key::from_slice(&bytes)))
+    }
+
+    fn from_bytes(key: &Key) -> Self {
+        // Short fingerprint stored with every value so rotation knows which key sealed it
+        let id = hex::encode(&Sha256::digest(key)[..4]);
+        Self {
+            id,
+            cipher: ChaCha20Poly1305::new(key),
+            hex: hex::encode(key),
+        }
+    }
+}
+
+/// Active key first; the others are only kept to decrypt values during a rotation
+struct Keyring {
+    keys: Vec<MasterKey>,
+    /// Where the keys are persisted; None when they come from `DOCKYARD_MASTER_KEY`
+    file: Option<PathBuf>,
+}
+
+static KEYRING: RwLock<Option<Keyring>> = RwLock::new(None);
+
+/// Load the master key from `DOCKYARD_MASTER_KEY` or the key file, creating the file
+/// with a new key on first start
+pub fn init() -> Result<(), DockyardError> {
+    let keyring = match std::env::var(MASTER_KEY_ENV) {
+        Ok(hex_key) => Keyring {
+            keys: vec![MasterKey::from_hex(&hex_key)?],
+            file: None,
+        },
+        Err(_) => {
+            let path = paths::master_key_path();
+            if path.exists() {
+                let content = std::fs::read_to_string(&path)?;
+                let keys = content
+                    .lines()
+                    .filter(|l| !l.trim().is_empty())
+                    .map(MasterKey::from_hex)
+                    .collect::<Result<Vec<_>, _>>()?;
+                if keys.is_empty() {
+                    return Err(DockyardError::Secrets(format!(
+                        "{} is empty",
+                        path.display()
+                    )));
+                }
+                Keyring {
+                    keys,
+                    file: Some(path),
+                }
+            } else {
+                tracing::info!("Creating master key at {}", path.display());
+                let keyring = Keyring {
+                    keys: vec![MasterKey::generate()],
+                    file: Some(path.clone()),
+                };
+                write_key_file(&path, &keyring)?;
+                keyring
+            }
+        }
+    };
+
+    tracing::info!("Secrets encrypted with master key {}", keyring.keys[0].id);
+    *KEYRING.write().unwrap() = Some(keyring);
+    Ok(())
+}
+
+fn write_key_file(path: &Path, keyring: &Keyring) -> Result<(), DockyardError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let content: String = keyring
+        .keys
+        .iter()
+        .map(|k| format!("{}\n", k.hex))
+        .collect();
+
+    // Write next to the file and rename so a crash never leaves a truncated key
+    let tmp = path.with_extension("key.tmp");
+    {
+        use std::io::Write;
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+pub fn is_sealed(value: &str) -> bool {
+    value.starts_with(SEALED_PREFIX)
+}
+
+/// Plain values are stored as they are, so one that starts like an encrypted value
+/// would later fail to decrypt
+pub fn check_plain(value: &str) -> Result<(), DockyardError> {
+    if is_sealed(value) {
+        return Err(DockyardError::Secrets(format!(
+            "plain values cannot start with '{}'; store it as a secret instead",
+            SEALED_PREFIX
+        )));
+    }
+    Ok(())
+}
+
+/// Encrypt a value with the active master key. Every value is encrypted, even one that
+/// already looks sealed: callers pass plain text.
+pub fn seal(value: &str) -> Result<String, DockyardError> {
+    let guard = KEYRING.read().unwrap();
+    let key = &guard
+        .as_ref()
+        .ok_or_else(|| DockyardError::Secrets("master key not loaded".to_string()))?
+        .keys[0];
+
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = key
+        .cipher
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| DockyardError::Secrets("encryption failed".to_string()))?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!(
+        "{}{}:{}",
+        SEALED_PREFIX,
+        key.id,
+        hex::encode(payload)
+    ))
+}
+
+/// Decrypt a sealed value; plain values are returned unchanged
+pub fn reveal(value: &str) -> Result<String, DockyardError> {
+    let Some(rest) = value.strip_prefix(SEALED_PREFIX) else {
+        return Ok(value.to_string());
+    };
+    let (key_id, payload) = rest
+        .split_once(':')
+        .ok_or_else(|| DockyardError::Secrets("malformed encrypted value".to_string()))?;
+    let payload = hex::decode(payload)
+        .ok()
+        .filter(|p| p.len() > NONCE_LEN)
+        .ok_or_else(|| DockyardError::Secrets("malformed encrypted value".to_string()))?;
+
+    let guard = KEYRING.read().unwrap();
+    let keyring = guard
+        .as_ref()
+        .ok_or_else(|| DockyardError::Secrets("master key not loaded".to_string()))?;
+    let key = keyring
+        .keys
+        .iter()
+        .find(|k| k.id == key_id)
+        .ok_or_else(|| {
+            DockyardError::Secrets(format!(
+                "value was encrypted with unknown master key {}",
+                key_id
+            ))
+        })?;
+
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    let plain = key
+        .cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| DockyardError::Secrets("decryption failed".to_string()))?;
+    String::from_utf8(plain)
+        .map_err(|_| DockyardError::Secrets("decrypted value is not UTF-8".to_string()))
+}
+
+/// Plain-text copy of a set of variables, for handing to Docker
+pub fn reveal_all(
+    vars: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, DockyardError> {
+    vars.iter()
+        .map(|(k, v)| Ok((k.clone(), reveal(v)?)))
+        .collect()
+}
+
+/// Re-encrypt a sealed value with the active key; plain values are left alone
+pub fn reseal(value: &mut String) -> Result<bool, DockyardError> {
+    if !is_sealed(value) {
+        return Ok(false);
+    }
+    *value = seal(&reveal(value)?)?;
+    Ok(true)
+}
+
+/// Make a new key active while keeping the old ones for decryption. Values must then
+/// be resealed before [`finish_rotation`] drops the old keys. Returns the new key ID.
+pub fn begin_rotation() -> Result<String, DockyardError> {
+    let mut guard = KEYRING.write().unwrap();
+    let keyring = guard
+        .as_mut()
+        .ok_or_else(|| DockyardError::Secrets("master key not loaded".to_string()))?;
+    let Some(path) = keyring.file.clone() else {
+        return Err(DockyardError::Secrets(format!(
+            "the master key comes from {}; unset it so dockyard can manage {}",
+            MASTER_KEY_ENV,
+            paths::master_key_path().display()
+        )));
+    };
+
+    let key = MasterKey::generate();
+    let id = key.id.clone();
+    keyring.keys.insert(0, key);
+    // Persist before anything is encrypted with the new key
+    write_key_file(&path, keyring)?;
+    Ok(id)
+}
+
+/// Forget every key but the active one
+pub fn finish_rotation() -> Result<(), DockyardError> {
+    let mut guard = KEYRING.write().unwrap();
+    let keyring = guard
+        .as_mut()
+        .ok_or_else(|| DockyardError::Secrets("master key not loaded".to_string()))?;
+    keyring.keys.truncate(1);
+    match keyring.file.clone() {
+        Some(path) => write_key_file(&path, keyring),
+        None => Ok(()),
+    }
+}
+
+/// Process-wide keyring for unit tests, with its key file under a temporary directory
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    /// Install a fresh keyring; the guard keeps other tests off it until dropped.
+    /// With `from_env` it behaves as if `DOCKYARD_MASTER_KEY` supplied the key.
+    pub fn keyring(from_env: bool) -> MutexGuard<'static, ()> {
+        let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let file = (!from_env).then(|| {
+            let dir = std::env::temp_dir().join(format!("dockyard-keys-{}", uuid::Uuid::new_v4()));
+            dir.join("master.key")
+        });
+        *KEYRING.write().unwrap() = Some(Keyring {
+            keys: vec![MasterKey::generate()],
+            file,
+        });
+        guard
+    }
+
+    /// ID of the key a sealed value names
+    pub fn key_of(value: &str) -> &str {
+        value
+            .strip_prefix(SEALED_PREFIX)
+            .and_then(|rest| rest.split_once(':'))
+            .map(|(id, _)| id)
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{key_of, keyring};
+    use super::*;
+
+    #[test]
+    fn sealed_values_round_trip() {
+        let _keyring = keyring(false);
+        let sealed = seal("postgres://app:hunter2@db/app").unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("hunter2"));
+        assert_eq!(reveal(&sealed).unwrap(), "postgres://app:hunter2@db/app");
+
+        // Each seal uses a fresh nonce
+        assert_ne!(seal("same").unwrap(), seal("same").unwrap());
+    }
+
+    #[test]
+    fn input_that_looks_sealed_is_encrypted_too() {
+        let _keyring = keyring(false);
+        let lookalike = "enc:v1:deadbeef:00";
+        let sealed = seal(lookalike).unwrap();
+        assert_ne!(sealed, lookalike);
+        assert_eq!(reveal(&sealed).unwrap(), lookalike);
+
+        assert!(check_plain(lookalike).is_err());
+        assert!(check_plain("postgres://db/app").is_ok());
+    }
+
+    #[test]
+    fn plain_values_pass_through() {
+        let _keyring = keyring(false);
+        assert_eq!(reveal("not secret").unwrap(), "not secret");
+        assert_eq!(reveal("").unwrap(), "");
+
+        let mut plain = "left alone".to_string();
+        assert!(!reseal(&mut plain).unwrap());
+        assert_eq!(plain, "left alone");
+    }
+
+    #[test]
+    fn unknown_and_malformed_values_error() {
+        let _keyring = keyring(false);
+        let sealed = seal("value").unwrap();
+        // A keyring that never had the key
+        *KEYRING.write().unwrap() = Some(Keyring {
+            keys: vec![MasterKey::generate()],
+            file: None,
+        });
+        let err = reveal(&sealed).unwrap_err().to_string();
+        assert!(err.contains(key_of(&sealed)), "{}", err);
+
+        assert!(reveal("enc:v1:nokeyid").is_err());
+        assert!(reveal("enc:v1:abcd:zz").is_err());
+    }
+
+    #[test]
+    fn rotation_keeps_old_values_readable_until_finished() {
+        let _keyring = keyring(false);
+        let mut resealed = seal("resealed").unwrap();
+        let forgotten = seal("forgotten").unwrap();
+        let old_key = key_of(&forgotten).to_string();
+
+        let new_key = begin_rotation().unwrap();
+        assert_ne!(new_key, old_key);
+        assert_eq!(reveal(&forgotten).unwrap(), "forgotten");
+        assert_eq!(key_of(&seal("new").unwrap()), new_key);
+
+        assert!(reseal(&mut resealed).unwrap());
+        assert_eq!(key_of(&resealed), new_key);
+        assert_eq!(reveal(&forgotten).unwrap(), "forgotten");
+
+        finish_rotation().unwrap();
+        assert_eq!(reveal(&resealed).unwrap(), "resealed");
+        assert!(reveal(&forgotten).is_err());
+    }
+
+    #[test]
+    fn key_from_the_environment_blocks_rotation() {
+        let _keyring = keyring(true);
+        std::env::set_var(MASTER_KEY_ENV, "11".repeat(32));
+        let loaded = init();
+        std::env::remove_var(MASTER_KEY_ENV);
+        loaded.unwrap();
+
+        let sealed = seal("value").unwrap();
+        let err = begin_rotation().unwrap_err().to_string();
+        assert!(err.contains(MASTER_KEY_ENV), "{}", err);
+        // Nothing was rotated
+        assert_eq!(reveal(&sealed).unwrap(), "value");
+    }
+
+    #[test]
+    fn environment_key_must_be_32_bytes_of_hex() {
+        let _keyring = keyring(true);
+        std::env::set_var(MASTER_KEY_ENV, "abc");
+        let loaded = init();
+        std::env::remove_var(MASTER_KEY_ENV);
+        assert!(loaded.is_err());
+    }
+}
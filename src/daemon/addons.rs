@@ -0,0 +1,421 @@
+use crate::config::addon::{AddonConfig, AddonKind};
+use crate::config::project::HealthCheck;
+use crate::config::release::ReleaseHistory;
+use crate::daemon::{build_log, health, scheduler, DaemonState};
+use crate::docker::client::ContainerSpec;
+use crate::docker::network;
+use crate::error::DockyardError;
+use crate::ipc::protocol::*;
+use crate::models::project::ProjectState;
+
+/// Databases can take a while to initialise their data directory on first start
+const READY_GRACE_SECS: u64 = 120;
+
+impl DaemonState {
+    pub async fn list_addons(&self) -> Result<Vec<AddonInfo>, DockyardError> {
+        let addons: Vec<AddonConfig> = self.addons.read().await.values().cloned().collect();
+
+        let mut infos = Vec::new();
+        for addon in addons {
+            let state = self
+                .docker
+                .get_container_state(&addon.container_name())
+                .await
+                .unwrap_or(ProjectState::Offline);
+            infos.push(AddonInfo {
+                address: format!("{}:{}", addon.name, addon.kind.port()),
+                name: addon.name,
+                kind: addon.kind,
+                version: addon.version,
+                project: addon.project,
+                env_var: addon.env_var,
+                state,
+            });
+        }
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(infos)
+    }
+
+    /// Start a new add-on with generated credentials, attaching it to a project if asked
+    pub async fn create_addon(
+        &self,
+        req: AddonCreateRequest,
+    ) -> Result<AddonResponse, DockyardError> {
+        let name = match (req.name, &req.project) {
+            (Some(name), _) => name,
+            (None, Some(project)) => format!("{}-{}", project, req.kind),
+            (None, None) => req.kind.to_string(),
+        };
+        if name.is_empty() || crate::utils::slugify(&name) != name {
+            return Err(DockyardError::Addon(format!(
+                "invalid name '{}' (use lowercase letters, digits and '-')",
+                name
+            )));
+        }
+        let version = req
+            .version
+            .unwrap_or_else(|| req.kind.default_version().to_string());
+        let valid_version = !version.is_empty()
+            && version
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c));
+        if !valid_version {
+            return Err(DockyardError::Addon(format!("invalid version '{}'", version)));
+        }
+
+        let mut addon = AddonConfig::new(name.clone(), req.kind, version)?;
+        if let Some(env_var) = req.env_var {
+            addon.env_var = env_var;
+        }
+        addon.project = req.project;
+        {
+            let projects = self.projects.read().await;
+            let mut addons = self.addons.write().await;
+            if addons.contains_key(&name) {
+                return Err(DockyardError::Addon(format!("'{}' already exists", name)));
+            }
+            // Both are reachable by name on dockyard-network
+            if projects.contains_key(&name) {
+                return Err(DockyardError::Addon(format!(
+                    "a project is already named '{}'",
+                    name
+                )));
+            }
+            if let Some(project) = &addon.project {
+                if !projects.contains_key(project) {
+                    return Err(DockyardError::ProjectNotFound(project.clone()));
+                }
+                validate_env_var(&addons, &name, project, &addon.env_var)?;
+            }
+            // Reserved before anything is pulled or started, so a concurrent create of
+            // the same name fails above instead of replacing this add-on's container
+            addons.insert(name.clone(), addon.clone());
+        }
+
+        let created = async {
+            // The engine only applies credentials when it initialises an empty volume
+            let volume = addon.volume_name();
+            if self.docker.inner().inspect_volume(&volume).await.is_ok() {
+                return Err(DockyardError::Addon(format!(
+                    "volume {} is left from an earlier add-on; remove it or choose another name",
+                    volume
+                )));
+            }
+            if let Err(e) = self.run_addon(&addon).await {
+                let _ = self.docker.remove_container(&addon.container_name()).await;
+                let _ = self.docker.remove_volume(&volume).await;
+                return Err(e);
+            }
+            addon.save()
+        }
+        .await;
+        if let Err(e) = created {
+            self.addons.write().await.remove(&name);
+            return Err(e);
+        }
+        tracing::info!("Created {} add-on '{}'", addon.kind, name);
+
+        let mut message = format!(
+            "{} add-on '{}' running at {}:{}",
+            addon.kind,
+            name,
+            name,
+            addon.kind.port()
+        );
+        let build_id = match &addon.project {
+            Some(project) => {
+                message.push_str(&format!("; {} is set for '{}'", addon.env_var, project));
+                self.apply_addon_env(project, &format!("attach add-on {}", name))
+                    .await?
+            }
+            None => None,
+        };
+        Ok(AddonResponse {
+            message,
+            project: addon.project,
+            build_id,
+        })
+    }
+
+    /// Inject an add-on's connection URL into a project, moving it from its current one
+    pub async fn attach_addon(
+        &self,
+        name: &str,
+        req: AddonAttachRequest,
+    ) -> Result<AddonResponse, DockyardError> {
+        let (addon, previous) = {
+            let projects = self.projects.read().await;
+            let mut addons = self.addons.write().await;
+            if !projects.contains_key(&req.project) {
+                return Err(DockyardError::ProjectNotFound(req.project.clone()));
+            }
+            let current = addons
+                .get(name)
+                .ok_or_else(|| DockyardError::AddonNotFound(name.to_string()))?;
+            let env_var = req.env_var.unwrap_or_else(|| current.env_var.clone());
+            validate_env_var(&addons, name, &req.project, &env_var)?;
+
+            let addon = addons.get_mut(name).expect("checked above");
+            let previous = addon.project.replace(req.project.clone());
+            addon.env_var = env_var;
+            addon.save()?;
+            (addon.clone(), previous)
+        };
+
+        if let Some(previous) = previous.as_deref().filter(|p| *p != req.project) {
+            self.leave_project_network(&addon, previous).await;
+        }
+        self.join_project_network(&addon, &req.project).await?;
+
+        let reason = format!("attach add-on {}", name);
+        if let Some(previous) = previous.as_deref().filter(|p| *p != req.project) {
+            self.apply_addon_env(previous, &format!("detach add-on {}", name))
+                .await?;
+        }
+        let build_id = self.apply_addon_env(&req.project, &reason).await?;
+
+        Ok(AddonResponse {
+            message: format!(
+                "Add-on '{}' attached to '{}' as {}",
+                name, req.project, addon.env_var
+            ),
+            project: Some(req.project),
+            build_id,
+        })
+    }
+
+    /// Stop injecting an add-on into its project; the database keeps running
+    pub async fn detach_addon(&self, name: &str) -> Result<AddonResponse, DockyardError> {
+        let (addon, project) = {
+            let mut addons = self.addons.write().await;
+            let addon = addons
+                .get_mut(name)
+                .ok_or_else(|| DockyardError::AddonNotFound(name.to_string()))?;
+            let project = addon.project.take().ok_or_else(|| {
+                DockyardError::Addon(format!("'{}' is not attached to a project", name))
+            })?;
+            addon.save()?;
+            (addon.clone(), project)
+        };
+
+        self.leave_project_network(&addon, &project).await;
+        let build_id = self
+            .apply_addon_env(&project, &format!("detach add-on {}", name))
+            .await?;
+
+        Ok(AddonResponse {
+            message: format!("Add-on '{}' detached from '{}'", name, project),
+            project: Some(project),
+            build_id,
+        })
+    }
+
+    /// Remove a detached add-on's container and, unless `keep_data`, its data volume
+    pub async fn destroy_addon(&self, name: &str, keep_data: bool) -> Result<String, DockyardError> {
+        let addon = {
+            let addons = self.addons.read().await;
+            let addon = addons
+                .get(name)
+                .ok_or_else(|| DockyardError::AddonNotFound(name.to_string()))?;
+            if let Some(project) = &addon.project {
+                return Err(DockyardError::Addon(format!(
+                    "'{}' is attached to '{}'; detach it first",
+                    name, project
+                )));
+            }
+            addon.clone()
+        };
+
+        let container = addon.container_name();
+        let _ = self.docker.stop_container(&container).await;
+        let _ = self.docker.remove_container(&container).await;
+        if !keep_data {
+            self.docker.remove_volume(&addon.volume_name()).await?;
+        }
+
+        AddonConfig::delete(name)?;
+        self.addons.write().await.remove(name);
+        tracing::info!("Destroyed add-on '{}'", name);
+
+        Ok(if keep_data {
+            format!(
+                "Add-on '{}' removed; its data is kept in volume {}",
+                name,
+                addon.volume_name()
+            )
+        } else {
+            format!("Add-on '{}' and its data removed", name)
+        })
+    }
+
+    /// Create and start an add-on's container, waiting until it accepts connections.
+    /// The data volume is reused when it already exists.
+    pub(crate) async fn run_addon(&self, addon: &AddonConfig) -> Result<(), DockyardError> {
+        let container = addon.container_name();
+        let spec = self.addon_spec(addon).await?;
+
+        tracing::info!("[{}] Pulling {}...", addon.name, spec.image);
+        self.docker.pull_image(&spec.image).await?;
+        self.docker
+            .ensure_addon_volume(&addon.volume_name(), &addon.name)
+            .await?;
+
+        let _ = self.docker.remove_container(&container).await;
+        self.docker.create_and_start_container(&spec).await?;
+        self.wait_database_ready(&container, &addon.name, addon.kind)
+            .await
+    }
+
+    /// Wait until a database container accepts connections on its engine's port
+    pub(crate) async fn wait_database_ready(
+        &self,
+        container: &str,
+        name: &str,
+        kind: AddonKind,
+    ) -> Result<(), DockyardError> {
+        if let Some(ip) = self.docker.get_container_ip(container).await? {
+            let check = HealthCheck {
+                start_period_secs: READY_GRACE_SECS,
+                ..Default::default()
+            };
+            let addr = format!("{}:{}", ip, kind.port());
+            health::wait_healthy(&self.docker, container, &addr, &check, |msg| {
+                tracing::warn!("[{}] {}", name, msg)
+            })
+            .await
+            .map_err(|e| DockyardError::Addon(format!("'{}' never became ready: {}", name, e)))?;
+        }
+        Ok(())
+    }
+
+    async fn addon_spec(&self, addon: &AddonConfig) -> Result<ContainerSpec, DockyardError> {
+        let mut networks = vec![network::DOCKYARD_NETWORK.to_string()];
+        // Internal projects are not on dockyard-network, so the database joins theirs
+        if let Some(project) = &addon.project {
+            let internal = self
+                .projects
+                .read()
+                .await
+                .get(project)
+                .is_some_and(|p| p.is_internal());
+            if internal {
+                network::ensure_project_network(&self.docker, project).await?;
+                networks.push(network::project_network(project));
+            }
+        }
+
+        Ok(ContainerSpec {
+            name: addon.container_name(),
+            image: addon.image(),
+            host_port: None,
+            container_port: addon.kind.port(),
+            env_vars: addon.container_env()?,
+            resources: Default::default(),
+            binds: vec![addon.bind_spec()],
+            networks,
+            alias: addon.name.clone(),
+            command: addon.command()?,
+            restart: true,
+            open_stdin: false,
+            tty: false,
+        })
+    }
+
+    /// Connect a running add-on to an internal project's network
+    pub(crate) async fn join_project_network(
+        &self,
+        addon: &AddonConfig,
+        project: &str,
+    ) -> Result<(), DockyardError> {
+        let internal = self
+            .projects
+            .read()
+            .await
+            .get(project)
+            .is_some_and(|p| p.is_internal());
+        if !internal {
+            return Ok(());
+        }
+
+        let container = addon.container_name();
+        let name = network::project_network(project);
+        if self
+            .docker
+            .get_container_networks(&container)
+            .await?
+            .contains(&name)
+        {
+            return Ok(());
+        }
+        network::ensure_project_network(&self.docker, project).await?;
+        self.docker
+            .connect_network(&name, &container, &addon.name)
+            .await
+    }
+
+    async fn leave_project_network(&self, addon: &AddonConfig, project: &str) {
+        let internal = self
+            .projects
+            .read()
+            .await
+            .get(project)
+            .is_some_and(|p| p.is_internal());
+        if internal {
+            let _ = self
+                .docker
+                .disconnect_network(&network::project_network(project), &addon.container_name())
+                .await;
+        }
+    }
+
+    /// Queue the env update that gives a project's container its current add-on
+    /// variables. Returns its build number, or None when nothing is deployed yet.
+    async fn apply_addon_env(
+        &self,
+        slug: &str,
+        reason: &str,
+    ) -> Result<Option<u32>, DockyardError> {
+        let mode = match self.projects.read().await.get(slug) {
+            Some(config) => config.container.env_update,
+            None => return Ok(None),
+        };
+        if ReleaseHistory::load(slug)?.current.is_none() {
+            return Ok(None);
+        }
+
+        let build_id = build_log::BuildLog::create(slug, &format!("{} ({})", reason, mode))?.id();
+        let _ = self
+            .scheduler_tx
+            .send(scheduler::Job::UpdateEnv {
+                slug: slug.to_string(),
+                mode,
+                build_id,
+            })
+            .await;
+        Ok(Some(build_id))
+    }
+}
+
+/// Reject variable names Docker cannot take and two add-ons injecting the same one
+fn validate_env_var(
+    addons: &std::collections::HashMap<String, AddonConfig>,
+    name: &str,
+    project: &str,
+    env_var: &str,
+) -> Result<(), DockyardError> {
+    if env_var.is_empty() || env_var.contains('=') || env_var.contains(char::is_whitespace) {
+        return Err(DockyardError::Config(format!(
+            "Invalid variable name '{}'",
+            env_var
+        )));
+    }
+    if let Some(other) = addons.values().find(|a| {
+        a.name != name && a.project.as_deref() == Some(project) && a.env_var == env_var
+    }) {
+        return Err(DockyardError::Addon(format!(
+            "'{}' already sets {} for '{}'; choose another variable",
+            other.name, env_var, project
+        )));
+    }
+    Ok(())
+}
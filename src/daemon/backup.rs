@@ -0,0 +1,995 @@
+use chrono::Utc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::addon::{AddonConfig, AddonKind};
+use crate::config::backup::{
+    ArtifactKind, Backup, BackupArtifact, BackupHistory, BackupLocation, BackupTrigger,
+};
+use crate::config::global::BackupStorageConfig;
+use crate::config::project::VolumeMount;
+use crate::config::secrets;
+use crate::daemon::s3::S3Client;
+use crate::daemon::scheduler::Job;
+use crate::daemon::DaemonState;
+use crate::docker::client::CommandOutput;
+use crate::error::DockyardError;
+use crate::ipc::protocol::*;
+use crate::models::project::{DeployPhase, ProjectState};
+
+/// Small image with tar, used to read and write volume contents
+const HELPER_IMAGE: &str = "alpine:3";
+
+/// How often backup schedules are checked
+const SCHEDULE_TICK_SECS: u64 = 30;
+
+/// A database saved as a logical dump rather than a copy of its files
+struct DumpTarget {
+    /// Add-on name, or the project's slug when its own image is a database
+    source: String,
+    kind: AddonKind,
+    container: String,
+    user: String,
+    password: Option<String>,
+    /// None dumps every database
+    database: Option<String>,
+    /// Volume holding the engine's data directory; Redis is restored through it
+    data_volume: Option<String>,
+}
+
+impl DumpTarget {
+    fn from_addon(addon: &AddonConfig) -> Result<Self, DockyardError> {
+        Ok(Self {
+            source: addon.name.clone(),
+            kind: addon.kind,
+            container: addon.container_name(),
+            user: addon.user.clone(),
+            password: Some(secrets::reveal(&addon.password)?),
+            database: Some(addon.database.clone()),
+            data_volume: Some(addon.volume_name()),
+        })
+    }
+
+    /// Recognise the official database images by the version variable they set, taking
+    /// the credentials from the environment the container was started with
+    fn detect(
+        slug: &str,
+        container: &str,
+        env: &HashMap<String, String>,
+        mounts: &[VolumeMount],
+    ) -> Option<Self> {
+        let var = |name: &str| env.get(name).filter(|v| !v.is_empty()).cloned();
+        let (kind, user, password, database) = if env.contains_key("PG_VERSION") {
+            let user = var("POSTGRES_USER").unwrap_or_else(|| "postgres".to_string());
+            let database = var("POSTGRES_DB").unwrap_or_else(|| user.clone());
+            (
+                AddonKind::Postgres,
+                user,
+                var("POSTGRES_PASSWORD"),
+                Some(database),
+            )
+        } else if env.contains_key("MYSQL_VERSION") {
+            if let Some(root) = var("MYSQL_ROOT_PASSWORD") {
+                (AddonKind::Mysql, "root".to_string(), Some(root), None)
+            } else if let Some(user) = var("MYSQL_USER") {
+                let database = var("MYSQL_DATABASE");
+                (AddonKind::Mysql, user, var("MYSQL_PASSWORD"), database)
+            } else if var("MYSQL_ALLOW_EMPTY_PASSWORD").is_some() {
+                (AddonKind::Mysql, "root".to_string(), None, None)
+            } else {
+                return None;
+            }
+        } else if env.contains_key("REDIS_VERSION") {
+            (AddonKind::Redis, String::new(), var("REDIS_PASSWORD"), None)
+        } else {
+            return None;
+        };
+
+        let data_volume = mounts
+            .iter()
+            .find(|m| m.target == kind.data_path())
+            .and_then(|m| m.volume_name(slug));
+        Some(Self {
+            source: slug.to_string(),
+            kind,
+            container: container.to_string(),
+            user,
+            password,
+            database,
+            data_volume,
+        })
+    }
+
+    fn file(&self) -> String {
+        let extension = match self.kind {
+            AddonKind::Postgres | AddonKind::Mysql => "sql",
+            AddonKind::Redis => "rdb",
+        };
+        format!("dump-{}.{}", self.source, extension)
+    }
+
+    /// Variable the engine's client tools read the password from
+    fn env(&self) -> Vec<String> {
+        let Some(password) = &self.password else {
+            return Vec::new();
+        };
+        let name = match self.kind {
+            AddonKind::Postgres => "PGPASSWORD",
+            AddonKind::Mysql => "MYSQL_PWD",
+            AddonKind::Redis => "REDISCLI_AUTH",
+        };
+        vec![format!("{}={}", name, password)]
+    }
+
+    /// Command printing the dump on stdout
+    fn dump_command(&self) -> Vec<String> {
+        let mut cmd = match self.kind {
+            AddonKind::Postgres => args(&["pg_dump", "-U", &self.user, "--clean", "--if-exists"]),
+            AddonKind::Mysql => args(&[
+                "mysqldump",
+                "-u",
+                &self.user,
+                "--single-transaction",
+                "--no-tablespaces",
+            ]),
+            // The RDB snapshot is written to a file first; redis-cli's messages go to stderr
+            AddonKind::Redis => args(&[
+                "sh",
+                "-c",
+                "redis-cli --rdb /tmp/dockyard-backup.rdb >&2 && cat /tmp/dockyard-backup.rdb \
+                 && rm -f /tmp/dockyard-backup.rdb",
+            ]),
+        };
+        match (self.kind, &self.database) {
+            (AddonKind::Redis, _) => {}
+            (_, Some(database)) => cmd.push(database.clone()),
+            (AddonKind::Mysql, None) => cmd.push("--all-databases".to_string()),
+            (AddonKind::Postgres, None) => {}
+        }
+        cmd
+    }
+
+    /// Command loading a dump from stdin; None for Redis, which loads its RDB file on start
+    fn restore_command(&self) -> Option<Vec<String>> {
+        let mut cmd = match self.kind {
+            AddonKind::Postgres => args(&["psql", "-q", "-U", &self.user, "-v", "ON_ERROR_STOP=1"]),
+            AddonKind::Mysql => args(&["mysql", "-u", &self.user]),
+            AddonKind::Redis => return None,
+        };
+        if let Some(database) = &self.database {
+            if self.kind == AddonKind::Postgres {
+                cmd.push("-d".to_string());
+            }
+            cmd.push(database.clone());
+        }
+        Some(cmd)
+    }
+}
+
+impl DaemonState {
+    pub async fn list_backups(&self, slug: &str) -> Result<BackupListResponse, DockyardError> {
+        let backup = {
+            let projects = self.projects.read().await;
+            let config = projects
+                .get(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            config.backup.clone()
+        };
+        let storage = self.config.read().await.backup.clone();
+
+        let next_run = backup
+            .schedule
+            .as_deref()
+            .and_then(|s| crate::utils::parse_cron(s).ok())
+            .and_then(|s| s.upcoming(Utc).next());
+        let mut backups = BackupHistory::load(slug)?.backups;
+        backups.sort_by_key(|b| std::cmp::Reverse(b.id));
+
+        Ok(BackupListResponse {
+            slug: slug.to_string(),
+            schedule: backup.schedule,
+            next_run,
+            keep: backup.keep,
+            storage: match &storage.s3 {
+                Some(s3) => format!("s3://{}/{}", s3.bucket, s3.prefix.as_deref().unwrap_or("")),
+                None => storage.local_dir().display().to_string(),
+            },
+            backups,
+        })
+    }
+
+    pub async fn set_backup_schedule(
+        &self,
+        slug: &str,
+        req: BackupScheduleRequest,
+    ) -> Result<String, DockyardError> {
+        if let Some(schedule) = &req.schedule {
+            crate::utils::parse_cron(schedule)?;
+        }
+        if req.keep == Some(0) {
+            return Err(DockyardError::Config(
+                "at least one backup has to be kept".to_string(),
+            ));
+        }
+
+        let mut projects = self.projects.write().await;
+        let config = projects
+            .get_mut(slug)
+            .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+        if req.clear {
+            config.backup.schedule = None;
+        } else if let Some(schedule) = req.schedule {
+            config.backup.schedule = Some(schedule.trim().to_string());
+        }
+        if let Some(keep) = req.keep {
+            config.backup.keep = keep;
+        }
+        config.save()?;
+
+        let keep = config.backup.keep;
+        Ok(match &config.backup.schedule {
+            Some(schedule) => format!(
+                "Backups of '{}' run on '{}' (UTC), keeping the last {}",
+                slug, schedule, keep
+            ),
+            None => format!(
+                "Scheduled backups of '{}' are off; manual backups keep the last {}",
+                slug, keep
+            ),
+        })
+    }
+
+    /// Snapshot the project's volumes and dump its databases, then apply retention
+    pub async fn backup_project(
+        &self,
+        slug: &str,
+        trigger: BackupTrigger,
+    ) -> Result<Backup, DockyardError> {
+        let (container, mounts, keep) = {
+            let projects = self.projects.read().await;
+            let config = projects
+                .get(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            (
+                config.container.container_name.clone(),
+                config.container.volumes.clone(),
+                config.backup.keep,
+            )
+        };
+        let storage = self.config.read().await.backup.clone();
+        let _guard = self.backup_lock.lock().await;
+
+        let mut history = BackupHistory::load(slug)?;
+        let id = history.next_id();
+        let dir = backup_dir(&storage, slug, id);
+        std::fs::create_dir_all(&dir)?;
+        let started = Instant::now();
+
+        let written = match self.write_backup(slug, &container, &mounts, &dir).await {
+            Ok(artifacts) if artifacts.is_empty() => Err(DockyardError::Backup(format!(
+                "'{}' has no volumes or databases to back up",
+                slug
+            ))),
+            other => other,
+        };
+        let artifacts = match written {
+            Ok(artifacts) => artifacts,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&dir);
+                return Err(e);
+            }
+        };
+
+        let location = match &storage.s3 {
+            Some(s3) => {
+                let client = S3Client::new(s3)?;
+                for artifact in &artifacts {
+                    let key = client.key(slug, id, &artifact.file);
+                    if let Err(e) = client.put_file(&key, &dir.join(&artifact.file)).await {
+                        let _ = std::fs::remove_dir_all(&dir);
+                        return Err(e);
+                    }
+                }
+                std::fs::remove_dir_all(&dir)?;
+                BackupLocation::S3
+            }
+            None => BackupLocation::Local,
+        };
+
+        let backup = Backup {
+            id,
+            trigger,
+            location,
+            created_at: Utc::now(),
+            duration_secs: started.elapsed().as_secs(),
+            artifacts,
+        };
+        history.backups.push(backup.clone());
+        let expired = history.prune(keep);
+        history.save(slug)?;
+        for old in expired {
+            self.delete_backup_files(slug, &old, &storage).await;
+        }
+
+        tracing::info!(
+            "[{}] Backup #{} stored ({}, {} file(s))",
+            slug,
+            id,
+            location,
+            backup.artifacts.len()
+        );
+        Ok(backup)
+    }
+
+    async fn write_backup(
+        &self,
+        slug: &str,
+        container: &str,
+        mounts: &[VolumeMount],
+        dir: &Path,
+    ) -> Result<Vec<BackupArtifact>, DockyardError> {
+        let targets = self.dump_targets(slug, container, mounts).await?;
+        let existing: Vec<String> = self
+            .docker
+            .project_volumes(slug)
+            .await?
+            .into_iter()
+            .map(|v| v.name)
+            .collect();
+
+        let mut artifacts = Vec::new();
+        for mount in mounts {
+            // Bind mounts are host directories, left to the host's own backups
+            let Some(volume) = mount.volume_name(slug) else {
+                continue;
+            };
+            if !existing.contains(&volume) {
+                continue;
+            }
+            // A running database's files are not consistent on disk; its dump replaces them
+            if targets
+                .iter()
+                .any(|t| t.data_volume.as_deref() == Some(volume.as_str()))
+            {
+                continue;
+            }
+
+            let file = format!("volume-{}.tar.gz", mount.source);
+            let binds = vec![
+                format!("{}:/source:ro", volume),
+                format!("{}:/backup", dir.display()),
+            ];
+            let cmd = args(&[
+                "tar",
+                "czf",
+                &format!("/backup/{}", file),
+                "-C",
+                "/source",
+                ".",
+            ]);
+            let output = self.docker.run_helper(HELPER_IMAGE, binds, cmd).await?;
+            check_output(&format!("snapshot of volume {}", volume), output)?;
+            artifacts.push(artifact(ArtifactKind::Volume, &mount.source, dir, file)?);
+        }
+
+        for target in &targets {
+            let file = target.file();
+            let output = self
+                .docker
+                .exec_to_file(
+                    &target.container,
+                    target.dump_command(),
+                    target.env(),
+                    &dir.join(&file),
+                )
+                .await?;
+            check_output(&format!("dump of '{}'", target.source), output)?;
+            artifacts.push(artifact(ArtifactKind::Dump, &target.source, dir, file)?);
+        }
+        Ok(artifacts)
+    }
+
+    /// The project itself when it runs a database image, and its attached add-ons
+    async fn dump_targets(
+        &self,
+        slug: &str,
+        container: &str,
+        mounts: &[VolumeMount],
+    ) -> Result<Vec<DumpTarget>, DockyardError> {
+        let mut targets = Vec::new();
+        // No container means nothing deployed yet, so nothing to dump
+        if let Ok(env) = self.docker.get_container_env(container).await {
+            targets.extend(DumpTarget::detect(slug, container, &env, mounts));
+        }
+
+        let addons: Vec<AddonConfig> = self
+            .addons
+            .read()
+            .await
+            .values()
+            .filter(|a| a.project.as_deref() == Some(slug))
+            .cloned()
+            .collect();
+        for addon in &addons {
+            targets.push(DumpTarget::from_addon(addon)?);
+        }
+        Ok(targets)
+    }
+
+    /// Put a project's volumes and databases back to the state of a backup. Runs as a
+    /// scheduler job so no deploy swaps containers while the data is replaced.
+    pub async fn restore_backup(&self, slug: &str, id: u32) -> Result<String, DockyardError> {
+        let (reply, restored) = tokio::sync::oneshot::channel();
+        self.scheduler_tx
+            .send(Job::Restore {
+                slug: slug.to_string(),
+                id,
+                reply,
+            })
+            .await
+            .map_err(|_| DockyardError::Backup("the scheduler has stopped".to_string()))?;
+        restored
+            .await
+            .map_err(|_| DockyardError::Backup("the restore was abandoned".to_string()))?
+    }
+
+    /// Restore a backup while the scheduler holds the project
+    pub(crate) async fn execute_restore(&self, slug: &str, id: u32) -> Result<String, DockyardError> {
+        let (containers, mounts, enabled) = {
+            let projects = self.projects.read().await;
+            let config = projects
+                .get(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            (
+                config.all_containers(),
+                config.container.volumes.clone(),
+                config.enabled,
+            )
+        };
+        let backup = BackupHistory::load(slug)?
+            .get(id)
+            .cloned()
+            .ok_or_else(|| DockyardError::BackupNotFound(slug.to_string(), id))?;
+        {
+            let mut runtime = self.runtime.write().await;
+            let rt = runtime.entry(slug.to_string()).or_default();
+            // A deploy may be queued without having reached the scheduler yet
+            if rt.is_busy() {
+                return Err(DockyardError::Backup(format!(
+                    "'{}' is being deployed; restore once it finishes",
+                    slug
+                )));
+            }
+            rt.begin(ProjectState::Rebuilding, DeployPhase::Restoring);
+            if let Err(e) = rt.save(slug) {
+                tracing::warn!("[{}] Could not persist runtime state: {}", slug, e);
+            }
+        }
+
+        let restored = async {
+            let storage = self.config.read().await.backup.clone();
+            let _guard = self.backup_lock.lock().await;
+
+            let (dir, staged) = match backup.location {
+                BackupLocation::Local => (backup_dir(&storage, slug, id), false),
+                BackupLocation::S3 => {
+                    let s3 = storage.s3.as_ref().ok_or_else(|| {
+                        DockyardError::Backup(format!(
+                            "backup #{} is stored in S3, but no S3 storage is configured",
+                            id
+                        ))
+                    })?;
+                    let client = S3Client::new(s3)?;
+                    let dir = storage
+                        .local_dir()
+                        .join(slug)
+                        .join(format!("restore-{}", id));
+                    std::fs::create_dir_all(&dir)?;
+                    for artifact in &backup.artifacts {
+                        let key = client.key(slug, id, &artifact.file);
+                        if let Err(e) = client.get_file(&key, &dir.join(&artifact.file)).await {
+                            let _ = std::fs::remove_dir_all(&dir);
+                            return Err(e);
+                        }
+                    }
+                    (dir, true)
+                }
+            };
+
+            let result = self
+                .apply_backup(slug, &backup, &dir, &containers, &mounts, enabled)
+                .await;
+            if staged {
+                let _ = std::fs::remove_dir_all(&dir);
+            }
+            result
+        }
+        .await;
+
+        // Release the claim unless the restore already recorded how the project came back
+        let observed = self
+            .docker
+            .get_container_state(&containers[0])
+            .await
+            .unwrap_or(ProjectState::Offline);
+        self.update_runtime(slug, |rt| {
+            if rt.phase == Some(DeployPhase::Restoring) {
+                rt.finish(observed)
+            }
+        })
+        .await;
+        restored?;
+
+        tracing::info!("[{}] Restored backup #{}", slug, id);
+        Ok(format!("Backup #{} of '{}' restored", id, slug))
+    }
+
+    async fn apply_backup(
+        &self,
+        slug: &str,
+        backup: &Backup,
+        dir: &Path,
+        containers: &[String],
+        mounts: &[VolumeMount],
+        enabled: bool,
+    ) -> Result<(), DockyardError> {
+        let container = &containers[0];
+        // Everything is checked before anything is stopped
+        let mut volumes = Vec::new();
+        let mut dumps = Vec::new();
+        for artifact in &backup.artifacts {
+            if !dir.join(&artifact.file).exists() {
+                return Err(DockyardError::Backup(format!(
+                    "{} is missing from {}",
+                    artifact.file,
+                    dir.display()
+                )));
+            }
+            match artifact.kind {
+                ArtifactKind::Volume => {
+                    let volume = mounts
+                        .iter()
+                        .find(|m| m.source == artifact.source)
+                        .and_then(|m| m.volume_name(slug))
+                        .ok_or_else(|| {
+                            DockyardError::Backup(format!(
+                                "volume '{}' is no longer mounted by '{}'",
+                                artifact.source, slug
+                            ))
+                        })?;
+                    volumes.push((volume, artifact.file.as_str()));
+                }
+                ArtifactKind::Dump => {
+                    let target = self
+                        .restore_target(slug, &artifact.source, container, mounts)
+                        .await?;
+                    if target.kind == AddonKind::Redis && target.data_volume.is_none() {
+                        return Err(DockyardError::Backup(format!(
+                            "'{}' has no volume at {} to restore its data into",
+                            target.source,
+                            target.kind.data_path()
+                        )));
+                    }
+                    dumps.push((target, artifact.file.as_str()));
+                }
+            }
+        }
+
+        // The project is stopped while its files are replaced
+        let own_rdb: Vec<_> = dumps
+            .iter()
+            .filter(|(t, _)| t.source == slug && t.kind == AddonKind::Redis)
+            .collect();
+        if !volumes.is_empty() || !own_rdb.is_empty() {
+            for name in containers {
+                let _ = self.docker.stop_container(name).await;
+            }
+
+            let mut replaced = Ok(());
+            for (volume, file) in &volumes {
+                replaced = self.restore_volume(slug, volume, dir, file).await;
+                if replaced.is_err() {
+                    break;
+                }
+            }
+            if replaced.is_ok() {
+                for (target, file) in &own_rdb {
+                    replaced = self.restore_rdb(target, dir, file).await;
+                    if replaced.is_err() {
+                        break;
+                    }
+                }
+            }
+
+            let mut started = Ok(());
+            if enabled {
+                for name in containers {
+                    let result = self
+                        .docker
+                        .inner()
+                        .start_container(
+                            name,
+                            None::<bollard::container::StartContainerOptions<String>>,
+                        )
+                        .await
+                        .map_err(DockyardError::from);
+                    started = started.and(result);
+                }
+            }
+            let result = replaced.and(started);
+            match &result {
+                Ok(()) => {
+                    let observed = self
+                        .docker
+                        .get_container_state(container)
+                        .await
+                        .unwrap_or(ProjectState::Offline);
+                    self.update_runtime(slug, |rt| rt.finish(observed)).await;
+                }
+                Err(e) => {
+                    let error = format!("restore failed: {}", e);
+                    self.update_runtime(slug, |rt| rt.fail(ProjectState::Error, error))
+                        .await;
+                }
+            }
+            result?;
+        }
+
+        for (target, file) in &dumps {
+            match target.restore_command() {
+                Some(cmd) => {
+                    self.wait_database_ready(&target.container, &target.source, target.kind)
+                        .await?;
+                    let output = self
+                        .docker
+                        .exec_from_file(&target.container, cmd, target.env(), &dir.join(file))
+                        .await?;
+                    check_output(&format!("restore of '{}'", target.source), output)?;
+                }
+                // The project's own RDB file was replaced while it was stopped
+                None if target.source == slug => {}
+                None => {
+                    self.docker.stop_container(&target.container).await?;
+                    self.restore_rdb(target, dir, file).await?;
+                    self.docker
+                        .inner()
+                        .start_container(
+                            &target.container,
+                            None::<bollard::container::StartContainerOptions<String>>,
+                        )
+                        .await?;
+                    self.wait_database_ready(&target.container, &target.source, target.kind)
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve the database a dump was taken from, as it is configured now
+    async fn restore_target(
+        &self,
+        slug: &str,
+        source: &str,
+        container: &str,
+        mounts: &[VolumeMount],
+    ) -> Result<DumpTarget, DockyardError> {
+        if source == slug {
+            let env = self.docker.get_container_env(container).await?;
+            return DumpTarget::detect(slug, container, &env, mounts).ok_or_else(|| {
+                DockyardError::Backup(format!("'{}' no longer runs a database image", slug))
+            });
+        }
+        let addon = self
+            .addons
+            .read()
+            .await
+            .get(source)
+            .cloned()
+            .ok_or_else(|| DockyardError::AddonNotFound(source.to_string()))?;
+        DumpTarget::from_addon(&addon)
+    }
+
+    async fn restore_volume(
+        &self,
+        slug: &str,
+        volume: &str,
+        dir: &Path,
+        file: &str,
+    ) -> Result<(), DockyardError> {
+        self.docker.ensure_volume(volume, slug).await?;
+        let binds = vec![
+            format!("{}:/target", volume),
+            format!("{}:/backup:ro", dir.display()),
+        ];
+        let script = format!(
+            "find /target -mindepth 1 -delete && tar xzf /backup/{} -C /target",
+            file
+        );
+        let output = self
+            .docker
+            .run_helper(HELPER_IMAGE, binds, args(&["sh", "-c", &script]))
+            .await?;
+        check_output(&format!("restore of volume {}", volume), output)
+    }
+
+    /// Replace the RDB file in a stopped Redis container's data volume
+    async fn restore_rdb(
+        &self,
+        target: &DumpTarget,
+        dir: &Path,
+        file: &str,
+    ) -> Result<(), DockyardError> {
+        let Some(volume) = &target.data_volume else {
+            return Ok(());
+        };
+        let binds = vec![
+            format!("{}:/data", volume),
+            format!("{}:/backup:ro", dir.display()),
+        ];
+        // Append-only files would be loaded instead of the restored snapshot
+        let script = format!(
+            "rm -rf /data/appendonlydir /data/*.aof && cp /backup/{} /data/dump.rdb",
+            file
+        );
+        let output = self
+            .docker
+            .run_helper(HELPER_IMAGE, binds, args(&["sh", "-c", &script]))
+            .await?;
+        check_output(&format!("restore of '{}'", target.source), output)
+    }
+
+    /// Remove the files of a backup dropped by retention; failures are only logged
+    async fn delete_backup_files(
+        &self,
+        slug: &str,
+        backup: &Backup,
+        storage: &BackupStorageConfig,
+    ) {
+        match backup.location {
+            BackupLocation::Local => {
+                let dir = backup_dir(storage, slug, backup.id);
+                if let Err(e) = std::fs::remove_dir_all(&dir) {
+                    tracing::warn!("[{}] Could not remove {}: {}", slug, dir.display(), e);
+                }
+            }
+            BackupLocation::S3 => {
+                let client = match storage.s3.as_ref().map(S3Client::new) {
+                    Some(Ok(client)) => client,
+                    Some(Err(e)) => {
+                        tracing::warn!("[{}] Could not delete backup #{}: {}", slug, backup.id, e);
+                        return;
+                    }
+                    None => {
+                        tracing::warn!(
+                            "[{}] Backup #{} is in S3, which is no longer configured; its files were kept",
+                            slug,
+                            backup.id
+                        );
+                        return;
+                    }
+                };
+                for artifact in &backup.artifacts {
+                    if let Err(e) = client
+                        .delete(&client.key(slug, backup.id, &artifact.file))
+                        .await
+                    {
+                        tracing::warn!("[{}] {}", slug, e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Run scheduled backups as their cron expressions come due
+pub async fn run(state: Arc<DaemonState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(SCHEDULE_TICK_SECS));
+    let mut since = Utc::now();
+
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        // Everything due since the last check, so slow backups do not make others skip
+        let due: Vec<String> = state
+            .projects
+            .read()
+            .await
+            .values()
+            .filter(|config| {
+                config
+                    .backup
+                    .schedule
+                    .as_deref()
+                    .and_then(|s| crate::utils::parse_cron(s).ok())
+                    .and_then(|s| s.after(&since).next())
+                    .is_some_and(|next| next <= now)
+            })
+            .map(|config| config.slug.clone())
+            .collect();
+        since = now;
+
+        for slug in due {
+            tracing::info!("[{}] Running scheduled backup", slug);
+            if let Err(e) = state.backup_project(&slug, BackupTrigger::Scheduled).await {
+                tracing::error!("[{}] Scheduled backup failed: {}", slug, e);
+            }
+        }
+    }
+}
+
+/// Local directory of a backup's files, also used to stage S3 uploads
+fn backup_dir(storage: &BackupStorageConfig, slug: &str, id: u32) -> PathBuf {
+    storage.local_dir().join(slug).join(id.to_string())
+}
+
+fn artifact(
+    kind: ArtifactKind,
+    source: &str,
+    dir: &Path,
+    file: String,
+) -> Result<BackupArtifact, DockyardError> {
+    let size_bytes = std::fs::metadata(dir.join(&file))?.len();
+    Ok(BackupArtifact {
+        kind,
+        source: source.to_string(),
+        file,
+        size_bytes,
+    })
+}
+
+fn check_output(what: &str, output: CommandOutput) -> Result<(), DockyardError> {
+    if output.success() {
+        return Ok(());
+    }
+    Err(DockyardError::Backup(format!(
+        "{} exited with {}: {}",
+        what,
+        output.exit_code,
+        output.stderr.trim()
+    )))
+}
+
+fn args(parts: &[&str]) -> Vec<String> {
+    parts.iter().map(|p| p.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)], mounts: &[VolumeMount]) -> Option<DumpTarget> {
+        let env = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        DumpTarget::detect("shop", "dockyard-shop", &env, mounts)
+    }
+
+    fn dump(target: &DumpTarget) -> String {
+        target.dump_command().join(" ")
+    }
+
+    fn restore(target: &DumpTarget) -> Option<String> {
+        target.restore_command().map(|cmd| cmd.join(" "))
+    }
+
+    #[test]
+    fn addon_dumps_its_own_database() {
+        let _keyring = secrets::testing::keyring(false);
+        let mut addon =
+            AddonConfig::new("shop-db".into(), AddonKind::Postgres, "16".into()).unwrap();
+        let password = secrets::reveal(&addon.password).unwrap();
+
+        let target = DumpTarget::from_addon(&addon).unwrap();
+        assert_eq!(target.file(), "dump-shop-db.sql");
+        assert_eq!(target.env(), vec![format!("PGPASSWORD={}", password)]);
+        assert_eq!(
+            dump(&target),
+            "pg_dump -U dockyard --clean --if-exists shop_db"
+        );
+        assert_eq!(
+            restore(&target).unwrap(),
+            "psql -q -U dockyard -v ON_ERROR_STOP=1 -d shop_db"
+        );
+        assert_eq!(
+            target.data_volume.as_deref(),
+            Some("dockyard-addon-shop-db-data")
+        );
+
+        addon.kind = AddonKind::Mysql;
+        let target = DumpTarget::from_addon(&addon).unwrap();
+        assert_eq!(target.env(), vec![format!("MYSQL_PWD={}", password)]);
+        assert_eq!(
+            dump(&target),
+            "mysqldump -u dockyard --single-transaction --no-tablespaces shop_db"
+        );
+        assert_eq!(restore(&target).unwrap(), "mysql -u dockyard shop_db");
+
+        addon.kind = AddonKind::Redis;
+        let target = DumpTarget::from_addon(&addon).unwrap();
+        assert_eq!(target.file(), "dump-shop-db.rdb");
+        assert_eq!(target.env(), vec![format!("REDISCLI_AUTH={}", password)]);
+        assert!(dump(&target).starts_with("sh -c redis-cli --rdb "));
+        assert_eq!(restore(&target), None);
+    }
+
+    #[test]
+    fn postgres_image_is_detected_with_its_defaults() {
+        let mounts = [VolumeMount {
+            source: "pgdata".into(),
+            target: "/var/lib/postgresql/data".into(),
+            read_only: false,
+        }];
+        let target = detect(&[("PG_VERSION", "16.2")], &mounts).unwrap();
+        assert_eq!(target.kind, AddonKind::Postgres);
+        assert_eq!(target.source, "shop");
+        assert!(target.env().is_empty());
+        assert_eq!(
+            dump(&target),
+            "pg_dump -U postgres --clean --if-exists postgres"
+        );
+        assert_eq!(target.data_volume.as_deref(), Some("dockyard-shop-pgdata"));
+
+        let target = detect(
+            &[
+                ("PG_VERSION", "16.2"),
+                ("POSTGRES_USER", "app"),
+                ("POSTGRES_PASSWORD", "secret"),
+            ],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(target.env(), vec!["PGPASSWORD=secret".to_string()]);
+        assert_eq!(
+            restore(&target).unwrap(),
+            "psql -q -U app -v ON_ERROR_STOP=1 -d app"
+        );
+        assert_eq!(target.data_volume, None);
+    }
+
+    #[test]
+    fn mysql_image_prefers_the_root_account() {
+        let target = detect(
+            &[
+                ("MYSQL_VERSION", "8.4"),
+                ("MYSQL_ROOT_PASSWORD", "root-secret"),
+                ("MYSQL_USER", "app"),
+                ("MYSQL_DATABASE", "shop"),
+            ],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            dump(&target),
+            "mysqldump -u root --single-transaction --no-tablespaces --all-databases"
+        );
+        assert_eq!(restore(&target).unwrap(), "mysql -u root");
+
+        let target = detect(
+            &[
+                ("MYSQL_VERSION", "8.4"),
+                ("MYSQL_USER", "app"),
+                ("MYSQL_PASSWORD", "secret"),
+                ("MYSQL_DATABASE", "shop"),
+            ],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(target.env(), vec!["MYSQL_PWD=secret".to_string()]);
+        assert_eq!(restore(&target).unwrap(), "mysql -u app shop");
+
+        assert!(detect(&[("MYSQL_VERSION", "8.4")], &[]).is_none());
+    }
+
+    #[test]
+    fn redis_and_other_images() {
+        let target = detect(&[("REDIS_VERSION", "7")], &[]).unwrap();
+        assert_eq!(target.kind, AddonKind::Redis);
+        assert!(target.env().is_empty());
+        assert_eq!(restore(&target), None);
+        assert!(detect(&[("NODE_VERSION", "20")], &[]).is_none());
+    }
+}
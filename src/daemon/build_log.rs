@@ -0,0 +1,378 @@
+use axum::body::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures_util::Stream;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+
+use crate::config::paths;
+use crate::error::DockyardError;
+use crate::models::build::{BuildStatus, BuildSummary};
+
+const SUCCESS_MARKER: &str = "==> Succeeded";
+const FAILURE_MARKER: &str = "==> Failed: ";
+
+/// Contents of the status file while a build runs, and the prefixes of its outcomes
+const STATUS_RUNNING: &str = "running";
+const STATUS_SUCCEEDED: &str = "succeeded";
+const STATUS_FAILED: &str = "failed: ";
+
+/// Append-only output of a single deploy/rebuild job, stored as `logs/build-<id>.log`.
+/// Its outcome goes to `logs/build-<id>.status`, out of reach of the output of the
+/// commands the job runs.
+pub struct BuildLog {
+    slug: String,
+    id: u32,
+    file: Mutex<File>,
+    path: PathBuf,
+}
+
+impl BuildLog {
+    /// Allocate the next build number for a project and create its log file
+    pub fn create(slug: &str, description: &str) -> Result<Self, DockyardError> {
+        Self::create_in(slug, &paths::project_logs_dir(slug), description)
+    }
+
+    fn create_in(slug: &str, dir: &Path, description: &str) -> Result<Self, DockyardError> {
+        std::fs::create_dir_all(dir)?;
+        let mut id = list_ids_in(dir)?.last().copied().unwrap_or(0) + 1;
+        // Jobs queued at the same time can pick the same number; creating the file decides
+        let (file, path) = loop {
+            let path = dir.join(log_file_name(id));
+            let created = OpenOptions::new().create_new(true).append(true).open(&path);
+            match created {
+                Ok(file) => break (file, path),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => id += 1,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        write_status(&path, STATUS_RUNNING)?;
+
+        let log = Self {
+            slug: slug.to_string(),
+            id,
+            file: Mutex::new(file),
+            path,
+        };
+        log.line(&format!("==> Build #{} queued ({})", id, description));
+        Ok(log)
+    }
+
+    /// Reopen an existing build log for appending
+    pub fn open(slug: &str, id: u32) -> Result<Self, DockyardError> {
+        let path = paths::project_build_log_path(slug, id);
+        if !path.exists() {
+            return Err(DockyardError::BuildNotFound(slug.to_string(), id));
+        }
+        Self::open_path(slug, id, path)
+    }
+
+    fn open_path(slug: &str, id: u32, path: PathBuf) -> Result<Self, DockyardError> {
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(Self {
+            slug: slug.to_string(),
+            id,
+            file: Mutex::new(file),
+            path,
+        })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Write a timestamped line to the log and mirror it to the daemon journal
+    pub fn line(&self, msg: &str) {
+        tracing::info!("[{}] {}", self.slug, msg);
+        let stamped = format!(
+            "[{}] {}\n",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            msg
+        );
+        if let Ok(mut file) = self.file.lock() {
+            if let Err(e) = file.write_all(stamped.as_bytes()) {
+                tracing::warn!("[{}] Could not write build log: {}", self.slug, e);
+            }
+        }
+    }
+
+    /// Close the log with the job's outcome; followers stop once it is recorded
+    pub fn finish<E: std::fmt::Display>(&self, result: &Result<(), E>) {
+        let status = match result {
+            Ok(()) => {
+                self.line(SUCCESS_MARKER);
+                STATUS_SUCCEEDED.to_string()
+            }
+            Err(e) => {
+                let reason = e.to_string().replace('\n', " ");
+                self.line(&format!("{}{}", FAILURE_MARKER, reason.trim()));
+                format!("{}{}", STATUS_FAILED, reason.trim())
+            }
+        };
+        if let Err(e) = write_status(&self.path, &status) {
+            tracing::warn!("[{}] Could not record build status: {}", self.slug, e);
+        }
+    }
+}
+
+/// Build numbers that have a log on disk, in ascending order
+pub fn list_ids(slug: &str) -> Result<Vec<u32>, DockyardError> {
+    list_ids_in(&paths::project_logs_dir(slug))
+}
+
+fn list_ids_in(dir: &Path) -> Result<Vec<u32>, DockyardError> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut ids = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if let Some(name) = entry.file_name().to_str() {
+            if let Some(id) = name
+                .strip_prefix("build-")
+                .and_then(|n| n.strip_suffix(".log"))
+                .and_then(|n| n.parse::<u32>().ok())
+            {
+                ids.push(id);
+            }
+        }
+    }
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+pub fn summary(slug: &str, id: u32) -> Result<BuildSummary, DockyardError> {
+    let path = paths::project_build_log_path(slug, id);
+    if !path.exists() {
+        return Err(DockyardError::BuildNotFound(slug.to_string(), id));
+    }
+
+    let mut first_line = String::new();
+    BufReader::new(File::open(&path)?).read_line(&mut first_line)?;
+    let started_at = parse_timestamp(&first_line);
+
+    let (status, error) = read_status(&path)?;
+    Ok(BuildSummary {
+        id,
+        status,
+        started_at,
+        error,
+    })
+}
+
+/// Mark builds left running by a previous daemon process as failed
+pub fn fail_interrupted(slug: &str) -> Result<(), DockyardError> {
+    fail_interrupted_in(slug, &paths::project_logs_dir(slug))
+}
+
+fn fail_interrupted_in(slug: &str, dir: &Path) -> Result<(), DockyardError> {
+    for id in list_ids_in(dir)? {
+        let path = dir.join(log_file_name(id));
+        if read_status(&path)?.0 == BuildStatus::Running {
+            BuildLog::open_path(slug, id, path)?.finish(&Err("interrupted by daemon restart"));
+        }
+    }
+    Ok(())
+}
+
+fn log_file_name(id: u32) -> String {
+    format!("build-{}.log", id)
+}
+
+fn status_path(log_path: &Path) -> PathBuf {
+    log_path.with_extension("status")
+}
+
+fn write_status(log_path: &Path, status: &str) -> std::io::Result<()> {
+    // Renamed into place so a reader never sees it half written
+    let path = status_path(log_path);
+    let tmp = path.with_extension("status.tmp");
+    std::fs::write(&tmp, status)?;
+    std::fs::rename(&tmp, &path)
+}
+
+/// Stream a build log; with `follow` the stream stays open until the build finishes
+pub async fn stream(
+    slug: &str,
+    id: u32,
+    follow: bool,
+) -> Result<impl Stream<Item = Result<Bytes, std::io::Error>>, DockyardError> {
+    let path = paths::project_build_log_path(slug, id);
+    if !path.exists() {
+        return Err(DockyardError::BuildNotFound(slug.to_string(), id));
+    }
+    let file = tokio::fs::File::open(&path).await?;
+
+    let tail = Tail {
+        file,
+        path: path.clone(),
+        follow,
+        finished: false,
+    };
+
+    Ok(futures_util::stream::unfold(
+        Some(tail),
+        |tail| async move {
+            let mut tail = tail?;
+            let mut buf = vec![0u8; 8192];
+            loop {
+                match tail.file.read(&mut buf).await {
+                    Ok(0) => {
+                        if tail.finished || !tail.follow {
+                            return None;
+                        }
+                        // Drain once more after the marker shows up, then stop
+                        match read_status(&tail.path) {
+                            Ok((BuildStatus::Running, _)) => {
+                                tokio::time::sleep(Duration::from_millis(250)).await;
+                            }
+                            _ => tail.finished = true,
+                        }
+                    }
+                    Ok(n) => return Some((Ok(Bytes::copy_from_slice(&buf[..n])), Some(tail))),
+                    Err(e) => return Some((Err(e), None)),
+                }
+            }
+        },
+    ))
+}
+
+struct Tail {
+    file: tokio::fs::File,
+    path: std::path::PathBuf,
+    follow: bool,
+    finished: bool,
+}
+
+/// Determine the outcome of the build logged at `path` from its status file
+fn read_status(path: &Path) -> Result<(BuildStatus, Option<String>), DockyardError> {
+    let status = match std::fs::read_to_string(status_path(path)) {
+        Ok(status) => status,
+        // Not written yet; after a daemon restart `fail_interrupted` settles it
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok((BuildStatus::Running, None))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if status == STATUS_SUCCEEDED {
+        Ok((BuildStatus::Succeeded, None))
+    } else if let Some(reason) = status.strip_prefix(STATUS_FAILED) {
+        Ok((BuildStatus::Failed, Some(reason.to_string())))
+    } else {
+        Ok((BuildStatus::Running, None))
+    }
+}
+
+fn parse_timestamp(line: &str) -> Option<DateTime<Utc>> {
+    let stamp = line.strip_prefix('[')?.split_once(']')?.0;
+    DateTime::parse_from_rfc3339(stamp)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("dockyard-logs-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn status(log: &BuildLog) -> (BuildStatus, Option<String>) {
+        read_status(&log.path).unwrap()
+    }
+
+    #[test]
+    fn builds_run_until_finished() {
+        let dir = temp_dir();
+        let log = BuildLog::create_in("web", &dir, "deploy").unwrap();
+        assert_eq!(log.id(), 1);
+        assert_eq!(status(&log), (BuildStatus::Running, None));
+
+        log.finish::<&str>(&Ok(()));
+        assert_eq!(status(&log), (BuildStatus::Succeeded, None));
+
+        let next = BuildLog::create_in("web", &dir, "rebuild").unwrap();
+        assert_eq!(next.id(), 2);
+        next.finish(&Err("image build failed\non step 3"));
+        assert_eq!(
+            status(&next),
+            (
+                BuildStatus::Failed,
+                Some("image build failed on step 3".to_string())
+            )
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn output_that_looks_like_an_outcome_is_not_one() {
+        let dir = temp_dir();
+        let log = BuildLog::create_in("web", &dir, "deploy").unwrap();
+        log.line(SUCCESS_MARKER);
+        assert_eq!(status(&log), (BuildStatus::Running, None));
+
+        log.line(&format!("{}not really", FAILURE_MARKER));
+        log.finish::<&str>(&Ok(()));
+        log.line(&format!("{}late output", FAILURE_MARKER));
+        assert_eq!(status(&log), (BuildStatus::Succeeded, None));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn logs_without_a_status_file_run_until_the_daemon_restarts() {
+        let dir = temp_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(log_file_name(1));
+        let content = "[2024-06-01T10:00:00Z] ==> Build #1 queued (deploy)\n\
+                       [2024-06-01T10:01:00Z] ==> Succeeded\n";
+        std::fs::write(&path, content).unwrap();
+        assert_eq!(read_status(&path).unwrap(), (BuildStatus::Running, None));
+
+        fail_interrupted_in("web", &dir).unwrap();
+        assert_eq!(
+            read_status(&path).unwrap(),
+            (
+                BuildStatus::Failed,
+                Some("interrupted by daemon restart".to_string())
+            )
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn interrupted_builds_are_failed_and_finished_ones_kept() {
+        let dir = temp_dir();
+        let done = BuildLog::create_in("web", &dir, "deploy").unwrap();
+        done.finish::<&str>(&Ok(()));
+        let broken = BuildLog::create_in("web", &dir, "rebuild").unwrap();
+        broken.finish(&Err("release command exited with 1"));
+        let running = BuildLog::create_in("web", &dir, "rebuild").unwrap();
+
+        fail_interrupted_in("web", &dir).unwrap();
+
+        assert_eq!(status(&done), (BuildStatus::Succeeded, None));
+        assert_eq!(
+            status(&broken),
+            (
+                BuildStatus::Failed,
+                Some("release command exited with 1".to_string())
+            )
+        );
+        assert_eq!(
+            status(&running),
+            (
+                BuildStatus::Failed,
+                Some("interrupted by daemon restart".to_string())
+            )
+        );
+        let content = std::fs::read_to_string(&running.path).unwrap();
+        assert!(content
+            .trim_end()
+            .ends_with("==> Failed: interrupted by daemon restart"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
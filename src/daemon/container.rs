@@ -1,6 +1,10 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
+use crate::daemon::build_log::BuildLog;
+use crate::config::project::ProjectConfig;
 use crate::daemon::DaemonState;
+use crate::docker::network;
 use crate::error::DockyardError;
 use crate::models::project::ProjectState;
 
@@ -11,6 +15,9 @@ pub async fn start_all_projects(state: &Arc<DaemonState>) -> Result<(), Dockyard
     for (slug, config) in projects.iter() {
         if !config.enabled {
             tracing::info!("[{}] Disabled, skipping", slug);
+            state
+                .update_runtime(slug, |rt| rt.observe(ProjectState::Stopped))
+                .await;
             continue;
         }
 
@@ -19,9 +26,27 @@ pub async fn start_all_projects(state: &Arc<DaemonState>) -> Result<(), Dockyard
             .get_container_state(&config.container.container_name)
             .await?;
 
+        if matches!(container_state, ProjectState::Online | ProjectState::Stopped) {
+            let container = &config.container.container_name;
+            if let Err(e) = attach_networks(state, &projects, config, container).await {
+                tracing::warn!("[{}] Could not attach to project networks: {}", slug, e);
+            }
+            start_other_containers(state, &projects, config).await?;
+        }
+
         match container_state {
             ProjectState::Online => {
                 tracing::info!("[{}] Already running", slug);
+                let container_id = state
+                    .docker
+                    .get_container_id(&config.container.container_name)
+                    .await?;
+                state
+                    .update_runtime(slug, |rt| {
+                        rt.observe(ProjectState::Online);
+                        rt.container_id = container_id;
+                    })
+                    .await;
             }
             ProjectState::Stopped => {
                 tracing::info!("[{}] Starting stopped container...", slug);
@@ -34,13 +59,18 @@ pub async fn start_all_projects(state: &Arc<DaemonState>) -> Result<(), Dockyard
                     )
                     .await?;
                 tracing::info!("[{}] Started", slug);
+                state
+                    .update_runtime(slug, |rt| rt.observe(ProjectState::Online))
+                    .await;
             }
             _ => {
                 tracing::info!("[{}] Container not found, queueing deploy...", slug);
+                let build_id = BuildLog::create(slug, "deploy on startup")?.id();
                 let _ = state
                     .scheduler_tx
                     .send(crate::daemon::scheduler::Job::Deploy {
                         slug: slug.clone(),
+                        build_id,
                     })
                     .await;
             }
@@ -49,3 +79,134 @@ pub async fn start_all_projects(state: &Arc<DaemonState>) -> Result<(), Dockyard
 
     Ok(())
 }
+
+/// Start the containers besides the first replica (more replicas, other process types),
+/// queueing a scale job to recreate any that are missing
+async fn start_other_containers(
+    state: &Arc<DaemonState>,
+    projects: &HashMap<String, ProjectConfig>,
+    config: &ProjectConfig,
+) -> Result<(), DockyardError> {
+    let slug = &config.slug;
+    let mut missing = false;
+    for name in config.all_containers().iter().skip(1) {
+        match state.docker.get_container_state(name).await? {
+            ProjectState::Online => {}
+            ProjectState::Stopped => {
+                tracing::info!("[{}] Starting stopped container {}...", slug, name);
+                state
+                    .docker
+                    .inner()
+                    .start_container(
+                        name,
+                        None::<bollard::container::StartContainerOptions<String>>,
+                    )
+                    .await?;
+            }
+            _ => {
+                missing = true;
+                continue;
+            }
+        }
+        if let Err(e) = attach_networks(state, projects, config, name).await {
+            tracing::warn!(
+                "[{}] Could not attach {} to project networks: {}",
+                slug,
+                name,
+                e
+            );
+        }
+    }
+
+    if missing {
+        tracing::info!("[{}] Containers missing, queueing scale...", slug);
+        let build_id = BuildLog::create(slug, "scale on startup")?.id();
+        let _ = state
+            .scheduler_tx
+            .send(crate::daemon::scheduler::Job::Scale {
+                slug: slug.clone(),
+                build_id,
+            })
+            .await;
+    }
+    Ok(())
+}
+
+/// Make sure every add-on's container is running, recreating missing ones on their
+/// existing data volume
+pub async fn start_all_addons(state: &Arc<DaemonState>) {
+    let addons: Vec<_> = state.addons.read().await.values().cloned().collect();
+
+    for addon in addons {
+        let container = addon.container_name();
+        let result = match state.docker.get_container_state(&container).await {
+            Ok(ProjectState::Online) => Ok(()),
+            Ok(ProjectState::Stopped) => {
+                tracing::info!("[{}] Starting stopped add-on...", addon.name);
+                state
+                    .docker
+                    .inner()
+                    .start_container(
+                        &container,
+                        None::<bollard::container::StartContainerOptions<String>>,
+                    )
+                    .await
+                    .map_err(DockyardError::from)
+            }
+            Ok(_) => {
+                tracing::info!("[{}] Add-on container not found, recreating...", addon.name);
+                state.run_addon(&addon).await
+            }
+            Err(e) => Err(e),
+        };
+        let result = match (result, &addon.project) {
+            (Ok(()), Some(project)) => state.join_project_network(&addon, project).await,
+            (result, _) => result,
+        };
+        if let Err(e) = result {
+            tracing::warn!("[{}] Could not start add-on: {}", addon.name, e);
+        }
+    }
+}
+
+/// Connect an existing container to the networks it should be on, for containers created
+/// before dockyard attached them or whose links changed while the daemon was down
+async fn attach_networks(
+    state: &DaemonState,
+    projects: &HashMap<String, ProjectConfig>,
+    config: &ProjectConfig,
+    container: &str,
+) -> Result<(), DockyardError> {
+    let current = state.docker.get_container_networks(container).await?;
+
+    // Internal projects whose private network the container belongs on
+    let mut internal: Vec<&str> = config
+        .links
+        .iter()
+        .filter(|link| projects.get(*link).is_some_and(|p| p.is_internal()))
+        .map(String::as_str)
+        .collect();
+    if config.is_internal() {
+        internal.push(&config.slug);
+    } else if !current.iter().any(|n| n == network::DOCKYARD_NETWORK) {
+        state
+            .docker
+            .connect_network(network::DOCKYARD_NETWORK, container, &config.slug)
+            .await?;
+        tracing::info!("[{}] Attached to {}", config.slug, network::DOCKYARD_NETWORK);
+    }
+
+    for slug in internal {
+        let name = network::project_network(slug);
+        if current.contains(&name) {
+            continue;
+        }
+        network::ensure_project_network(&state.docker, slug).await?;
+        state
+            .docker
+            .connect_network(&name, container, &config.slug)
+            .await?;
+        tracing::info!("[{}] Attached to {}", config.slug, name);
+    }
+    Ok(())
+}
@@ -0,0 +1,377 @@
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::cron::{CronHistory, CronRun, CronTrigger};
+use crate::config::paths;
+use crate::config::project::{default_cron_timeout, CronJobConfig, ProjectConfig};
+use crate::config::release::ReleaseHistory;
+use crate::daemon::{scheduler, DaemonState};
+use crate::docker::client::LogFilter;
+use crate::docker::DockerClient;
+use crate::error::DockyardError;
+use crate::ipc::protocol::*;
+use crate::models::log::LogStream;
+
+/// How often cron schedules are checked
+const SCHEDULE_TICK_SECS: u64 = 30;
+
+/// Runs kept per job; older ones and their output are deleted
+const RUNS_KEPT: usize = 20;
+
+impl DaemonState {
+    pub async fn list_cron_jobs(&self, slug: &str) -> Result<CronListResponse, DockyardError> {
+        let jobs = {
+            let projects = self.projects.read().await;
+            let config = projects
+                .get(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            config.cron.clone()
+        };
+        let history = CronHistory::load(slug)?;
+        let running = self.cron_running.lock().await.clone();
+
+        let jobs = jobs
+            .into_iter()
+            .map(|(name, job)| CronJobInfo {
+                next_run: crate::utils::parse_cron(&job.schedule)
+                    .ok()
+                    .and_then(|s| s.upcoming(Utc).next()),
+                last_run: history.last(&name).cloned(),
+                running: running.contains(&run_key(slug, &name)),
+                name,
+                schedule: job.schedule,
+                command: job.command,
+                timeout_secs: job.timeout_secs,
+            })
+            .collect();
+        Ok(CronListResponse {
+            slug: slug.to_string(),
+            jobs,
+        })
+    }
+
+    /// Runs of a project, newest first, optionally of one job only
+    pub async fn cron_history(
+        &self,
+        slug: &str,
+        job: Option<&str>,
+    ) -> Result<Vec<CronRun>, DockyardError> {
+        if !self.projects.read().await.contains_key(slug) {
+            return Err(DockyardError::ProjectNotFound(slug.to_string()));
+        }
+        let mut runs: Vec<CronRun> = CronHistory::load(slug)?
+            .runs
+            .into_iter()
+            .filter(|r| job.is_none_or(|job| r.job == job))
+            .collect();
+        runs.sort_by_key(|r| std::cmp::Reverse(r.id));
+        Ok(runs)
+    }
+
+    /// A run with the output it captured so far
+    pub async fn cron_run(&self, slug: &str, id: u32) -> Result<CronRunResponse, DockyardError> {
+        let run = CronHistory::load(slug)?
+            .get(id)
+            .cloned()
+            .ok_or_else(|| DockyardError::CronRunNotFound(slug.to_string(), id))?;
+        let output =
+            std::fs::read_to_string(paths::project_cron_log_path(slug, id)).unwrap_or_default();
+        Ok(CronRunResponse { run, output })
+    }
+
+    /// Declare a job or change its schedule, command or timeout
+    pub async fn set_cron_job(
+        &self,
+        slug: &str,
+        name: &str,
+        req: CronJobRequest,
+    ) -> Result<String, DockyardError> {
+        super::validate_name("cron job", name)?;
+        if let Some(schedule) = &req.schedule {
+            crate::utils::parse_cron(schedule)?;
+        }
+        if req.command.as_deref().is_some_and(|c| c.trim().is_empty()) {
+            return Err(DockyardError::Config("command cannot be empty".to_string()));
+        }
+        if req.timeout_secs == Some(0) {
+            return Err(DockyardError::Config(
+                "timeout must be at least one second".to_string(),
+            ));
+        }
+
+        let mut projects = self.projects.write().await;
+        let config = projects
+            .get_mut(slug)
+            .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+        let job = match config.cron.get(name).cloned() {
+            Some(job) => CronJobConfig {
+                schedule: req.schedule.unwrap_or(job.schedule),
+                command: req.command.unwrap_or(job.command),
+                timeout_secs: req.timeout_secs.unwrap_or(job.timeout_secs),
+            },
+            None => match (req.schedule, req.command) {
+                (Some(schedule), Some(command)) => CronJobConfig {
+                    schedule,
+                    command,
+                    timeout_secs: req.timeout_secs.unwrap_or_else(default_cron_timeout),
+                },
+                _ => {
+                    return Err(DockyardError::Config(format!(
+                        "new cron job '{}' needs a schedule and a command",
+                        name
+                    )))
+                }
+            },
+        };
+        let message = format!(
+            "Cron job '{}' of '{}' runs on '{}' (UTC)",
+            name,
+            slug,
+            job.schedule.trim()
+        );
+        config.cron.insert(
+            name.to_string(),
+            CronJobConfig {
+                schedule: job.schedule.trim().to_string(),
+                ..job
+            },
+        );
+        config.save()?;
+        Ok(message)
+    }
+
+    /// Forget a job; its run history is kept
+    pub async fn remove_cron_job(&self, slug: &str, name: &str) -> Result<String, DockyardError> {
+        let mut projects = self.projects.write().await;
+        let config = projects
+            .get_mut(slug)
+            .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+        if config.cron.remove(name).is_none() {
+            return Err(DockyardError::CronJobNotFound(
+                slug.to_string(),
+                name.to_string(),
+            ));
+        }
+        config.save()?;
+        Ok(format!("Cron job '{}' removed from '{}'", name, slug))
+    }
+
+    /// Run a job to completion in a one-off container from the current release and record
+    /// its exit code and output. A job never runs twice at the same time.
+    pub async fn run_cron_job(
+        &self,
+        slug: &str,
+        name: &str,
+        trigger: CronTrigger,
+    ) -> Result<CronRun, DockyardError> {
+        let (job, container_name, image_name) = {
+            let projects = self.projects.read().await;
+            let config = projects
+                .get(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            let job = config.cron.get(name).cloned().ok_or_else(|| {
+                DockyardError::CronJobNotFound(slug.to_string(), name.to_string())
+            })?;
+            (
+                job,
+                config.container.container_name.clone(),
+                config.container.image_name.clone(),
+            )
+        };
+        let release = ReleaseHistory::load(slug)?.current.ok_or_else(|| {
+            DockyardError::Cron(format!("'{}' has no deployed release to run from", slug))
+        })?;
+        let image = format!("{}:{}", image_name, scheduler::release_tag(release));
+
+        let key = run_key(slug, name);
+        let mut run = {
+            let mut running = self.cron_running.lock().await;
+            if running.contains(&key) {
+                return Err(DockyardError::Cron(format!(
+                    "'{}' of '{}' is still running",
+                    name, slug
+                )));
+            }
+            let mut history = CronHistory::load(slug)?;
+            let run = CronRun {
+                id: history.next_id(),
+                job: name.to_string(),
+                trigger,
+                started_at: Utc::now(),
+                duration_secs: None,
+                exit_code: None,
+                error: None,
+            };
+            history.runs.push(run.clone());
+            history.save(slug)?;
+            running.insert(key.clone());
+            run
+        };
+
+        let started = Instant::now();
+        let container = run_container(&container_name, run.id);
+        match self
+            .execute_cron_job(slug, &container, &image, &job, run.id)
+            .await
+        {
+            Ok(Some(exit_code)) => run.exit_code = Some(exit_code),
+            Ok(None) => run.error = Some(format!("timed out after {}s", job.timeout_secs)),
+            Err(e) => run.error = Some(e.to_string()),
+        }
+        run.duration_secs = Some(started.elapsed().as_secs());
+
+        let mut running = self.cron_running.lock().await;
+        running.remove(&key);
+        let mut history = CronHistory::load(slug)?;
+        if let Some(entry) = history.get_mut(run.id) {
+            *entry = run.clone();
+        }
+        let expired = history.prune(name, RUNS_KEPT);
+        history.save(slug)?;
+        drop(running);
+        for old in expired {
+            let _ = std::fs::remove_file(paths::project_cron_log_path(slug, old.id));
+        }
+
+        match (&run.error, run.exit_code) {
+            (Some(error), _) => tracing::warn!("[{}] Cron job {} failed: {}", slug, name, error),
+            (None, Some(code)) => {
+                tracing::info!("[{}] Cron job {} exited with {}", slug, name, code)
+            }
+            (None, None) => {}
+        }
+        Ok(run)
+    }
+
+    /// Returns the exit code, or None when the job was stopped for running too long
+    async fn execute_cron_job(
+        &self,
+        slug: &str,
+        container: &str,
+        image: &str,
+        job: &CronJobConfig,
+        run_id: u32,
+    ) -> Result<Option<i64>, DockyardError> {
+        let command = scheduler::shell(&job.command);
+        let spec = scheduler::one_off_spec(self, slug, container, image, command)
+            .await
+            .map_err(|e| DockyardError::Cron(e.to_string()))?;
+        self.docker.create_container(&spec).await?;
+
+        let result = self
+            .docker
+            .run_to_exit(container, Duration::from_secs(job.timeout_secs))
+            .await;
+        if let Err(e) = self.save_cron_output(slug, container, run_id).await {
+            tracing::warn!(
+                "[{}] Could not save output of cron run #{}: {}",
+                slug,
+                run_id,
+                e
+            );
+        }
+        if let Err(e) = self.docker.remove_container(container).await {
+            tracing::warn!(
+                "[{}] Failed to remove cron container {}: {}",
+                slug,
+                container,
+                e
+            );
+        }
+        result
+    }
+
+    async fn save_cron_output(
+        &self,
+        slug: &str,
+        container: &str,
+        run_id: u32,
+    ) -> Result<(), DockyardError> {
+        let entries = self
+            .docker
+            .get_logs(container, &LogFilter::default())
+            .await?;
+        let mut output = String::new();
+        for entry in entries {
+            if entry.stream == LogStream::Stderr {
+                output.push_str("[stderr] ");
+            }
+            output.push_str(&entry.message);
+            output.push('\n');
+        }
+        std::fs::create_dir_all(paths::project_logs_dir(slug))?;
+        std::fs::write(paths::project_cron_log_path(slug, run_id), output)?;
+        Ok(())
+    }
+}
+
+/// Run cron jobs as their schedules come due
+pub async fn run(state: Arc<DaemonState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(SCHEDULE_TICK_SECS));
+    let mut since = Utc::now();
+
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        let mut due = Vec::new();
+        for config in state.projects.read().await.values() {
+            // Stopped projects run no jobs
+            if !config.enabled {
+                continue;
+            }
+            for (name, job) in &config.cron {
+                let next = crate::utils::parse_cron(&job.schedule)
+                    .ok()
+                    .and_then(|s| s.after(&since).next());
+                if next.is_some_and(|next| next <= now) {
+                    due.push((config.slug.clone(), name.clone()));
+                }
+            }
+        }
+        since = now;
+
+        // Each job in its own task, so a long one does not hold back the others
+        for (slug, name) in due {
+            let state = Arc::clone(&state);
+            tokio::spawn(async move {
+                tracing::info!("[{}] Running cron job {}", slug, name);
+                if let Err(e) = state
+                    .run_cron_job(&slug, &name, CronTrigger::Scheduled)
+                    .await
+                {
+                    tracing::error!("[{}] Cron job {} did not run: {}", slug, name, e);
+                }
+            });
+        }
+    }
+}
+
+/// Fail the runs a previous daemon process left in progress and remove their containers
+pub async fn fail_interrupted(
+    docker: &DockerClient,
+    config: &ProjectConfig,
+) -> Result<(), DockyardError> {
+    let mut history = CronHistory::load(&config.slug)?;
+    let interrupted = history.fail_unfinished("interrupted by daemon restart");
+    if interrupted.is_empty() {
+        return Ok(());
+    }
+    history.save(&config.slug)?;
+    for id in interrupted {
+        tracing::warn!("[{}] Cron run #{} was interrupted", config.slug, id);
+        let container = run_container(&config.container.container_name, id);
+        let _ = docker.stop_container(&container).await;
+        let _ = docker.remove_container(&container).await;
+    }
+    Ok(())
+}
+
+/// One-off container of a run; `.` appears in no replica, worker or project container name
+fn run_container(container_name: &str, run_id: u32) -> String {
+    format!("{}_cron.{}", container_name, run_id)
+}
+
+fn run_key(slug: &str, job: &str) -> String {
+    format!("{}/{}", slug, job)
+}
@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+use crate::config::project::HealthCheck;
+use crate::docker::DockerClient;
+
+/// Address to probe for a container: its network IP and container port when Docker
+/// reports one, so the check reaches the app rather than Docker's port proxy
+pub async fn target(
+    docker: &DockerClient,
+    container_name: &str,
+    host_port: u16,
+    container_port: u16,
+) -> String {
+    match docker.get_container_ip(container_name).await {
+        Ok(Some(ip)) => format!("{}:{}", ip, container_port),
+        _ => format!("127.0.0.1:{}", host_port),
+    }
+}
+
+/// Run the check once against `addr`, returning why it failed
+pub async fn probe(check: &HealthCheck, addr: &str) -> Result<(), String> {
+    let timeout = Duration::from_secs(check.timeout_secs.max(1));
+
+    let Some(path) = &check.path else {
+        return match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr)).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => Err(format!("tcp {}: {}", addr, e)),
+            Err(_) => Err(format!("tcp {}: timed out", addr)),
+        };
+    };
+
+    let url = format!("http://{}/{}", addr, path.trim_start_matches('/'));
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| e.to_string())?;
+    let status = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("GET {}: {}", url, e))?
+        .status();
+
+    let ok = match check.expected_status {
+        Some(expected) => status.as_u16() == expected,
+        None => status.is_success() || status.is_redirection(),
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(format!("GET {}: status {}", url, status.as_u16()))
+    }
+}
+
+/// Poll a freshly started container until it passes the check or runs out of retries.
+/// `on_failure` receives every failed attempt that counts against the retries.
+pub async fn wait_healthy(
+    docker: &DockerClient,
+    container_name: &str,
+    addr: &str,
+    check: &HealthCheck,
+    on_failure: impl Fn(&str),
+) -> Result<(), String> {
+    let started = Instant::now();
+    let start_period = Duration::from_secs(check.start_period_secs);
+    let interval = Duration::from_secs(check.interval_secs.max(1));
+    let mut failures = 0;
+
+    loop {
+        if !docker
+            .is_container_running(container_name)
+            .await
+            .unwrap_or(false)
+        {
+            return Err("container exited".to_string());
+        }
+
+        match probe(check, addr).await {
+            Ok(()) => return Ok(()),
+            Err(e) if started.elapsed() < start_period => {
+                tracing::debug!("[{}] Not ready yet: {}", container_name, e);
+            }
+            Err(e) => {
+                failures += 1;
+                on_failure(&format!(
+                    "Health check failed ({}/{}): {}",
+                    failures, check.retries, e
+                ));
+                if failures >= check.retries {
+                    return Err(e);
+                }
+            }
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
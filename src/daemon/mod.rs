@@ -1,28 +1,55 @@
+pub mod addons;
+pub mod backup;
+pub mod build_log;
 pub mod container;
+pub mod cron;
+pub mod health;
 pub mod proxy;
+pub mod s3;
 pub mod scheduler;
 pub mod server;
+pub mod session;
 pub mod tunnel;
 pub mod watcher;
 pub mod webhook;
 
-use std::collections::HashMap;
+use futures_util::future::join_all;
+use futures_util::Stream;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::sync::RwLock;
 
+use crate::config::addon::AddonConfig;
 use crate::config::global::GlobalConfig;
-use crate::config::project::ProjectConfig;
+use crate::config::project::{
+    NetworkMode, ProcessConfig, ProjectConfig, ResourceLimits, VolumeMount, MAX_REPLICAS,
+    WEB_PROCESS,
+};
+use crate::config::release::{ReleaseHistory, ReleaseTrigger};
+use crate::config::runtime::RuntimeState;
+use crate::config::secrets;
+use crate::docker::client::LogFilter;
 use crate::docker::DockerClient;
 use crate::error::DockyardError;
 use crate::ipc::protocol::*;
-use crate::models::project::{ProjectState, ProjectStatus};
+use crate::models::build::BuildSummary;
+use crate::models::log::LogEntry;
+use crate::models::project::{DeployPhase, ProcessStatus, ProjectState, ProjectStatus};
 
 /// Shared daemon state accessible by all handlers
 pub struct DaemonState {
     pub config: RwLock<GlobalConfig>,
     pub docker: DockerClient,
+    pub proxy: proxy::CaddyProxy,
     pub projects: RwLock<HashMap<String, ProjectConfig>>,
+    /// Managed databases, by name
+    pub addons: RwLock<HashMap<String, AddonConfig>>,
+    pub runtime: RwLock<HashMap<String, RuntimeState>>,
+    /// Held by the backup or restore in progress; they run one at a time
+    pub backup_lock: tokio::sync::Mutex<()>,
+    /// Cron jobs running now, as `slug/job`; also guards the cron run histories
+    pub cron_running: tokio::sync::Mutex<HashSet<String>>,
     pub started_at: Instant,
     pub scheduler_tx: tokio::sync::mpsc::Sender<scheduler::Job>,
 }
@@ -37,248 +64,1436 @@ impl DaemonState {
     }
 
     pub async fn list_project_statuses(&self) -> Result<Vec<ProjectStatus>, DockyardError> {
-        let projects = self.projects.read().await;
-        let mut statuses = Vec::new();
+        // Docker is asked for stats outside the lock, so writers are not held up meanwhile
+        let configs: Vec<ProjectConfig> = self.projects.read().await.values().cloned().collect();
+        let mut statuses = join_all(configs.iter().map(|c| self.project_status(c))).await;
+        statuses.sort_by(|a, b| a.slug.cmp(&b.slug));
+
+        Ok(statuses)
+    }
+
+    pub async fn get_project_detail(
+        &self,
+        slug: &str,
+    ) -> Result<ProjectDetailResponse, DockyardError> {
+        let config = self
+            .projects
+            .read()
+            .await
+            .get(slug)
+            .cloned()
+            .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+
+        let status = self.project_status(&config).await;
+
+        Ok(ProjectDetailResponse {
+            status,
+            repo_url: config.repo_url,
+            branch: config.branch,
+            git: config.git,
+            webhook_secret: secrets::REDACTED.to_string(),
+        })
+    }
+
+    async fn webhook_url(&self, slug: &str) -> String {
+        let webhook_port = self.config.read().await.daemon.webhook_port;
+        format!("http://YOUR_SERVER:{}/webhook/{}", webhook_port, slug)
+    }
+
+    /// The plain-text webhook secret, for pasting into GitHub
+    pub async fn get_webhook_secret(
+        &self,
+        slug: &str,
+    ) -> Result<WebhookSecretResponse, DockyardError> {
+        let secret = {
+            let projects = self.projects.read().await;
+            let config = projects
+                .get(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            secrets::reveal(&config.webhook.secret)?
+        };
+        Ok(WebhookSecretResponse {
+            webhook_url: self.webhook_url(slug).await,
+            secret,
+        })
+    }
+
+    /// Replace the webhook secret; pushes signed with the old one are refused from now on
+    pub async fn rotate_webhook_secret(
+        &self,
+        slug: &str,
+    ) -> Result<WebhookSecretResponse, DockyardError> {
+        let secret = crate::utils::generate_webhook_secret();
+        {
+            let mut projects = self.projects.write().await;
+            let config = projects
+                .get_mut(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            config.webhook.secret = secrets::seal(&secret)?;
+            config.save()?;
+        }
+        tracing::info!("[{}] Webhook secret rotated", slug);
+        Ok(WebhookSecretResponse {
+            webhook_url: self.webhook_url(slug).await,
+            secret,
+        })
+    }
+
+    /// Build a status from the tracked runtime state, asking Docker only for live stats
+    async fn project_status(&self, config: &ProjectConfig) -> ProjectStatus {
+        let runtime = self
+            .runtime
+            .read()
+            .await
+            .get(&config.slug)
+            .cloned()
+            .unwrap_or_default();
+
+        let online = runtime.state == ProjectState::Online;
+        let release = ReleaseHistory::load(&config.slug).ok().and_then(|h| {
+            h.current
+                .and_then(|id| h.get(id))
+                .map(|r| (r.procfile.clone(), r.commit_sha.clone()))
+        });
+        let (procfile, commit_sha) = release.unwrap_or_default();
+        let mut groups = vec![(
+            WEB_PROCESS.to_string(),
+            config.replica_names(),
+            Some(config.domain.container_port),
+        )];
+        for (name, process) in &config.container.processes {
+            let containers = (0..process.replicas as usize)
+                .map(|i| config.worker_name(name, i))
+                .collect();
+            groups.push((name.clone(), containers, process.port));
+        }
+
+        // Usage of the project as a whole, across all of its containers
+        let (mut memory, mut cpu, mut pids) = (0.0, 0.0, None);
+        let mut processes = Vec::new();
+        for (name, containers, port) in groups {
+            let (mut process_memory, mut process_cpu, mut running) = (0.0, 0.0, 0);
+            for container in &containers {
+                if !self
+                    .docker
+                    .is_container_running(container)
+                    .await
+                    .unwrap_or(false)
+                {
+                    continue;
+                }
+                running += 1;
+                if !online {
+                    continue;
+                }
+                if let Ok((m, c, p)) = self.docker.get_container_stats(container).await {
+                    process_memory += m;
+                    process_cpu += c;
+                    if let Some(p) = p {
+                        pids = Some(pids.unwrap_or(0) + p);
+                    }
+                }
+            }
+            memory += process_memory;
+            cpu += process_cpu;
+            processes.push(ProcessStatus {
+                command: config.process_command(&name, &procfile),
+                replicas: match name.as_str() {
+                    WEB_PROCESS => config.container.replicas,
+                    _ => containers.len() as u32,
+                },
+                name,
+                running,
+                port,
+                memory_usage_mb: online.then_some(process_memory),
+                cpu_percent: online.then_some(process_cpu),
+            });
+        }
 
-        for (slug, config) in projects.iter() {
-            let state = self
+        let (memory, cpu, pids, uptime) = if online {
+            let uptime = self
                 .docker
-                .get_container_state(&config.container.container_name)
+                .get_container_uptime(&config.container.container_name)
                 .await
-                .unwrap_or(ProjectState::Offline);
+                .unwrap_or(None);
+            (Some(memory), Some(cpu), pids, uptime)
+        } else {
+            (None, None, None, None)
+        };
 
-            let (memory, cpu) = if state == ProjectState::Online {
-                self.docker
-                    .get_container_stats(&config.container.container_name)
-                    .await
-                    .unwrap_or((0.0, 0.0))
-            } else {
-                (0.0, 0.0)
-            };
+        let url = match (&config.network_mode, &config.domain.hostname) {
+            (_, Some(hostname)) => Some(format!("https://{}", hostname)),
+            (NetworkMode::LocalOnly, None) => {
+                Some(format!("http://localhost:{}", config.domain.host_port))
+            }
+            // Only resolvable from other project containers
+            (NetworkMode::Internal, None) => Some(format!(
+                "http://{}:{}",
+                config.slug, config.domain.container_port
+            )),
+            _ => None,
+        };
 
-            let uptime = if state == ProjectState::Online {
-                self.docker
-                    .get_container_uptime(&config.container.container_name)
-                    .await
-                    .unwrap_or(None)
+        ProjectStatus {
+            slug: config.slug.clone(),
+            name: config.name.clone(),
+            state: runtime.state,
+            phase: runtime.phase,
+            container_id: runtime.container_id,
+            uptime_secs: uptime,
+            memory_usage_mb: memory,
+            cpu_percent: cpu,
+            pids,
+            replicas: config.container.replicas,
+            processes,
+            resources: config.container.resources.clone(),
+            url,
+            host_port: (!config.is_internal()).then_some(config.domain.host_port),
+            container_port: config.domain.container_port,
+            network_mode: config.network_mode.to_string(),
+            last_deploy: runtime.last_deploy.or(Some(config.updated_at)),
+            commit_sha,
+            last_error: runtime.last_error,
+        }
+    }
+
+    /// Apply a change to a project's runtime state and persist it
+    pub async fn update_runtime(&self, slug: &str, f: impl FnOnce(&mut RuntimeState)) {
+        let mut runtime = self.runtime.write().await;
+        let entry = runtime.entry(slug.to_string()).or_default();
+        f(entry);
+        if let Err(e) = entry.save(slug) {
+            tracing::warn!("[{}] Could not persist runtime state: {}", slug, e);
+        }
+    }
+
+    /// Refuse a change to a project's containers while a deploy or rebuild owns it
+    async fn reject_if_busy(&self, slug: &str) -> Result<(), DockyardError> {
+        let runtime = self.runtime.read().await;
+        if runtime.get(slug).is_some_and(|rt| rt.is_busy()) {
+            return Err(scheduler::busy(slug));
+        }
+        Ok(())
+    }
+
+    pub async fn deploy_project(
+        &self,
+        req: DeployRequest,
+    ) -> Result<DeployResponse, DockyardError> {
+        let name = crate::utils::repo_name(&req.repo_url);
+        let slug = crate::utils::slugify(&name);
+
+        {
+            let projects = self.projects.read().await;
+            if projects.contains_key(&slug) {
+                return Err(DockyardError::ProjectAlreadyExists(slug));
+            }
+        }
+        // Projects and add-ons share one namespace on dockyard-network
+        if self.addons.read().await.contains_key(&slug) {
+            return Err(DockyardError::Config(format!(
+                "an add-on is already named '{}'",
+                slug
+            )));
+        }
+
+        if req.network_mode == NetworkMode::Internal && req.hostname.is_some() {
+            return Err(DockyardError::Config(
+                "internal projects have no host port and cannot be given a hostname".to_string(),
+            ));
+        }
+        {
+            let projects = self.projects.read().await;
+            let internal = req.network_mode == NetworkMode::Internal;
+            validate_links(&slug, internal, &req.links, &projects)?;
+        }
+
+        for value in req.env_vars.values() {
+            secrets::check_plain(value)?;
+        }
+
+        let host_port = crate::utils::find_available_port()?;
+
+        let mut config = ProjectConfig::new(
+            name.clone(),
+            slug.clone(),
+            req.repo_url,
+            req.branch,
+            req.network_mode,
+            req.hostname,
+            req.container_port.unwrap_or(3000),
+            host_port,
+        );
+        config.domain.auto_port = req.container_port.is_none();
+        config.links = req.links;
+        config.container.env_vars = req.env_vars;
+        config.container.resources = req.resources;
+        if let Some(replicas) = req.replicas {
+            validate_replicas(replicas)?;
+            config.container.replicas = replicas;
+        }
+        validate_volumes(&req.volumes)?;
+        config.container.volumes = req.volumes;
+        config.seal_secrets()?;
+
+        config.save()?;
+        let internal = config.is_internal();
+        let build_id = build_log::BuildLog::create(&slug, "deploy")?.id();
+
+        // Registered before the job is queued, which may otherwise run without finding it
+        {
+            let mut projects = self.projects.write().await;
+            projects.insert(slug.clone(), config);
+        }
+        self.update_runtime(&slug, |rt| {
+            rt.begin(ProjectState::Building, DeployPhase::Cloning)
+        })
+        .await;
+
+        let _ = self
+            .scheduler_tx
+            .send(scheduler::Job::Deploy {
+                slug: slug.clone(),
+                build_id,
+            })
+            .await;
+
+        let webhook_url = self.webhook_url(&slug).await;
+
+        let (url, host_port) = if internal {
+            (None, None)
+        } else {
+            (Some(format!("http://localhost:{}", host_port)), Some(host_port))
+        };
+        Ok(DeployResponse {
+            slug,
+            name,
+            url,
+            webhook_url,
+            host_port,
+            build_id,
+        })
+    }
+
+    /// Queue a rebuild and return its build number
+    pub async fn rebuild_project(
+        &self,
+        slug: &str,
+        req: RebuildRequest,
+    ) -> Result<u32, DockyardError> {
+        {
+            let projects = self.projects.read().await;
+            if !projects.contains_key(slug) {
+                return Err(DockyardError::ProjectNotFound(slug.to_string()));
+            }
+        }
+        if req.commit.is_some() && req.git_ref.is_some() {
+            return Err(DockyardError::Config(
+                "give either a commit or a ref, not both".to_string(),
+            ));
+        }
+        if let Some(commit) = &req.commit {
+            if !crate::utils::is_commit_sha(commit) {
+                return Err(DockyardError::Config(format!(
+                    "'{}' is not a commit SHA (7 to 40 hex digits)",
+                    commit
+                )));
+            }
+        }
+        if let Some(git_ref) = &req.git_ref {
+            if !crate::utils::is_valid_ref(git_ref) {
+                return Err(DockyardError::Config(format!(
+                    "'{}' is not a valid ref",
+                    git_ref
+                )));
+            }
+        }
+
+        let trigger = match req.commit.as_deref().or(req.git_ref.as_deref()) {
+            Some(revision) => format!("rebuild of {}", revision),
+            None => "rebuild".to_string(),
+        };
+        let build_id = build_log::BuildLog::create(slug, &trigger)?.id();
+        let _ = self
+            .scheduler_tx
+            .send(scheduler::Job::Rebuild {
+                slug: slug.to_string(),
+                commit_sha: req.commit.map(|c| c.to_lowercase()),
+                git_ref: req.git_ref,
+                trigger: ReleaseTrigger::Manual,
+                build_id,
+            })
+            .await;
+
+        Ok(build_id)
+    }
+
+    pub async fn list_releases(&self, slug: &str) -> Result<ReleaseListResponse, DockyardError> {
+        {
+            let projects = self.projects.read().await;
+            if !projects.contains_key(slug) {
+                return Err(DockyardError::ProjectNotFound(slug.to_string()));
+            }
+        }
+
+        let history = ReleaseHistory::load(slug)?;
+        Ok(ReleaseListResponse {
+            current: history.current,
+            releases: history.releases,
+        })
+    }
+
+    /// Queue a rollback and return the release it will redeploy with its build number
+    pub async fn rollback_project(
+        &self,
+        slug: &str,
+        release: Option<u32>,
+    ) -> Result<(u32, u32), DockyardError> {
+        {
+            let projects = self.projects.read().await;
+            if !projects.contains_key(slug) {
+                return Err(DockyardError::ProjectNotFound(slug.to_string()));
+            }
+        }
+
+        let history = ReleaseHistory::load(slug)?;
+        let target = match release {
+            Some(id) => history
+                .get(id)
+                .ok_or_else(|| DockyardError::ReleaseNotFound(slug.to_string(), id))?,
+            None => history.previous().ok_or_else(|| {
+                DockyardError::Rollback(format!("'{}' has no earlier release", slug))
+            })?,
+        };
+
+        if target.pruned {
+            return Err(DockyardError::Rollback(format!(
+                "image for release v{} was pruned",
+                target.id
+            )));
+        }
+        if history.current == Some(target.id) {
+            return Err(DockyardError::Rollback(format!(
+                "release v{} is already live",
+                target.id
+            )));
+        }
+
+        let release = target.id;
+        let build_id =
+            build_log::BuildLog::create(slug, &format!("rollback to v{}", release))?.id();
+        let _ = self
+            .scheduler_tx
+            .send(scheduler::Job::Rollback {
+                slug: slug.to_string(),
+                release,
+                build_id,
+            })
+            .await;
+
+        Ok((release, build_id))
+    }
+
+    pub async fn list_builds(&self, slug: &str) -> Result<Vec<BuildSummary>, DockyardError> {
+        {
+            let projects = self.projects.read().await;
+            if !projects.contains_key(slug) {
+                return Err(DockyardError::ProjectNotFound(slug.to_string()));
+            }
+        }
+
+        let mut builds = Vec::new();
+        for id in build_log::list_ids(slug)? {
+            builds.push(build_log::summary(slug, id)?);
+        }
+        Ok(builds)
+    }
+
+    /// Resolve a build reference ("latest" or a number) to a build number
+    pub async fn resolve_build(&self, slug: &str, build: &str) -> Result<u32, DockyardError> {
+        {
+            let projects = self.projects.read().await;
+            if !projects.contains_key(slug) {
+                return Err(DockyardError::ProjectNotFound(slug.to_string()));
+            }
+        }
+
+        if build == "latest" {
+            return build_log::list_ids(slug)?
+                .last()
+                .copied()
+                .ok_or_else(|| DockyardError::BuildNotFound(slug.to_string(), 0));
+        }
+        build
+            .parse()
+            .map_err(|_| DockyardError::Config(format!("Invalid build number: {}", build)))
+    }
+
+    /// Show the Dockerfile the next build would use, generating it if needed
+    pub async fn get_dockerfile(&self, slug: &str) -> Result<DockerfileResponse, DockyardError> {
+        let (container, build) = {
+            let projects = self.projects.read().await;
+            let config = projects
+                .get(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            (config.container.clone(), config.build.clone())
+        };
+
+        let repo_dir = crate::config::paths::project_repo_dir(slug);
+        if !repo_dir.exists() {
+            return Err(DockyardError::Config(format!(
+                "'{}' has not been cloned yet",
+                slug
+            )));
+        }
+
+        let plan = crate::docker::build::plan_build(
+            &repo_dir,
+            &container,
+            &build,
+            &crate::config::paths::project_dockerfile_override_path(slug),
+        )?;
+        Ok(DockerfileResponse {
+            source: plan.source.to_string(),
+            path: plan.dockerfile.clone(),
+            container_port: plan.port,
+            content: plan.dockerfile_content()?,
+        })
+    }
+
+    /// Store or remove the Dockerfile override used instead of the repository's or generated one
+    pub async fn set_dockerfile(
+        &self,
+        slug: &str,
+        content: Option<String>,
+    ) -> Result<(), DockyardError> {
+        {
+            let projects = self.projects.read().await;
+            if !projects.contains_key(slug) {
+                return Err(DockyardError::ProjectNotFound(slug.to_string()));
+            }
+        }
+
+        let path = crate::config::paths::project_dockerfile_override_path(slug);
+        match content {
+            Some(content) => std::fs::write(&path, content)?,
+            None if path.exists() => std::fs::remove_file(&path)?,
+            None => {}
+        }
+        Ok(())
+    }
+
+    pub async fn get_build_config(&self, slug: &str) -> Result<BuildConfigResponse, DockyardError> {
+        let projects = self.projects.read().await;
+        let config = projects
+            .get(slug)
+            .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+
+        let mut secrets: Vec<String> = config.build.secrets.keys().cloned().collect();
+        secrets.sort();
+        Ok(BuildConfigResponse {
+            args: config.build.args.clone(),
+            target: config.build.target.clone(),
+            secrets,
+            release_command: config.build.release_command.clone(),
+        })
+    }
+
+    /// Change build args, secrets, target and release command; takes effect on the next
+    /// build
+    pub async fn update_build_config(
+        &self,
+        slug: &str,
+        req: BuildConfigUpdateRequest,
+    ) -> Result<BuildConfigResponse, DockyardError> {
+        if req.release_command.as_deref().is_some_and(|c| c.trim().is_empty()) {
+            return Err(DockyardError::Config(
+                "release command cannot be empty".to_string(),
+            ));
+        }
+        {
+            let mut projects = self.projects.write().await;
+            let config = projects
+                .get_mut(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+
+            for key in req.unset_args {
+                config.build.args.remove(&key);
+            }
+            config.build.args.extend(req.set_args);
+            for key in req.unset_secrets {
+                config.build.secrets.remove(&key);
+            }
+            for (key, value) in req.set_secrets {
+                config.build.secrets.insert(key, secrets::seal(&value)?);
+            }
+            if req.clear_target {
+                config.build.target = None;
+            }
+            if let Some(target) = req.target {
+                config.build.target = Some(target);
+            }
+            if req.clear_release_command {
+                config.build.release_command = None;
+            }
+            if let Some(command) = req.release_command {
+                config.build.release_command = Some(command);
+            }
+            config.save()?;
+        }
+
+        self.get_build_config(slug).await
+    }
+
+    pub async fn get_env(&self, slug: &str) -> Result<EnvResponse, DockyardError> {
+        let projects = self.projects.read().await;
+        let config = projects
+            .get(slug)
+            .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+
+        let mut secret_names = Vec::new();
+        let env_vars = config
+            .container
+            .env_vars
+            .iter()
+            .map(|(key, value)| {
+                if secrets::is_sealed(value) {
+                    secret_names.push(key.clone());
+                    (key.clone(), secrets::REDACTED.to_string())
+                } else {
+                    (key.clone(), value.clone())
+                }
+            })
+            .collect();
+        secret_names.sort();
+
+        Ok(EnvResponse {
+            env_vars,
+            secrets: secret_names,
+            update_mode: config.container.env_update,
+        })
+    }
+
+    /// Change a project's environment and queue the container update that applies it
+    pub async fn update_env(
+        &self,
+        slug: &str,
+        req: EnvUpdateRequest,
+    ) -> Result<EnvUpdateResponse, DockyardError> {
+        if let Some(key) = req
+            .set
+            .keys()
+            .find(|k| k.is_empty() || k.contains('=') || k.contains(char::is_whitespace))
+        {
+            return Err(DockyardError::Config(format!(
+                "Invalid variable name '{}'",
+                key
+            )));
+        }
+        if !req.secret {
+            for value in req.set.values() {
+                secrets::check_plain(value)?;
+            }
+        }
+
+        let (changed, mode) = {
+            let mut projects = self.projects.write().await;
+            let config = projects
+                .get_mut(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+
+            let mut env_vars = if req.replace {
+                HashMap::new()
             } else {
-                None
+                config.container.env_vars.clone()
             };
+            for key in &req.unset {
+                env_vars.remove(key);
+            }
+            for (key, value) in req.set {
+                let value = if req.secret {
+                    secrets::seal(&value)?
+                } else {
+                    value
+                };
+                env_vars.insert(key, value);
+            }
+
+            let changed = env_vars != config.container.env_vars;
+            config.container.env_vars = env_vars;
+            if let Some(mode) = req.default_mode {
+                config.container.env_update = mode;
+            }
+            config.save()?;
+            (changed, req.mode.unwrap_or(config.container.env_update))
+        };
+
+        let saved = |message: String| {
+            Ok(EnvUpdateResponse {
+                message,
+                build_id: None,
+            })
+        };
+        if !changed {
+            return saved(format!("Environment of '{}' unchanged", slug));
+        }
+        // Nothing deployed yet means there is no image to recreate the container from
+        if req.no_apply || ReleaseHistory::load(slug)?.current.is_none() {
+            return saved(format!(
+                "Environment of '{}' saved; it applies on the next deploy",
+                slug
+            ));
+        }
+
+        let build_id = build_log::BuildLog::create(slug, &format!("env update ({})", mode))?.id();
+        let _ = self
+            .scheduler_tx
+            .send(scheduler::Job::UpdateEnv {
+                slug: slug.to_string(),
+                mode,
+                build_id,
+            })
+            .await;
+
+        Ok(EnvUpdateResponse {
+            message: format!("Applying new environment to '{}' ({})", slug, mode),
+            build_id: Some(build_id),
+        })
+    }
+
+    pub async fn get_resources(&self, slug: &str) -> Result<ResourceLimits, DockyardError> {
+        let projects = self.projects.read().await;
+        let config = projects
+            .get(slug)
+            .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+        Ok(config.container.resources.clone())
+    }
+
+    /// Store new limits and apply them to the running container where Docker allows it
+    pub async fn update_resources(
+        &self,
+        slug: &str,
+        limits: ResourceLimits,
+    ) -> Result<ResourcesUpdateResponse, DockyardError> {
+        if limits.cpus.is_some_and(|c| c <= 0.0) {
+            return Err(DockyardError::Config("cpus must be positive".to_string()));
+        }
+        if let (Some(memory), Some(swap)) = (limits.memory_mb, limits.memory_swap_mb) {
+            if swap >= 0 && (swap as u64) < memory {
+                return Err(DockyardError::Config(
+                    "memory_swap must be at least memory (it is the total of both)".to_string(),
+                ));
+            }
+        }
+
+        let (containers, previous) = {
+            let mut projects = self.projects.write().await;
+            let config = projects
+                .get_mut(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            let previous = std::mem::replace(&mut config.container.resources, limits.clone());
+            config.save()?;
+            (config.all_containers(), previous)
+        };
+
+        // Docker can tighten or loosen these live, but cannot drop a memory limit or
+        // change ulimits on an existing container
+        let needs_new_container = previous.ulimits != limits.ulimits
+            || (previous.memory_mb.is_some() && limits.memory_mb.is_none())
+            || (previous.memory_swap_mb.is_some() && limits.memory_swap_mb.is_none());
+
+        let running = self
+            .docker
+            .is_container_running(&containers[0])
+            .await
+            .unwrap_or(false);
+        let mut message = if !running {
+            format!("Limits of '{}' saved; they apply when its container is next created", slug)
+        } else {
+            // The limits apply to each container on its own
+            let mut failed = None;
+            for name in &containers {
+                if let Err(e) = self.docker.update_resources(name, &limits).await {
+                    failed.get_or_insert(e);
+                }
+            }
+            match failed {
+                None => format!("Limits of '{}' applied to the running container", slug),
+                Some(e) => format!(
+                    "Limits of '{}' saved, but the running container could not be updated ({}); \
+                     they apply on the next deploy",
+                    slug, e
+                ),
+            }
+        };
+        if running && needs_new_container {
+            message.push_str(
+                "; removed memory limits and ulimit changes take effect on the next deploy",
+            );
+        }
+
+        Ok(ResourcesUpdateResponse { message, limits })
+    }
+
+    pub async fn start_project(&self, slug: &str) -> Result<(), DockyardError> {
+        let containers = self.set_enabled(slug, true).await?;
+
+        for (index, name) in containers.iter().enumerate() {
+            let started = self
+                .docker
+                .inner()
+                .start_container(
+                    name,
+                    None::<bollard::container::StartContainerOptions<String>>,
+                )
+                .await;
+            match started {
+                Ok(()) => {}
+                Err(e) if index == 0 => {
+                    let _ = self.set_enabled(slug, false).await;
+                    return Err(e.into());
+                }
+                Err(e) => tracing::warn!("[{}] Could not start {}: {}", slug, name, e),
+            }
+        }
+
+        self.update_runtime(slug, |rt| rt.observe(ProjectState::Online))
+            .await;
+        Ok(())
+    }
+
+    pub async fn stop_project(&self, slug: &str) -> Result<(), DockyardError> {
+        // Disabled first, so the watcher does not restart what is being stopped
+        let containers = self.set_enabled(slug, false).await?;
+
+        for name in containers.iter().skip(1) {
+            let _ = self.docker.stop_container(name).await;
+        }
+        if let Err(e) = self.docker.stop_container(&containers[0]).await {
+            let _ = self.set_enabled(slug, true).await;
+            return Err(e);
+        }
 
-            let url = match (&config.network_mode, &config.domain.hostname) {
-                (_, Some(hostname)) => Some(format!("https://{}", hostname)),
-                (crate::config::project::NetworkMode::LocalOnly, None) => {
-                    Some(format!("http://localhost:{}", config.domain.host_port))
+        self.update_runtime(slug, |rt| rt.observe(ProjectState::Stopped))
+            .await;
+        Ok(())
+    }
+
+    /// Persist whether a project should be running and return its containers, primary
+    /// first. Docker is only called once the lock is released.
+    async fn set_enabled(&self, slug: &str, enabled: bool) -> Result<Vec<String>, DockyardError> {
+        let mut projects = self.projects.write().await;
+        let config = projects
+            .get_mut(slug)
+            .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+        config.enabled = enabled;
+        config.save()?;
+        Ok(config.all_containers())
+    }
+
+    /// Change how many containers serve a project and queue the job that starts or
+    /// removes them
+    pub async fn scale_project(
+        &self,
+        slug: &str,
+        replicas: u32,
+    ) -> Result<ScaleResponse, DockyardError> {
+        validate_replicas(replicas)?;
+        let previous = {
+            let mut projects = self.projects.write().await;
+            let config = projects
+                .get_mut(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            let previous = std::mem::replace(&mut config.container.replicas, replicas);
+            config.save()?;
+            previous
+        };
+
+        if previous == replicas {
+            return Ok(ScaleResponse {
+                message: format!("'{}' already runs {} replica(s)", slug, replicas),
+                build_id: None,
+            });
+        }
+        // Without a release the deploy in progress starts the new count itself
+        if ReleaseHistory::load(slug)?.current.is_none() {
+            return Ok(ScaleResponse {
+                message: format!(
+                    "'{}' set to {} replica(s); they start with the first deploy",
+                    slug, replicas
+                ),
+                build_id: None,
+            });
+        }
+
+        let build_id =
+            build_log::BuildLog::create(slug, &format!("scale {} -> {}", previous, replicas))?.id();
+        let _ = self
+            .scheduler_tx
+            .send(scheduler::Job::Scale {
+                slug: slug.to_string(),
+                build_id,
+            })
+            .await;
+
+        Ok(ScaleResponse {
+            message: format!(
+                "Scaling '{}' from {} to {} replica(s)",
+                slug, previous, replicas
+            ),
+            build_id: Some(build_id),
+        })
+    }
+
+    /// Declare or change a process type. New containers are started by a scale job, surplus
+    /// ones removed right away; a changed command or port applies on the next deploy.
+    pub async fn set_process(
+        &self,
+        slug: &str,
+        name: &str,
+        req: ProcessUpdateRequest,
+    ) -> Result<ProcessUpdateResponse, DockyardError> {
+        if req.command.as_deref().is_some_and(|c| c.trim().is_empty()) {
+            return Err(DockyardError::Config("command cannot be empty".to_string()));
+        }
+        if let Some(replicas) = req.replicas {
+            validate_replicas(replicas)?;
+        }
+        if name == WEB_PROCESS {
+            return self.set_web_process(slug, req).await;
+        }
+        validate_name("process", name)?;
+        self.reject_if_busy(slug).await?;
+
+        let (reply, updated) = tokio::sync::oneshot::channel();
+        self.scheduler_tx
+            .send(scheduler::Job::SetProcess {
+                slug: slug.to_string(),
+                name: name.to_string(),
+                req,
+                reply,
+            })
+            .await
+            .map_err(|_| DockyardError::Config("the scheduler has stopped".to_string()))?;
+        updated
+            .await
+            .map_err(|_| DockyardError::Config("the process update was abandoned".to_string()))?
+    }
+
+    /// Save a process type and remove its surplus workers while the scheduler holds the
+    /// project. A `build_id` in the response asks the scheduler to start the new ones.
+    pub(crate) async fn execute_set_process(
+        &self,
+        slug: &str,
+        name: &str,
+        req: ProcessUpdateRequest,
+    ) -> Result<ProcessUpdateResponse, DockyardError> {
+        let history = ReleaseHistory::load(slug)?;
+        let release = history.current.and_then(|id| history.get(id));
+        let (surplus, grew, changed) = {
+            let mut projects = self.projects.write().await;
+            let config = projects
+                .get_mut(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+
+            let previous = config.container.processes.get(name).cloned();
+            let mut process = previous.clone().unwrap_or(ProcessConfig {
+                command: None,
+                replicas: 1,
+                port: None,
+            });
+            if req.command.is_some() || req.clear_command {
+                process.command = req.command;
+            }
+            if let Some(replicas) = req.replicas {
+                process.replicas = replicas;
+            }
+            if req.port.is_some() || req.clear_port {
+                process.port = req.port;
+            }
+            if let Some(release) = release {
+                if process.command.is_none() && !release.procfile.contains_key(name) {
+                    return Err(DockyardError::Config(format!(
+                        "the Procfile of release v{} has no '{}'; give it a command",
+                        release.id, name
+                    )));
                 }
-                _ => None,
-            };
+            }
 
-            statuses.push(ProjectStatus {
-                slug: slug.clone(),
-                name: config.name.clone(),
-                state,
-                container_id: None,
-                uptime_secs: uptime,
-                memory_usage_mb: Some(memory),
-                cpu_percent: Some(cpu),
-                url,
-                host_port: config.domain.host_port,
-                container_port: config.domain.container_port,
-                network_mode: config.network_mode.to_string(),
-                last_deploy: Some(config.updated_at),
-                last_error: None,
+            let old_replicas = previous.as_ref().map_or(0, |p| p.replicas);
+            let surplus: Vec<String> = (process.replicas..old_replicas)
+                .map(|i| config.worker_name(name, i as usize))
+                .collect();
+            let changed = previous
+                .as_ref()
+                .is_some_and(|p| p.command != process.command || p.port != process.port);
+            let grew = process.replicas > old_replicas;
+            config.container.processes.insert(name.to_string(), process);
+            config.save()?;
+            (surplus, grew, changed)
+        };
+
+        for container in &surplus {
+            let _ = self.docker.stop_container(container).await;
+            self.docker.remove_container(container).await?;
+        }
+
+        let mut message = format!("Process '{}' of '{}' saved", name, slug);
+        if changed {
+            message.push_str("; the new command or port applies on the next deploy");
+        }
+        if !grew {
+            return Ok(ProcessUpdateResponse {
+                message,
+                build_id: None,
+            });
+        }
+        if release.is_none() {
+            message.push_str("; it starts with the first deploy");
+            return Ok(ProcessUpdateResponse {
+                message,
+                build_id: None,
             });
         }
 
-        Ok(statuses)
+        let build_id = build_log::BuildLog::create(slug, &format!("start process {}", name))?.id();
+        Ok(ProcessUpdateResponse {
+            message,
+            build_id: Some(build_id),
+        })
     }
 
-    pub async fn get_project_detail(
+    /// `web` keeps the project's port; its command is stored and its replicas scaled
+    async fn set_web_process(
         &self,
         slug: &str,
-    ) -> Result<ProjectDetailResponse, DockyardError> {
-        let projects = self.projects.read().await;
-        let config = projects
-            .get(slug)
-            .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
-
-        let state = self
-            .docker
-            .get_container_state(&config.container.container_name)
-            .await
-            .unwrap_or(ProjectState::Offline);
-
-        let (memory, cpu) = if state == ProjectState::Online {
-            self.docker
-                .get_container_stats(&config.container.container_name)
-                .await
-                .unwrap_or((0.0, 0.0))
-        } else {
-            (0.0, 0.0)
-        };
+        req: ProcessUpdateRequest,
+    ) -> Result<ProcessUpdateResponse, DockyardError> {
+        if req.port.is_some() || req.clear_port {
+            return Err(DockyardError::Config(
+                "web listens on the project's container port; only its command and replicas \
+                 can be set"
+                    .to_string(),
+            ));
+        }
 
-        let uptime = if state == ProjectState::Online {
-            self.docker
-                .get_container_uptime(&config.container.container_name)
-                .await
-                .unwrap_or(None)
-        } else {
-            None
-        };
+        let mut message = String::new();
+        if req.command.is_some() || req.clear_command {
+            let mut projects = self.projects.write().await;
+            let config = projects
+                .get_mut(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            config.container.command = req.command;
+            config.save()?;
+            message = format!(
+                "Command of web in '{}' saved; it applies on the next deploy",
+                slug
+            );
+        }
 
-        let url = match (&config.network_mode, &config.domain.hostname) {
-            (_, Some(hostname)) => Some(format!("https://{}", hostname)),
-            (crate::config::project::NetworkMode::LocalOnly, None) => {
-                Some(format!("http://localhost:{}", config.domain.host_port))
+        let build_id = match req.replicas {
+            Some(replicas) => {
+                let scaled = self.scale_project(slug, replicas).await?;
+                if !message.is_empty() {
+                    message.push_str(". ");
+                }
+                message.push_str(&scaled.message);
+                scaled.build_id
             }
-            _ => None,
+            None => None,
         };
+        if message.is_empty() {
+            message = format!("Nothing to change for web in '{}'", slug);
+        }
+        Ok(ProcessUpdateResponse { message, build_id })
+    }
 
-        let status = ProjectStatus {
-            slug: slug.to_string(),
-            name: config.name.clone(),
-            state,
-            container_id: None,
-            uptime_secs: uptime,
-            memory_usage_mb: Some(memory),
-            cpu_percent: Some(cpu),
-            url,
-            host_port: config.domain.host_port,
-            container_port: config.domain.container_port,
-            network_mode: config.network_mode.to_string(),
-            last_deploy: Some(config.updated_at),
-            last_error: None,
-        };
+    /// Stop a process type and forget it; `web` cannot be removed
+    pub async fn remove_process(&self, slug: &str, name: &str) -> Result<String, DockyardError> {
+        if name == WEB_PROCESS {
+            return Err(DockyardError::Config(
+                "web cannot be removed; stop the project instead".to_string(),
+            ));
+        }
+        self.reject_if_busy(slug).await?;
 
-        Ok(ProjectDetailResponse {
-            status,
-            repo_url: config.repo_url.clone(),
-            branch: config.branch.clone(),
-            webhook_secret: config.webhook.secret.clone(),
-        })
+        let (reply, removed) = tokio::sync::oneshot::channel();
+        self.scheduler_tx
+            .send(scheduler::Job::RemoveProcess {
+                slug: slug.to_string(),
+                name: name.to_string(),
+                reply,
+            })
+            .await
+            .map_err(|_| DockyardError::Config("the scheduler has stopped".to_string()))?;
+        removed
+            .await
+            .map_err(|_| DockyardError::Config("the process removal was abandoned".to_string()))?
     }
 
-    pub async fn deploy_project(
+    /// Forget a process type and remove its workers while the scheduler holds the project
+    pub(crate) async fn execute_remove_process(
         &self,
-        req: DeployRequest,
-    ) -> Result<DeployResponse, DockyardError> {
-        let name = crate::utils::repo_name(&req.repo_url);
-        let slug = crate::utils::slugify(&name);
-
-        {
-            let projects = self.projects.read().await;
-            if projects.contains_key(&slug) {
-                return Err(DockyardError::ProjectAlreadyExists(slug));
+        slug: &str,
+        name: &str,
+    ) -> Result<String, DockyardError> {
+        let containers: Vec<String> = {
+            let mut projects = self.projects.write().await;
+            let config = projects
+                .get_mut(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            let containers = config
+                .worker_containers()
+                .into_iter()
+                .filter(|(process, _, _)| process == name)
+                .map(|(_, _, container)| container)
+                .collect();
+            if config.container.processes.remove(name).is_none() {
+                return Err(DockyardError::Config(format!(
+                    "'{}' has no process '{}'",
+                    slug, name
+                )));
             }
+            config.save()?;
+            containers
+        };
+
+        for container in &containers {
+            let _ = self.docker.stop_container(container).await;
+            let _ = self.docker.remove_container(container).await;
         }
+        Ok(format!("Process '{}' removed from '{}'", name, slug))
+    }
 
-        let host_port = crate::utils::find_available_port()?;
+    /// Declared mounts with their sizes, plus project volumes that are no longer mounted
+    pub async fn get_volumes(&self, slug: &str) -> Result<VolumesResponse, DockyardError> {
+        let mounts = {
+            let projects = self.projects.read().await;
+            let config = projects
+                .get(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            config.container.volumes.clone()
+        };
+        let mut usage = self.docker.project_volumes(slug).await?;
 
-        let config = ProjectConfig::new(
-            name.clone(),
-            slug.clone(),
-            req.repo_url,
-            req.branch,
-            req.network_mode,
-            req.hostname,
-            req.container_port,
-            host_port,
-        );
+        let mut volumes = Vec::new();
+        for mount in mounts {
+            let (volume, size_bytes) = match mount.volume_name(slug) {
+                Some(name) => {
+                    let size = usage
+                        .iter()
+                        .position(|u| u.name == name)
+                        .and_then(|i| usage.remove(i).size_bytes);
+                    (Some(name), size)
+                }
+                None => {
+                    let path = std::path::PathBuf::from(&mount.source);
+                    let size = tokio::task::spawn_blocking(move || crate::utils::dir_size(&path))
+                        .await
+                        .ok()
+                        .flatten();
+                    (None, size)
+                }
+            };
+            volumes.push(VolumeInfo {
+                mount: Some(mount),
+                volume,
+                size_bytes,
+            });
+        }
+        volumes.extend(usage.into_iter().map(|u| VolumeInfo {
+            mount: None,
+            volume: Some(u.name),
+            size_bytes: u.size_bytes,
+        }));
 
-        config.save()?;
+        Ok(VolumesResponse {
+            slug: slug.to_string(),
+            volumes,
+        })
+    }
 
-        let _ = self
-            .scheduler_tx
-            .send(scheduler::Job::Deploy {
-                slug: slug.clone(),
-            })
-            .await;
+    pub async fn list_volumes(&self) -> Result<Vec<VolumesResponse>, DockyardError> {
+        let mut slugs: Vec<String> = self.projects.read().await.keys().cloned().collect();
+        slugs.sort();
 
-        {
-            let mut projects = self.projects.write().await;
-            projects.insert(slug.clone(), config);
+        let mut all = Vec::new();
+        for slug in slugs {
+            all.push(self.get_volumes(&slug).await?);
         }
-
-        let webhook_port = self.config.read().await.daemon.webhook_port;
-        let webhook_url = format!("http://YOUR_SERVER:{}/webhook/{}", webhook_port, slug);
-
-        Ok(DeployResponse {
-            slug,
-            name,
-            url: Some(format!("http://localhost:{}", host_port)),
-            webhook_url,
-            host_port,
-        })
+        Ok(all)
     }
 
-    pub async fn rebuild_project(&self, slug: &str) -> Result<(), DockyardError> {
-        {
-            let projects = self.projects.read().await;
-            if !projects.contains_key(slug) {
-                return Err(DockyardError::ProjectNotFound(slug.to_string()));
+    /// Change the declared mounts; the container picks them up on its next deploy
+    pub async fn update_volumes(
+        &self,
+        slug: &str,
+        req: VolumesUpdateRequest,
+    ) -> Result<String, DockyardError> {
+        let mut projects = self.projects.write().await;
+        let config = projects
+            .get_mut(slug)
+            .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+
+        let mut volumes = config.container.volumes.clone();
+        for target in &req.remove {
+            let target = target.trim_end_matches('/');
+            let before = volumes.len();
+            volumes.retain(|v| v.target != target);
+            if volumes.len() == before {
+                return Err(DockyardError::Config(format!(
+                    "nothing is mounted at '{}'",
+                    target
+                )));
             }
         }
+        for mount in req.add {
+            // Re-adding a path replaces its mount
+            volumes.retain(|v| v.target != mount.target);
+            volumes.push(mount);
+        }
+        validate_volumes(&volumes)?;
 
-        let _ = self
-            .scheduler_tx
-            .send(scheduler::Job::Rebuild {
-                slug: slug.to_string(),
-                commit_sha: None,
-            })
-            .await;
+        config.container.volumes = volumes;
+        config.save()?;
 
-        Ok(())
+        Ok(format!(
+            "Volumes of '{}' updated; they are mounted from the next deploy",
+            slug
+        ))
     }
 
-    pub async fn start_project(&self, slug: &str) -> Result<(), DockyardError> {
+    pub async fn get_links(&self, slug: &str) -> Result<LinksResponse, DockyardError> {
         let projects = self.projects.read().await;
         let config = projects
             .get(slug)
             .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
 
-        self.docker
-            .inner()
-            .start_container(
-                &config.container.container_name,
-                None::<bollard::container::StartContainerOptions<String>>,
-            )
-            .await?;
+        let links = config
+            .links
+            .iter()
+            .filter_map(|link| projects.get(link))
+            .map(|target| LinkInfo {
+                slug: target.slug.clone(),
+                url: format!("http://{}:{}", target.slug, target.domain.container_port),
+                internal: target.is_internal(),
+            })
+            .collect();
+        let mut linked_by: Vec<String> = projects
+            .values()
+            .filter(|p| p.links.iter().any(|l| l == slug))
+            .map(|p| p.slug.clone())
+            .collect();
+        linked_by.sort();
 
-        Ok(())
+        Ok(LinksResponse {
+            slug: slug.to_string(),
+            links,
+            linked_by,
+        })
     }
 
-    pub async fn stop_project(&self, slug: &str) -> Result<(), DockyardError> {
-        let projects = self.projects.read().await;
+    /// Change the tracked branch and what is checked out with it; applies from the next
+    /// rebuild, whose sync moves the checkout over
+    pub async fn update_settings(
+        &self,
+        slug: &str,
+        req: ProjectSettingsRequest,
+    ) -> Result<String, DockyardError> {
+        if let Some(branch) = &req.branch {
+            if !crate::utils::is_valid_ref(branch) {
+                return Err(DockyardError::Config(format!(
+                    "'{}' is not a valid branch name",
+                    branch
+                )));
+            }
+        }
+
+        let mut projects = self.projects.write().await;
         let config = projects
-            .get(slug)
+            .get_mut(slug)
             .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+        let mut changes = Vec::new();
+        if let Some(branch) = req.branch {
+            changes.push(format!("branch={}", branch));
+            config.branch = branch;
+        }
+        if let Some(submodules) = req.submodules {
+            changes.push(format!("submodules={}", submodules));
+            config.git.submodules = submodules;
+        }
+        if let Some(lfs) = req.lfs {
+            changes.push(format!("lfs={}", lfs));
+            config.git.lfs = lfs;
+        }
+        if changes.is_empty() {
+            return Err(DockyardError::Config("nothing to change".to_string()));
+        }
+        config.save()?;
+        Ok(format!(
+            "'{}' updated ({}); rebuild to apply",
+            slug,
+            changes.join(", ")
+        ))
+    }
+
+    /// Change which projects `slug` may call. Network access to internal projects
+    /// changes immediately; the `<SLUG>_URL` variables follow on the next deploy.
+    pub async fn update_links(
+        &self,
+        slug: &str,
+        req: LinksUpdateRequest,
+    ) -> Result<String, DockyardError> {
+        let (containers, joined, left) = {
+            let mut projects = self.projects.write().await;
+            let config = projects
+                .get(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
 
-        self.docker
-            .stop_container(&config.container.container_name)
-            .await?;
+            let mut links = config.links.clone();
+            links.retain(|l| !req.remove.contains(l));
+            for link in req.add {
+                if !links.contains(&link) {
+                    links.push(link);
+                }
+            }
+            validate_links(slug, config.is_internal(), &links, &projects)?;
 
-        Ok(())
+            let internal = |link: &String| projects.get(link).is_some_and(|p| p.is_internal());
+            let joined: Vec<String> = links
+                .iter()
+                .filter(|l| !config.links.contains(l) && internal(l))
+                .cloned()
+                .collect();
+            let left: Vec<String> = config
+                .links
+                .iter()
+                .filter(|l| !links.contains(l) && internal(l))
+                .cloned()
+                .collect();
+
+            let config = projects.get_mut(slug).expect("checked above");
+            config.links = links;
+            config.save()?;
+            (config.all_containers(), joined, left)
+        };
+
+        for container_name in &containers {
+            if !self
+                .docker
+                .is_container_running(container_name)
+                .await
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            for link in &joined {
+                crate::docker::network::ensure_project_network(&self.docker, link).await?;
+                self.docker
+                    .connect_network(
+                        &crate::docker::network::project_network(link),
+                        container_name,
+                        slug,
+                    )
+                    .await?;
+            }
+            for link in &left {
+                let _ = self
+                    .docker
+                    .disconnect_network(
+                        &crate::docker::network::project_network(link),
+                        container_name,
+                    )
+                    .await;
+            }
+        }
+
+        Ok(format!(
+            "Links of '{}' updated; link variables are set on the next deploy",
+            slug
+        ))
+    }
+
+    /// Delete a project once no build holds it
+    pub async fn delete_project(&self, slug: &str, purge_volumes: bool) -> Result<(), DockyardError> {
+        let (reply, deleted) = tokio::sync::oneshot::channel();
+        self.scheduler_tx
+            .send(scheduler::Job::Delete {
+                slug: slug.to_string(),
+                purge_volumes,
+                reply,
+            })
+            .await
+            .map_err(|_| DockyardError::Config("the scheduler has stopped".to_string()))?;
+        deleted
+            .await
+            .map_err(|_| DockyardError::Config("the delete was abandoned".to_string()))?
     }
 
-    pub async fn delete_project(&self, slug: &str) -> Result<(), DockyardError> {
-        let container_name;
+    /// Remove a project while the scheduler holds it
+    pub(crate) async fn execute_delete(
+        &self,
+        slug: &str,
+        purge_volumes: bool,
+    ) -> Result<(), DockyardError> {
+        let containers;
         let image_name;
+        let internal;
         {
             let projects = self.projects.read().await;
             let config = projects
                 .get(slug)
                 .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
-            container_name = config.container.container_name.clone();
+            containers = config.all_containers();
             image_name = config.container.image_name.clone();
+            internal = config.is_internal();
         }
 
-        let _ = self.docker.stop_container(&container_name).await;
-        let _ = self.docker.remove_container(&container_name).await;
+        let _ = self.proxy.remove_route(slug).await;
+        for container_name in &containers {
+            let _ = self.docker.stop_container(container_name).await;
+            let _ = self.docker.remove_container(container_name).await;
+        }
         let _ = self.docker.remove_image(&image_name).await;
+        if purge_volumes {
+            // Bind mounts are host directories and are never deleted
+            for volume in self.docker.project_volumes(slug).await? {
+                self.docker.remove_volume(&volume.name).await?;
+                tracing::info!("[{}] Removed volume {}", slug, volume.name);
+            }
+        }
+        if let Ok(history) = ReleaseHistory::load(slug) {
+            for tag in history.live_tags() {
+                let _ = self.docker.remove_image(&tag).await;
+            }
+        }
+
+        if internal {
+            crate::docker::network::remove_project_network(&self.docker, slug).await?;
+        }
 
         {
             let mut projects = self.projects.write().await;
             projects.remove(slug);
+            for other in projects.values_mut() {
+                if other.links.iter().any(|l| l == slug) {
+                    other.links.retain(|l| l != slug);
+                    if let Err(e) = other.save() {
+                        tracing::warn!("[{}] Could not drop link to '{}': {}", other.slug, slug, e);
+                    }
+                }
+            }
+        }
+        self.runtime.write().await.remove(slug);
+
+        // Add-ons outlive the project so their data is not lost with it
+        for addon in self.addons.write().await.values_mut() {
+            if addon.project.as_deref() == Some(slug) {
+                addon.project = None;
+                if let Err(e) = addon.save() {
+                    tracing::warn!("[{}] Could not detach add-on '{}': {}", slug, addon.name, e);
+                }
+            }
         }
 
         ProjectConfig::delete(slug)?;
@@ -286,19 +1501,76 @@ impl DaemonState {
         Ok(())
     }
 
-    pub async fn get_project_logs(
-        &self,
-        slug: &str,
-        tail: u32,
-    ) -> Result<Vec<String>, DockyardError> {
+    /// Container a log request reads, checked against the project's processes
+    async fn log_container(&self, slug: &str, source: &LogSource) -> Result<String, DockyardError> {
         let projects = self.projects.read().await;
         let config = projects
             .get(slug)
             .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
 
-        self.docker
-            .get_logs(&config.container.container_name, tail, false)
-            .await
+        let process = source.process.as_deref().unwrap_or(WEB_PROCESS);
+        let replica = source.replica.unwrap_or(1);
+        replica
+            .checked_sub(1)
+            .and_then(|index| config.process_container(process, index as usize))
+            .ok_or_else(|| {
+                DockyardError::Config(format!(
+                    "'{}' runs no replica {} of process '{}'",
+                    slug, replica, process
+                ))
+            })
+    }
+
+    pub async fn get_project_logs(
+        &self,
+        slug: &str,
+        source: &LogSource,
+        filter: &LogFilter,
+    ) -> Result<Vec<LogEntry>, DockyardError> {
+        let container = self.log_container(slug, source).await?;
+        self.docker.get_logs(&container, filter).await
+    }
+
+    /// Open a live log stream for one of a project's containers
+    pub async fn stream_project_logs(
+        &self,
+        slug: &str,
+        source: &LogSource,
+        filter: &LogFilter,
+        follow: bool,
+    ) -> Result<impl Stream<Item = Result<LogEntry, DockyardError>>, DockyardError> {
+        let container = self.log_container(slug, source).await?;
+        Ok(self.docker.log_stream(&container, filter, follow))
+    }
+
+    /// Encrypt all project secrets with a new master key; returns its ID and how many
+    /// values were re-encrypted
+    pub async fn rotate_master_key(&self) -> Result<(String, usize), DockyardError> {
+        // Held throughout so no config is saved with a half-rotated keyring
+        let mut projects = self.projects.write().await;
+        let mut addons = self.addons.write().await;
+        let mut global = self.config.write().await;
+
+        let key_id = secrets::begin_rotation()?;
+        let mut count = 0;
+        for config in projects.values_mut() {
+            count += config.reseal_secrets()?;
+            config.save()?;
+        }
+        for addon in addons.values_mut() {
+            count += addon.reseal_secrets()?;
+            addon.save()?;
+        }
+        count += global.reseal_secrets()?;
+        global.save()?;
+        secrets::finish_rotation()?;
+
+        tracing::info!(
+            "Rotated master key to {} ({} secret(s) re-encrypted)",
+            key_id,
+            count
+        );
+        Ok((key_id, count))
     }
 
     pub async fn get_config_info(&self) -> Result<ConfigResponse, DockyardError> {
@@ -342,7 +1614,18 @@ impl DaemonState {
 pub async fn run() -> anyhow::Result<()> {
     tracing::info!("Starting dockyard daemon...");
 
-    let config = GlobalConfig::load()?;
+    let mut config = GlobalConfig::load()?;
+    secrets::init()?;
+    // An S3 secret key written into the config by hand is encrypted on first load
+    match config.seal_secrets() {
+        Ok(true) => {
+            if let Err(e) = config.save() {
+                tracing::warn!("Could not save the encrypted S3 secret key: {}", e);
+            }
+        }
+        Ok(false) => {}
+        Err(e) => tracing::warn!("Could not encrypt the S3 secret key: {}", e),
+    }
 
     let docker = DockerClient::connect()?;
     docker.ping().await?;
@@ -354,22 +1637,64 @@ pub async fn run() -> anyhow::Result<()> {
 
     let project_configs = ProjectConfig::load_all()?;
     let mut projects = HashMap::new();
-    for pc in project_configs {
+    let mut runtime = HashMap::new();
+    for mut pc in project_configs {
         tracing::info!("Loaded project: {}", pc.slug);
+        // Configs written before secrets were encrypted are migrated on first load
+        match pc.seal_secrets() {
+            Ok(true) => {
+                if let Err(e) = pc.save() {
+                    tracing::warn!("[{}] Could not save encrypted secrets: {}", pc.slug, e);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => tracing::warn!("[{}] Could not encrypt secrets: {}", pc.slug, e),
+        }
+        if let Err(e) = build_log::fail_interrupted(&pc.slug) {
+            tracing::warn!("[{}] Could not close interrupted builds: {}", pc.slug, e);
+        }
+        if let Err(e) = cron::fail_interrupted(&docker, &pc).await {
+            tracing::warn!("[{}] Could not close interrupted cron runs: {}", pc.slug, e);
+        }
+        let mut rt = RuntimeState::load(&pc.slug).unwrap_or_else(|e| {
+            tracing::warn!("[{}] Could not load runtime state: {}", pc.slug, e);
+            RuntimeState::default()
+        });
+        rt.recover();
+        runtime.insert(pc.slug.clone(), rt);
         projects.insert(pc.slug.clone(), pc);
     }
 
+    let mut addons = HashMap::new();
+    for addon in AddonConfig::load_all()? {
+        tracing::info!("Loaded add-on: {}", addon.name);
+        addons.insert(addon.name.clone(), addon);
+    }
+
     let state = Arc::new(DaemonState {
         config: RwLock::new(config.clone()),
         docker,
+        proxy: proxy::CaddyProxy::new(&config.caddy.admin_api, &config.caddy.listen),
         projects: RwLock::new(projects),
+        addons: RwLock::new(addons),
+        runtime: RwLock::new(runtime),
+        backup_lock: tokio::sync::Mutex::new(()),
+        cron_running: tokio::sync::Mutex::new(HashSet::new()),
         started_at: Instant::now(),
         scheduler_tx,
     });
 
+    // Databases first, so projects find them when they start
+    container::start_all_addons(&state).await;
+
     // Start all enabled projects
     container::start_all_projects(&state).await?;
 
+    // Bring Caddy's routes in line with the project list
+    if let Err(e) = state.proxy.reconcile(&*state.projects.read().await).await {
+        tracing::warn!("Could not reconcile proxy routes: {}", e);
+    }
+
     // Write PID file
     let pid = std::process::id();
     let pid_path = crate::config::paths::pid_file_path();
@@ -389,6 +1714,18 @@ pub async fn run() -> anyhow::Result<()> {
         watcher::run(watcher_state).await;
     });
 
+    // Start backup schedules
+    let backup_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        backup::run(backup_state).await;
+    });
+
+    // Start cron jobs
+    let cron_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        cron::run(cron_state).await;
+    });
+
     // Start servers (IPC + webhook)
     let server_state = Arc::clone(&state);
     let server_config = config.clone();
@@ -411,3 +1748,74 @@ pub async fn run() -> anyhow::Result<()> {
     tracing::info!("Dockyard daemon stopped");
     Ok(())
 }
+
+fn validate_volumes(volumes: &[VolumeMount]) -> Result<(), DockyardError> {
+    for (i, volume) in volumes.iter().enumerate() {
+        // Mounts sent over the API skip the CLI's parsing
+        volume
+            .to_string()
+            .parse::<VolumeMount>()
+            .map_err(DockyardError::Config)?;
+        if volumes[..i].iter().any(|v| v.target == volume.target) {
+            return Err(DockyardError::Config(format!(
+                "'{}' is mounted more than once",
+                volume.target
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_replicas(replicas: u32) -> Result<(), DockyardError> {
+    if !(1..=MAX_REPLICAS).contains(&replicas) {
+        return Err(DockyardError::Config(format!(
+            "replicas must be between 1 and {}",
+            MAX_REPLICAS
+        )));
+    }
+    Ok(())
+}
+
+/// Process names end up in container names and host names; cron jobs follow the same rule
+fn validate_name(kind: &str, name: &str) -> Result<(), DockyardError> {
+    let valid = name.len() <= 32
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+    if !valid {
+        return Err(DockyardError::Config(format!(
+            "invalid {} name '{}': use up to 32 lowercase letters and digits, starting with a \
+             letter",
+            kind, name
+        )));
+    }
+    Ok(())
+}
+
+fn validate_links(
+    slug: &str,
+    internal: bool,
+    links: &[String],
+    projects: &HashMap<String, ProjectConfig>,
+) -> Result<(), DockyardError> {
+    for link in links {
+        if link == slug {
+            return Err(DockyardError::Config(format!(
+                "'{}' cannot link to itself",
+                slug
+            )));
+        }
+        let Some(target) = projects.get(link) else {
+            return Err(DockyardError::ProjectNotFound(link.clone()));
+        };
+        // An internal project only sits on its own network and the ones it links into
+        if internal && !target.is_internal() {
+            return Err(DockyardError::Config(format!(
+                "internal project '{}' can only link to other internal projects, not '{}'",
+                slug, link
+            )));
+        }
+    }
+    Ok(())
+}
@@ -1,112 +1,99 @@
-use reqwest::Client;
-use serde_json::json;
+use reqwest::{Client, Response};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
 
+use crate::config::project::ProjectConfig;
 use crate::error::DockyardError;
 
+/// Name of the Caddy HTTP server that holds all dockyard routes
+const SERVER_NAME: &str = "dockyard";
+const ROUTE_PREFIX: &str = "dockyard-";
+
 pub struct CaddyProxy {
     client: Client,
     admin_api: String,
+    listen: Vec<String>,
 }
 
 impl CaddyProxy {
-    pub fn new(admin_api: &str) -> Self {
+    pub fn new(admin_api: &str, listen: &[String]) -> Self {
         Self {
             client: Client::new(),
-            admin_api: admin_api.to_string(),
+            admin_api: admin_api.trim_end_matches('/').to_string(),
+            listen: listen.to_vec(),
         }
     }
 
-    /// Add a reverse proxy route for a project
+    /// Add a reverse proxy route for a project, replacing it if it already exists.
+    /// Requests are spread round-robin across the upstream ports, one per replica.
     pub async fn add_route(
         &self,
         slug: &str,
         hostname: &str,
-        upstream_port: u16,
+        upstream_ports: &[u16],
     ) -> Result<(), DockyardError> {
         let route = json!({
-            "@id": format!("dockyard-{}", slug),
+            "@id": route_id(slug),
             "match": [{"host": [hostname]}],
             "handle": [{
                 "handler": "reverse_proxy",
-                "upstreams": [{"dial": format!("localhost:{}", upstream_port)}]
+                "upstreams": upstreams(upstream_ports),
+                "load_balancing": {"selection_policy": {"policy": "round_robin"}}
             }]
         });
 
-        let url = format!(
-            "{}/config/apps/http/servers/dockyard/routes",
-            self.admin_api
-        );
-
-        let resp = self.client.post(&url).json(&route).send().await;
+        let resp = if self.route_exists(slug).await? {
+            let url = format!("{}/id/{}", self.admin_api, route_id(slug));
+            self.client.patch(&url).json(&route).send().await
+        } else {
+            let url = format!(
+                "{}/config/apps/http/servers/{}/routes",
+                self.admin_api, SERVER_NAME
+            );
+            self.client.post(&url).json(&route).send().await
+        };
+        check(resp).await?;
 
-        match resp {
-            Ok(r) if r.status().is_success() => {
-                tracing::info!(
-                    "Added Caddy route: {} -> localhost:{}",
-                    hostname,
-                    upstream_port
-                );
-                Ok(())
-            }
-            Ok(r) => {
-                let status = r.status();
-                let body = r.text().await.unwrap_or_default();
-                tracing::warn!("Caddy API responded with {}: {}", status, body);
-                Err(DockyardError::Proxy(format!(
-                    "Caddy API error {}: {}",
-                    status, body
-                )))
-            }
-            Err(e) => {
-                tracing::warn!("Could not reach Caddy API: {}", e);
-                Err(DockyardError::Proxy(format!("Caddy not available: {}", e)))
-            }
-        }
+        tracing::info!(
+            "Added Caddy route: {} -> localhost:{:?}",
+            hostname,
+            upstream_ports
+        );
+        Ok(())
     }
 
-    /// Update the upstream port for an existing route
+    /// Point an existing route at new upstream ports, keeping its host match
     pub async fn update_route(
         &self,
         slug: &str,
-        new_upstream_port: u16,
+        upstream_ports: &[u16],
     ) -> Result<(), DockyardError> {
-        let route_id = format!("dockyard-{}", slug);
-        let url = format!("{}/id/{}", self.admin_api, route_id);
-
-        let route = json!({
-            "@id": route_id,
-            "handle": [{
-                "handler": "reverse_proxy",
-                "upstreams": [{"dial": format!("localhost:{}", new_upstream_port)}]
-            }]
-        });
+        let url = format!(
+            "{}/id/{}/handle/0/upstreams",
+            self.admin_api,
+            route_id(slug)
+        );
 
-        let resp = self.client.put(&url).json(&route).send().await;
+        check(
+            self.client
+                .patch(&url)
+                .json(&upstreams(upstream_ports))
+                .send()
+                .await,
+        )
+        .await?;
 
-        match resp {
-            Ok(r) if r.status().is_success() => {
-                tracing::info!(
-                    "Updated Caddy route '{}' -> localhost:{}",
-                    slug,
-                    new_upstream_port
-                );
-                Ok(())
-            }
-            Ok(r) => {
-                let body = r.text().await.unwrap_or_default();
-                Err(DockyardError::Proxy(format!(
-                    "Caddy update error: {}",
-                    body
-                )))
-            }
-            Err(e) => Err(DockyardError::Proxy(format!("Caddy not available: {}", e))),
-        }
+        tracing::info!(
+            "Updated Caddy route '{}' -> localhost:{:?}",
+            slug,
+            upstream_ports
+        );
+        Ok(())
     }
 
     /// Remove a route for a project
     pub async fn remove_route(&self, slug: &str) -> Result<(), DockyardError> {
-        let route_id = format!("dockyard-{}", slug);
-        let url = format!("{}/id/{}", self.admin_api, route_id);
+        let url = format!("{}/id/{}", self.admin_api, route_id(slug));
 
         let resp = self.client.delete(&url).send().await;
 
@@ -124,4 +111,461 @@ impl CaddyProxy {
             }
         }
     }
+
+    async fn route_exists(&self, slug: &str) -> Result<bool, DockyardError> {
+        let url = format!("{}/id/{}", self.admin_api, route_id(slug));
+        let resp = self.client.get(&url).send().await;
+        match resp {
+            Ok(r) if r.status().is_success() => Ok(true),
+            // Caddy answers 404 for unknown IDs
+            Ok(r) if r.status().is_client_error() => Ok(false),
+            other => check(other).await.map(|_| false),
+        }
+    }
+
+    /// Create the dockyard server block, and any missing parents, if Caddy does not have it
+    pub async fn ensure_server(&self) -> Result<(), DockyardError> {
+        let url = format!("{}/config/", self.admin_api);
+        let config: Value = check(self.client.get(&url).send().await)
+            .await?
+            .json()
+            .await
+            .map_err(|e| DockyardError::Proxy(format!("Invalid Caddy config: {}", e)))?;
+
+        let server = json!({
+            "listen": self.listen,
+            "routes": []
+        });
+
+        // Create the shallowest missing level so existing config is left untouched
+        let (path, body) = if config.is_null() {
+            (
+                String::new(),
+                json!({"apps": {"http": {"servers": {SERVER_NAME: server}}}}),
+            )
+        } else if config.pointer("/apps").is_none() {
+            (
+                "apps".to_string(),
+                json!({"http": {"servers": {SERVER_NAME: server}}}),
+            )
+        } else if config.pointer("/apps/http").is_none() {
+            (
+                "apps/http".to_string(),
+                json!({"servers": {SERVER_NAME: server}}),
+            )
+        } else if config.pointer("/apps/http/servers").is_none() {
+            (
+                "apps/http/servers".to_string(),
+                json!({SERVER_NAME: server}),
+            )
+        } else if config
+            .pointer(&format!("/apps/http/servers/{}", SERVER_NAME))
+            .is_none()
+        {
+            (format!("apps/http/servers/{}", SERVER_NAME), server)
+        } else {
+            return Ok(());
+        };
+
+        let url = format!("{}/config/{}", self.admin_api, path);
+        check(self.client.post(&url).json(&body).send().await).await?;
+        tracing::info!("Created Caddy server block '{}'", SERVER_NAME);
+        Ok(())
+    }
+
+    /// Slugs of all dockyard routes currently loaded in Caddy
+    async fn list_routes(&self) -> Result<Vec<String>, DockyardError> {
+        let url = format!(
+            "{}/config/apps/http/servers/{}/routes",
+            self.admin_api, SERVER_NAME
+        );
+        let routes: Value = check(self.client.get(&url).send().await)
+            .await?
+            .json()
+            .await
+            .map_err(|e| DockyardError::Proxy(format!("Invalid Caddy routes: {}", e)))?;
+
+        Ok(routes
+            .as_array()
+            .map(|routes| {
+                routes
+                    .iter()
+                    .filter_map(|r| r.get("@id")?.as_str()?.strip_prefix(ROUTE_PREFIX))
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Make Caddy's routes match the project list: one route per project with a hostname.
+    /// Only a missing server block is an error; a route that fails is logged and skipped.
+    pub async fn reconcile(
+        &self,
+        projects: &HashMap<String, ProjectConfig>,
+    ) -> Result<(), DockyardError> {
+        self.ensure_server().await?;
+
+        let wanted: HashSet<&str> = projects
+            .values()
+            .filter(|p| p.domain.hostname.is_some())
+            .map(|p| p.slug.as_str())
+            .collect();
+
+        // One broken route must not keep the others from being healed
+        match self.list_routes().await {
+            Ok(routes) => {
+                for slug in routes {
+                    if !wanted.contains(slug.as_str()) {
+                        tracing::info!("Removing stale Caddy route for '{}'", slug);
+                        self.remove_route(&slug).await?;
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("Could not list Caddy routes: {}", e),
+        }
+
+        for config in projects.values() {
+            if let Some(hostname) = &config.domain.hostname {
+                let ports = config.upstream_ports();
+                if let Err(e) = self.add_route(&config.slug, hostname, &ports).await {
+                    tracing::warn!("Could not restore Caddy route for '{}': {}", config.slug, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn route_id(slug: &str) -> String {
+    format!("{}{}", ROUTE_PREFIX, slug)
+}
+
+fn upstreams(ports: &[u16]) -> Value {
+    ports
+        .iter()
+        .map(|port| json!({"dial": format!("localhost:{}", port)}))
+        .collect()
+}
+
+/// Turn a transport error or non-2xx answer from the admin API into a proxy error
+async fn check(resp: reqwest::Result<Response>) -> Result<Response, DockyardError> {
+    match resp {
+        Ok(r) if r.status().is_success() => Ok(r),
+        Ok(r) => {
+            let status = r.status();
+            let body = r.text().await.unwrap_or_default();
+            tracing::warn!("Caddy API responded with {}: {}", status, body);
+            Err(DockyardError::Proxy(format!(
+                "Caddy API error {}: {}",
+                status,
+                body.trim()
+            )))
+        }
+        Err(e) => {
+            tracing::warn!("Could not reach Caddy API: {}", e);
+            Err(DockyardError::Proxy(format!("Caddy not available: {}", e)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::project::NetworkMode;
+    use axum::body::Bytes;
+    use axum::extract::State;
+    use axum::http::{Method, StatusCode, Uri};
+    use axum::Json;
+    use std::sync::{Arc, Mutex};
+
+    type Config = Arc<Mutex<Value>>;
+
+    /// Start an in-memory stand-in for Caddy's admin API on a free localhost port.
+    /// Routes matching a host that starts with `fail.` are rejected.
+    async fn fake_caddy(initial: Value) -> (CaddyProxy, Config) {
+        let config: Config = Arc::new(Mutex::new(initial));
+        let app = axum::Router::new()
+            .fallback(admin)
+            .with_state(Arc::clone(&config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let proxy = CaddyProxy::new(&format!("http://{}/", addr), &[":80".to_string()]);
+        (proxy, config)
+    }
+
+    async fn admin(
+        State(config): State<Config>,
+        method: Method,
+        uri: Uri,
+        body: Bytes,
+    ) -> (StatusCode, Json<Value>) {
+        let mut config = config.lock().unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+        if body
+            .pointer("/match/0/host/0")
+            .and_then(Value::as_str)
+            .is_some_and(|h| h.starts_with("fail."))
+        {
+            return (StatusCode::BAD_REQUEST, Json(json!({"error": "rejected"})));
+        }
+
+        let path = uri.path();
+        let pointer = if let Some(rest) = path.strip_prefix("/config") {
+            rest.trim_end_matches('/').to_string()
+        } else if let Some(rest) = path.strip_prefix("/id/") {
+            let (id, rest) = rest.split_once('/').map_or((rest, ""), |(id, r)| (id, r));
+            let Some(found) = find_id(&config, id, String::new()) else {
+                return (StatusCode::NOT_FOUND, Json(json!({"error": "unknown id"})));
+            };
+            match rest {
+                "" => found,
+                rest => format!("{}/{}", found, rest),
+            }
+        } else {
+            return (StatusCode::NOT_FOUND, Json(Value::Null));
+        };
+
+        let found = match method {
+            Method::GET => {
+                return match config.pointer(&pointer) {
+                    Some(value) => (StatusCode::OK, Json(value.clone())),
+                    None => (StatusCode::NOT_FOUND, Json(Value::Null)),
+                }
+            }
+            Method::POST if pointer.is_empty() => {
+                *config = body;
+                true
+            }
+            Method::POST => match config.pointer_mut(&pointer) {
+                Some(Value::Array(items)) => {
+                    items.push(body);
+                    true
+                }
+                _ => set(&mut config, &pointer, body),
+            },
+            Method::PATCH => match config.pointer_mut(&pointer) {
+                Some(value) => {
+                    *value = body;
+                    true
+                }
+                None => false,
+            },
+            Method::DELETE => {
+                let (parent, key) = pointer.rsplit_once('/').unwrap();
+                match config.pointer_mut(parent) {
+                    Some(Value::Array(items)) => match key.parse::<usize>() {
+                        Ok(i) if i < items.len() => {
+                            items.remove(i);
+                            true
+                        }
+                        _ => false,
+                    },
+                    Some(Value::Object(map)) => map.remove(key).is_some(),
+                    _ => false,
+                }
+            }
+            _ => false,
+        };
+        match found {
+            true => (StatusCode::OK, Json(Value::Null)),
+            false => (StatusCode::NOT_FOUND, Json(Value::Null)),
+        }
+    }
+
+    /// JSON pointer of the object carrying `"@id": id`
+    fn find_id(value: &Value, id: &str, at: String) -> Option<String> {
+        match value {
+            Value::Object(map) => {
+                if map.get("@id").and_then(Value::as_str) == Some(id) {
+                    return Some(at);
+                }
+                map.iter()
+                    .find_map(|(k, v)| find_id(v, id, format!("{}/{}", at, k)))
+            }
+            Value::Array(items) => items
+                .iter()
+                .enumerate()
+                .find_map(|(i, v)| find_id(v, id, format!("{}/{}", at, i))),
+            _ => None,
+        }
+    }
+
+    /// Set a key of an existing object
+    fn set(config: &mut Value, pointer: &str, body: Value) -> bool {
+        let (parent, key) = pointer.rsplit_once('/').unwrap();
+        match config.pointer_mut(parent) {
+            Some(Value::Object(map)) => {
+                map.insert(key.to_string(), body);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn routes(config: &Config) -> Vec<Value> {
+        config
+            .lock()
+            .unwrap()
+            .pointer("/apps/http/servers/dockyard/routes")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn route<'a>(routes: &'a [Value], slug: &str) -> Option<&'a Value> {
+        routes.iter().find(|r| r["@id"] == route_id(slug).as_str())
+    }
+
+    fn dials(route: &Value) -> Vec<&str> {
+        route["handle"][0]["upstreams"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|u| u["dial"].as_str().unwrap())
+            .collect()
+    }
+
+    fn project(slug: &str, hostname: Option<&str>, port: u16) -> ProjectConfig {
+        ProjectConfig::new(
+            slug.to_string(),
+            slug.to_string(),
+            "https://example.com/repo.git".to_string(),
+            "main".to_string(),
+            NetworkMode::Public,
+            hostname.map(str::to_string),
+            3000,
+            port,
+        )
+    }
+
+    #[tokio::test]
+    async fn ensure_server_creates_missing_levels() {
+        let (proxy, config) = fake_caddy(Value::Null).await;
+        proxy.ensure_server().await.unwrap();
+        assert_eq!(
+            config
+                .lock()
+                .unwrap()
+                .pointer("/apps/http/servers/dockyard"),
+            Some(&json!({"listen": [":80"], "routes": []}))
+        );
+    }
+
+    #[tokio::test]
+    async fn ensure_server_keeps_existing_servers() {
+        let other = json!({"listen": [":8080"], "routes": [{"@id": "mine"}]});
+        let (proxy, config) =
+            fake_caddy(json!({"apps": {"http": {"servers": {"other": other}}}})).await;
+        proxy.ensure_server().await.unwrap();
+        proxy.ensure_server().await.unwrap();
+
+        let config = config.lock().unwrap();
+        assert_eq!(config.pointer("/apps/http/servers/other"), Some(&other));
+        assert_eq!(
+            config.pointer("/apps/http/servers/dockyard/routes"),
+            Some(&json!([]))
+        );
+    }
+
+    #[tokio::test]
+    async fn add_route_creates_then_replaces() {
+        let (proxy, config) = fake_caddy(Value::Null).await;
+        proxy.ensure_server().await.unwrap();
+
+        proxy
+            .add_route("web", "web.example.com", &[4000])
+            .await
+            .unwrap();
+        proxy
+            .add_route("web", "www.example.com", &[4001, 4002])
+            .await
+            .unwrap();
+
+        let routes = routes(&config);
+        assert_eq!(routes.len(), 1);
+        let web = route(&routes, "web").unwrap();
+        assert_eq!(web["match"][0]["host"], json!(["www.example.com"]));
+        assert_eq!(dials(web), ["localhost:4001", "localhost:4002"]);
+    }
+
+    #[tokio::test]
+    async fn update_route_moves_upstreams_only() {
+        let (proxy, config) = fake_caddy(Value::Null).await;
+        proxy.ensure_server().await.unwrap();
+        proxy
+            .add_route("web", "web.example.com", &[4000])
+            .await
+            .unwrap();
+
+        proxy.update_route("web", &[5000, 5001]).await.unwrap();
+
+        let routes = routes(&config);
+        let web = route(&routes, "web").unwrap();
+        assert_eq!(web["match"][0]["host"], json!(["web.example.com"]));
+        assert_eq!(dials(web), ["localhost:5000", "localhost:5001"]);
+    }
+
+    #[tokio::test]
+    async fn update_route_fails_for_unknown_route() {
+        let (proxy, _) = fake_caddy(Value::Null).await;
+        proxy.ensure_server().await.unwrap();
+        assert!(proxy.update_route("missing", &[5000]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reconcile_removes_stale_and_adds_missing_routes() {
+        let (proxy, config) = fake_caddy(Value::Null).await;
+        proxy.ensure_server().await.unwrap();
+        proxy
+            .add_route("gone", "gone.example.com", &[4000])
+            .await
+            .unwrap();
+        proxy
+            .add_route("api", "old.example.com", &[4001])
+            .await
+            .unwrap();
+
+        let projects: HashMap<String, ProjectConfig> = [
+            project("api", Some("api.example.com"), 4100),
+            project("web", Some("web.example.com"), 4200),
+            project("worker", None, 4300),
+        ]
+        .into_iter()
+        .map(|p| (p.slug.clone(), p))
+        .collect();
+        proxy.reconcile(&projects).await.unwrap();
+
+        let routes = routes(&config);
+        assert_eq!(routes.len(), 2);
+        assert!(route(&routes, "gone").is_none());
+        assert!(route(&routes, "worker").is_none());
+        let api = route(&routes, "api").unwrap();
+        assert_eq!(api["match"][0]["host"], json!(["api.example.com"]));
+        assert_eq!(dials(api), ["localhost:4100"]);
+        assert_eq!(dials(route(&routes, "web").unwrap()), ["localhost:4200"]);
+    }
+
+    #[tokio::test]
+    async fn reconcile_continues_past_a_failing_route() {
+        let (proxy, config) = fake_caddy(Value::Null).await;
+
+        let projects: HashMap<String, ProjectConfig> = [
+            project("a", Some("a.example.com"), 4100),
+            project("broken", Some("fail.example.com"), 4200),
+            project("z", Some("z.example.com"), 4300),
+        ]
+        .into_iter()
+        .map(|p| (p.slug.clone(), p))
+        .collect();
+        proxy.reconcile(&projects).await.unwrap();
+
+        let routes = routes(&config);
+        assert_eq!(routes.len(), 2);
+        assert!(route(&routes, "a").is_some());
+        assert!(route(&routes, "z").is_some());
+        assert!(route(&routes, "broken").is_none());
+    }
 }
@@ -1,80 +1,435 @@
-use std::collections::HashSet;
+use bollard::container::LogOutput;
+use futures_util::StreamExt;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, Mutex};
 
-use crate::config::paths;
-use crate::daemon::DaemonState;
-use crate::docker::build;
+use crate::config::{paths, secrets};
+use crate::config::project::{EnvUpdateMode, HealthCheck, ProjectConfig, WEB_PROCESS};
+use crate::config::release::{Release, ReleaseHistory, ReleaseTrigger};
+use crate::daemon::build_log::BuildLog;
+use crate::daemon::{health, DaemonState};
+use crate::docker::{build, network};
+use crate::docker::client::{ContainerSpec, LogFilter};
+use crate::error::DockyardError;
+use crate::ipc::protocol::{ProcessUpdateRequest, ProcessUpdateResponse};
+use crate::models::project::{DeployPhase, ProjectState};
 
 #[derive(Debug)]
 pub enum Job {
-    Deploy { slug: String },
-    Rebuild { slug: String, commit_sha: Option<String> },
-    Stop { slug: String },
-    Delete { slug: String },
+    Deploy {
+        slug: String,
+        build_id: u32,
+    },
+    /// Rebuild from `commit_sha` or `git_ref` when set, else from the branch's latest commit
+    Rebuild {
+        slug: String,
+        commit_sha: Option<String>,
+        git_ref: Option<String>,
+        trigger: ReleaseTrigger,
+        build_id: u32,
+    },
+    Rollback {
+        slug: String,
+        release: u32,
+        build_id: u32,
+    },
+    UpdateEnv {
+        slug: String,
+        mode: EnvUpdateMode,
+        build_id: u32,
+    },
+    /// Bring the running replicas in line with the configured count
+    Scale {
+        slug: String,
+        build_id: u32,
+    },
+    /// Put volumes and databases back to a backup; the outcome is sent on `reply`
+    Restore {
+        slug: String,
+        id: u32,
+        reply: oneshot::Sender<Result<String, DockyardError>>,
+    },
+    /// Save a worker process type, then remove or start its replicas; the outcome is
+    /// sent on `reply`
+    SetProcess {
+        slug: String,
+        name: String,
+        req: ProcessUpdateRequest,
+        reply: oneshot::Sender<Result<ProcessUpdateResponse, DockyardError>>,
+    },
+    /// Forget a worker process type and remove its containers; the outcome is sent on `reply`
+    RemoveProcess {
+        slug: String,
+        name: String,
+        reply: oneshot::Sender<Result<String, DockyardError>>,
+    },
+    /// Remove a project and its containers; the outcome is sent on `reply`
+    Delete {
+        slug: String,
+        purge_volumes: bool,
+        reply: oneshot::Sender<Result<(), DockyardError>>,
+    },
 }
 
 /// Run the scheduler loop
 pub async fn run(mut rx: mpsc::Receiver<Job>, state: Arc<DaemonState>) {
-    let building: Arc<RwLock<HashSet<String>>> = Arc::new(RwLock::new(HashSet::new()));
+    let claims: Arc<Mutex<Claims>> = Arc::new(Mutex::new(Claims::default()));
 
     while let Some(job) = rx.recv().await {
         let state = Arc::clone(&state);
-        let building = Arc::clone(&building);
+        let claims = Arc::clone(&claims);
 
         tokio::spawn(async move {
             match job {
-                Job::Deploy { slug } => {
-                    {
-                        let mut set = building.write().await;
-                        if set.contains(&slug) {
-                            tracing::warn!("Deploy for '{}' already in progress", slug);
-                            return;
-                        }
-                        set.insert(slug.clone());
+                Job::Deploy { slug, build_id } => {
+                    let Some(log) = open_log(&slug, build_id) else {
+                        return;
+                    };
+                    if !claim(&claims, &slug).await {
+                        tracing::warn!("Deploy for '{}' already in progress", slug);
+                        log.finish(&Err("another build is already in progress"));
+                        return;
                     }
 
-                    if let Err(e) = execute_deploy(&state, &slug).await {
+                    let result = execute_deploy(&state, &slug, &log).await;
+                    if let Err(e) = &result {
                         tracing::error!("Deploy failed for '{}': {}", slug, e);
+                        mark_failed(&state, &slug, e).await;
                     }
+                    log.finish(&result);
 
-                    building.write().await.remove(&slug);
+                    unclaim(&state, &claims, &slug).await;
                 }
-                Job::Rebuild { slug, commit_sha } => {
+                Job::Rebuild {
+                    slug,
+                    commit_sha,
+                    git_ref,
+                    trigger,
+                    build_id,
+                } => {
+                    let Some(log) = open_log(&slug, build_id) else {
+                        return;
+                    };
                     {
-                        let mut set = building.write().await;
-                        if set.contains(&slug) {
-                            tracing::warn!("Rebuild for '{}' already in progress", slug);
+                        let mut held = claims.lock().await;
+                        if !held.building.insert(slug.clone()) {
+                            // Runs when the current job releases the project
+                            log.line("Waiting for the running build to finish...");
+                            let next = PendingRebuild {
+                                commit_sha,
+                                git_ref,
+                                trigger,
+                                log,
+                            };
+                            if let Some(previous) = held.pending.insert(slug.clone(), next) {
+                                previous
+                                    .log
+                                    .finish(&Err(format!("superseded by build #{}", build_id)));
+                            }
                             return;
                         }
-                        set.insert(slug.clone());
                     }
 
-                    if let Err(e) =
-                        execute_rebuild(&state, &slug, commit_sha.as_deref()).await
-                    {
-                        tracing::error!("Rebuild failed for '{}': {}", slug, e);
+                    run_rebuild(
+                        &state,
+                        &slug,
+                        commit_sha.as_deref(),
+                        git_ref.as_deref(),
+                        trigger,
+                        &log,
+                    )
+                    .await;
+
+                    unclaim(&state, &claims, &slug).await;
+                }
+                Job::Rollback {
+                    slug,
+                    release,
+                    build_id,
+                } => {
+                    let Some(log) = open_log(&slug, build_id) else {
+                        return;
+                    };
+                    if !claim(&claims, &slug).await {
+                        tracing::warn!("Rollback for '{}' blocked by a running build", slug);
+                        log.finish(&Err("another build is already in progress"));
+                        return;
+                    }
+
+                    let result = execute_rollback(&state, &slug, release, &log).await;
+                    if let Err(e) = &result {
+                        tracing::error!("Rollback failed for '{}': {}", slug, e);
+                        mark_failed(&state, &slug, e).await;
+                    }
+                    log.finish(&result);
+
+                    unclaim(&state, &claims, &slug).await;
+                }
+                Job::UpdateEnv {
+                    slug,
+                    mode,
+                    build_id,
+                } => {
+                    let Some(log) = open_log(&slug, build_id) else {
+                        return;
+                    };
+                    if !claim(&claims, &slug).await {
+                        tracing::warn!("Env update for '{}' blocked by a running build", slug);
+                        log.finish(&Err("another build is already in progress"));
+                        return;
+                    }
+
+                    let result = execute_update_env(&state, &slug, mode, &log).await;
+                    if let Err(e) = &result {
+                        tracing::error!("Env update failed for '{}': {}", slug, e);
+                        mark_failed(&state, &slug, e).await;
+                    }
+                    log.finish(&result);
+
+                    unclaim(&state, &claims, &slug).await;
+                }
+                Job::Scale { slug, build_id } => {
+                    let Some(log) = open_log(&slug, build_id) else {
+                        return;
+                    };
+                    if !claim(&claims, &slug).await {
+                        tracing::warn!("Scale for '{}' blocked by a running build", slug);
+                        log.finish(&Err("another build is already in progress"));
+                        return;
+                    }
+
+                    let result = execute_scale(&state, &slug, &log).await;
+                    if let Err(e) = &result {
+                        tracing::error!("Scale failed for '{}': {}", slug, e);
+                        mark_failed(&state, &slug, e).await;
+                    }
+                    log.finish(&result);
+
+                    unclaim(&state, &claims, &slug).await;
+                }
+                Job::Restore { slug, id, reply } => {
+                    if !claim(&claims, &slug).await {
+                        tracing::warn!("Restore for '{}' blocked by a running build", slug);
+                        let _ = reply.send(Err(DockyardError::Backup(format!(
+                            "'{}' is being deployed; restore once it finishes",
+                            slug
+                        ))));
+                        return;
+                    }
+
+                    let _ = reply.send(state.execute_restore(&slug, id).await);
+
+                    unclaim(&state, &claims, &slug).await;
+                }
+                Job::SetProcess {
+                    slug,
+                    name,
+                    req,
+                    reply,
+                } => {
+                    if !claim(&claims, &slug).await {
+                        tracing::warn!("Process update for '{}' blocked by a running build", slug);
+                        let _ = reply.send(Err(busy(&slug)));
+                        return;
                     }
 
-                    building.write().await.remove(&slug);
+                    let result = state.execute_set_process(&slug, &name, req).await;
+                    let build_id = result.as_ref().ok().and_then(|r| r.build_id);
+                    let _ = reply.send(result);
+
+                    // New workers start before another job can take the project
+                    if let Some(log) = build_id.and_then(|id| open_log(&slug, id)) {
+                        let result = execute_scale(&state, &slug, &log).await;
+                        if let Err(e) = &result {
+                            tracing::error!("Scale failed for '{}': {}", slug, e);
+                            mark_failed(&state, &slug, e).await;
+                        }
+                        log.finish(&result);
+                    }
+
+                    unclaim(&state, &claims, &slug).await;
                 }
-                Job::Stop { slug } => {
-                    if let Err(e) = state.stop_project(&slug).await {
-                        tracing::error!("Stop failed for '{}': {}", slug, e);
+                Job::RemoveProcess { slug, name, reply } => {
+                    if !claim(&claims, &slug).await {
+                        tracing::warn!("Process removal for '{}' blocked by a running build", slug);
+                        let _ = reply.send(Err(busy(&slug)));
+                        return;
                     }
+
+                    let _ = reply.send(state.execute_remove_process(&slug, &name).await);
+
+                    unclaim(&state, &claims, &slug).await;
                 }
-                Job::Delete { slug } => {
-                    if let Err(e) = state.delete_project(&slug).await {
+                Job::Delete {
+                    slug,
+                    purge_volumes,
+                    reply,
+                } => {
+                    if !claim(&claims, &slug).await {
+                        tracing::warn!("Delete for '{}' blocked by a running build", slug);
+                        let _ = reply.send(Err(DockyardError::Config(format!(
+                            "'{}' is being deployed; delete it once the build finishes",
+                            slug
+                        ))));
+                        return;
+                    }
+
+                    let result = state.execute_delete(&slug, purge_volumes).await;
+                    let deleted = result.is_ok();
+                    if let Err(e) = &result {
                         tracing::error!("Delete failed for '{}': {}", slug, e);
                     }
+                    let _ = reply.send(result);
+
+                    if !deleted {
+                        unclaim(&state, &claims, &slug).await;
+                        return;
+                    }
+                    // Nothing is left to rebuild
+                    let mut held = claims.lock().await;
+                    if let Some(pending) = held.pending.remove(&slug) {
+                        pending.log.finish(&Err("project deleted"));
+                    }
+                    held.building.remove(&slug);
                 }
             }
         });
     }
 }
 
-async fn execute_deploy(state: &DaemonState, slug: &str) -> anyhow::Result<()> {
-    let (repo_url, branch, container_name, image_name, host_port, container_port, env_vars) = {
+fn open_log(slug: &str, build_id: u32) -> Option<BuildLog> {
+    match BuildLog::open(slug, build_id) {
+        Ok(log) => Some(log),
+        Err(e) => {
+            tracing::error!("[{}] Could not open build log #{}: {}", slug, build_id, e);
+            None
+        }
+    }
+}
+
+/// Error for a request that needs the project while a build holds it
+pub(crate) fn busy(slug: &str) -> DockyardError {
+    DockyardError::Config(format!(
+        "'{}' is being deployed; try again once it finishes",
+        slug
+    ))
+}
+
+async fn set_phase(
+    state: &DaemonState,
+    slug: &str,
+    project_state: ProjectState,
+    phase: DeployPhase,
+) {
+    state
+        .update_runtime(slug, |rt| rt.begin(project_state, phase))
+        .await;
+}
+
+/// Record a job failure; the project stays Online if its previous container survived
+async fn mark_failed(state: &DaemonState, slug: &str, error: &anyhow::Error) {
+    let container_name = {
+        let projects = state.projects.read().await;
+        projects
+            .get(slug)
+            .map(|c| c.container.container_name.clone())
+    };
+    let still_running = match container_name {
+        Some(name) => state
+            .docker
+            .is_container_running(&name)
+            .await
+            .unwrap_or(false),
+        None => false,
+    };
+    let next = if still_running {
+        ProjectState::Online
+    } else {
+        ProjectState::Error
+    };
+    state
+        .update_runtime(slug, |rt| rt.fail(next, error.to_string()))
+        .await;
+}
+
+/// Projects a job is running for, and the rebuild each of them runs next
+#[derive(Default)]
+struct Claims {
+    building: HashSet<String>,
+    pending: HashMap<String, PendingRebuild>,
+}
+
+/// A rebuild that arrived while its project was busy; a newer one replaces it
+struct PendingRebuild {
+    commit_sha: Option<String>,
+    git_ref: Option<String>,
+    trigger: ReleaseTrigger,
+    log: BuildLog,
+}
+
+/// Mark a project as building; false if another job already holds it
+async fn claim(claims: &Mutex<Claims>, slug: &str) -> bool {
+    claims.lock().await.building.insert(slug.to_string())
+}
+
+/// Give up a job's claim on a project, running the rebuild queued behind it first
+async fn unclaim(state: &DaemonState, claims: &Mutex<Claims>, slug: &str) {
+    loop {
+        let next = {
+            let mut held = claims.lock().await;
+            match held.pending.remove(slug) {
+                Some(next) => next,
+                None => {
+                    held.building.remove(slug);
+                    return;
+                }
+            }
+        };
+        run_rebuild(
+            state,
+            slug,
+            next.commit_sha.as_deref(),
+            next.git_ref.as_deref(),
+            next.trigger,
+            &next.log,
+        )
+        .await;
+    }
+}
+
+async fn run_rebuild(
+    state: &DaemonState,
+    slug: &str,
+    commit_sha: Option<&str>,
+    git_ref: Option<&str>,
+    trigger: ReleaseTrigger,
+    log: &BuildLog,
+) {
+    let result = execute_rebuild(state, slug, commit_sha, git_ref, trigger, log).await;
+    if let Err(e) = &result {
+        tracing::error!("Rebuild failed for '{}': {}", slug, e);
+        mark_failed(state, slug, e).await;
+    }
+    log.finish(&result);
+}
+
+/// How long a process without a port must stay up before it counts as started
+const WORKER_GRACE_SECS: u64 = 5;
+
+/// A release command still running after this long fails the deploy
+const RELEASE_TIMEOUT_SECS: u64 = 1800;
+
+/// Tag name used for the image of a given release
+pub fn release_tag(release_id: u32) -> String {
+    format!("release-{}", release_id)
+}
+
+async fn execute_deploy(state: &DaemonState, slug: &str, log: &BuildLog) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let (repo_url, branch, git, image_name, hostname, host_port, health_check) = {
         let projects = state.projects.read().await;
         let config = projects
             .get(slug)
@@ -82,36 +437,133 @@ async fn execute_deploy(state: &DaemonState, slug: &str) -> anyhow::Result<()> {
         (
             config.repo_url.clone(),
             config.branch.clone(),
-            config.container.container_name.clone(),
+            config.git.clone(),
             config.container.image_name.clone(),
+            config.domain.hostname.clone(),
             config.domain.host_port,
-            config.domain.container_port,
-            config.container.env_vars.clone(),
+            config.container.health_check.clone(),
         )
     };
 
     let repo_dir = paths::project_repo_dir(slug);
 
-    tracing::info!("[{}] Cloning repository...", slug);
-    crate::utils::git_clone(&repo_url, &repo_dir, &branch).await?;
+    set_phase(state, slug, ProjectState::Building, DeployPhase::Cloning).await;
+    log.line("Cloning repository...");
+    let sha = crate::utils::git_sync(&repo_url, &repo_dir, &branch, None, &git).await?;
+    log.line(&format!("Checked out commit: {}", &sha[..7.min(sha.len())]));
 
-    tracing::info!("[{}] Building Docker image...", slug);
-    let dockerfile = build::find_dockerfile(&repo_dir)?;
-    let tag = format!("{}:latest", image_name);
-    build::build_image(&state.docker, &repo_dir, &tag, &dockerfile).await?;
+    let history = ReleaseHistory::load(slug)?;
+    let release_id = history.next_id();
+    let tag = format!("{}:{}", image_name, release_tag(release_id));
 
-    tracing::info!("[{}] Starting container...", slug);
-    let container_id = state
-        .docker
-        .create_and_start_container(&container_name, &tag, host_port, container_port, &env_vars)
-        .await?;
+    set_phase(state, slug, ProjectState::Building, DeployPhase::Building).await;
+    log.line("Building Docker image...");
+    let procfile = build_release(state, slug, &repo_dir, &tag, log).await?;
+    let released =
+        run_release_command(state, slug, ProjectState::Building, release_id, &tag, log).await;
+    if let Err(e) = released {
+        let _ = state.docker.remove_image(&tag).await;
+        return Err(e);
+    }
+    state.docker.tag_image(&tag, &image_name, "latest").await?;
+    let image = ReleaseImage {
+        tag: &tag,
+        procfile: &procfile,
+    };
 
-    tracing::info!(
-        "[{}] Deployed (container: {}, port: {})",
-        slug,
-        &container_id[..12.min(container_id.len())],
-        host_port
-    );
+    set_phase(state, slug, ProjectState::Building, DeployPhase::Starting).await;
+    let project = project_config(state, slug).await?;
+    // A hand-edited config may ask for none; the first replica always runs
+    let replicas = (project.container.replicas as usize).max(1);
+    // Containers left behind by an earlier deploy would block the names below
+    for name in project.all_containers().iter().skip(1) {
+        let _ = state.docker.stop_container(name).await;
+        let _ = state.docker.remove_container(name).await;
+    }
+    let mut ports = Vec::new();
+    let mut container_id = String::new();
+    for index in 0..replicas {
+        let name = project.replica_name(index);
+        // Each replica binds its port before the next one looks for a free one
+        let port = match index {
+            0 => host_port,
+            _ => crate::utils::find_available_port()?,
+        };
+        // Built after the build, which may have detected the port
+        let spec = container_spec(state, slug, &name, WEB_PROCESS, &image, port).await?;
+        if replicas > 1 {
+            log.line(&format!("Starting replica {}/{}...", index + 1, replicas));
+        } else {
+            log.line("Starting container...");
+        }
+        let id = state.docker.create_and_start_container(&spec).await?;
+
+        // Nothing else is serving yet, so an unhealthy container is kept for inspection
+        await_healthy(state, &name, port, spec.container_port, &health_check, log)
+            .await
+            .map_err(|e| anyhow::anyhow!("Container never became healthy: {}", e))?;
+
+        if index == 0 {
+            container_id = id;
+        }
+        ports.push(port);
+    }
+    {
+        let mut projects = state.projects.write().await;
+        if let Some(config) = projects.get_mut(slug) {
+            config.domain.replica_ports = ports[1..].to_vec();
+            config.save()?;
+        }
+    }
+
+    if replicas > 1 {
+        log.line(&format!(
+            "Deployed {} replicas (ports: {:?})",
+            replicas, ports
+        ));
+    } else {
+        log.line(&format!(
+            "Deployed (container: {}, port: {})",
+            &container_id[..12.min(container_id.len())],
+            host_port
+        ));
+    }
+
+    if let Some(hostname) = &hostname {
+        log.line(&format!("Routing {} through Caddy...", hostname));
+        // The containers are already serving; a missing route is healed on daemon startup
+        if let Err(e) = state.proxy.add_route(slug, hostname, &ports).await {
+            log.line(&format!("Warning: could not add proxy route: {}", e));
+        }
+    }
+
+    for (process, index, name) in project.worker_containers() {
+        log.line(&format!(
+            "Starting {} process (replica {})...",
+            process,
+            index + 1
+        ));
+        let spec = container_spec(state, slug, &name, &process, &image, 0).await?;
+        state.docker.create_and_start_container(&spec).await?;
+        await_worker(state, &project, &process, &name, log)
+            .await
+            .map_err(|e| anyhow::anyhow!("{} never became healthy: {}", name, e))?;
+    }
+
+    let release = Release {
+        id: release_id,
+        commit_sha: Some(sha),
+        git_ref: None,
+        image_id: state.docker.get_image_id(&tag).await?,
+        image_tag: tag,
+        trigger: ReleaseTrigger::Deploy,
+        created_at: chrono::Utc::now(),
+        duration_secs: started.elapsed().as_secs(),
+        source_release: None,
+        pruned: false,
+        procfile,
+    };
+    record_release(state, slug, history, release).await?;
 
     {
         let mut projects = state.projects.write().await;
@@ -120,6 +572,9 @@ async fn execute_deploy(state: &DaemonState, slug: &str) -> anyhow::Result<()> {
             let _ = config.save();
         }
     }
+    state
+        .update_runtime(slug, |rt| rt.succeed(container_id))
+        .await;
 
     Ok(())
 }
@@ -127,99 +582,181 @@ async fn execute_deploy(state: &DaemonState, slug: &str) -> anyhow::Result<()> {
 async fn execute_rebuild(
     state: &DaemonState,
     slug: &str,
-    _commit_sha: Option<&str>,
+    commit_sha: Option<&str>,
+    git_ref: Option<&str>,
+    trigger: ReleaseTrigger,
+    log: &BuildLog,
 ) -> anyhow::Result<()> {
-    let (branch, container_name, image_name, host_port, container_port, env_vars) = {
+    let started = Instant::now();
+    let (repo_url, branch, git, image_name) = {
         let projects = state.projects.read().await;
         let config = projects
             .get(slug)
             .ok_or_else(|| anyhow::anyhow!("Project '{}' not found", slug))?;
         (
+            config.repo_url.clone(),
             config.branch.clone(),
-            config.container.container_name.clone(),
+            config.git.clone(),
             config.container.image_name.clone(),
-            config.domain.host_port,
-            config.domain.container_port,
-            config.container.env_vars.clone(),
         )
     };
 
     let repo_dir = paths::project_repo_dir(slug);
 
-    // Pull latest code
-    tracing::info!("[{}] Pulling latest code...", slug);
-    let sha = crate::utils::git_pull(&repo_dir, &branch).await?;
-    tracing::info!("[{}] Latest commit: {}", slug, &sha[..7.min(sha.len())]);
+    set_phase(state, slug, ProjectState::Rebuilding, DeployPhase::Cloning).await;
+    // Exactly what was asked for, even if the branch has moved on since
+    let revision = commit_sha.or(git_ref);
+    match revision {
+        Some(revision) => log.line(&format!("Fetching {}...", revision)),
+        None => log.line(&format!("Fetching latest {}...", branch)),
+    }
+    let sha = crate::utils::git_sync(&repo_url, &repo_dir, &branch, revision, &git).await?;
+    log.line(&format!("Checked out commit: {}", &sha[..7.min(sha.len())]));
 
     // Build new image
-    let timestamp = chrono::Utc::now().timestamp();
-    let new_tag = format!("{}:build-{}", image_name, timestamp);
-    tracing::info!("[{}] Building new image...", slug);
-    let dockerfile = build::find_dockerfile(&repo_dir)?;
-    build::build_image(&state.docker, &repo_dir, &new_tag, &dockerfile).await?;
-
-    // Blue-green: start new container
-    let temp_container = format!("{}-new", container_name);
-    let temp_port = crate::utils::find_available_port()?;
-
-    tracing::info!("[{}] Starting new container (blue-green)...", slug);
-    let _ = state
+    let history = ReleaseHistory::load(slug)?;
+    let release_id = history.next_id();
+    let new_tag = format!("{}:{}", image_name, release_tag(release_id));
+    set_phase(state, slug, ProjectState::Rebuilding, DeployPhase::Building).await;
+    log.line(&format!("Building new image (release v{})...", release_id));
+    let procfile = build_release(state, slug, &repo_dir, &new_tag, log).await?;
+    // The old containers keep serving if it fails
+    let released = run_release_command(
+        state,
+        slug,
+        ProjectState::Rebuilding,
+        release_id,
+        &new_tag,
+        log,
+    )
+    .await;
+    if let Err(e) = released {
+        let _ = state.docker.remove_image(&new_tag).await;
+        return Err(e);
+    }
+
+    let image = ReleaseImage {
+        tag: &new_tag,
+        procfile: &procfile,
+    };
+    let container_id = match switch_container(state, slug, &image, log).await {
+        Ok(id) => id,
+        Err(e) => {
+            let _ = state.docker.remove_image(&new_tag).await;
+            return Err(e);
+        }
+    };
+
+    // Re-tag image as latest
+    state
         .docker
-        .create_and_start_container(
-            &temp_container,
-            &new_tag,
-            temp_port,
-            container_port,
-            &env_vars,
-        )
+        .tag_image(&new_tag, &image_name, "latest")
         .await?;
 
-    // Wait for stabilization
-    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+    let release = Release {
+        id: release_id,
+        commit_sha: Some(sha),
+        git_ref: git_ref.map(str::to_string),
+        image_id: state.docker.get_image_id(&new_tag).await?,
+        image_tag: new_tag,
+        trigger,
+        created_at: chrono::Utc::now(),
+        duration_secs: started.elapsed().as_secs(),
+        source_release: None,
+        pruned: false,
+        procfile,
+    };
+    record_release(state, slug, history, release).await?;
 
-    if !state
-        .docker
-        .is_container_running(&temp_container)
-        .await?
+    // Update timestamp
     {
-        let _ = state.docker.remove_container(&temp_container).await;
-        let _ = state.docker.remove_image(&new_tag).await;
-        return Err(anyhow::anyhow!("New container failed to start"));
+        let mut projects = state.projects.write().await;
+        if let Some(config) = projects.get_mut(slug) {
+            config.updated_at = chrono::Utc::now();
+            let _ = config.save();
+        }
     }
 
-    // Stop old container
-    tracing::info!("[{}] Switching to new container...", slug);
-    let _ = state.docker.stop_container(&container_name).await;
-    let _ = state.docker.remove_container(&container_name).await;
+    state
+        .update_runtime(slug, |rt| rt.succeed(container_id))
+        .await;
+
+    log.line("Rebuild complete (zero-downtime)");
+    Ok(())
+}
+
+async fn execute_rollback(
+    state: &DaemonState,
+    slug: &str,
+    target_id: u32,
+    log: &BuildLog,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let image_name = {
+        let projects = state.projects.read().await;
+        let config = projects
+            .get(slug)
+            .ok_or_else(|| anyhow::anyhow!("Project '{}' not found", slug))?;
+        config.container.image_name.clone()
+    };
+
+    let history = ReleaseHistory::load(slug)?;
+    let target = history
+        .get(target_id)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("Release v{} not found", target_id))?;
+    if target.pruned {
+        return Err(anyhow::anyhow!(
+            "Image for release v{} is no longer available",
+            target_id
+        ));
+    }
 
-    // Rename new container
+    // Give the old image a fresh release tag so retention treats it as new
+    let release_id = history.next_id();
+    let new_tag = format!("{}:{}", image_name, release_tag(release_id));
+    log.line(&format!(
+        "Rolling back to release v{} (as v{})...",
+        target_id, release_id
+    ));
     state
         .docker
-        .inner()
-        .rename_container(
-            &temp_container,
-            bollard::container::RenameContainerOptions {
-                name: container_name.clone(),
-            },
-        )
+        .tag_image(&target.image_tag, &image_name, &release_tag(release_id))
         .await?;
 
-    // Re-tag image as latest
+    // The rolled back release runs the processes its own Procfile declared
+    let image = ReleaseImage {
+        tag: &new_tag,
+        procfile: &target.procfile,
+    };
+    let container_id = match switch_container(state, slug, &image, log).await {
+        Ok(id) => id,
+        Err(e) => {
+            let _ = state.docker.remove_image(&new_tag).await;
+            return Err(e);
+        }
+    };
+
     state
         .docker
-        .inner()
-        .tag_image(
-            &new_tag,
-            Some(bollard::image::TagImageOptions {
-                repo: image_name.as_str(),
-                tag: "latest",
-            }),
-        )
+        .tag_image(&new_tag, &image_name, "latest")
         .await?;
 
-    let _ = state.docker.remove_image(&new_tag).await;
+    let release = Release {
+        id: release_id,
+        commit_sha: target.commit_sha.clone(),
+        git_ref: target.git_ref.clone(),
+        image_id: target.image_id.clone(),
+        image_tag: new_tag,
+        trigger: ReleaseTrigger::Rollback,
+        created_at: chrono::Utc::now(),
+        duration_secs: started.elapsed().as_secs(),
+        source_release: Some(target.id),
+        pruned: false,
+        procfile: target.procfile.clone(),
+    };
+    record_release(state, slug, history, release).await?;
 
-    // Update timestamp
     {
         let mut projects = state.projects.write().await;
         if let Some(config) = projects.get_mut(slug) {
@@ -228,6 +765,1016 @@ async fn execute_rebuild(
         }
     }
 
-    tracing::info!("[{}] Rebuild complete (zero-downtime)", slug);
+    state
+        .update_runtime(slug, |rt| rt.succeed(container_id))
+        .await;
+
+    log.line(&format!("Rollback to v{} complete", target_id));
+    Ok(())
+}
+
+/// Recreate the project's containers from its current release so they pick up changed
+/// environment variables
+async fn execute_update_env(
+    state: &DaemonState,
+    slug: &str,
+    mode: EnvUpdateMode,
+    log: &BuildLog,
+) -> anyhow::Result<()> {
+    let project = project_config(state, slug).await?;
+    let replicas = (project.container.replicas as usize).max(1);
+
+    let history = ReleaseHistory::load(slug)?;
+    let release = history
+        .current
+        .and_then(|id| history.get(id))
+        .ok_or_else(|| anyhow::anyhow!("'{}' has no deployed release", slug))?;
+    let image = ReleaseImage {
+        tag: &release.image_tag,
+        procfile: &release.procfile,
+    };
+    let spec = container_spec(
+        state,
+        slug,
+        &project.container.container_name,
+        WEB_PROCESS,
+        &image,
+        project.domain.host_port,
+    )
+    .await?;
+    log.line(&format!(
+        "Applying {} variable(s) to release v{}",
+        spec.env_vars.len(),
+        release.id
+    ));
+
+    // A stopped project gets fresh containers that `start` will pick up as is
+    if !project.enabled {
+        log.line("Project is stopped; recreating its containers without starting them...");
+        let mut container_id = String::new();
+        for index in 0..replicas {
+            let name = project.replica_name(index);
+            let port = match project.replica_port(index) {
+                Some(port) => port,
+                None => crate::utils::find_available_port()?,
+            };
+            let spec = container_spec(state, slug, &name, WEB_PROCESS, &image, port).await?;
+            let _ = state.docker.remove_container(&name).await;
+            let id = state.docker.create_container(&spec).await?;
+            move_traffic(state, slug, false, |c| c.set_replica_port(index, port)).await?;
+            if index == 0 {
+                container_id = id;
+            }
+        }
+        for (process, _, name) in project.worker_containers() {
+            let spec = container_spec(state, slug, &name, &process, &image, 0).await?;
+            let _ = state.docker.remove_container(&name).await;
+            state.docker.create_container(&spec).await?;
+        }
+        state
+            .update_runtime(slug, |rt| rt.container_id = Some(container_id))
+            .await;
+        log.line("Environment updated; it takes effect when the project is started");
+        return Ok(());
+    }
+
+    let container_id = match mode {
+        EnvUpdateMode::BlueGreen => switch_container(state, slug, &image, log).await?,
+        EnvUpdateMode::Restart => {
+            set_phase(state, slug, ProjectState::Rebuilding, DeployPhase::Starting).await;
+            let routed = project.domain.hostname.is_some();
+            let mut container_id = String::new();
+            // One replica at a time, so the others keep serving
+            for index in 0..replicas {
+                let name = project.replica_name(index);
+                let port = match project.replica_port(index) {
+                    Some(port) => port,
+                    None => crate::utils::find_available_port()?,
+                };
+                if replicas > 1 {
+                    log.line(&format!(
+                        "Recreating replica {}/{} in place...",
+                        index + 1,
+                        replicas
+                    ));
+                } else {
+                    log.line("Recreating container in place...");
+                }
+                let spec = container_spec(state, slug, &name, WEB_PROCESS, &image, port).await?;
+                let _ = state.docker.remove_container(&name).await;
+                let id = state.docker.create_and_start_container(&spec).await?;
+                await_healthy(
+                    state,
+                    &name,
+                    port,
+                    spec.container_port,
+                    &project.container.health_check,
+                    log,
+                )
+                .await
+                .map_err(|e| anyhow::anyhow!("Container never became healthy: {}", e))?;
+                move_traffic(state, slug, routed, |c| c.set_replica_port(index, port)).await?;
+                if index == 0 {
+                    container_id = id;
+                }
+            }
+            for (process, _, name) in project.worker_containers() {
+                log.line(&format!("Recreating {} in place...", name));
+                let spec = container_spec(state, slug, &name, &process, &image, 0).await?;
+                let _ = state.docker.remove_container(&name).await;
+                state.docker.create_and_start_container(&spec).await?;
+                await_worker(state, &project, &process, &name, log)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("{} never became healthy: {}", name, e))?;
+            }
+            container_id
+        }
+    };
+
+    state
+        .update_runtime(slug, |rt| rt.succeed(container_id))
+        .await;
+    log.line("Environment updated");
+    Ok(())
+}
+
+/// Bring the project's containers in line with its replica counts: surplus web replicas
+/// are taken out of the route before they are removed, missing ones are started from the
+/// current release and join the route once healthy. Missing containers of the other
+/// process types are started too; their surplus is removed when they are scaled down.
+async fn execute_scale(state: &DaemonState, slug: &str, log: &BuildLog) -> anyhow::Result<()> {
+    let project = project_config(state, slug).await?;
+    let replicas = (project.container.replicas as usize).max(1);
+    let routed = project.domain.hostname.is_some();
+
+    let history = ReleaseHistory::load(slug)?;
+    let release = history
+        .current
+        .and_then(|id| history.get(id))
+        .ok_or_else(|| anyhow::anyhow!("'{}' has no deployed release", slug))?;
+    let image = ReleaseImage {
+        tag: &release.image_tag,
+        procfile: &release.procfile,
+    };
+
+    let existing = project.replica_names();
+    if existing.len() > replicas {
+        let surplus = &existing[replicas..];
+        log.line(&format!("Removing {} replica(s)...", surplus.len()));
+        move_traffic(state, slug, routed, |c| {
+            c.domain.replica_ports.truncate(replicas - 1)
+        })
+        .await?;
+
+        let drain_secs = state.config.read().await.daemon.drain_secs;
+        if project.enabled && drain_secs > 0 {
+            log.line(&format!("Draining removed replicas for {}s...", drain_secs));
+            tokio::time::sleep(std::time::Duration::from_secs(drain_secs)).await;
+        }
+        for name in surplus {
+            let _ = state.docker.stop_container(name).await;
+            state.docker.remove_container(name).await?;
+        }
+    }
+
+    for index in 0..replicas {
+        let name = project.replica_name(index);
+        if state.docker.get_container_state(&name).await? != ProjectState::Offline {
+            continue;
+        }
+
+        // A replica keeps its port; users and firewalls rely on the first one's
+        let port = match project.replica_port(index) {
+            Some(port) => port,
+            None => crate::utils::find_available_port()?,
+        };
+        log.line(&format!(
+            "Creating replica {}/{} from release v{} on port {}...",
+            index + 1,
+            replicas,
+            release.id,
+            port
+        ));
+        let spec = container_spec(state, slug, &name, WEB_PROCESS, &image, port).await?;
+        // Dead and paused containers count as offline but still hold the name
+        let _ = state.docker.remove_container(&name).await;
+        if project.enabled {
+            state.docker.create_and_start_container(&spec).await?;
+            if let Err(e) = await_healthy(
+                state,
+                &name,
+                port,
+                spec.container_port,
+                &project.container.health_check,
+                log,
+            )
+            .await
+            {
+                let _ = state.docker.remove_container(&name).await;
+                return Err(anyhow::anyhow!(
+                    "Replica {} never became healthy: {}",
+                    index + 1,
+                    e
+                ));
+            }
+        } else {
+            state.docker.create_container(&spec).await?;
+        }
+        move_traffic(state, slug, routed, |c| c.set_replica_port(index, port)).await?;
+    }
+
+    for (process, index, name) in project.worker_containers() {
+        if state.docker.get_container_state(&name).await? != ProjectState::Offline {
+            continue;
+        }
+        log.line(&format!(
+            "Creating {} process (replica {}) from release v{}...",
+            process,
+            index + 1,
+            release.id
+        ));
+        let spec = container_spec(state, slug, &name, &process, &image, 0).await?;
+        let _ = state.docker.remove_container(&name).await;
+        if !project.enabled {
+            state.docker.create_container(&spec).await?;
+            continue;
+        }
+        state.docker.create_and_start_container(&spec).await?;
+        if let Err(e) = await_worker(state, &project, &process, &name, log).await {
+            let _ = state.docker.remove_container(&name).await;
+            return Err(anyhow::anyhow!("{} never became healthy: {}", name, e));
+        }
+    }
+
+    log.line(&format!("Running {} replica(s)", replicas));
+    Ok(())
+}
+
+/// The image a job runs and the Procfile it was built with
+struct ReleaseImage<'a> {
+    tag: &'a str,
+    procfile: &'a BTreeMap<String, String>,
+}
+
+/// How to create a container running `process` from `image`, with secrets decrypted.
+/// Only `web` containers publish `host_port` and answer to the project's slug; the
+/// others are reachable as `<process>.<slug>`.
+async fn container_spec(
+    state: &DaemonState,
+    slug: &str,
+    name: &str,
+    process: &str,
+    image: &ReleaseImage<'_>,
+    host_port: u16,
+) -> anyhow::Result<ContainerSpec> {
+    let (spec, volumes, internal) = {
+        let projects = state.projects.read().await;
+        let config = projects
+            .get(slug)
+            .ok_or_else(|| anyhow::anyhow!("Project '{}' not found", slug))?;
+
+        let web = process == WEB_PROCESS;
+        let command = config.process_command(process, image.procfile);
+        if !web && command.is_none() {
+            return Err(anyhow::anyhow!(
+                "Process '{}' has no command; set one or declare it in the Procfile",
+                process
+            ));
+        }
+        let container_port = match config.container.processes.get(process) {
+            Some(p) if !web => p.port.unwrap_or(config.domain.container_port),
+            _ => config.domain.container_port,
+        };
+
+        let mut env_vars = secrets::reveal_all(&config.container.env_vars)?;
+        let mut networks = vec![if config.is_internal() {
+            network::project_network(slug)
+        } else {
+            network::DOCKYARD_NETWORK.to_string()
+        }];
+        // Slugs of internal projects whose networks this container joins
+        let mut internal: Vec<String> = config
+            .is_internal()
+            .then(|| slug.to_string())
+            .into_iter()
+            .collect();
+
+        for link in &config.links {
+            let Some(target) = projects.get(link) else {
+                tracing::warn!("[{}] Linked project '{}' no longer exists", slug, link);
+                continue;
+            };
+            if target.is_internal() {
+                networks.push(network::project_network(link));
+                internal.push(link.clone());
+            }
+            // Variables the user set themselves take precedence
+            let prefix = link.to_uppercase().replace('-', "_");
+            let port = target.domain.container_port;
+            env_vars
+                .entry(format!("{}_HOST", prefix))
+                .or_insert_with(|| link.clone());
+            env_vars
+                .entry(format!("{}_PORT", prefix))
+                .or_insert_with(|| port.to_string());
+            env_vars
+                .entry(format!("{}_URL", prefix))
+                .or_insert_with(|| format!("http://{}:{}", link, port));
+        }
+
+        for addon in state.addons.read().await.values() {
+            if addon.project.as_deref() == Some(slug) {
+                env_vars
+                    .entry(addon.env_var.clone())
+                    .or_insert(addon.url()?);
+            }
+        }
+
+        let volumes = &config.container.volumes;
+        let spec = ContainerSpec {
+            name: name.to_string(),
+            image: image.tag.to_string(),
+            host_port: (web && !config.is_internal()).then_some(host_port),
+            container_port,
+            env_vars,
+            resources: config.container.resources.clone(),
+            binds: volumes.iter().map(|v| v.bind_spec(slug)).collect(),
+            networks,
+            alias: if web {
+                slug.to_string()
+            } else {
+                format!("{}.{}", process, slug)
+            },
+            command: command.map(|c| shell(&c)),
+            restart: true,
+            open_stdin: false,
+            tty: false,
+        };
+        (spec, volumes.clone(), internal)
+    };
+
+    for project in &internal {
+        network::ensure_project_network(&state.docker, project).await?;
+    }
+    // Create named volumes up front so they carry the project label
+    for volume in volumes.iter().filter_map(|v| v.volume_name(slug)) {
+        state.docker.ensure_volume(&volume, slug).await?;
+    }
+    Ok(spec)
+}
+
+/// Spec of a container that runs `command` once from `image_tag`, with the project's env,
+/// volumes and networks but no published port
+pub async fn one_off_spec(
+    state: &DaemonState,
+    slug: &str,
+    name: &str,
+    image_tag: &str,
+    command: Vec<String>,
+) -> anyhow::Result<ContainerSpec> {
+    let procfile = BTreeMap::new();
+    let image = ReleaseImage {
+        tag: image_tag,
+        procfile: &procfile,
+    };
+    let mut spec = container_spec(state, slug, name, WEB_PROCESS, &image, 0).await?;
+    spec.host_port = None;
+    // Not under the project's name, which would send it traffic
+    spec.alias = name.to_string();
+    spec.command = Some(command);
+    spec.restart = false;
+    Ok(spec)
+}
+
+/// Procfile and cron commands are shell command lines
+pub fn shell(command: &str) -> Vec<String> {
+    vec!["/bin/sh".to_string(), "-c".to_string(), command.to_string()]
+}
+
+/// Build the project's image from its checkout, honoring its Dockerfile and context settings.
+/// Returns the process types of the checkout's Procfile.
+async fn build_release(
+    state: &DaemonState,
+    slug: &str,
+    repo_dir: &Path,
+    tag: &str,
+    log: &BuildLog,
+) -> anyhow::Result<BTreeMap<String, String>> {
+    let (container, mut build_config) = {
+        let projects = state.projects.read().await;
+        let config = projects
+            .get(slug)
+            .ok_or_else(|| anyhow::anyhow!("Project '{}' not found", slug))?;
+        (config.container.clone(), config.build.clone())
+    };
+    // Decrypted only for the duration of the build
+    build_config.secrets = secrets::reveal_all(&build_config.secrets)?;
+
+    let plan = build::plan_build(
+        repo_dir,
+        &container,
+        &build_config,
+        &paths::project_dockerfile_override_path(slug),
+    )?;
+    log.line(&format!(
+        "Using {} Dockerfile {} (context: {})",
+        plan.source,
+        plan.dockerfile,
+        container.build_context.as_deref().unwrap_or(".")
+    ));
+    if let Some(target) = &plan.target {
+        log.line(&format!("Target stage: {}", target));
+    }
+    if !plan.args.is_empty() || !plan.secrets.is_empty() {
+        let mut args: Vec<&str> = plan.args.keys().map(String::as_str).collect();
+        let mut secrets: Vec<&str> = plan.secrets.keys().map(String::as_str).collect();
+        args.sort_unstable();
+        secrets.sort_unstable();
+        log.line(&format!(
+            "Build args: [{}], secrets: [{}]",
+            args.join(", "),
+            secrets.join(", ")
+        ));
+    }
+
+    // Projects deployed without an explicit port follow what the build declares
+    if let Some(port) = plan.port {
+        let mut projects = state.projects.write().await;
+        if let Some(config) = projects.get_mut(slug) {
+            if config.domain.auto_port && config.domain.container_port != port {
+                log.line(&format!("Container port set to {}", port));
+                config.domain.container_port = port;
+                config.save()?;
+            }
+        }
+    }
+
+    // Next to the build context, or at the repository root
+    let mut procfile = BTreeMap::new();
+    if container.build_context.is_some() {
+        procfile = build::read_procfile(&plan.context_dir)?;
+    }
+    if procfile.is_empty() {
+        procfile = build::read_procfile(repo_dir)?;
+    }
+    if !procfile.is_empty() {
+        let names: Vec<&str> = procfile.keys().map(String::as_str).collect();
+        log.line(&format!("Procfile process types: {}", names.join(", ")));
+    }
+    for name in procfile.keys() {
+        if name != WEB_PROCESS && !container.processes.contains_key(name) {
+            log.line(&format!(
+                "Not running '{}' from the Procfile; add it with `dockyard process set {} {}`",
+                name, slug, name
+            ));
+        }
+    }
+    // Fail before building rather than after
+    let project = project_config(state, slug).await?;
+    for process in container.processes.keys() {
+        if project.process_command(process, &procfile).is_none() {
+            return Err(anyhow::anyhow!(
+                "Process '{}' has no command; set one or declare it in the Procfile",
+                process
+            ));
+        }
+    }
+
+    let max_context_mb = state.config.read().await.daemon.max_context_mb;
+    build::build_image(
+        &state.docker,
+        &plan,
+        tag,
+        max_context_mb * 1024 * 1024,
+        &|line| log.line(line),
+    )
+    .await?;
+    Ok(procfile)
+}
+
+/// Run the project's release command, if it has one, in a one-off container from the
+/// freshly built image, copying its output into the build log. Fails unless it exits 0,
+/// before any container of the release is started.
+async fn run_release_command(
+    state: &DaemonState,
+    slug: &str,
+    project_state: ProjectState,
+    release_id: u32,
+    image_tag: &str,
+    log: &BuildLog,
+) -> anyhow::Result<()> {
+    let project = project_config(state, slug).await?;
+    let Some(command) = project.build.release_command else {
+        return Ok(());
+    };
+
+    set_phase(state, slug, project_state, DeployPhase::Releasing).await;
+    log.line(&format!("Running release command: {}", command));
+    // `.` appears in no replica, worker or project container name
+    let name = format!(
+        "{}_release.{}",
+        project.container.container_name, release_id
+    );
+    // Left behind if the daemon stopped during an earlier attempt
+    let _ = state.docker.remove_container(&name).await;
+    let spec = one_off_spec(state, slug, &name, image_tag, shell(&command)).await?;
+    state.docker.create_container(&spec).await?;
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(RELEASE_TIMEOUT_SECS),
+        copy_release_output(state, &name, log),
+    )
+    .await;
+    if result.is_err() {
+        let _ = state.docker.stop_container(&name).await;
+    }
+    if let Err(e) = state.docker.remove_container(&name).await {
+        tracing::warn!(
+            "[{}] Failed to remove release container {}: {}",
+            slug,
+            name,
+            e
+        );
+    }
+
+    match result {
+        Ok(Ok(0)) => {
+            log.line("Release command succeeded");
+            Ok(())
+        }
+        Ok(Ok(code)) => Err(anyhow::anyhow!(
+            "Release command exited with {}; keeping the current release",
+            code
+        )),
+        Ok(Err(e)) => Err(anyhow::anyhow!("Release command failed: {}", e)),
+        Err(_) => Err(anyhow::anyhow!(
+            "Release command timed out after {}s; keeping the current release",
+            RELEASE_TIMEOUT_SECS
+        )),
+    }
+}
+
+/// Start a created container, write its output to the build log line by line and return
+/// its exit code
+async fn copy_release_output(
+    state: &DaemonState,
+    name: &str,
+    log: &BuildLog,
+) -> Result<i64, crate::error::DockyardError> {
+    let mut io = state.docker.start_attached(name).await?;
+    let mut pending = String::new();
+    while let Some(chunk) = io.output.next().await {
+        let message = match chunk? {
+            LogOutput::StdOut { message }
+            | LogOutput::StdErr { message }
+            | LogOutput::Console { message } => message,
+            LogOutput::StdIn { .. } => continue,
+        };
+        pending.push_str(&String::from_utf8_lossy(&message));
+        while let Some(end) = pending.find('\n') {
+            log.line(&format!("  {}", pending[..end].trim_end_matches('\r')));
+            pending.drain(..=end);
+        }
+    }
+    if !pending.is_empty() {
+        log.line(&format!("  {}", pending.trim_end_matches('\r')));
+    }
+    state.docker.wait_exit(name).await
+}
+
+/// A container that a rolling switch has replaced. The container it replaced is only
+/// stopped, as `<name>-old`, until every container has switched.
+struct SwitchedReplica {
+    /// Index of a `web` replica; None for other process types, which take no traffic
+    index: Option<usize>,
+    name: String,
+    old_port: Option<u16>,
+    had_old: bool,
+}
+
+/// Rolling blue-green: replace the `web` replicas one at a time, each by a container
+/// started next to it on its own host port, then the containers of the other process
+/// types. A replica's persisted port and its upstream in the proxy route move to the new
+/// container once it is healthy; if any container fails, the ones already switched are
+/// put back on their previous containers.
+/// Returns the ID of the container now serving as the first replica.
+async fn switch_container(
+    state: &DaemonState,
+    slug: &str,
+    image: &ReleaseImage<'_>,
+    log: &BuildLog,
+) -> anyhow::Result<String> {
+    let project = project_config(state, slug).await?;
+    let replicas = (project.container.replicas as usize).max(1);
+    let routed = project.domain.hostname.is_some();
+
+    let mut switched: Vec<SwitchedReplica> = Vec::new();
+    let mut container_id = String::new();
+    let mut result = Ok(());
+    for index in 0..replicas {
+        if replicas > 1 {
+            log.line(&format!("Replacing replica {}/{}...", index + 1, replicas));
+        }
+        match switch_replica(state, slug, index, image, log).await {
+            Ok((id, replica)) => {
+                if index == 0 {
+                    container_id = id;
+                }
+                switched.push(replica);
+            }
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    if result.is_ok() {
+        for (process, _, name) in project.worker_containers() {
+            log.line(&format!("Replacing {}...", name));
+            match switch_worker(state, &project, &process, &name, image, log).await {
+                Ok(replica) => switched.push(replica),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+    }
+
+    if let Err(e) = result {
+        if !switched.is_empty() {
+            log.line(&format!(
+                "Putting back {} container(s) already switched...",
+                switched.len()
+            ));
+        }
+        for replica in switched.iter().rev() {
+            revert_replica(state, slug, routed, replica, log).await;
+        }
+        return Err(e);
+    }
+
+    for replica in &switched {
+        let _ = state
+            .docker
+            .remove_container(&format!("{}-old", replica.name))
+            .await;
+    }
+    Ok(container_id)
+}
+
+/// Move one replica to a new container running `image`, leaving the previous one stopped
+async fn switch_replica(
+    state: &DaemonState,
+    slug: &str,
+    index: usize,
+    image: &ReleaseImage<'_>,
+    log: &BuildLog,
+) -> anyhow::Result<(String, SwitchedReplica)> {
+    let project = project_config(state, slug).await?;
+    let name = project.replica_name(index);
+    let old_port = project.replica_port(index);
+    let routed = project.domain.hostname.is_some();
+
+    let new_container = format!("{}-new", name);
+    let old_container = format!("{}-old", name);
+    let new_port = crate::utils::find_available_port()?;
+
+    // Leftovers from an interrupted switch would block the names below
+    let _ = state.docker.remove_container(&new_container).await;
+    let _ = state.docker.remove_container(&old_container).await;
+    let had_old = state.docker.get_container_state(&name).await? != ProjectState::Offline;
+
+    set_phase(state, slug, ProjectState::Rebuilding, DeployPhase::Starting).await;
+    log.line(&format!(
+        "Starting new container on port {} (blue-green)...",
+        new_port
+    ));
+    let spec = container_spec(state, slug, &new_container, WEB_PROCESS, image, new_port).await?;
+    let container_port = spec.container_port;
+    let container_id = state.docker.create_and_start_container(&spec).await?;
+
+    if let Err(e) = await_healthy(
+        state,
+        &new_container,
+        new_port,
+        container_port,
+        &project.container.health_check,
+        log,
+    )
+    .await
+    {
+        let _ = state.docker.remove_container(&new_container).await;
+        return Err(anyhow::anyhow!("New container never became healthy: {}", e));
+    }
+
+    set_phase(
+        state,
+        slug,
+        ProjectState::Rebuilding,
+        DeployPhase::Switching,
+    )
+    .await;
+    log.line("Switching traffic to new container...");
+
+    let restore_port = |c: &mut ProjectConfig| match old_port {
+        Some(port) => c.set_replica_port(index, port),
+        None => c.domain.replica_ports.truncate(index.saturating_sub(1)),
+    };
+    if let Err(e) = move_traffic(state, slug, routed, |c| c.set_replica_port(index, new_port)).await
+    {
+        log.line(&format!(
+            "Switch failed, keeping the current container: {}",
+            e
+        ));
+        let _ = move_traffic(state, slug, routed, restore_port).await;
+        let _ = state.docker.remove_container(&new_container).await;
+        return Err(e);
+    }
+
+    if let Err(e) = swap_names(state, &name, had_old).await {
+        log.line(&format!(
+            "Switch failed, keeping the current container: {}",
+            e
+        ));
+        let _ = move_traffic(state, slug, routed, restore_port).await;
+        let _ = state.docker.remove_container(&new_container).await;
+        return Err(e.into());
+    }
+
+    if had_old {
+        let drain_secs = state.config.read().await.daemon.drain_secs;
+        if drain_secs > 0 {
+            log.line(&format!(
+                "Draining previous container for {}s...",
+                drain_secs
+            ));
+            tokio::time::sleep(std::time::Duration::from_secs(drain_secs)).await;
+        }
+        let _ = state.docker.stop_container(&old_container).await;
+    }
+
+    log.line(&format!("Now serving on port {}", new_port));
+    Ok((
+        container_id,
+        SwitchedReplica {
+            index: Some(index),
+            name,
+            old_port,
+            had_old,
+        },
+    ))
+}
+
+/// Move a container of a non-web process type to `image`. It takes no traffic, so the
+/// previous container is stopped as soon as the new one is up.
+async fn switch_worker(
+    state: &DaemonState,
+    project: &ProjectConfig,
+    process: &str,
+    name: &str,
+    image: &ReleaseImage<'_>,
+    log: &BuildLog,
+) -> anyhow::Result<SwitchedReplica> {
+    let new_container = format!("{}-new", name);
+    let old_container = format!("{}-old", name);
+
+    let _ = state.docker.remove_container(&new_container).await;
+    let _ = state.docker.remove_container(&old_container).await;
+    let had_old = state.docker.get_container_state(name).await? != ProjectState::Offline;
+
+    let spec = container_spec(state, &project.slug, &new_container, process, image, 0).await?;
+    state.docker.create_and_start_container(&spec).await?;
+    if let Err(e) = await_worker(state, project, process, &new_container, log).await {
+        let _ = state.docker.remove_container(&new_container).await;
+        return Err(anyhow::anyhow!(
+            "New {} container never became healthy: {}",
+            process,
+            e
+        ));
+    }
+
+    if let Err(e) = swap_names(state, name, had_old).await {
+        let _ = state.docker.remove_container(&new_container).await;
+        return Err(e.into());
+    }
+    if had_old {
+        let _ = state.docker.stop_container(&old_container).await;
+    }
+
+    Ok(SwitchedReplica {
+        index: None,
+        name: name.to_string(),
+        old_port: None,
+        had_old,
+    })
+}
+
+/// Rename `<name>` to `<name>-old` and `<name>-new` to `<name>`, so the live container
+/// always carries the name; nothing is renamed if either step fails
+async fn swap_names(
+    state: &DaemonState,
+    name: &str,
+    had_old: bool,
+) -> Result<(), crate::error::DockyardError> {
+    let new_container = format!("{}-new", name);
+    let old_container = format!("{}-old", name);
+    if had_old {
+        state.docker.rename_container(name, &old_container).await?;
+    }
+    if let Err(e) = state.docker.rename_container(&new_container, name).await {
+        if had_old {
+            let _ = state.docker.rename_container(&old_container, name).await;
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Undo a switch: restart the previous container, send its traffic back and drop the
+/// new one. Best effort, since it only runs once a switch has already failed.
+async fn revert_replica(
+    state: &DaemonState,
+    slug: &str,
+    routed: bool,
+    replica: &SwitchedReplica,
+    log: &BuildLog,
+) {
+    let old_container = format!("{}-old", replica.name);
+    if replica.had_old {
+        if let Err(e) = state.docker.restart_container(&old_container).await {
+            log.line(&format!(
+                "Could not restart the previous {}, keeping the new one: {}",
+                replica.name, e
+            ));
+            let _ = state.docker.remove_container(&old_container).await;
+            return;
+        }
+    }
+
+    if let Some(index) = replica.index {
+        let restored = move_traffic(state, slug, routed, |c| match replica.old_port {
+            Some(port) => c.set_replica_port(index, port),
+            None => c.domain.replica_ports.truncate(index.saturating_sub(1)),
+        })
+        .await;
+        if let Err(e) = restored {
+            log.line(&format!(
+                "Could not move traffic back to the previous {}: {}",
+                replica.name, e
+            ));
+        }
+    }
+
+    let _ = state.docker.stop_container(&replica.name).await;
+    let _ = state.docker.remove_container(&replica.name).await;
+    if replica.had_old {
+        let _ = state
+            .docker
+            .rename_container(&old_container, &replica.name)
+            .await;
+    }
+}
+
+/// Gate on the project's health check, copying the container's last output into the
+/// build log when it fails
+async fn await_healthy(
+    state: &DaemonState,
+    container_name: &str,
+    host_port: u16,
+    container_port: u16,
+    health_check: &HealthCheck,
+    log: &BuildLog,
+) -> Result<(), String> {
+    log.line(&match &health_check.path {
+        Some(path) => format!("Waiting for GET {} to pass...", path),
+        None => "Waiting for the container port to accept connections...".to_string(),
+    });
+    let addr = health::target(&state.docker, container_name, host_port, container_port).await;
+
+    let result = health::wait_healthy(&state.docker, container_name, &addr, health_check, |msg| {
+        log.line(msg)
+    })
+    .await;
+
+    match &result {
+        Ok(()) => log.line("Container is healthy"),
+        Err(_) => log_last_output(state, container_name, log).await,
+    }
+    result
+}
+
+/// Gate a container of a non-web process type: on its port when it declares one,
+/// otherwise on it still running once `WORKER_GRACE_SECS` have passed
+async fn await_worker(
+    state: &DaemonState,
+    project: &ProjectConfig,
+    process: &str,
+    container_name: &str,
+    log: &BuildLog,
+) -> Result<(), String> {
+    let port = project
+        .container
+        .processes
+        .get(process)
+        .and_then(|p| p.port);
+    if let Some(port) = port {
+        let check = HealthCheck {
+            path: None,
+            ..project.container.health_check.clone()
+        };
+        return await_healthy(state, container_name, port, port, &check, log).await;
+    }
+
+    log.line(&format!(
+        "Checking that the container keeps running for {}s...",
+        WORKER_GRACE_SECS
+    ));
+    tokio::time::sleep(std::time::Duration::from_secs(WORKER_GRACE_SECS)).await;
+    if state
+        .docker
+        .is_container_running(container_name)
+        .await
+        .unwrap_or(false)
+    {
+        log.line("Container is running");
+        Ok(())
+    } else {
+        log_last_output(state, container_name, log).await;
+        Err("container exited".to_string())
+    }
+}
+
+/// Copy the last lines a container wrote into the build log
+async fn log_last_output(state: &DaemonState, container_name: &str, log: &BuildLog) {
+    let filter = LogFilter {
+        tail: Some(20),
+        ..Default::default()
+    };
+    if let Ok(entries) = state.docker.get_logs(container_name, &filter).await {
+        log.line("Last container output:");
+        for entry in entries {
+            log.line(&format!("  {}", entry.message));
+        }
+    }
+}
+
+/// Change a project's replica host ports with `change`, moving its proxy route along
+/// when it has one
+async fn move_traffic(
+    state: &DaemonState,
+    slug: &str,
+    routed: bool,
+    change: impl Fn(&mut ProjectConfig),
+) -> anyhow::Result<()> {
+    if routed {
+        let mut config = project_config(state, slug).await?;
+        change(&mut config);
+        state
+            .proxy
+            .update_route(slug, &config.upstream_ports())
+            .await?;
+    }
+
+    let mut projects = state.projects.write().await;
+    let config = projects
+        .get_mut(slug)
+        .ok_or_else(|| anyhow::anyhow!("Project '{}' not found", slug))?;
+    let previous = config.domain.clone();
+    change(config);
+    if let Err(e) = config.save() {
+        config.domain = previous;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+async fn project_config(state: &DaemonState, slug: &str) -> anyhow::Result<ProjectConfig> {
+    state
+        .projects
+        .read()
+        .await
+        .get(slug)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("Project '{}' not found", slug))
+}
+
+/// Persist a finished release and drop images that fall outside retention
+async fn record_release(
+    state: &DaemonState,
+    slug: &str,
+    mut history: ReleaseHistory,
+    release: Release,
+) -> anyhow::Result<()> {
+    let keep = state.config.read().await.daemon.keep_releases;
+
+    history.record(release);
+    for tag in history.prune(keep) {
+        if let Err(e) = state.docker.remove_image(&tag).await {
+            tracing::warn!("[{}] Could not remove old image {}: {}", slug, tag, e);
+        }
+    }
+    history.save(slug)?;
+
     Ok(())
 }
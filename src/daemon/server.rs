@@ -65,9 +65,123 @@ fn create_router(state: Arc<DaemonState>) -> Router {
             post(handlers::start_project),
         )
         .route("/api/projects/{slug}/stop", post(handlers::stop_project))
+        .route(
+            "/api/projects/{slug}/webhook-secret",
+            get(handlers::get_webhook_secret),
+        )
+        .route(
+            "/api/projects/{slug}/webhook-secret/rotate",
+            post(handlers::rotate_webhook_secret),
+        )
+        .route("/api/projects/{slug}/exec", post(handlers::exec_session))
+        .route("/api/projects/{slug}/run", post(handlers::run_session))
         .route("/api/projects/{slug}/logs", get(handlers::get_logs))
+        .route(
+            "/api/projects/{slug}/logs/stream",
+            get(handlers::stream_logs),
+        )
+        .route("/api/projects/{slug}/builds", get(handlers::list_builds))
+        .route(
+            "/api/projects/{slug}/builds/{build}",
+            get(handlers::get_build),
+        )
+        .route(
+            "/api/projects/{slug}/builds/{build}/log",
+            get(handlers::get_build_log),
+        )
+        .route(
+            "/api/projects/{slug}/releases",
+            get(handlers::list_releases),
+        )
+        .route(
+            "/api/projects/{slug}/releases/rollback",
+            post(handlers::rollback_project),
+        )
+        .route(
+            "/api/projects/{slug}/dockerfile",
+            get(handlers::get_dockerfile).put(handlers::set_dockerfile),
+        )
+        .route(
+            "/api/projects/{slug}/build-config",
+            get(handlers::get_build_config).put(handlers::update_build_config),
+        )
+        .route(
+            "/api/projects/{slug}/env",
+            get(handlers::get_env).put(handlers::update_env),
+        )
+        .route(
+            "/api/projects/{slug}/scale",
+            post(handlers::scale_project),
+        )
+        .route(
+            "/api/projects/{slug}/processes/{name}",
+            put(handlers::set_process).delete(handlers::remove_process),
+        )
+        .route(
+            "/api/projects/{slug}/resources",
+            get(handlers::get_resources).put(handlers::update_resources),
+        )
+        .route(
+            "/api/projects/{slug}/settings",
+            put(handlers::update_settings),
+        )
+        .route(
+            "/api/projects/{slug}/links",
+            get(handlers::get_links).put(handlers::update_links),
+        )
+        .route(
+            "/api/projects/{slug}/volumes",
+            get(handlers::get_volumes).put(handlers::update_volumes),
+        )
+        .route(
+            "/api/projects/{slug}/backups",
+            get(handlers::list_backups).post(handlers::run_backup),
+        )
+        .route(
+            "/api/projects/{slug}/backups/schedule",
+            put(handlers::set_backup_schedule),
+        )
+        .route(
+            "/api/projects/{slug}/backups/{id}/restore",
+            post(handlers::restore_backup),
+        )
+        .route("/api/projects/{slug}/cron", get(handlers::list_cron_jobs))
+        .route(
+            "/api/projects/{slug}/cron/jobs/{name}",
+            put(handlers::set_cron_job).delete(handlers::remove_cron_job),
+        )
+        .route(
+            "/api/projects/{slug}/cron/jobs/{name}/run",
+            post(handlers::run_cron_job),
+        )
+        .route(
+            "/api/projects/{slug}/cron/runs",
+            get(handlers::cron_history),
+        )
+        .route(
+            "/api/projects/{slug}/cron/runs/{id}",
+            get(handlers::get_cron_run),
+        )
+        .route("/api/volumes", get(handlers::list_volumes))
+        .route(
+            "/api/addons",
+            get(handlers::list_addons).post(handlers::create_addon),
+        )
+        .route("/api/addons/{name}", delete(handlers::destroy_addon))
+        .route(
+            "/api/addons/{name}/attach",
+            post(handlers::attach_addon),
+        )
+        .route(
+            "/api/addons/{name}/detach",
+            post(handlers::detach_addon),
+        )
         .route("/api/config", get(handlers::get_config))
         .route("/api/config", put(handlers::update_config))
+        .route(
+            "/api/config/rotate-key",
+            post(handlers::rotate_master_key),
+        )
         .with_state(state)
 }
 
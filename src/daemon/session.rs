@@ -0,0 +1,338 @@
+use bollard::container::LogOutput;
+use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+
+use crate::config::release::ReleaseHistory;
+use crate::daemon::{scheduler, DaemonState};
+use crate::docker::client::{AttachedIo, ContainerSpec};
+use crate::docker::DockerClient;
+use crate::error::DockyardError;
+use crate::ipc::protocol::SessionRequest;
+use crate::ipc::session::Frame;
+
+/// Exit code sent when the command could not be started, as `docker run` does
+const START_FAILED: i64 = 125;
+
+/// Shell started when a session names no command
+const DEFAULT_COMMAND: &str = "/bin/sh";
+
+/// How long an exec whose client is gone gets to exit before each signal
+const HANGUP_GRACE_SECS: u64 = 5;
+
+/// Set in the environment of exec'd commands, with a value unique to the session, so
+/// their processes can be found from inside the container
+const SESSION_ENV: &str = "DOCKYARD_SESSION";
+
+/// Where a session's command runs, resolved before the connection is upgraded
+pub enum SessionPlan {
+    /// Exec in the project's live container
+    Exec { container: String },
+    /// A one-off container from the current release, removed when the command exits
+    Run { spec: Box<ContainerSpec> },
+}
+
+/// A started session's process
+#[derive(Clone)]
+enum Attached {
+    /// `tag` is the `SESSION_ENV` variable its processes carry
+    Exec {
+        id: String,
+        container: String,
+        tag: String,
+    },
+    Container(String),
+}
+
+impl Attached {
+    async fn resize(
+        &self,
+        docker: &DockerClient,
+        rows: u16,
+        cols: u16,
+    ) -> Result<(), DockyardError> {
+        match self {
+            Attached::Exec { id, .. } => docker.resize_exec(id, rows, cols).await,
+            Attached::Container(name) => docker.resize_container(name, rows, cols).await,
+        }
+    }
+
+    async fn exit_code(&self, docker: &DockerClient) -> Result<i64, DockyardError> {
+        match self {
+            Attached::Exec { id, .. } => docker.exec_exit_code(id).await,
+            Attached::Container(name) => docker.wait_exit(name).await,
+        }
+    }
+}
+
+impl DaemonState {
+    /// Pick the first running container of the project to exec in
+    pub async fn plan_exec(&self, slug: &str) -> Result<SessionPlan, DockyardError> {
+        let containers = {
+            let projects = self.projects.read().await;
+            let config = projects
+                .get(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            config.replica_names()
+        };
+        for container in containers {
+            if self
+                .docker
+                .is_container_running(&container)
+                .await
+                .unwrap_or(false)
+            {
+                return Ok(SessionPlan::Exec { container });
+            }
+        }
+        Err(DockyardError::Config(format!(
+            "'{}' has no running container; use run for a new one",
+            slug
+        )))
+    }
+
+    /// Prepare a one-off container from the current release, with the project's env,
+    /// volumes and networks
+    pub async fn plan_run(
+        &self,
+        slug: &str,
+        req: &SessionRequest,
+    ) -> Result<SessionPlan, DockyardError> {
+        let (container_name, image_name) = {
+            let projects = self.projects.read().await;
+            let config = projects
+                .get(slug)
+                .ok_or_else(|| DockyardError::ProjectNotFound(slug.to_string()))?;
+            (
+                config.container.container_name.clone(),
+                config.container.image_name.clone(),
+            )
+        };
+        let release = ReleaseHistory::load(slug)?.current.ok_or_else(|| {
+            DockyardError::Config(format!("'{}' has no deployed release to run from", slug))
+        })?;
+        let image = format!("{}:{}", image_name, scheduler::release_tag(release));
+
+        // `.` appears in no replica, worker or project container name
+        let name = format!(
+            "{}_run.{}",
+            container_name,
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        let mut spec = scheduler::one_off_spec(self, slug, &name, &image, command(req))
+            .await
+            .map_err(|e| DockyardError::Config(e.to_string()))?;
+        spec.open_stdin = true;
+        spec.tty = req.tty;
+        Ok(SessionPlan::Run {
+            spec: Box::new(spec),
+        })
+    }
+}
+
+/// Run a planned session over an upgraded IPC connection: stdin and resizes come in as
+/// frames, output goes out as frames, and the exit code is sent last
+pub async fn serve<S>(state: Arc<DaemonState>, plan: SessionPlan, req: SessionRequest, stream: S)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let code = match run(&state, plan, &req, reader, &mut writer).await {
+        Ok(code) => code,
+        Err(e) => {
+            let message = format!("dockyard: {}\r\n", e);
+            let _ = Frame::Stderr(message.into_bytes()).write(&mut writer).await;
+            START_FAILED
+        }
+    };
+    let _ = Frame::Exit(code).write(&mut writer).await;
+    let _ = writer.shutdown().await;
+}
+
+async fn run<S>(
+    state: &Arc<DaemonState>,
+    plan: SessionPlan,
+    req: &SessionRequest,
+    reader: ReadHalf<S>,
+    writer: &mut WriteHalf<S>,
+) -> Result<i64, DockyardError>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (attached, io) = match plan {
+        SessionPlan::Exec { container } => {
+            let tag = format!("{}={}", SESSION_ENV, uuid::Uuid::new_v4().simple());
+            let (id, io) = state
+                .docker
+                .exec_attached(&container, command(req), vec![tag.clone()], req.tty)
+                .await?;
+            (Attached::Exec { id, container, tag }, io)
+        }
+        SessionPlan::Run { spec } => {
+            state.docker.create_container(&spec).await?;
+            match state.docker.start_attached(&spec.name).await {
+                Ok(io) => (Attached::Container(spec.name), io),
+                Err(e) => {
+                    let _ = state.docker.remove_container(&spec.name).await;
+                    return Err(e);
+                }
+            }
+        }
+    };
+
+    let result = pump(state, &attached, io, req, reader, writer).await;
+    if let Attached::Container(name) = &attached {
+        if let Err(e) = state.docker.remove_container(name).await {
+            tracing::warn!("Failed to remove one-off container {}: {}", name, e);
+        }
+    }
+    result
+}
+
+/// Copy frames to the process and its output back until it exits
+async fn pump<S>(
+    state: &Arc<DaemonState>,
+    attached: &Attached,
+    io: AttachedIo,
+    req: &SessionRequest,
+    mut reader: ReadHalf<S>,
+    writer: &mut WriteHalf<S>,
+) -> Result<i64, DockyardError>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let AttachedIo {
+        mut output,
+        mut input,
+    } = io;
+    if let (true, Some(rows), Some(cols)) = (req.tty, req.rows, req.cols) {
+        let _ = attached.resize(&state.docker, rows, cols).await;
+    }
+
+    // Resolves to whether the client went away, as opposed to the process's stdin closing
+    let mut forward = {
+        let state = Arc::clone(state);
+        let attached = attached.clone();
+        tokio::spawn(async move {
+            loop {
+                let frame = match Frame::read(&mut reader).await {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) | Err(_) => break,
+                };
+                match frame {
+                    Frame::Stdin(data) => {
+                        let written = async {
+                            input.write_all(&data).await?;
+                            input.flush().await
+                        };
+                        if written.await.is_err() {
+                            return false;
+                        }
+                    }
+                    Frame::StdinClosed => {
+                        let _ = input.shutdown().await;
+                    }
+                    Frame::Resize { rows, cols } => {
+                        let _ = attached.resize(&state.docker, rows, cols).await;
+                    }
+                    _ => {}
+                }
+            }
+            let _ = input.shutdown().await;
+            true
+        })
+    };
+
+    let mut forwarding = true;
+    let mut client_gone = false;
+    loop {
+        let chunk = tokio::select! {
+            chunk = output.next() => chunk,
+            disconnected = &mut forward, if forwarding => {
+                forwarding = false;
+                // Otherwise only the process's stdin closed, and its output still flows
+                if disconnected.unwrap_or(true) {
+                    client_gone = true;
+                    break;
+                }
+                continue;
+            }
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        let frame = match chunk? {
+            LogOutput::StdOut { message } | LogOutput::Console { message } => {
+                Frame::Stdout(message.to_vec())
+            }
+            LogOutput::StdErr { message } => Frame::Stderr(message.to_vec()),
+            LogOutput::StdIn { .. } => continue,
+        };
+        if frame.write(writer).await.is_err() {
+            client_gone = true;
+            break;
+        }
+    }
+    // Closes the attach connection, and with it the exec's stdin and TTY
+    if forwarding {
+        forward.abort();
+        let _ = forward.await;
+    }
+    drop(output);
+
+    // Nobody is left to read the output, so the process is not left running
+    if client_gone {
+        match attached {
+            Attached::Exec { id, container, tag } => {
+                hang_up(&state.docker, container, id, tag).await
+            }
+            Attached::Container(name) => {
+                let _ = state.docker.stop_container(name).await;
+            }
+        }
+    }
+    attached.exit_code(&state.docker).await
+}
+
+/// Stop an exec whose client disconnected, as a closing terminal would. Losing its stdin
+/// and TTY ends a shell; what still runs after the grace period gets SIGHUP, then SIGKILL,
+/// sent from inside the container to the processes carrying the session's tag.
+async fn hang_up(docker: &DockerClient, container: &str, exec_id: &str, tag: &str) {
+    for signal in ["HUP", "KILL"] {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(HANGUP_GRACE_SECS);
+        loop {
+            if !matches!(docker.is_exec_running(exec_id).await, Ok(true)) {
+                return;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+        match docker.signal_tagged(container, tag, signal).await {
+            Ok(output) if output.success() => {}
+            Ok(output) => {
+                tracing::warn!(
+                    "Could not send SIG{} to exec {}: {}",
+                    signal,
+                    exec_id,
+                    output.stderr
+                );
+                return;
+            }
+            Err(e) => {
+                tracing::warn!("Could not send SIG{} to exec {}: {}", signal, exec_id, e);
+                return;
+            }
+        }
+    }
+}
+
+fn command(req: &SessionRequest) -> Vec<String> {
+    if req.command.is_empty() {
+        vec![DEFAULT_COMMAND.to_string()]
+    } else {
+        req.command.clone()
+    }
+}
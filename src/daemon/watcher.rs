@@ -1,51 +1,336 @@
-use std::sync::Arc;
-use std::time::Duration;
+use futures_util::future::join_all;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
 
-use crate::daemon::DaemonState;
+use crate::config::project::{HealthCheck, ProjectConfig};
+use crate::daemon::{health, DaemonState};
 use crate::models::project::ProjectState;
 
-/// Run the health check watcher loop
+/// How often the watcher looks for projects whose health check interval has elapsed
+const TICK_SECS: u64 = 1;
+
+/// Per-container bookkeeping shared by the checks running concurrently
+#[derive(Default)]
+struct Tracker {
+    /// Consecutive failed health checks per container
+    failures: HashMap<String, u32>,
+    /// Restarted containers that are not probed until their start period has passed
+    settling: HashMap<String, Instant>,
+}
+
+impl Tracker {
+    fn is_settling(&mut self, container: &str) -> bool {
+        match self.settling.get(container) {
+            Some(until) if *until > Instant::now() => true,
+            Some(_) => {
+                self.settling.remove(container);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn passed(&mut self, container: &str) {
+        self.failures.remove(container);
+    }
+
+    /// Count a failed check, returning the number of consecutive failures
+    fn failed(&mut self, container: &str) -> u32 {
+        let count = self.failures.entry(container.to_string()).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    fn restarted(&mut self, container: &str, check: &HealthCheck) {
+        self.failures.remove(container);
+        self.settling.insert(
+            container.to_string(),
+            Instant::now() + Duration::from_secs(check.start_period_secs),
+        );
+    }
+}
+
+/// Run the health check watcher loop; each project is checked every
+/// `health_check.interval_secs`
 pub async fn run(state: Arc<DaemonState>) {
-    let mut interval = tokio::time::interval(Duration::from_secs(30));
+    let mut interval = tokio::time::interval(Duration::from_secs(TICK_SECS));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let tracker = Mutex::new(Tracker::default());
+    // When each project is next due for a check
+    let mut next_check: HashMap<String, Instant> = HashMap::new();
 
     loop {
         interval.tick().await;
 
-        let projects = state.projects.read().await;
-        for (slug, config) in projects.iter() {
-            if !config.enabled {
-                continue;
-            }
+        // Copied out so that no probe or restart runs with the projects lock held
+        let now = Instant::now();
+        let due: Vec<ProjectConfig> = {
+            let projects = state.projects.read().await;
+            next_check.retain(|slug, _| projects.contains_key(slug));
+            projects
+                .values()
+                .filter(|c| next_check.get(&c.slug).is_none_or(|at| *at <= now))
+                .cloned()
+                .collect()
+        };
 
-            match state
+        for config in &due {
+            let every = Duration::from_secs(config.container.health_check.interval_secs.max(1));
+            next_check.insert(config.slug.clone(), now + every);
+        }
+
+        join_all(
+            due.iter()
+                .map(|config| check_project(&state, config, &tracker)),
+        )
+        .await;
+    }
+}
+
+/// Check every replica and worker of a project at once
+async fn check_project(state: &DaemonState, config: &ProjectConfig, tracker: &Mutex<Tracker>) {
+    let busy = state
+        .runtime
+        .read()
+        .await
+        .get(&config.slug)
+        .map(|rt| rt.is_busy())
+        .unwrap_or(false);
+    if busy {
+        return;
+    }
+
+    let replicas = config.replica_names();
+    let workers = config.worker_containers();
+    tokio::join!(
+        join_all(
+            replicas
+                .iter()
+                .enumerate()
+                .map(|(index, container)| check_replica(state, config, index, container, tracker))
+        ),
+        join_all(
+            workers
+                .iter()
+                .map(|(process, index, container)| check_worker(
+                    state, config, process, *index, container, tracker
+                ))
+        ),
+    );
+}
+
+/// Every replica is checked, but only the first one drives the project's state
+async fn check_replica(
+    state: &DaemonState,
+    config: &ProjectConfig,
+    index: usize,
+    container: &str,
+    tracker: &Mutex<Tracker>,
+) {
+    let slug = &config.slug;
+    let check = &config.container.health_check;
+    let primary = index == 0;
+    let label = if primary {
+        slug.clone()
+    } else {
+        format!("{} replica {}", slug, index + 1)
+    };
+
+    match state.docker.get_container_state(container).await {
+        Ok(ProjectState::Stopped) if config.enabled => {
+            tracing::warn!("[{}] Stopped unexpectedly, restarting...", label);
+            if let Err(e) = state
                 .docker
-                .get_container_state(&config.container.container_name)
+                .inner()
+                .start_container(
+                    container,
+                    None::<bollard::container::StartContainerOptions<String>>,
+                )
                 .await
             {
-                Ok(ProjectState::Stopped) => {
-                    tracing::warn!("[{}] Stopped unexpectedly, restarting...", slug);
-                    if let Err(e) = state
-                        .docker
-                        .inner()
-                        .start_container(
-                            &config.container.container_name,
-                            None::<bollard::container::StartContainerOptions<String>>,
-                        )
-                        .await
-                    {
-                        tracing::error!("[{}] Failed to restart: {}", slug, e);
-                    } else {
-                        tracing::info!("[{}] Restarted successfully", slug);
+                tracing::error!("[{}] Failed to restart: {}", label, e);
+                if primary {
+                    state
+                        .update_runtime(slug, |rt| {
+                            rt.fail(ProjectState::Error, format!("restart failed: {}", e))
+                        })
+                        .await;
+                }
+            } else {
+                tracing::info!("[{}] Restarted successfully", label);
+                tracker.lock().unwrap().restarted(container, check);
+                if primary {
+                    state
+                        .update_runtime(slug, |rt| rt.observe(ProjectState::Online))
+                        .await;
+                }
+            }
+        }
+        Ok(ProjectState::Online) => {
+            let Some(host_port) = config.replica_port(index) else {
+                return;
+            };
+            if tracker.lock().unwrap().is_settling(container) {
+                return;
+            }
+            let addr = health::target(
+                &state.docker,
+                container,
+                host_port,
+                config.domain.container_port,
+            )
+            .await;
+
+            match health::probe(check, &addr).await {
+                Ok(()) => {
+                    tracker.lock().unwrap().passed(container);
+                    if primary {
+                        state
+                            .update_runtime(slug, |rt| rt.observe(ProjectState::Online))
+                            .await;
                     }
                 }
-                Ok(ProjectState::Online) => {}
-                Ok(s) => {
-                    tracing::debug!("[{}] State: {}", slug, s);
+                Err(e) => {
+                    let count = tracker.lock().unwrap().failed(container);
+                    tracing::warn!(
+                        "[{}] Health check failed ({}/{}): {}",
+                        label,
+                        count,
+                        check.retries,
+                        e
+                    );
+                    if count >= check.retries {
+                        tracing::warn!("[{}] Unhealthy, restarting...", label);
+                        if primary {
+                            state
+                                .update_runtime(slug, |rt| {
+                                    rt.fail(ProjectState::Error, format!("unhealthy: {}", e))
+                                })
+                                .await;
+                        }
+                        match state.docker.restart_container(container).await {
+                            Ok(()) => tracker.lock().unwrap().restarted(container, check),
+                            Err(e) => tracing::error!("[{}] Failed to restart: {}", label, e),
+                        }
+                    }
+                }
+            }
+        }
+        Ok(s) if primary => {
+            tracing::debug!("[{}] State: {}", label, s);
+            state.update_runtime(slug, |rt| rt.observe(s)).await;
+        }
+        Ok(s) => {
+            tracing::debug!("[{}] State: {}", label, s);
+        }
+        Err(e) => {
+            tracing::error!("[{}] Health check failed: {}", label, e);
+        }
+    }
+}
+
+/// Workers are restarted like replicas but never change the project's state
+async fn check_worker(
+    state: &DaemonState,
+    config: &ProjectConfig,
+    process: &str,
+    index: usize,
+    container: &str,
+    tracker: &Mutex<Tracker>,
+) {
+    let check = HealthCheck {
+        path: None,
+        ..config.container.health_check.clone()
+    };
+    let label = if index == 0 {
+        format!("{} {}", config.slug, process)
+    } else {
+        format!("{} {} {}", config.slug, process, index + 1)
+    };
+
+    match state.docker.get_container_state(container).await {
+        Ok(ProjectState::Stopped) if config.enabled => {
+            tracing::warn!("[{}] Stopped unexpectedly, restarting...", label);
+            match state.docker.restart_container(container).await {
+                Ok(()) => {
+                    tracing::info!("[{}] Restarted successfully", label);
+                    tracker.lock().unwrap().restarted(container, &check);
+                }
+                Err(e) => tracing::error!("[{}] Failed to restart: {}", label, e),
+            }
+        }
+        Ok(ProjectState::Online) => {
+            let Some(port) = config.container.processes.get(process).and_then(|p| p.port) else {
+                return;
+            };
+            if tracker.lock().unwrap().is_settling(container) {
+                return;
+            }
+            let addr = health::target(&state.docker, container, port, port).await;
+
+            match health::probe(&check, &addr).await {
+                Ok(()) => {
+                    tracker.lock().unwrap().passed(container);
                 }
                 Err(e) => {
-                    tracing::error!("[{}] Health check failed: {}", slug, e);
+                    let count = tracker.lock().unwrap().failed(container);
+                    tracing::warn!(
+                        "[{}] Health check failed ({}/{}): {}",
+                        label,
+                        count,
+                        check.retries,
+                        e
+                    );
+                    if count >= check.retries {
+                        tracing::warn!("[{}] Unhealthy, restarting...", label);
+                        match state.docker.restart_container(container).await {
+                            Ok(()) => tracker.lock().unwrap().restarted(container, &check),
+                            Err(e) => tracing::error!("[{}] Failed to restart: {}", label, e),
+                        }
+                    }
                 }
             }
         }
+        Ok(s) => {
+            tracing::debug!("[{}] State: {}", label, s);
+        }
+        Err(e) => {
+            tracing::error!("[{}] Health check failed: {}", label, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_resets_failures_and_waits_for_the_start_period() {
+        let mut tracker = Tracker::default();
+        let check = HealthCheck {
+            start_period_secs: 60,
+            ..HealthCheck::default()
+        };
+        assert_eq!(tracker.failed("app"), 1);
+        assert_eq!(tracker.failed("app"), 2);
+
+        tracker.restarted("app", &check);
+        assert!(tracker.is_settling("app"));
+        assert_eq!(tracker.failed("app"), 1);
+        assert!(!tracker.is_settling("other"));
+    }
+
+    #[test]
+    fn settling_ends_after_the_start_period() {
+        let mut tracker = Tracker::default();
+        let check = HealthCheck {
+            start_period_secs: 0,
+            ..HealthCheck::default()
+        };
+        tracker.restarted("app", &check);
+        assert!(!tracker.is_settling("app"));
+        assert!(!tracker.settling.contains_key("app"));
     }
 }
@@ -6,19 +6,22 @@ use hmac::{Hmac, Mac};
 use sha2::Sha256;
 use std::sync::Arc;
 
+use crate::config::release::ReleaseTrigger;
+use crate::daemon::build_log::BuildLog;
 use crate::daemon::scheduler::Job;
 use crate::daemon::DaemonState;
 use crate::ipc::protocol::ErrorResponse;
 use crate::models::events::GitHubPushEvent;
 
 type HmacSha256 = Hmac<Sha256>;
+type WebhookError = (StatusCode, Json<ErrorResponse>);
 
 pub async fn handle_webhook(
     State(state): State<Arc<DaemonState>>,
     Path(slug): Path<String>,
     headers: HeaderMap,
     body: Bytes,
-) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<StatusCode, WebhookError> {
     let (webhook_secret, tracked_branch) = {
         let projects = state.projects.read().await;
         let config = projects.get(&slug).ok_or_else(|| {
@@ -32,34 +35,9 @@ pub async fn handle_webhook(
         (config.webhook.secret.clone(), config.branch.clone())
     };
 
-    // Verify HMAC signature
-    if let Some(signature_header) = headers.get("x-hub-signature-256") {
-        let signature = signature_header.to_str().unwrap_or("");
-        let expected_signature = signature.strip_prefix("sha256=").unwrap_or(signature);
-
-        let mut mac = HmacSha256::new_from_slice(webhook_secret.as_bytes()).map_err(|_| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: "HMAC error".to_string(),
-                }),
-            )
-        })?;
-        mac.update(&body);
-
-        let computed = hex::encode(mac.finalize().into_bytes());
-
-        if !constant_time_eq(computed.as_bytes(), expected_signature.as_bytes()) {
-            return Err((
-                StatusCode::UNAUTHORIZED,
-                Json(ErrorResponse {
-                    error: "Invalid signature".to_string(),
-                }),
-            ));
-        }
-    } else {
-        tracing::warn!("Webhook for '{}' received without signature header", slug);
-    }
+    verify_signature(&headers, &body, &webhook_secret).inspect_err(|e| {
+        tracing::warn!("Webhook for '{}' rejected: {}", slug, e.1.error);
+    })?;
 
     // Parse push event
     let event: GitHubPushEvent = serde_json::from_slice(&body).map_err(|e| {
@@ -71,26 +49,41 @@ pub async fn handle_webhook(
         )
     })?;
 
-    // Check if push is to the tracked branch
-    if let Some(branch) = event.branch() {
-        if branch == tracked_branch {
-            tracing::info!(
-                "Webhook triggered rebuild for '{}' (branch: {}, commit: {})",
-                slug,
-                branch,
-                &event.after[..7.min(event.after.len())]
-            );
-
-            let _ = state
-                .scheduler_tx
-                .send(Job::Rebuild {
-                    slug: slug.clone(),
-                    commit_sha: Some(event.after),
-                })
-                .await;
-
-            return Ok(StatusCode::OK);
-        }
+    let commit = pushed_commit(&event, &tracked_branch).map_err(|error| {
+        tracing::warn!("Webhook for '{}' rejected: {}", slug, error);
+        (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }))
+    })?;
+    if let Some(commit) = commit {
+        tracing::info!(
+            "Webhook triggered rebuild for '{}' (branch: {}, commit: {})",
+            slug,
+            tracked_branch,
+            &commit[..7.min(commit.len())]
+        );
+
+        let build_id = BuildLog::create(&slug, "webhook push")
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse {
+                        error: e.to_string(),
+                    }),
+                )
+            })?
+            .id();
+
+        let _ = state
+            .scheduler_tx
+            .send(Job::Rebuild {
+                slug: slug.clone(),
+                commit_sha: Some(commit.to_string()),
+                git_ref: None,
+                trigger: ReleaseTrigger::Webhook,
+                build_id,
+            })
+            .await;
+
+        return Ok(StatusCode::OK);
     }
 
     tracing::debug!(
@@ -101,6 +94,68 @@ pub async fn handle_webhook(
     Ok(StatusCode::OK)
 }
 
+/// Check the `X-Hub-Signature-256` HMAC of the body against the project's webhook
+/// secret. Unsigned requests are refused: the payload picks the commit that gets deployed.
+fn verify_signature(headers: &HeaderMap, body: &[u8], secret: &str) -> Result<(), WebhookError> {
+    let unauthorized = |error: &str| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: error.to_string(),
+            }),
+        )
+    };
+
+    let signature = headers
+        .get("x-hub-signature-256")
+        .ok_or_else(|| unauthorized("Missing X-Hub-Signature-256 header"))?
+        .to_str()
+        .unwrap_or("");
+    let expected_signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+
+    let secret = crate::config::secrets::reveal(secret).map_err(|e| {
+        tracing::error!("Could not decrypt webhook secret: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "Could not verify signature".to_string(),
+            }),
+        )
+    })?;
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "HMAC error".to_string(),
+            }),
+        )
+    })?;
+    mac.update(body);
+
+    let computed = hex::encode(mac.finalize().into_bytes());
+    if !constant_time_eq(computed.as_bytes(), expected_signature.as_bytes()) {
+        return Err(unauthorized("Invalid signature"));
+    }
+    Ok(())
+}
+
+/// The commit to rebuild from for a push to the tracked branch; None for pushes to other
+/// branches and for branch deletions. `after` ends up on git's command line, so anything
+/// but a commit SHA is refused.
+fn pushed_commit<'a>(
+    event: &'a GitHubPushEvent,
+    tracked_branch: &str,
+) -> Result<Option<&'a str>, String> {
+    if !crate::utils::is_commit_sha(&event.after) {
+        return Err(format!("'after' is not a commit SHA: {:?}", event.after));
+    }
+    // `after` is all zeros when the branch was deleted
+    if event.branch() != Some(tracked_branch) || event.after.chars().all(|c| c == '0') {
+        return Ok(None);
+    }
+    Ok(Some(&event.after))
+}
+
 fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
     if a.len() != b.len() {
         return false;
@@ -110,3 +165,76 @@ fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
         .fold(0u8, |acc, (x, y)| acc | (x ^ y))
         == 0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(git_ref: &str, after: &str) -> GitHubPushEvent {
+        serde_json::from_value(serde_json::json!({
+            "ref": git_ref,
+            "after": after,
+            "repository": {
+                "full_name": "acme/web",
+                "clone_url": "https://github.com/acme/web.git",
+                "ssh_url": "git@github.com:acme/web.git"
+            },
+            "pusher": {"name": "octocat"}
+        }))
+        .unwrap()
+    }
+
+    const SHA: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    fn signed(secret: &str, body: &[u8]) -> HeaderMap {
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        let mut headers = HeaderMap::new();
+        let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+        headers.insert("x-hub-signature-256", signature.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn unsigned_requests_are_refused() {
+        let body = br#"{"ref":"refs/heads/main"}"#;
+        let (status, _) = verify_signature(&HeaderMap::new(), body, "s3cret").unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn signature_must_match_the_secret_and_body() {
+        let body = br#"{"ref":"refs/heads/main"}"#;
+        assert!(verify_signature(&signed("s3cret", body), body, "s3cret").is_ok());
+
+        let (status, _) = verify_signature(&signed("other", body), body, "s3cret").unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = verify_signature(&signed("s3cret", b"{}"), body, "s3cret").unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn push_to_tracked_branch_rebuilds_its_commit() {
+        let event = push("refs/heads/main", SHA);
+        assert_eq!(pushed_commit(&event, "main"), Ok(Some(SHA)));
+    }
+
+    #[test]
+    fn other_branches_and_deletions_are_ignored() {
+        assert_eq!(
+            pushed_commit(&push("refs/heads/dev", SHA), "main"),
+            Ok(None)
+        );
+        assert_eq!(pushed_commit(&push("refs/tags/v1", SHA), "main"), Ok(None));
+        let deleted = push("refs/heads/main", &"0".repeat(40));
+        assert_eq!(pushed_commit(&deleted, "main"), Ok(None));
+    }
+
+    #[test]
+    fn option_like_commits_are_rejected() {
+        for after in ["--upload-pack=x", "-c", "HEAD", "main", "", "abc"] {
+            let event = push("refs/heads/main", after);
+            assert!(pushed_commit(&event, "main").is_err(), "{:?}", after);
+        }
+    }
+}
@@ -1,37 +1,409 @@
 use bollard::image::BuildImageOptions;
+use bollard::models::BuildInfo;
 use futures_util::StreamExt;
-use std::path::Path;
+use http_body_util::{BodyExt, StreamBody};
+use hyper::body::{Bytes, Frame};
+use hyper::{Method, Request};
+use hyper_util::rt::TokioIo;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
 
+use crate::config::project::{BuildConfig, ContainerConfig};
+use crate::docker::detect::{self, Runtime};
+use crate::docker::ignore::DockerIgnore;
 use crate::docker::DockerClient;
 use crate::error::DockyardError;
 
+/// Name under which a Dockerfile that does not come from the repository is added to the context
+const INJECTED_DOCKERFILE: &str = ".dockyard.Dockerfile";
+
+/// Where the Dockerfile of a build comes from
+#[derive(Debug, Clone)]
+pub enum DockerfileSource {
+    /// Committed in the repository
+    Repository,
+    /// Stored by the user through `dockyard dockerfile --set`
+    Override,
+    /// Generated from the detected runtime
+    Generated(Runtime),
+}
+
+impl std::fmt::Display for DockerfileSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DockerfileSource::Repository => write!(f, "repository"),
+            DockerfileSource::Override => write!(f, "override"),
+            DockerfileSource::Generated(runtime) => write!(f, "generated ({})", runtime),
+        }
+    }
+}
+
+/// Everything needed to build an image from a checkout
+#[derive(Debug, Clone)]
+pub struct BuildPlan {
+    pub context_dir: PathBuf,
+    /// Dockerfile path inside the context
+    pub dockerfile: String,
+    pub source: DockerfileSource,
+    /// Dockerfile text for overrides and generated builds, added to the context on the fly
+    pub content: Option<String>,
+    /// Port declared by the Dockerfile or implied by the detected runtime
+    pub port: Option<u16>,
+    pub args: HashMap<String, String>,
+    pub target: Option<String>,
+    pub secrets: HashMap<String, String>,
+}
+
+impl BuildPlan {
+    /// The Dockerfile text used for this build
+    pub fn dockerfile_content(&self) -> Result<String, DockyardError> {
+        match &self.content {
+            Some(content) => Ok(content.clone()),
+            None => Ok(std::fs::read_to_string(
+                self.context_dir.join(&self.dockerfile),
+            )?),
+        }
+    }
+}
+
+/// Work out how to build a checkout. A user override wins, then the configured
+/// Dockerfile (falling back to the usual names when left at the default), and
+/// finally a Dockerfile generated from the detected runtime.
+pub fn plan_build(
+    repo_dir: &Path,
+    container: &ContainerConfig,
+    build: &BuildConfig,
+    override_path: &Path,
+) -> Result<BuildPlan, DockyardError> {
+    let context_dir = match &container.build_context {
+        Some(dir) => repo_dir.join(relative_path(dir)?),
+        None => repo_dir.to_path_buf(),
+    };
+    if !context_dir.is_dir() {
+        return Err(DockyardError::BuildFailed(format!(
+            "Build context '{}' not found in repository",
+            container.build_context.as_deref().unwrap_or(".")
+        )));
+    }
+    let context_dir = resolve_inside(
+        repo_dir,
+        &context_dir,
+        container.build_context.as_deref().unwrap_or("."),
+    )?;
+
+    if override_path.is_file() {
+        let content = std::fs::read_to_string(override_path)?;
+        return Ok(BuildPlan {
+            context_dir,
+            dockerfile: INJECTED_DOCKERFILE.to_string(),
+            source: DockerfileSource::Override,
+            port: detect::exposed_port(&content),
+            content: Some(content),
+            args: build.args.clone(),
+            target: build.target.clone(),
+            secrets: build.secrets.clone(),
+        });
+    }
+
+    let configured = relative_path(&container.dockerfile_path)?;
+    let dockerfile = if context_dir.join(&configured).is_file() {
+        Some(configured.to_string_lossy().replace('\\', "/"))
+    } else if container.dockerfile_path == "Dockerfile" {
+        find_dockerfile(&context_dir)
+    } else {
+        return Err(DockyardError::BuildFailed(format!(
+            "Dockerfile '{}' not found in build context",
+            container.dockerfile_path
+        )));
+    };
+
+    if let Some(dockerfile) = dockerfile {
+        resolve_inside(repo_dir, &context_dir.join(&dockerfile), &dockerfile)?;
+        let port = std::fs::read_to_string(context_dir.join(&dockerfile))
+            .ok()
+            .and_then(|c| detect::exposed_port(&c));
+        return Ok(BuildPlan {
+            context_dir,
+            dockerfile,
+            source: DockerfileSource::Repository,
+            content: None,
+            port,
+            args: build.args.clone(),
+            target: build.target.clone(),
+            secrets: build.secrets.clone(),
+        });
+    }
+
+    match detect::detect(&context_dir) {
+        Some(detected) => Ok(BuildPlan {
+            context_dir,
+            dockerfile: INJECTED_DOCKERFILE.to_string(),
+            source: DockerfileSource::Generated(detected.runtime),
+            content: Some(detected.dockerfile),
+            port: Some(detected.port),
+            args: build.args.clone(),
+            target: build.target.clone(),
+            secrets: build.secrets.clone(),
+        }),
+        None => Err(DockyardError::BuildFailed(
+            "No Dockerfile found and no supported runtime detected \
+             (package.json, requirements.txt, pyproject.toml, go.mod, Cargo.toml, index.html)"
+                .into(),
+        )),
+    }
+}
+
+/// Validate a repository-relative path from project config
+fn relative_path(path: &str) -> Result<PathBuf, DockyardError> {
+    let path = Path::new(path.trim_start_matches("./"));
+    if path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(DockyardError::BuildFailed(format!(
+            "Path '{}' must stay inside the repository",
+            path.display()
+        )));
+    }
+    Ok(path.to_path_buf())
+}
+
+/// Follow symlinks in a checkout path; a repository may not point the build outside itself
+fn resolve_inside(repo_dir: &Path, path: &Path, shown: &str) -> Result<PathBuf, DockyardError> {
+    let resolved = path.canonicalize()?;
+    if !resolved.starts_with(repo_dir.canonicalize()?) {
+        return Err(DockyardError::BuildFailed(format!(
+            "Path '{}' links outside the repository",
+            shown
+        )));
+    }
+    Ok(resolved)
+}
+
 /// Find the Dockerfile in a project directory
-pub fn find_dockerfile(project_dir: &Path) -> Result<String, DockyardError> {
+pub fn find_dockerfile(project_dir: &Path) -> Option<String> {
     let candidates = ["Dockerfile", "dockerfile", "Dockerfile.prod"];
-    for candidate in &candidates {
-        if project_dir.join(candidate).exists() {
-            return Ok(candidate.to_string());
-        }
+    candidates
+        .iter()
+        .find(|candidate| project_dir.join(candidate).exists())
+        .map(|candidate| candidate.to_string())
+}
+
+/// Process types declared in a `Procfile` (`name: command` per line) in `dir`; empty when
+/// there is none. Comments and lines that are not a declaration are skipped.
+pub fn read_procfile(dir: &Path) -> Result<BTreeMap<String, String>, DockyardError> {
+    let path = dir.join("Procfile");
+    if !path.exists() {
+        return Ok(BTreeMap::new());
     }
-    Err(DockyardError::BuildFailed(
-        "No Dockerfile found in repository".into(),
-    ))
+    let content = std::fs::read_to_string(&path)?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, command)| (name.trim(), command.trim()))
+        .filter(|(name, command)| {
+            !command.is_empty()
+                && !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .map(|(name, command)| (name.to_string(), command.to_string()))
+        .collect())
 }
 
-/// Build a Docker image from a project directory
+/// Build a Docker image from a context directory, passing each output line to `on_output`.
+/// The context is tarred on a blocking task and streamed to Docker as it is produced;
+/// the build fails once more than `max_context_bytes` have been sent.
 pub async fn build_image(
     docker: &DockerClient,
-    project_dir: &Path,
+    plan: &BuildPlan,
     image_name: &str,
-    dockerfile: &str,
+    max_context_bytes: u64,
+    on_output: &(dyn Fn(&str) + Send + Sync),
+) -> Result<(), DockyardError> {
+    let (rx, tar_task) = spawn_context(plan, max_context_bytes);
+
+    let result = match docker.socket_path() {
+        // Secrets need BuildKit, which the classic build endpoint cannot drive
+        _ if !plan.secrets.is_empty() => build_with_buildkit(plan, image_name, rx, on_output).await,
+        Some(socket) => build_over_socket(socket, plan, image_name, rx, on_output).await,
+        None => build_buffered(docker, plan, image_name, rx, on_output).await,
+    };
+
+    // A failed tar explains a truncated upload better than Docker's error does
+    match tar_task.await {
+        Ok(Ok(())) => result,
+        Ok(Err(e)) => Err(e),
+        Err(e) => Err(DockyardError::BuildFailed(format!(
+            "Build context task failed: {}",
+            e
+        ))),
+    }
+}
+
+type ContextChunk = Result<Bytes, std::io::Error>;
+
+/// Start writing the context tar on a blocking task, returning its chunks as they fill
+fn spawn_context(
+    plan: &BuildPlan,
+    max_bytes: u64,
+) -> (
+    mpsc::Receiver<ContextChunk>,
+    tokio::task::JoinHandle<Result<(), DockyardError>>,
+) {
+    let (tx, rx) = mpsc::channel(8);
+    let plan = plan.clone();
+
+    let task = tokio::task::spawn_blocking(move || {
+        let writer = ContextWriter {
+            tx: tx.clone(),
+            buf: Vec::with_capacity(CHUNK_SIZE),
+            written: 0,
+            max_bytes,
+        };
+        let result = write_build_context(&plan, writer);
+        // Nothing reads the context once the build has failed, and that failure says why
+        if result.is_err() && tx.is_closed() {
+            return Ok(());
+        }
+        if let Err(e) = &result {
+            // Abort the upload so Docker does not build a truncated context
+            let _ = tx.blocking_send(Err(std::io::Error::other(e.to_string())));
+        }
+        result
+    });
+
+    (rx, task)
+}
+
+/// POST the context to Docker's `/build` endpoint over its unix socket as a chunked body
+async fn build_over_socket(
+    socket: &Path,
+    plan: &BuildPlan,
+    image_name: &str,
+    rx: mpsc::Receiver<ContextChunk>,
+    on_output: &(dyn Fn(&str) + Send + Sync),
+) -> Result<(), DockyardError> {
+    let stream = UnixStream::connect(socket)
+        .await
+        .map_err(|e| DockyardError::BuildFailed(format!("Cannot reach Docker: {}", e)))?;
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(|e| DockyardError::BuildFailed(e.to_string()))?;
+    tokio::spawn(async move {
+        if let Err(e) = conn.await {
+            tracing::debug!("Docker build connection closed: {}", e);
+        }
+    });
+
+    let chunks = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk.map(Frame::data), rx))
+    });
+    let mut params = vec![
+        ("t", image_name.to_string()),
+        ("dockerfile", plan.dockerfile.clone()),
+        ("rm", "true".to_string()),
+        ("forcerm", "true".to_string()),
+    ];
+    if !plan.args.is_empty() {
+        params.push(("buildargs", serde_json::to_string(&plan.args)?));
+    }
+    if let Some(target) = &plan.target {
+        params.push(("target", target.clone()));
+    }
+    let query = params
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, encode_query(v)))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("http://localhost/build?{}", query))
+        .header("content-type", "application/x-tar")
+        .body(StreamBody::new(Box::pin(chunks)))
+        .map_err(|e| DockyardError::BuildFailed(e.to_string()))?;
+
+    let response = sender
+        .send_request(req)
+        .await
+        .map_err(|e| DockyardError::BuildFailed(format!("Docker build request failed: {}", e)))?;
+
+    let status = response.status();
+    let mut body = response.into_body();
+    if !status.is_success() {
+        let bytes = body
+            .collect()
+            .await
+            .map(|b| b.to_bytes())
+            .unwrap_or_default();
+        let message = serde_json::from_slice::<serde_json::Value>(&bytes)
+            .ok()
+            .and_then(|v| v["message"].as_str().map(str::to_string))
+            .unwrap_or_else(|| String::from_utf8_lossy(&bytes).to_string());
+        return Err(DockyardError::BuildFailed(message));
+    }
+
+    // Docker answers with one JSON object per line
+    let mut pending = Vec::new();
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|e| DockyardError::BuildFailed(e.to_string()))?;
+        let Ok(data) = frame.into_data() else {
+            continue;
+        };
+        pending.extend_from_slice(&data);
+        while let Some(pos) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=pos).collect();
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let info: BuildInfo = serde_json::from_slice(&line)?;
+            handle_output(info, on_output)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fallback for remote Docker hosts: collect the context and hand it to bollard
+async fn build_buffered(
+    docker: &DockerClient,
+    plan: &BuildPlan,
+    image_name: &str,
+    mut rx: mpsc::Receiver<ContextChunk>,
+    on_output: &(dyn Fn(&str) + Send + Sync),
 ) -> Result<(), DockyardError> {
-    let tar_bytes = create_build_context(project_dir)?;
+    let mut tar_bytes = Vec::new();
+    while let Some(chunk) = rx.recv().await {
+        match chunk {
+            Ok(data) => tar_bytes.extend_from_slice(&data),
+            // The tar task reports the cause
+            Err(_) => return Ok(()),
+        }
+    }
 
+    if plan.target.is_some() {
+        return Err(DockyardError::BuildFailed(
+            "Build targets need a local Docker socket".into(),
+        ));
+    }
     let build_options = BuildImageOptions {
         t: image_name,
-        dockerfile,
+        dockerfile: plan.dockerfile.as_str(),
         rm: true,
         forcerm: true,
+        buildargs: plan
+            .args
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect(),
         ..Default::default()
     };
 
@@ -41,17 +413,7 @@ pub async fn build_image(
 
     while let Some(result) = stream.next().await {
         match result {
-            Ok(output) => {
-                if let Some(stream_msg) = output.stream {
-                    let msg = stream_msg.trim();
-                    if !msg.is_empty() {
-                        tracing::info!("[build] {}", msg);
-                    }
-                }
-                if let Some(error) = output.error {
-                    return Err(DockyardError::BuildFailed(error));
-                }
-            }
+            Ok(output) => handle_output(output, on_output)?,
             Err(e) => {
                 return Err(DockyardError::Docker(e));
             }
@@ -61,15 +423,432 @@ pub async fn build_image(
     Ok(())
 }
 
-/// Create a tar archive from a directory for Docker build context
-fn create_build_context(project_dir: &Path) -> Result<Vec<u8>, DockyardError> {
-    let mut ar = tar::Builder::new(Vec::new());
-    ar.append_dir_all(".", project_dir)
-        .map_err(|e| DockyardError::BuildFailed(format!("Failed to create build context: {}", e)))?;
-    ar.finish()
-        .map_err(|e| DockyardError::BuildFailed(format!("Failed to finalize tar: {}", e)))?;
-    let bytes = ar
-        .into_inner()
-        .map_err(|e| DockyardError::BuildFailed(format!("Failed to get tar bytes: {}", e)))?;
-    Ok(bytes)
+/// Build through the docker CLI with BuildKit so secrets are mounted only while the
+/// `RUN --mount=type=secret,id=<NAME>` steps that ask for them execute, and never end up
+/// in a layer or the image history. Secret values reach the CLI through its environment.
+/// The CLI reads the same filtered context tar as the other builds on its stdin.
+async fn build_with_buildkit(
+    plan: &BuildPlan,
+    image_name: &str,
+    rx: mpsc::Receiver<ContextChunk>,
+    on_output: &(dyn Fn(&str) + Send + Sync),
+) -> Result<(), DockyardError> {
+    let mut cmd = tokio::process::Command::new("docker");
+    cmd.arg("build")
+        .arg("--progress=plain")
+        .arg("-t")
+        .arg(image_name)
+        // Relative to the context, where the tar also carries generated Dockerfiles
+        .arg("-f")
+        .arg(&plan.dockerfile)
+        .env("DOCKER_BUILDKIT", "1")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+    if let Some(target) = &plan.target {
+        cmd.arg("--target").arg(target);
+    }
+    for (key, value) in &plan.args {
+        cmd.arg("--build-arg").arg(format!("{}={}", key, value));
+    }
+    for (i, (key, value)) in plan.secrets.iter().enumerate() {
+        let var = format!("DOCKYARD_BUILD_SECRET_{}", i);
+        cmd.arg("--secret").arg(format!("id={},env={}", key, var));
+        cmd.env(var, value);
+    }
+    cmd.arg("-");
+
+    run_cli_build(cmd, rx, on_output).await
+}
+
+async fn run_cli_build(
+    mut cmd: tokio::process::Command,
+    context: mpsc::Receiver<ContextChunk>,
+    on_output: &(dyn Fn(&str) + Send + Sync),
+) -> Result<(), DockyardError> {
+    let mut child = cmd.spawn().map_err(|e| {
+        DockyardError::BuildFailed(format!("Builds with secrets need the docker CLI: {}", e))
+    })?;
+
+    // BuildKit reports progress on stderr; interleave both pipes line by line
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(stdout, tx.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(stderr, tx);
+    }
+    let stdin = child.stdin.take();
+
+    let read_output = async {
+        let mut last_line = String::new();
+        while let Some(line) = rx.recv().await {
+            let msg = line.trim_end();
+            if !msg.is_empty() {
+                on_output(msg);
+                last_line = msg.to_string();
+            }
+        }
+        last_line
+    };
+    let send_context = async {
+        let sent = match stdin {
+            Some(stdin) => feed_context(stdin, context).await,
+            None => false,
+        };
+        // Killed rather than left to build whatever part of the context arrived
+        if !sent {
+            let _ = child.start_kill();
+        }
+    };
+    let (last_line, ()) = tokio::join!(read_output, send_context);
+
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(DockyardError::BuildFailed(if last_line.is_empty() {
+            format!("docker build exited with {}", status)
+        } else {
+            last_line
+        }));
+    }
+    Ok(())
+}
+
+/// Write the context chunks to the CLI's stdin, closing it at the end of the tar.
+/// False if the context could not be produced in full.
+async fn feed_context(
+    mut stdin: tokio::process::ChildStdin,
+    mut context: mpsc::Receiver<ContextChunk>,
+) -> bool {
+    while let Some(chunk) = context.recv().await {
+        // The tar task reports the cause
+        let Ok(data) = chunk else {
+            return false;
+        };
+        // The CLI stopped reading; its output says why
+        if stdin.write_all(&data).await.is_err() {
+            break;
+        }
+    }
+    true
+}
+
+fn forward_lines(
+    reader: impl tokio::io::AsyncRead + Unpin + Send + 'static,
+    tx: mpsc::UnboundedSender<String>,
+) {
+    tokio::spawn(async move {
+        let mut lines = tokio::io::BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn handle_output(
+    output: BuildInfo,
+    on_output: &(dyn Fn(&str) + Send + Sync),
+) -> Result<(), DockyardError> {
+    if let Some(stream_msg) = output.stream {
+        for line in stream_msg.lines() {
+            let msg = line.trim_end();
+            if !msg.is_empty() {
+                on_output(msg);
+            }
+        }
+    }
+    if let Some(error) = output.error {
+        return Err(DockyardError::BuildFailed(error));
+    }
+    Ok(())
+}
+
+fn encode_query(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// `Write` end of the context stream: batches tar output into chunks and enforces the size cap
+struct ContextWriter {
+    tx: mpsc::Sender<ContextChunk>,
+    buf: Vec<u8>,
+    written: u64,
+    max_bytes: u64,
+}
+
+impl ContextWriter {
+    fn send(&mut self) -> std::io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::replace(
+            &mut self.buf,
+            Vec::with_capacity(CHUNK_SIZE),
+        ));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+    }
+}
+
+impl std::io::Write for ContextWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.written += data.len() as u64;
+        if self.written > self.max_bytes {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::FileTooLarge,
+                "context size limit exceeded",
+            ));
+        }
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.send()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send()
+    }
+}
+
+/// Load the ignore rules for a build: `<Dockerfile>.dockerignore` wins over the context's
+/// `.dockerignore`; without either, only `.git` is left out
+fn load_ignore(context_dir: &Path, dockerfile: &str) -> Result<DockerIgnore, DockyardError> {
+    let candidates = [
+        context_dir.join(format!("{}.dockerignore", dockerfile)),
+        context_dir.join(".dockerignore"),
+    ];
+    for path in &candidates {
+        if path.is_file() {
+            return Ok(DockerIgnore::load(path)?);
+        }
+    }
+    Ok(DockerIgnore::parse(".git"))
+}
+
+/// Write a tar of the build context, skipping paths excluded by `.dockerignore`
+fn write_build_context(plan: &BuildPlan, writer: ContextWriter) -> Result<(), DockyardError> {
+    let ignore = load_ignore(&plan.context_dir, &plan.dockerfile)?;
+    let max_bytes = writer.max_bytes;
+
+    let mut ar = tar::Builder::new(writer);
+    ar.follow_symlinks(false);
+    let injected = plan.content.is_some();
+    let result = append_dir(
+        &mut ar,
+        &plan.context_dir,
+        "",
+        &ignore,
+        &plan.dockerfile,
+        injected,
+    )
+    .and_then(|_| match &plan.content {
+        Some(content) => {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(chrono::Utc::now().timestamp() as u64);
+            header.set_cksum();
+            ar.append_data(&mut header, &plan.dockerfile, content.as_bytes())
+        }
+        None => Ok(()),
+    })
+    .and_then(|_| ar.finish())
+    .and_then(|_| ar.get_mut().flush());
+
+    if ar.get_ref().written > max_bytes {
+        return Err(DockyardError::BuildFailed(format!(
+            "Build context exceeds the {} MB limit (daemon.max_context_mb); \
+             exclude large files with .dockerignore",
+            max_bytes / (1024 * 1024)
+        )));
+    }
+    result.map_err(|e| DockyardError::BuildFailed(format!("Failed to create build context: {}", e)))
+}
+
+fn append_dir<W: std::io::Write>(
+    ar: &mut tar::Builder<W>,
+    dir: &Path,
+    rel: &str,
+    ignore: &DockerIgnore,
+    dockerfile: &str,
+    injected: bool,
+) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = if rel.is_empty() {
+            name
+        } else {
+            format!("{}/{}", rel, name)
+        };
+        // An injected Dockerfile is appended afterwards and replaces the repository's file
+        if injected && path == dockerfile {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        // The daemon always needs the Dockerfile, even when it is ignored
+        let excluded = ignore.is_excluded(&path) && path != dockerfile;
+
+        if file_type.is_dir() {
+            if !excluded {
+                ar.append_dir(&path, entry.path())?;
+                append_dir(ar, &entry.path(), &path, ignore, dockerfile, injected)?;
+            } else if ignore.has_exceptions() {
+                // An exception may re-include something below an excluded directory
+                append_dir(ar, &entry.path(), &path, ignore, dockerfile, injected)?;
+            }
+        } else if !excluded {
+            ar.append_path_with_name(entry.path(), &path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::project::{NetworkMode, ProjectConfig};
+
+    fn checkout(files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dockyard-build-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        dir
+    }
+
+    fn plan(dir: &Path) -> BuildPlan {
+        let config = ProjectConfig::new(
+            "app".into(),
+            "app".into(),
+            "https://example.com/app.git".into(),
+            "main".into(),
+            NetworkMode::LocalOnly,
+            None,
+            3000,
+            3000,
+        );
+        plan_build(
+            dir,
+            &config.container,
+            &config.build,
+            &dir.join("no-override"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn committed_dockerfile_wins_over_detection() {
+        let dir = checkout(&[
+            ("package.json", r#"{"scripts": {"start": "node ."}}"#),
+            ("Dockerfile", "FROM node:20\nEXPOSE 4000\n"),
+        ]);
+        let plan = plan(&dir);
+        assert!(matches!(plan.source, DockerfileSource::Repository));
+        assert_eq!(plan.dockerfile, "Dockerfile");
+        assert_eq!(plan.content, None);
+        assert_eq!(plan.port, Some(4000));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn detected_runtime_is_used_without_a_dockerfile() {
+        let dir = checkout(&[("go.mod", "module app\n")]);
+        let plan = plan(&dir);
+        assert!(matches!(
+            plan.source,
+            DockerfileSource::Generated(Runtime::Go)
+        ));
+        assert_eq!(plan.dockerfile, INJECTED_DOCKERFILE);
+        assert_eq!(plan.port, Some(8080));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn symlinks_out_of_the_checkout_are_refused() {
+        let outside = checkout(&[("secret", "FROM scratch\nEXPOSE 1234\n")]);
+        let dir = checkout(&[("go.mod", "module app\n")]);
+        std::os::unix::fs::symlink(&outside, dir.join("app")).unwrap();
+        std::os::unix::fs::symlink(outside.join("secret"), dir.join("Dockerfile")).unwrap();
+        std::fs::create_dir(dir.join("web")).unwrap();
+        std::os::unix::fs::symlink(dir.join("go.mod"), dir.join("web").join("Dockerfile")).unwrap();
+
+        let mut config = ProjectConfig::new(
+            "app".into(),
+            "app".into(),
+            "https://example.com/app.git".into(),
+            "main".into(),
+            NetworkMode::LocalOnly,
+            None,
+            3000,
+            3000,
+        );
+        let no_override = dir.join("no-override");
+        let err = plan_build(&dir, &config.container, &config.build, &no_override).unwrap_err();
+        assert!(
+            err.to_string().contains("'Dockerfile' links outside"),
+            "{}",
+            err
+        );
+
+        config.container.build_context = Some("app".into());
+        let err = plan_build(&dir, &config.container, &config.build, &no_override).unwrap_err();
+        assert!(err.to_string().contains("'app' links outside"), "{}", err);
+
+        // Links that stay inside the checkout are fine
+        config.container.build_context = Some("web".into());
+        let plan = plan_build(&dir, &config.container, &config.build, &no_override).unwrap();
+        assert_eq!(plan.dockerfile, "Dockerfile");
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_dir_all(&outside).unwrap();
+    }
+
+    fn archived(dir: &Path, dockerfile: &str, injected: bool) -> Vec<String> {
+        let mut ar = tar::Builder::new(Vec::new());
+        append_dir(
+            &mut ar,
+            dir,
+            "",
+            &DockerIgnore::parse(".git"),
+            dockerfile,
+            injected,
+        )
+        .unwrap();
+        let data = ar.into_inner().unwrap();
+        tar::Archive::new(data.as_slice())
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn injected_dockerfile_replaces_a_repository_file_of_the_same_name() {
+        let dir = checkout(&[
+            ("go.mod", "module app\n"),
+            (INJECTED_DOCKERFILE, "FROM scratch\n"),
+        ]);
+        assert_eq!(archived(&dir, INJECTED_DOCKERFILE, true), vec!["go.mod"]);
+        assert_eq!(
+            archived(&dir, INJECTED_DOCKERFILE, false),
+            vec![INJECTED_DOCKERFILE, "go.mod"]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
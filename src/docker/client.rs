@@ -1,24 +1,138 @@
 use bollard::container::{
-    Config, CreateContainerOptions, ListContainersOptions, LogsOptions, RemoveContainerOptions,
-    StartContainerOptions, StatsOptions, StopContainerOptions,
+    AttachContainerOptions, AttachContainerResults, Config, CreateContainerOptions,
+    ListContainersOptions, LogOutput, LogsOptions, NetworkingConfig, RemoveContainerOptions,
+    ResizeContainerTtyOptions, RestartContainerOptions, StartContainerOptions, StatsOptions,
+    StopContainerOptions, UpdateContainerOptions, WaitContainerOptions,
 };
-use bollard::image::RemoveImageOptions;
-use bollard::models::{HostConfig, PortBinding, RestartPolicy, RestartPolicyNameEnum};
+use bollard::exec::{CreateExecOptions, ResizeExecOptions, StartExecOptions, StartExecResults};
+use bollard::image::{CreateImageOptions, RemoveImageOptions, TagImageOptions};
+use bollard::volume::{CreateVolumeOptions, RemoveVolumeOptions};
+use bollard::models::{
+    EndpointSettings, HostConfig, PortBinding, ResourcesUlimits, RestartPolicy,
+    RestartPolicyNameEnum,
+};
+use bollard::network::{ConnectNetworkOptions, DisconnectNetworkOptions};
 use bollard::Docker;
-use futures_util::StreamExt;
+use futures_util::{Stream, StreamExt};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
 
+use crate::config::project::ResourceLimits;
 use crate::error::DockyardError;
+use crate::models::log::{LogEntry, LogStream};
 use crate::models::project::ProjectState;
 
+/// Everything needed to create a project container
+#[derive(Debug, Clone)]
+pub struct ContainerSpec {
+    pub name: String,
+    pub image: String,
+    /// None publishes no port on the host (internal projects)
+    pub host_port: Option<u16>,
+    pub container_port: u16,
+    pub env_vars: HashMap<String, String>,
+    pub resources: ResourceLimits,
+    /// `source:target[:ro]` mounts
+    pub binds: Vec<String>,
+    /// Networks to join, primary first; the container is reachable as `alias` on each
+    pub networks: Vec<String>,
+    pub alias: String,
+    /// Replaces the image's default command
+    pub command: Option<Vec<String>>,
+    /// Restarted by Docker unless stopped; off for containers that run to completion
+    pub restart: bool,
+    /// Keep stdin open for a client to attach to, for interactive one-off containers
+    pub open_stdin: bool,
+    pub tty: bool,
+}
+
+/// Streams of a process attached to by `exec_attached` or `start_attached`
+pub struct AttachedIo {
+    pub output: Pin<Box<dyn Stream<Item = Result<LogOutput, bollard::errors::Error>> + Send>>,
+    pub input: Pin<Box<dyn AsyncWrite + Send>>,
+}
+
+/// Label put on volumes dockyard creates; the value is the owning project's slug
+pub const PROJECT_LABEL: &str = "dockyard.project";
+
+/// Label put on the data volume of a database add-on; the value is the add-on's name
+pub const ADDON_LABEL: &str = "dockyard.addon";
+
+/// A dockyard-managed volume and the disk space it uses
+#[derive(Debug, Clone)]
+pub struct VolumeUsage {
+    pub name: String,
+    /// None when Docker does not report a size (e.g. non-local drivers)
+    pub size_bytes: Option<u64>,
+}
+
+/// How a command run to completion ended
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub exit_code: i64,
+    /// Last lines written to stderr, for error messages
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+/// Lines of stderr kept in a `CommandOutput`
+const STDERR_TAIL: usize = 20;
+
+/// CFS period the CPU quota is expressed against (Docker's default, 100ms)
+const CPU_PERIOD: i64 = 100_000;
+
+fn mib(value: u64) -> i64 {
+    (value * 1024 * 1024) as i64
+}
+
+fn swap_bytes(value: i64) -> i64 {
+    if value < 0 {
+        -1
+    } else {
+        mib(value as u64)
+    }
+}
+
+fn cpu_quota(cpus: f64) -> i64 {
+    (cpus * CPU_PERIOD as f64).round() as i64
+}
+
+fn endpoint(alias: &str) -> EndpointSettings {
+    EndpointSettings {
+        aliases: Some(vec![alias.to_string()]),
+        ..Default::default()
+    }
+}
+
 pub struct DockerClient {
     docker: Docker,
+    /// Local socket of the Docker daemon, used for requests bollard cannot stream
+    socket: Option<PathBuf>,
+}
+
+/// Which part of a container's output to return; times are unix seconds
+#[derive(Debug, Default, Clone)]
+pub struct LogFilter {
+    pub tail: Option<u32>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
 }
 
 impl DockerClient {
     pub fn connect() -> Result<Self, DockyardError> {
         let docker = Docker::connect_with_local_defaults()?;
-        Ok(Self { docker })
+        let socket = match std::env::var("DOCKER_HOST") {
+            Ok(host) => host.strip_prefix("unix://").map(PathBuf::from),
+            Err(_) => Some(PathBuf::from("/var/run/docker.sock")),
+        };
+        Ok(Self { docker, socket })
     }
 
     pub async fn ping(&self) -> Result<(), DockyardError> {
@@ -30,61 +144,176 @@ impl DockerClient {
         &self.docker
     }
 
+    pub fn socket_path(&self) -> Option<&Path> {
+        self.socket.as_deref()
+    }
+
     pub async fn create_and_start_container(
         &self,
-        container_name: &str,
-        image: &str,
-        host_port: u16,
-        container_port: u16,
-        env_vars: &HashMap<String, String>,
+        spec: &ContainerSpec,
     ) -> Result<String, DockyardError> {
-        let env: Vec<String> = env_vars
+        let container_id = self.create_container(spec).await?;
+
+        self.docker
+            .start_container(&container_id, None::<StartContainerOptions<String>>)
+            .await?;
+
+        Ok(container_id)
+    }
+
+    /// Create a project container without starting it
+    pub async fn create_container(&self, spec: &ContainerSpec) -> Result<String, DockyardError> {
+        let env: Vec<String> = spec
+            .env_vars
             .iter()
             .map(|(k, v)| format!("{}={}", k, v))
             .collect();
 
-        let port_key = format!("{}/tcp", container_port);
+        let port_key = format!("{}/tcp", spec.container_port);
 
         let mut port_bindings = HashMap::new();
-        port_bindings.insert(
-            port_key.clone(),
-            Some(vec![PortBinding {
-                host_ip: Some("0.0.0.0".to_string()),
-                host_port: Some(host_port.to_string()),
-            }]),
-        );
+        if let Some(host_port) = spec.host_port {
+            port_bindings.insert(
+                port_key.clone(),
+                Some(vec![PortBinding {
+                    host_ip: Some("0.0.0.0".to_string()),
+                    host_port: Some(host_port.to_string()),
+                }]),
+            );
+        }
 
         let mut exposed_ports = HashMap::new();
         exposed_ports.insert(port_key, HashMap::new());
 
+        // Docker only takes one network at creation; the rest are connected afterwards
+        let (primary, extra) = match spec.networks.split_first() {
+            Some((primary, extra)) => (Some(primary), extra),
+            None => (None, &[][..]),
+        };
+        let networking_config = primary.map(|network| NetworkingConfig {
+            endpoints_config: HashMap::from([(network.clone(), endpoint(&spec.alias))]),
+        });
+
+        let limits = &spec.resources;
         let config = Config {
-            image: Some(image.to_string()),
+            image: Some(spec.image.clone()),
+            cmd: spec.command.clone(),
             env: Some(env),
+            open_stdin: Some(spec.open_stdin),
+            attach_stdin: Some(spec.open_stdin),
+            // Stdin closes when the attached client goes away
+            stdin_once: Some(spec.open_stdin),
+            tty: Some(spec.tty),
             exposed_ports: Some(exposed_ports),
+            networking_config,
             host_config: Some(HostConfig {
                 port_bindings: Some(port_bindings),
+                binds: (!spec.binds.is_empty()).then(|| spec.binds.clone()),
                 restart_policy: Some(RestartPolicy {
-                    name: Some(RestartPolicyNameEnum::UNLESS_STOPPED),
+                    name: Some(if spec.restart {
+                        RestartPolicyNameEnum::UNLESS_STOPPED
+                    } else {
+                        RestartPolicyNameEnum::NO
+                    }),
                     maximum_retry_count: None,
                 }),
+                memory: limits.memory_mb.map(mib),
+                memory_swap: limits.memory_swap_mb.map(swap_bytes),
+                cpu_period: limits.cpus.map(|_| CPU_PERIOD),
+                cpu_quota: limits.cpus.map(cpu_quota),
+                cpu_shares: limits.cpu_shares.map(|s| s as i64),
+                pids_limit: limits.pids_limit.map(|p| p as i64),
+                ulimits: (!limits.ulimits.is_empty()).then(|| {
+                    limits
+                        .ulimits
+                        .iter()
+                        .map(|u| ResourcesUlimits {
+                            name: Some(u.name.clone()),
+                            soft: Some(u.soft),
+                            hard: Some(u.hard),
+                        })
+                        .collect()
+                }),
                 ..Default::default()
             }),
             ..Default::default()
         };
 
         let options = CreateContainerOptions {
-            name: container_name,
+            name: spec.name.as_str(),
             platform: None,
         };
 
         let response = self.docker.create_container(Some(options), config).await?;
-        let container_id = response.id;
+        for network in extra {
+            self.connect_network(network, &response.id, &spec.alias)
+                .await?;
+        }
+        Ok(response.id)
+    }
 
+    /// Attach a container to a network, reachable there as `alias`
+    pub async fn connect_network(
+        &self,
+        network: &str,
+        container: &str,
+        alias: &str,
+    ) -> Result<(), DockyardError> {
         self.docker
-            .start_container(&container_id, None::<StartContainerOptions<String>>)
+            .connect_network(
+                network,
+                ConnectNetworkOptions {
+                    container,
+                    endpoint_config: endpoint(alias),
+                },
+            )
             .await?;
+        Ok(())
+    }
 
-        Ok(container_id)
+    pub async fn disconnect_network(
+        &self,
+        network: &str,
+        container: &str,
+    ) -> Result<(), DockyardError> {
+        self.docker
+            .disconnect_network(
+                network,
+                DisconnectNetworkOptions {
+                    container,
+                    force: true,
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Apply new limits to a running container. Memory limits cannot be lifted and
+    /// ulimits cannot be changed this way; those need a new container.
+    pub async fn update_resources(
+        &self,
+        container_name: &str,
+        limits: &ResourceLimits,
+    ) -> Result<(), DockyardError> {
+        // Docker keeps the old swap limit otherwise, which fails once memory exceeds it
+        let memory_swap = match (limits.memory_mb, limits.memory_swap_mb) {
+            (_, Some(swap)) => Some(swap_bytes(swap)),
+            (Some(memory), None) => Some(mib(memory) * 2),
+            (None, None) => None,
+        };
+
+        let options = UpdateContainerOptions::<String> {
+            memory: limits.memory_mb.map(mib),
+            memory_swap,
+            cpu_period: Some(CPU_PERIOD),
+            // -1 lifts the quota
+            cpu_quota: Some(limits.cpus.map(cpu_quota).unwrap_or(-1)),
+            cpu_shares: limits.cpu_shares.map(|s| s as isize),
+            pids_limit: Some(limits.pids_limit.map(|p| p as i64).unwrap_or(-1)),
+            ..Default::default()
+        };
+        self.docker.update_container(container_name, options).await?;
+        Ok(())
     }
 
     pub async fn stop_container(&self, container_name: &str) -> Result<(), DockyardError> {
@@ -100,6 +329,7 @@ impl DockerClient {
                 container_name,
                 Some(RemoveContainerOptions {
                     force: true,
+                    // Only anonymous volumes; named project volumes outlive the container
                     v: true,
                     ..Default::default()
                 }),
@@ -108,6 +338,499 @@ impl DockerClient {
         Ok(())
     }
 
+    /// Create a project volume unless it already exists
+    pub async fn ensure_volume(&self, name: &str, slug: &str) -> Result<(), DockyardError> {
+        self.ensure_labeled_volume(name, PROJECT_LABEL, slug).await
+    }
+
+    /// Create the data volume of an add-on unless it already exists
+    pub async fn ensure_addon_volume(&self, name: &str, addon: &str) -> Result<(), DockyardError> {
+        self.ensure_labeled_volume(name, ADDON_LABEL, addon).await
+    }
+
+    async fn ensure_labeled_volume(
+        &self,
+        name: &str,
+        label: &str,
+        value: &str,
+    ) -> Result<(), DockyardError> {
+        let labels = HashMap::from([(label.to_string(), value.to_string())]);
+        // Docker returns the existing volume when the name is taken
+        self.docker
+            .create_volume(CreateVolumeOptions {
+                name: name.to_string(),
+                driver: "local".to_string(),
+                labels,
+                ..Default::default()
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Volumes created for a project, with their sizes
+    pub async fn project_volumes(&self, slug: &str) -> Result<Vec<VolumeUsage>, DockyardError> {
+        // Sizes are only reported by the disk usage endpoint, not by the volume list
+        let usage = self.docker.df().await?;
+        let mut volumes: Vec<VolumeUsage> = usage
+            .volumes
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|v| v.labels.get(PROJECT_LABEL).map(String::as_str) == Some(slug))
+            .map(|v| VolumeUsage {
+                name: v.name,
+                size_bytes: v
+                    .usage_data
+                    .and_then(|u| u64::try_from(u.size).ok()),
+            })
+            .collect();
+        volumes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(volumes)
+    }
+
+    pub async fn remove_volume(&self, name: &str) -> Result<(), DockyardError> {
+        self.docker
+            .remove_volume(name, None::<RemoveVolumeOptions>)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn restart_container(&self, container_name: &str) -> Result<(), DockyardError> {
+        self.docker
+            .restart_container(container_name, Some(RestartContainerOptions { t: 10 }))
+            .await?;
+        Ok(())
+    }
+
+    /// Address of a container on its first Docker network, if it has one
+    pub async fn get_container_ip(
+        &self,
+        container_name: &str,
+    ) -> Result<Option<String>, DockyardError> {
+        let info = self.docker.inspect_container(container_name, None).await?;
+        let ip = info.network_settings.and_then(|settings| {
+            settings
+                .networks
+                .unwrap_or_default()
+                .into_values()
+                .filter_map(|n| n.ip_address)
+                .find(|ip| !ip.is_empty())
+        });
+        Ok(ip)
+    }
+
+    /// Names of the networks a container is attached to
+    pub async fn get_container_networks(
+        &self,
+        container_name: &str,
+    ) -> Result<Vec<String>, DockyardError> {
+        let info = self.docker.inspect_container(container_name, None).await?;
+        Ok(info
+            .network_settings
+            .and_then(|settings| settings.networks)
+            .map(|networks| networks.into_keys().collect())
+            .unwrap_or_default())
+    }
+
+    /// Environment a container was created with, image defaults included
+    pub async fn get_container_env(
+        &self,
+        container_name: &str,
+    ) -> Result<HashMap<String, String>, DockyardError> {
+        let info = self.docker.inspect_container(container_name, None).await?;
+        Ok(info
+            .config
+            .and_then(|config| config.env)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|var| {
+                var.split_once('=')
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+            })
+            .collect())
+    }
+
+    /// Pull an image from its registry unless it is already present
+    pub async fn pull_image(&self, image: &str) -> Result<(), DockyardError> {
+        if self.docker.inspect_image(image).await.is_ok() {
+            return Ok(());
+        }
+        let (from_image, tag) = image.split_once(':').unwrap_or((image, "latest"));
+        let mut progress = self.docker.create_image(
+            Some(CreateImageOptions {
+                from_image,
+                tag,
+                ..Default::default()
+            }),
+            None,
+            None,
+        );
+        while let Some(step) = progress.next().await {
+            step?;
+        }
+        Ok(())
+    }
+
+    /// Run a throwaway container to completion with the given mounts, then remove it
+    pub async fn run_helper(
+        &self,
+        image: &str,
+        binds: Vec<String>,
+        cmd: Vec<String>,
+    ) -> Result<CommandOutput, DockyardError> {
+        self.pull_image(image).await?;
+        let name = format!("dockyard-helper-{}", uuid::Uuid::new_v4().simple());
+        let config = Config {
+            image: Some(image.to_string()),
+            cmd: Some(cmd),
+            host_config: Some(HostConfig {
+                binds: Some(binds),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        self.docker
+            .create_container(
+                Some(CreateContainerOptions {
+                    name: name.as_str(),
+                    platform: None,
+                }),
+                config,
+            )
+            .await?;
+
+        let result = self.wait_helper(&name).await;
+        if let Err(e) = self.remove_container(&name).await {
+            tracing::warn!("Failed to remove helper container {}: {}", name, e);
+        }
+        result
+    }
+
+    async fn wait_helper(&self, name: &str) -> Result<CommandOutput, DockyardError> {
+        self.docker
+            .start_container(name, None::<StartContainerOptions<String>>)
+            .await?;
+        let exit_code = self.wait_exit(name).await?;
+
+        let filter = LogFilter {
+            tail: Some(STDERR_TAIL as u32),
+            ..Default::default()
+        };
+        let stderr = self
+            .get_logs(name, &filter)
+            .await?
+            .into_iter()
+            .filter(|entry| entry.stream == LogStream::Stderr)
+            .map(|entry| entry.message)
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(CommandOutput { exit_code, stderr })
+    }
+
+    /// Start a created container and wait for it to exit. Returns its exit code, or None
+    /// when it was stopped after running for `timeout`.
+    pub async fn run_to_exit(
+        &self,
+        name: &str,
+        timeout: std::time::Duration,
+    ) -> Result<Option<i64>, DockyardError> {
+        self.docker
+            .start_container(name, None::<StartContainerOptions<String>>)
+            .await?;
+        match tokio::time::timeout(timeout, self.wait_exit(name)).await {
+            Ok(exit_code) => exit_code.map(Some),
+            Err(_) => {
+                self.stop_container(name).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Attach to a created container's stdin and output, then start it
+    pub async fn start_attached(&self, name: &str) -> Result<AttachedIo, DockyardError> {
+        let AttachContainerResults { output, input } = self
+            .docker
+            .attach_container(
+                name,
+                Some(AttachContainerOptions::<String> {
+                    stdin: Some(true),
+                    stdout: Some(true),
+                    stderr: Some(true),
+                    stream: Some(true),
+                    logs: Some(true),
+                    ..Default::default()
+                }),
+            )
+            .await?;
+        self.docker
+            .start_container(name, None::<StartContainerOptions<String>>)
+            .await?;
+        Ok(AttachedIo { output, input })
+    }
+
+    pub async fn resize_container(
+        &self,
+        name: &str,
+        rows: u16,
+        cols: u16,
+    ) -> Result<(), DockyardError> {
+        self.docker
+            .resize_container_tty(
+                name,
+                ResizeContainerTtyOptions {
+                    width: cols,
+                    height: rows,
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Exit code of a container, waiting for it to stop
+    pub async fn wait_exit(&self, name: &str) -> Result<i64, DockyardError> {
+        let mut wait = self
+            .docker
+            .wait_container(name, None::<WaitContainerOptions<String>>);
+        let mut exit_code = 0;
+        while let Some(status) = wait.next().await {
+            match status {
+                Ok(status) => exit_code = status.status_code,
+                // Non-zero exits are reported as errors
+                Err(bollard::errors::Error::DockerContainerWaitError { code, .. }) => {
+                    exit_code = code
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(exit_code)
+    }
+
+    /// Run a command inside a running container and write its stdout to a file
+    pub async fn exec_to_file(
+        &self,
+        container_name: &str,
+        cmd: Vec<String>,
+        env: Vec<String>,
+        path: &Path,
+    ) -> Result<CommandOutput, DockyardError> {
+        let exec = self
+            .docker
+            .create_exec(
+                container_name,
+                CreateExecOptions {
+                    cmd: Some(cmd),
+                    env: Some(env),
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        let StartExecResults::Attached { mut output, .. } =
+            self.docker.start_exec(&exec.id, None).await?
+        else {
+            return Err(DockyardError::Config("exec did not attach".to_string()));
+        };
+
+        let mut file = BufWriter::new(tokio::fs::File::create(path).await?);
+        let mut stderr = Vec::new();
+        while let Some(chunk) = output.next().await {
+            match chunk? {
+                LogOutput::StdOut { message } | LogOutput::Console { message } => {
+                    file.write_all(&message).await?
+                }
+                LogOutput::StdErr { message } => stderr.extend_from_slice(&message),
+                LogOutput::StdIn { .. } => {}
+            }
+        }
+        file.flush().await?;
+
+        self.exec_result(&exec.id, &stderr).await
+    }
+
+    /// Start a command in a running container with stdin and output attached. Returns the
+    /// exec id, used to resize its TTY and read its exit code.
+    pub async fn exec_attached(
+        &self,
+        container_name: &str,
+        cmd: Vec<String>,
+        env: Vec<String>,
+        tty: bool,
+    ) -> Result<(String, AttachedIo), DockyardError> {
+        let exec = self
+            .docker
+            .create_exec(
+                container_name,
+                CreateExecOptions {
+                    cmd: Some(cmd),
+                    env: Some(env),
+                    attach_stdin: Some(true),
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    tty: Some(tty),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        let options = StartExecOptions {
+            detach: false,
+            tty,
+            output_capacity: None,
+        };
+        let StartExecResults::Attached { output, input } =
+            self.docker.start_exec(&exec.id, Some(options)).await?
+        else {
+            return Err(DockyardError::Config("exec did not attach".to_string()));
+        };
+        Ok((exec.id, AttachedIo { output, input }))
+    }
+
+    pub async fn resize_exec(
+        &self,
+        exec_id: &str,
+        rows: u16,
+        cols: u16,
+    ) -> Result<(), DockyardError> {
+        self.docker
+            .resize_exec(
+                exec_id,
+                ResizeExecOptions {
+                    height: rows,
+                    width: cols,
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Exit code of a finished exec; -1 when Docker does not report one
+    pub async fn exec_exit_code(&self, exec_id: &str) -> Result<i64, DockyardError> {
+        let info = self.docker.inspect_exec(exec_id).await?;
+        Ok(info.exit_code.unwrap_or(-1))
+    }
+
+    pub async fn is_exec_running(&self, exec_id: &str) -> Result<bool, DockyardError> {
+        let info = self.docker.inspect_exec(exec_id).await?;
+        Ok(info.running.unwrap_or(false))
+    }
+
+    /// Send `signal` (a name such as HUP) to every process in a container whose
+    /// environment holds `env_var` (`KEY=VALUE`). `kill` runs inside the container, so
+    /// only its own processes can be hit; this needs a shell in the image.
+    pub async fn signal_tagged(
+        &self,
+        container_name: &str,
+        env_var: &str,
+        signal: &str,
+    ) -> Result<CommandOutput, DockyardError> {
+        let script = r#"for dir in /proc/[0-9]*; do
+            tr '\0' '\n' 2>/dev/null < "$dir/environ" | grep -qxF "$1" \
+                && kill -s "$2" "${dir#/proc/}" 2>/dev/null
+        done; true"#;
+        let cmd = ["sh", "-c", script, "sh", env_var, signal]
+            .map(String::from)
+            .to_vec();
+        let exec = self
+            .docker
+            .create_exec(
+                container_name,
+                CreateExecOptions {
+                    cmd: Some(cmd),
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        let StartExecResults::Attached { mut output, .. } =
+            self.docker.start_exec(&exec.id, None).await?
+        else {
+            return Err(DockyardError::Config("exec did not attach".to_string()));
+        };
+
+        let mut stderr = Vec::new();
+        while let Some(chunk) = output.next().await {
+            if let LogOutput::StdErr { message } = chunk? {
+                stderr.extend_from_slice(&message);
+            }
+        }
+        self.exec_result(&exec.id, &stderr).await
+    }
+
+    /// Run a command inside a running container with a file as its stdin
+    pub async fn exec_from_file(
+        &self,
+        container_name: &str,
+        cmd: Vec<String>,
+        env: Vec<String>,
+        path: &Path,
+    ) -> Result<CommandOutput, DockyardError> {
+        let exec = self
+            .docker
+            .create_exec(
+                container_name,
+                CreateExecOptions {
+                    cmd: Some(cmd),
+                    env: Some(env),
+                    attach_stdin: Some(true),
+                    attach_stdout: Some(true),
+                    attach_stderr: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        let StartExecResults::Attached { mut output, mut input } =
+            self.docker.start_exec(&exec.id, None).await?
+        else {
+            return Err(DockyardError::Config("exec did not attach".to_string()));
+        };
+
+        // Output has to be drained while feeding stdin, or the command can block on it
+        let mut file = tokio::fs::File::open(path).await?;
+        let feed = async {
+            tokio::io::copy(&mut file, &mut input).await?;
+            input.shutdown().await
+        };
+        let drain = async {
+            let mut stderr = Vec::new();
+            while let Some(chunk) = output.next().await {
+                if let LogOutput::StdErr { message } = chunk? {
+                    stderr.extend_from_slice(&message);
+                }
+            }
+            Ok::<_, DockyardError>(stderr)
+        };
+        let (fed, stderr) = tokio::join!(feed, drain);
+        let stderr = stderr?;
+        if let Err(e) = fed {
+            // The command may exit before reading all of its input; its status says more
+            tracing::debug!("Writing stdin of exec in {} failed: {}", container_name, e);
+        }
+
+        self.exec_result(&exec.id, &stderr).await
+    }
+
+    async fn exec_result(&self, exec_id: &str, stderr: &[u8]) -> Result<CommandOutput, DockyardError> {
+        let info = self.docker.inspect_exec(exec_id).await?;
+        let stderr = String::from_utf8_lossy(stderr);
+        let lines: Vec<&str> = stderr.lines().collect();
+        Ok(CommandOutput {
+            exit_code: info.exit_code.unwrap_or(-1),
+            stderr: lines[lines.len().saturating_sub(STDERR_TAIL)..].join("\n"),
+        })
+    }
+
+    pub async fn rename_container(&self, from: &str, to: &str) -> Result<(), DockyardError> {
+        self.docker
+            .rename_container(
+                from,
+                bollard::container::RenameContainerOptions {
+                    name: to.to_string(),
+                },
+            )
+            .await?;
+        Ok(())
+    }
+
     pub async fn remove_image(&self, image_name: &str) -> Result<(), DockyardError> {
         self.docker
             .remove_image(
@@ -122,9 +845,28 @@ impl DockerClient {
         Ok(())
     }
 
+    pub async fn tag_image(
+        &self,
+        source: &str,
+        repo: &str,
+        tag: &str,
+    ) -> Result<(), DockyardError> {
+        self.docker
+            .tag_image(source, Some(TagImageOptions { repo, tag }))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_image_id(&self, image_name: &str) -> Result<String, DockyardError> {
+        let inspect = self.docker.inspect_image(image_name).await?;
+        Ok(inspect.id.unwrap_or_default())
+    }
+
     pub async fn is_container_running(&self, container_name: &str) -> Result<bool, DockyardError> {
+        // Names are matched as a pattern; anchor it so replicas and `-new` siblings do not match
+        let exact = format!("^/{}$", container_name);
         let mut filters = HashMap::new();
-        filters.insert("name", vec![container_name]);
+        filters.insert("name", vec![exact.as_str()]);
 
         let containers = self
             .docker
@@ -146,10 +888,11 @@ impl DockerClient {
         Ok(false)
     }
 
+    /// Memory in MB, CPU percent and process count of a running container
     pub async fn get_container_stats(
         &self,
         container_name: &str,
-    ) -> Result<(f64, f64), DockyardError> {
+    ) -> Result<(f64, f64, Option<u64>), DockyardError> {
         let mut stream = self.docker.stats(
             container_name,
             Some(StatsOptions {
@@ -184,45 +927,71 @@ impl DockerClient {
                 }
             };
 
-            Ok((memory_mb, cpu_percent))
+            Ok((memory_mb, cpu_percent, stats.pids_stats.current))
         } else {
-            Ok((0.0, 0.0))
+            Ok((0.0, 0.0, None))
         }
     }
 
     pub async fn get_logs(
         &self,
         container_name: &str,
-        tail: u32,
+        filter: &LogFilter,
+    ) -> Result<Vec<LogEntry>, DockyardError> {
+        let mut stream = Box::pin(self.log_stream(container_name, filter, false));
+        let mut logs = Vec::new();
+
+        while let Some(entry) = stream.next().await {
+            logs.push(entry?);
+        }
+
+        Ok(logs)
+    }
+
+    /// Stream container output with timestamps, split into stdout and stderr entries
+    pub fn log_stream(
+        &self,
+        container_name: &str,
+        filter: &LogFilter,
         follow: bool,
-    ) -> Result<Vec<String>, DockyardError> {
+    ) -> impl Stream<Item = Result<LogEntry, DockyardError>> {
         let options = LogsOptions::<String> {
             stdout: true,
             stderr: true,
-            tail: tail.to_string(),
+            timestamps: true,
+            since: filter.since.unwrap_or(0),
+            until: filter.until.unwrap_or(0),
+            tail: filter
+                .tail
+                .map(|t| t.to_string())
+                .unwrap_or_else(|| "all".to_string()),
             follow,
-            ..Default::default()
         };
 
-        let mut stream = self.docker.logs(container_name, Some(options));
-        let mut logs = Vec::new();
-
-        while let Some(Ok(log)) = stream.next().await {
-            logs.push(log.to_string());
-            if !follow && logs.len() >= tail as usize {
-                break;
-            }
-        }
-
-        Ok(logs)
+        self.docker
+            .logs(container_name, Some(options))
+            .filter_map(|result| async move {
+                match result {
+                    Ok(LogOutput::StdOut { message }) | Ok(LogOutput::Console { message }) => {
+                        Some(Ok(parse_log_line(LogStream::Stdout, &message)))
+                    }
+                    Ok(LogOutput::StdErr { message }) => {
+                        Some(Ok(parse_log_line(LogStream::Stderr, &message)))
+                    }
+                    Ok(LogOutput::StdIn { .. }) => None,
+                    Err(e) => Some(Err(DockyardError::Docker(e))),
+                }
+            })
     }
 
     pub async fn get_container_state(
         &self,
         container_name: &str,
     ) -> Result<ProjectState, DockyardError> {
+        // Names are matched as a pattern; anchor it so replicas and `-new` siblings do not match
+        let exact = format!("^/{}$", container_name);
         let mut filters = HashMap::new();
-        filters.insert("name", vec![container_name]);
+        filters.insert("name", vec![exact.as_str()]);
 
         let containers = self
             .docker
@@ -246,6 +1015,19 @@ impl DockerClient {
         }
     }
 
+    pub async fn get_container_id(
+        &self,
+        container_name: &str,
+    ) -> Result<Option<String>, DockyardError> {
+        match self.docker.inspect_container(container_name, None).await {
+            Ok(inspect) => Ok(inspect.id),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     pub async fn get_container_uptime(
         &self,
         container_name: &str,
@@ -265,3 +1047,23 @@ impl DockerClient {
         Ok(None)
     }
 }
+
+/// Split the RFC 3339 timestamp Docker prepends when `timestamps` is set
+fn parse_log_line(stream: LogStream, raw: &[u8]) -> LogEntry {
+    let line = String::from_utf8_lossy(raw);
+    let line = line.trim_end_matches(['\n', '\r']);
+
+    let (timestamp, message) = match line.split_once(' ') {
+        Some((stamp, rest)) => match chrono::DateTime::parse_from_rfc3339(stamp) {
+            Ok(t) => (Some(t.with_timezone(&chrono::Utc)), rest),
+            Err(_) => (None, line),
+        },
+        None => (None, line),
+    };
+
+    LogEntry {
+        stream,
+        timestamp,
+        message: message.to_string(),
+    }
+}
@@ -0,0 +1,327 @@
+use std::path::Path;
+
+/// Language/runtime recognized in a repository without a Dockerfile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Runtime {
+    Node,
+    Python,
+    Go,
+    Rust,
+    Static,
+}
+
+impl std::fmt::Display for Runtime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Runtime::Node => write!(f, "node"),
+            Runtime::Python => write!(f, "python"),
+            Runtime::Go => write!(f, "go"),
+            Runtime::Rust => write!(f, "rust"),
+            Runtime::Static => write!(f, "static"),
+        }
+    }
+}
+
+/// A generated Dockerfile and the port the resulting container listens on
+#[derive(Debug, Clone)]
+pub struct Detected {
+    pub runtime: Runtime,
+    pub port: u16,
+    pub dockerfile: String,
+}
+
+/// Inspect a checkout and generate a Dockerfile for the first runtime recognized
+pub fn detect(dir: &Path) -> Option<Detected> {
+    if dir.join("package.json").is_file() {
+        return Some(node(dir));
+    }
+    if dir.join("Cargo.toml").is_file() {
+        if let Some(detected) = rust(dir) {
+            return Some(detected);
+        }
+    }
+    if dir.join("go.mod").is_file() {
+        return Some(go());
+    }
+    if dir.join("requirements.txt").is_file() || dir.join("pyproject.toml").is_file() {
+        return Some(python(dir));
+    }
+    if dir.join("index.html").is_file() {
+        return Some(static_site());
+    }
+    None
+}
+
+/// First port declared with `EXPOSE` in a Dockerfile
+pub fn exposed_port(dockerfile: &str) -> Option<u16> {
+    dockerfile.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        if !words.next()?.eq_ignore_ascii_case("EXPOSE") {
+            return None;
+        }
+        words.next()?.split('/').next()?.parse().ok()
+    })
+}
+
+fn node(dir: &Path) -> Detected {
+    let package: serde_json::Value = std::fs::read_to_string(dir.join("package.json"))
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default();
+    let scripts = &package["scripts"];
+    let has_script = |name: &str| scripts.get(name).is_some();
+
+    let install = if dir.join("pnpm-lock.yaml").is_file() {
+        "corepack enable && pnpm install --frozen-lockfile"
+    } else if dir.join("yarn.lock").is_file() {
+        "corepack enable && yarn install --frozen-lockfile"
+    } else if dir.join("package-lock.json").is_file() {
+        "npm ci"
+    } else {
+        "npm install"
+    };
+
+    let deps = format!(
+        "FROM node:20-alpine AS deps\n\
+         WORKDIR /app\n\
+         COPY package*.json yarn.lock* pnpm-lock.yaml* ./\n\
+         RUN {install}\n\
+         \n\
+         FROM node:20-alpine AS build\n\
+         WORKDIR /app\n\
+         COPY --from=deps /app/node_modules ./node_modules\n\
+         COPY . .\n\
+         RUN npm run build --if-present\n"
+    );
+
+    // A build script without a start script is a frontend: serve its output statically
+    if has_script("build") && !has_script("start") {
+        let uses_cra = package["dependencies"].get("react-scripts").is_some()
+            || package["devDependencies"].get("react-scripts").is_some();
+        let out_dir = if uses_cra { "build" } else { "dist" };
+        return Detected {
+            runtime: Runtime::Static,
+            port: 80,
+            dockerfile: format!(
+                "{deps}\n\
+                 FROM nginx:alpine\n\
+                 COPY --from=build /app/{out_dir} /usr/share/nginx/html\n\
+                 EXPOSE 80\n"
+            ),
+        };
+    }
+
+    Detected {
+        runtime: Runtime::Node,
+        port: 3000,
+        dockerfile: format!(
+            "{deps}\n\
+             FROM node:20-alpine\n\
+             WORKDIR /app\n\
+             ENV NODE_ENV=production PORT=3000\n\
+             COPY --from=build /app ./\n\
+             EXPOSE 3000\n\
+             CMD [\"npm\", \"start\"]\n"
+        ),
+    }
+}
+
+fn python(dir: &Path) -> Detected {
+    let requirements = std::fs::read_to_string(dir.join("requirements.txt"))
+        .or_else(|_| std::fs::read_to_string(dir.join("pyproject.toml")))
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let install = if dir.join("requirements.txt").is_file() {
+        "COPY requirements.txt ./\n\
+         RUN pip install --no-cache-dir -r requirements.txt"
+    } else {
+        "COPY . .\n\
+         RUN pip install --no-cache-dir ."
+    };
+
+    let module = ["main", "app", "server"]
+        .into_iter()
+        .find(|m| dir.join(format!("{}.py", m)).is_file())
+        .unwrap_or("main");
+    let command = if dir.join("manage.py").is_file() {
+        r#"["python", "manage.py", "runserver", "0.0.0.0:8000"]"#.to_string()
+    } else if requirements.contains("uvicorn") {
+        format!(
+            r#"["uvicorn", "{}:app", "--host", "0.0.0.0", "--port", "8000"]"#,
+            module
+        )
+    } else if requirements.contains("gunicorn") {
+        format!(
+            r#"["gunicorn", "--bind", "0.0.0.0:8000", "{}:app"]"#,
+            module
+        )
+    } else {
+        format!(r#"["python", "{}.py"]"#, module)
+    };
+
+    Detected {
+        runtime: Runtime::Python,
+        port: 8000,
+        dockerfile: format!(
+            "FROM python:3.12-slim AS build\n\
+             WORKDIR /app\n\
+             RUN python -m venv /venv\n\
+             ENV PATH=/venv/bin:$PATH\n\
+             {install}\n\
+             \n\
+             FROM python:3.12-slim\n\
+             WORKDIR /app\n\
+             COPY --from=build /venv /venv\n\
+             ENV PATH=/venv/bin:$PATH PYTHONUNBUFFERED=1 PORT=8000\n\
+             COPY . .\n\
+             EXPOSE 8000\n\
+             CMD {command}\n"
+        ),
+    }
+}
+
+fn go() -> Detected {
+    Detected {
+        runtime: Runtime::Go,
+        port: 8080,
+        dockerfile: "FROM golang:1.22-alpine AS build\n\
+                     WORKDIR /src\n\
+                     COPY go.mod go.sum* ./\n\
+                     RUN go mod download\n\
+                     COPY . .\n\
+                     RUN CGO_ENABLED=0 go build -o /out/app .\n\
+                     \n\
+                     FROM gcr.io/distroless/static-debian12\n\
+                     COPY --from=build /out/app /app\n\
+                     ENV PORT=8080\n\
+                     EXPOSE 8080\n\
+                     ENTRYPOINT [\"/app\"]\n"
+            .to_string(),
+    }
+}
+
+/// Needs a `[package]` name to know which binary to run; workspaces are not detected
+fn rust(dir: &Path) -> Option<Detected> {
+    let manifest: toml::Value = std::fs::read_to_string(dir.join("Cargo.toml"))
+        .ok()?
+        .parse()
+        .ok()?;
+    let name = manifest.get("package")?.get("name")?.as_str()?;
+
+    Some(Detected {
+        runtime: Runtime::Rust,
+        port: 8080,
+        dockerfile: format!(
+            "FROM rust:1-slim AS build\n\
+             WORKDIR /src\n\
+             COPY . .\n\
+             RUN cargo install --path . --root /out --bin {name}\n\
+             \n\
+             FROM debian:bookworm-slim\n\
+             RUN apt-get update && apt-get install -y --no-install-recommends ca-certificates \\\n    \
+             && rm -rf /var/lib/apt/lists/*\n\
+             COPY --from=build /out/bin/{name} /usr/local/bin/{name}\n\
+             ENV PORT=8080\n\
+             EXPOSE 8080\n\
+             CMD [\"{name}\"]\n"
+        ),
+    })
+}
+
+fn static_site() -> Detected {
+    Detected {
+        runtime: Runtime::Static,
+        port: 80,
+        dockerfile: "FROM nginx:alpine\n\
+                     COPY . /usr/share/nginx/html\n\
+                     EXPOSE 80\n"
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A checkout holding `files`, as (name, content) pairs
+    fn checkout(files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dockyard-detect-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        dir
+    }
+
+    /// Files of a checkout and the runtime and port detected from them
+    type Case<'a> = (&'a [(&'a str, &'a str)], Option<(Runtime, u16)>);
+
+    #[test]
+    fn runtimes_are_detected_from_their_manifests() {
+        let cases: &[Case] = &[
+            (
+                &[("package.json", r#"{"scripts": {"start": "node ."}}"#)],
+                Some((Runtime::Node, 3000)),
+            ),
+            (
+                &[("package.json", r#"{"scripts": {"build": "vite build"}}"#)],
+                Some((Runtime::Static, 80)),
+            ),
+            (
+                &[("Cargo.toml", "[package]\nname = \"api\"\n")],
+                Some((Runtime::Rust, 8080)),
+            ),
+            (&[("Cargo.toml", "[workspace]\nmembers = []\n")], None),
+            (
+                &[("requirements.txt", "flask\n")],
+                Some((Runtime::Python, 8000)),
+            ),
+            (
+                &[("pyproject.toml", "[project]\nname = \"api\"\n")],
+                Some((Runtime::Python, 8000)),
+            ),
+            (&[("go.mod", "module api\n")], Some((Runtime::Go, 8080))),
+            (
+                &[("index.html", "<html></html>")],
+                Some((Runtime::Static, 80)),
+            ),
+            (&[("README.md", "# api")], None),
+            (&[], None),
+        ];
+
+        for (files, expected) in cases {
+            let dir = checkout(files);
+            let detected = detect(&dir).map(|d| (d.runtime, d.port));
+            assert_eq!(detected, *expected, "files: {:?}", files);
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn generated_dockerfiles_follow_the_manifest() {
+        let dir = checkout(&[("Cargo.toml", "[package]\nname = \"api\"\n")]);
+        let detected = detect(&dir).unwrap();
+        assert!(detected.dockerfile.contains("--bin api"));
+        assert_eq!(exposed_port(&detected.dockerfile), Some(detected.port));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let dir = checkout(&[
+            ("requirements.txt", "uvicorn\n"),
+            ("server.py", "app = None\n"),
+        ]);
+        let detected = detect(&dir).unwrap();
+        assert!(detected.dockerfile.contains(r#""uvicorn", "server:app""#));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn exposed_port_reads_the_first_expose() {
+        assert_eq!(
+            exposed_port("FROM x\nexpose 8080/tcp\nEXPOSE 9000\n"),
+            Some(8080)
+        );
+        assert_eq!(exposed_port("FROM x\n"), None);
+    }
+}
@@ -0,0 +1,257 @@
+use std::path::Path;
+
+/// Parsed `.dockerignore` rules, matched the way Docker matches them: patterns are
+/// relative to the context root, `**` spans directories, a pattern that matches a
+/// directory excludes everything below it, `!` re-includes, and the last match wins.
+#[derive(Debug, Default)]
+pub struct DockerIgnore {
+    patterns: Vec<Pattern>,
+}
+
+#[derive(Debug)]
+struct Pattern {
+    segments: Vec<String>,
+    exception: bool,
+}
+
+impl DockerIgnore {
+    /// Load rules from a file; a missing file means nothing is ignored
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let patterns = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let (exception, pattern) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest.trim()),
+                    None => (false, line),
+                };
+                let segments = clean(pattern);
+                if segments.is_empty() {
+                    return None;
+                }
+                Some(Pattern {
+                    segments,
+                    exception,
+                })
+            })
+            .collect();
+        Self { patterns }
+    }
+
+    /// Whether any `!` rule exists, in which case excluded directories must still be walked
+    pub fn has_exceptions(&self) -> bool {
+        self.patterns.iter().any(|p| p.exception)
+    }
+
+    /// Whether a `/`-separated path relative to the context root is excluded
+    pub fn is_excluded(&self, path: &str) -> bool {
+        let path: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let mut excluded = false;
+        for pattern in &self.patterns {
+            // A match on any parent directory applies to the path as well
+            let matched =
+                (1..=path.len()).any(|len| match_segments(&pattern.segments, &path[..len]));
+            if matched {
+                excluded = !pattern.exception;
+            }
+        }
+        excluded
+    }
+}
+
+/// Split a pattern into path segments, resolving `.` and `..` like `filepath.Clean`
+fn clean(pattern: &str) -> Vec<String> {
+    let mut segments: Vec<String> = Vec::new();
+    for segment in pattern.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            s => segments.push(s.to_string()),
+        }
+    }
+    segments
+}
+
+fn match_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                let pattern: Vec<char> = first.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                match_glob(&pattern, &name) && match_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Match one path segment against `*`, `?`, `[...]` and `\` escapes
+fn match_glob(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| match_glob(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && match_glob(rest, &name[1..]),
+        Some(('[', rest)) => {
+            let Some((&c, name_rest)) = name.split_first() else {
+                return false;
+            };
+            match match_class(rest, c) {
+                Some((true, after)) => match_glob(after, name_rest),
+                _ => false,
+            }
+        }
+        Some(('\\', rest)) if !rest.is_empty() => {
+            name.first() == Some(&rest[0]) && match_glob(&rest[1..], &name[1..])
+        }
+        Some((&p, rest)) => name.first() == Some(&p) && match_glob(rest, &name[1..]),
+    }
+}
+
+/// Match `c` against a character class body (after `[`); returns whether it matched
+/// and the pattern remaining after the closing `]`, or `None` if the class is unclosed
+fn match_class(class: &[char], c: char) -> Option<(bool, &[char])> {
+    let (negated, mut rest) = match class.first() {
+        Some('^') | Some('!') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let (&lo, after) = rest.split_first()?;
+        if lo == ']' && !first {
+            rest = after;
+            break;
+        }
+        first = false;
+
+        let (lo, after) = if lo == '\\' {
+            let (&escaped, after) = after.split_first()?;
+            (escaped, after)
+        } else {
+            (lo, after)
+        };
+
+        if after.len() >= 2 && after[0] == '-' && after[1] != ']' {
+            let hi = after[1];
+            if lo <= c && c <= hi {
+                matched = true;
+            }
+            rest = &after[2..];
+        } else {
+            if lo == c {
+                matched = true;
+            }
+            rest = after;
+        }
+    }
+
+    Some((matched != negated, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excluded(rules: &str, path: &str) -> bool {
+        DockerIgnore::parse(rules).is_excluded(path)
+    }
+
+    #[test]
+    fn double_star_spans_directories() {
+        let rules = "**/*.log\n**/node_modules";
+        assert!(excluded(rules, "debug.log"));
+        assert!(excluded(rules, "var/cache/debug.log"));
+        assert!(!excluded(rules, "debug.log.txt"));
+        assert!(excluded(rules, "node_modules"));
+        assert!(excluded(rules, "web/app/node_modules/react/index.js"));
+        assert!(excluded("docs/**/*.png", "docs/a.png"));
+        assert!(excluded("docs/**/*.png", "docs/img/2024/a.png"));
+        assert!(!excluded("docs/**/*.png", "img/a.png"));
+    }
+
+    #[test]
+    fn single_star_stays_within_a_segment() {
+        assert!(excluded("*.md", "README.md"));
+        assert!(!excluded("*.md", "docs/README.md"));
+        assert!(excluded("docs/*/draft", "docs/2024/draft/a.md"));
+        assert!(!excluded("docs/*/draft", "docs/2024/05/draft"));
+    }
+
+    #[test]
+    fn exceptions_re_include() {
+        let rules = "*.md\n!README.md";
+        assert!(excluded(rules, "CHANGELOG.md"));
+        assert!(!excluded(rules, "README.md"));
+
+        let ignore = DockerIgnore::parse("docs\n!docs/keep.txt");
+        assert!(ignore.has_exceptions());
+        assert!(ignore.is_excluded("docs"));
+        assert!(ignore.is_excluded("docs/other.txt"));
+        assert!(!ignore.is_excluded("docs/keep.txt"));
+        assert!(!DockerIgnore::parse("docs").has_exceptions());
+    }
+
+    #[test]
+    fn character_classes() {
+        assert!(excluded("file[0-9].txt", "file7.txt"));
+        assert!(!excluded("file[0-9].txt", "filea.txt"));
+        assert!(excluded("[abc].txt", "b.txt"));
+        assert!(!excluded("[abc].txt", "d.txt"));
+        assert!(excluded("[!a]*.tmp", "b1.tmp"));
+        assert!(!excluded("[!a]*.tmp", "a1.tmp"));
+        assert!(excluded("[^a]*.tmp", "b1.tmp"));
+        assert!(excluded("[]]x", "]x"));
+        assert!(excluded("[\\-]x", "-x"));
+        // An unclosed class matches nothing
+        assert!(!excluded("[abc", "a"));
+    }
+
+    #[test]
+    fn escapes_and_single_characters() {
+        assert!(excluded("\\*.txt", "*.txt"));
+        assert!(!excluded("\\*.txt", "a.txt"));
+        assert!(excluded("?.txt", "a.txt"));
+        assert!(!excluded("?.txt", "ab.txt"));
+    }
+
+    #[test]
+    fn leading_slash_is_the_context_root() {
+        let rules = "/build";
+        assert!(excluded(rules, "build"));
+        assert!(excluded(rules, "build/out/app"));
+        assert!(!excluded(rules, "src/build"));
+        assert!(excluded("./build/../dist", "dist/app.js"));
+    }
+
+    #[test]
+    fn last_match_wins() {
+        assert!(excluded("!keep.txt\n*.txt", "keep.txt"));
+        assert!(!excluded("*.txt\n!keep.txt", "keep.txt"));
+        assert!(excluded("*.txt\n!keep.txt\nkeep.txt", "keep.txt"));
+        // A later rule that does not match leaves the earlier outcome alone
+        assert!(!excluded("*.txt\n!keep.txt\nother.txt", "keep.txt"));
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let ignore = DockerIgnore::parse("# build output\n\n  target  \n#!target");
+        assert!(ignore.is_excluded("target/debug"));
+        assert!(!ignore.has_exceptions());
+    }
+}
@@ -1,5 +1,7 @@
 pub mod build;
 pub mod client;
+pub mod detect;
+pub mod ignore;
 pub mod network;
 
 pub use client::DockerClient;
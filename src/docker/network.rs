@@ -1,27 +1,66 @@
 use bollard::network::CreateNetworkOptions;
+use std::collections::HashMap;
 
+use crate::docker::client::PROJECT_LABEL;
 use crate::docker::DockerClient;
 use crate::error::DockyardError;
 
 pub const DOCKYARD_NETWORK: &str = "dockyard-network";
 
+/// Private network of an internal project, joined only by the projects linking to it
+pub fn project_network(slug: &str) -> String {
+    format!("dockyard-{}-internal", slug)
+}
+
 /// Ensure the dockyard Docker network exists
 pub async fn ensure_network(docker: &DockerClient) -> Result<(), DockyardError> {
+    create_if_missing(docker, DOCKYARD_NETWORK, HashMap::new()).await
+}
+
+/// Ensure the private network of an internal project exists
+pub async fn ensure_project_network(docker: &DockerClient, slug: &str) -> Result<(), DockyardError> {
+    let labels = HashMap::from([(PROJECT_LABEL, slug)]);
+    create_if_missing(docker, &project_network(slug), labels).await
+}
+
+async fn create_if_missing(
+    docker: &DockerClient,
+    name: &str,
+    labels: HashMap<&str, &str>,
+) -> Result<(), DockyardError> {
     let networks = docker.inner().list_networks::<String>(None).await?;
 
-    let exists = networks
-        .iter()
-        .any(|n| n.name.as_deref() == Some(DOCKYARD_NETWORK));
+    let exists = networks.iter().any(|n| n.name.as_deref() == Some(name));
 
     if !exists {
         let config = CreateNetworkOptions {
-            name: DOCKYARD_NETWORK,
+            name,
             driver: "bridge",
+            labels,
             ..Default::default()
         };
         docker.inner().create_network(config).await?;
-        tracing::info!("Created Docker network: {}", DOCKYARD_NETWORK);
+        tracing::info!("Created Docker network: {}", name);
     }
 
     Ok(())
 }
+
+/// Remove an internal project's network, disconnecting whatever is still attached
+pub async fn remove_project_network(docker: &DockerClient, slug: &str) -> Result<(), DockyardError> {
+    let name = project_network(slug);
+    let network = match docker.inner().inspect_network::<String>(&name, None).await {
+        Ok(network) => network,
+        Err(bollard::errors::Error::DockerResponseServerError {
+            status_code: 404, ..
+        }) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    for container in network.containers.unwrap_or_default().keys() {
+        docker.disconnect_network(&name, container).await?;
+    }
+    docker.inner().remove_network(&name).await?;
+    tracing::info!("Removed Docker network: {}", name);
+    Ok(())
+}
@@ -14,12 +14,42 @@ pub enum DockyardError {
     #[error("Project '{0}' already exists")]
     ProjectAlreadyExists(String),
 
+    #[error("Add-on '{0}' not found")]
+    AddonNotFound(String),
+
+    #[error("Add-on error: {0}")]
+    Addon(String),
+
     #[error("IPC error: {0}")]
     Ipc(String),
 
     #[error("Daemon not running. Start with: sudo dockyard daemon")]
     DaemonNotRunning,
 
+    #[error("Release v{1} not found for project '{0}'")]
+    ReleaseNotFound(String, u32),
+
+    #[error("Build #{1} not found for project '{0}'")]
+    BuildNotFound(String, u32),
+
+    #[error("Backup #{1} not found for project '{0}'")]
+    BackupNotFound(String, u32),
+
+    #[error("Backup error: {0}")]
+    Backup(String),
+
+    #[error("Cron job '{1}' not found for project '{0}'")]
+    CronJobNotFound(String, String),
+
+    #[error("Cron run #{1} not found for project '{0}'")]
+    CronRunNotFound(String, u32),
+
+    #[error("Cron error: {0}")]
+    Cron(String),
+
+    #[error("Rollback error: {0}")]
+    Rollback(String),
+
     #[error("Build failed: {0}")]
     BuildFailed(String),
 
@@ -32,6 +62,9 @@ pub enum DockyardError {
     #[error("Tunnel error: {0}")]
     Tunnel(String),
 
+    #[error("Secret store error: {0}")]
+    Secrets(String),
+
     #[error("Webhook error: {0}")]
     Webhook(String),
 
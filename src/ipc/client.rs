@@ -1,14 +1,22 @@
 use http_body_util::{BodyExt, Full};
 use hyper::body::Bytes;
-use hyper::{Method, Request};
+use hyper::body::Incoming;
+use hyper::upgrade::Upgraded;
+use hyper::{header, Method, Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use tokio::net::UnixStream;
 
+use crate::config::backup::Backup;
+use crate::config::cron::CronRun;
 use crate::config::paths;
+use crate::config::project::ResourceLimits;
 use crate::error::DockyardError;
 use crate::ipc::protocol::*;
+use crate::ipc::session::UPGRADE_PROTOCOL;
+use crate::models::build::BuildSummary;
+use crate::models::log::LogEntry;
 use crate::models::project::ProjectStatus;
 
 pub struct IpcClient {
@@ -22,12 +30,12 @@ impl IpcClient {
         }
     }
 
-    async fn request<T: DeserializeOwned>(
+    async fn send(
         &self,
         method: Method,
         path: &str,
         body: Option<&impl Serialize>,
-    ) -> Result<T, DockyardError> {
+    ) -> Result<Response<Incoming>, DockyardError> {
         let stream = UnixStream::connect(&self.socket_path)
             .await
             .map_err(|_| DockyardError::DaemonNotRunning)?;
@@ -57,10 +65,19 @@ impl IpcClient {
             .body(body_bytes)
             .map_err(|e| DockyardError::Ipc(e.to_string()))?;
 
-        let response = sender
+        sender
             .send_request(req)
             .await
-            .map_err(|e| DockyardError::Ipc(e.to_string()))?;
+            .map_err(|e| DockyardError::Ipc(e.to_string()))
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&impl Serialize>,
+    ) -> Result<T, DockyardError> {
+        let response = self.send(method, path, body).await?;
 
         let status = response.status();
         let body_bytes = response
@@ -71,15 +88,93 @@ impl IpcClient {
             .to_bytes();
 
         if !status.is_success() {
-            if let Ok(err) = serde_json::from_slice::<ErrorResponse>(&body_bytes) {
-                return Err(DockyardError::Ipc(err.error));
-            }
-            return Err(DockyardError::Ipc(format!("HTTP {}", status)));
+            return Err(error_from_body(status, &body_bytes));
         }
 
         serde_json::from_slice(&body_bytes).map_err(|e| DockyardError::Ipc(e.to_string()))
     }
 
+    /// Issue a GET and hand each body chunk to `on_chunk` as it arrives
+    async fn stream(
+        &self,
+        path: &str,
+        mut on_chunk: impl FnMut(&[u8]),
+    ) -> Result<(), DockyardError> {
+        let response = self.send(Method::GET, path, None::<&()>).await?;
+
+        let status = response.status();
+        let mut body = response.into_body();
+
+        if !status.is_success() {
+            let body_bytes = body
+                .collect()
+                .await
+                .map_err(|e| DockyardError::Ipc(e.to_string()))?
+                .to_bytes();
+            return Err(error_from_body(status, &body_bytes));
+        }
+
+        while let Some(frame) = body.frame().await {
+            let frame = frame.map_err(|e| DockyardError::Ipc(e.to_string()))?;
+            if let Some(chunk) = frame.data_ref() {
+                on_chunk(chunk);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// POST `body` and switch the connection to session frames
+    async fn upgrade(
+        &self,
+        path: &str,
+        body: &impl Serialize,
+    ) -> Result<TokioIo<Upgraded>, DockyardError> {
+        let stream = UnixStream::connect(&self.socket_path)
+            .await
+            .map_err(|_| DockyardError::DaemonNotRunning)?;
+
+        let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(|e| DockyardError::Ipc(e.to_string()))?;
+
+        tokio::spawn(async move {
+            if let Err(e) = conn.with_upgrades().await {
+                tracing::error!("IPC connection error: {}", e);
+            }
+        });
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(format!("http://localhost{}", path))
+            .header("content-type", "application/json")
+            .header(header::CONNECTION, "upgrade")
+            .header(header::UPGRADE, UPGRADE_PROTOCOL)
+            .body(Full::new(Bytes::from(serde_json::to_vec(body)?)))
+            .map_err(|e| DockyardError::Ipc(e.to_string()))?;
+
+        let response = sender
+            .send_request(req)
+            .await
+            .map_err(|e| DockyardError::Ipc(e.to_string()))?;
+
+        let status = response.status();
+        if status != StatusCode::SWITCHING_PROTOCOLS {
+            let body_bytes = response
+                .into_body()
+                .collect()
+                .await
+                .map_err(|e| DockyardError::Ipc(e.to_string()))?
+                .to_bytes();
+            return Err(error_from_body(status, &body_bytes));
+        }
+
+        let upgraded = hyper::upgrade::on(response)
+            .await
+            .map_err(|e| DockyardError::Ipc(e.to_string()))?;
+        Ok(TokioIo::new(upgraded))
+    }
+
     pub async fn health(&self) -> Result<HealthResponse, DockyardError> {
         self.request::<HealthResponse>(Method::GET, "/api/health", None::<&()>)
             .await
@@ -105,11 +200,15 @@ impl IpcClient {
         self.request(Method::POST, "/api/projects", Some(req)).await
     }
 
-    pub async fn rebuild(&self, slug: &str) -> Result<SuccessResponse, DockyardError> {
+    pub async fn rebuild(
+        &self,
+        slug: &str,
+        req: &RebuildRequest,
+    ) -> Result<JobResponse, DockyardError> {
         self.request(
             Method::POST,
             &format!("/api/projects/{}/rebuild", slug),
-            None::<&()>,
+            Some(req),
         )
         .await
     }
@@ -132,24 +231,516 @@ impl IpcClient {
         .await
     }
 
-    pub async fn delete_project(&self, slug: &str) -> Result<SuccessResponse, DockyardError> {
+    pub async fn delete_project(
+        &self,
+        slug: &str,
+        purge_volumes: bool,
+    ) -> Result<SuccessResponse, DockyardError> {
         self.request(
             Method::DELETE,
-            &format!("/api/projects/{}", slug),
+            &format!("/api/projects/{}?purge_volumes={}", slug, purge_volumes),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn get_logs(
+        &self,
+        slug: &str,
+        source: &LogSource,
+        tail: u32,
+        since: Option<i64>,
+        until: Option<i64>,
+    ) -> Result<LogsResponse, DockyardError> {
+        self.request(
+            Method::GET,
+            &format!(
+                "/api/projects/{}/logs?{}",
+                slug,
+                logs_query(source, Some(tail), since, until)
+            ),
+            None::<&()>,
+        )
+        .await
+    }
+
+    /// Stream container logs, calling `on_entry` for every line as it arrives
+    pub async fn stream_logs(
+        &self,
+        slug: &str,
+        source: &LogSource,
+        tail: Option<u32>,
+        since: Option<i64>,
+        until: Option<i64>,
+        mut on_entry: impl FnMut(LogEntry),
+    ) -> Result<(), DockyardError> {
+        let mut pending = Vec::new();
+        self.stream(
+            &format!(
+                "/api/projects/{}/logs/stream?follow=true&{}",
+                slug,
+                logs_query(source, tail, since, until)
+            ),
+            |chunk| {
+                pending.extend_from_slice(chunk);
+                while let Some(pos) = pending.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = pending.drain(..=pos).collect();
+                    match serde_json::from_slice::<LogEntry>(&line) {
+                        Ok(entry) => on_entry(entry),
+                        Err(e) => tracing::warn!("Skipping malformed log line: {}", e),
+                    }
+                }
+            },
+        )
+        .await
+    }
+
+    /// Run a command in the project's live container
+    pub async fn exec_session(
+        &self,
+        slug: &str,
+        req: &SessionRequest,
+    ) -> Result<TokioIo<Upgraded>, DockyardError> {
+        self.upgrade(&format!("/api/projects/{}/exec", slug), req)
+            .await
+    }
+
+    /// Run a command in a one-off container from the project's current image
+    pub async fn run_session(
+        &self,
+        slug: &str,
+        req: &SessionRequest,
+    ) -> Result<TokioIo<Upgraded>, DockyardError> {
+        self.upgrade(&format!("/api/projects/{}/run", slug), req)
+            .await
+    }
+
+    pub async fn list_builds(&self, slug: &str) -> Result<Vec<BuildSummary>, DockyardError> {
+        let resp: BuildListResponse = self
+            .request(
+                Method::GET,
+                &format!("/api/projects/{}/builds", slug),
+                None::<&()>,
+            )
+            .await?;
+        Ok(resp.builds)
+    }
+
+    pub async fn get_build(&self, slug: &str, build: &str) -> Result<BuildSummary, DockyardError> {
+        self.request(
+            Method::GET,
+            &format!("/api/projects/{}/builds/{}", slug, build),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn stream_build_log(
+        &self,
+        slug: &str,
+        build: &str,
+        follow: bool,
+        on_chunk: impl FnMut(&[u8]),
+    ) -> Result<(), DockyardError> {
+        self.stream(
+            &format!(
+                "/api/projects/{}/builds/{}/log?follow={}",
+                slug, build, follow
+            ),
+            on_chunk,
+        )
+        .await
+    }
+
+    pub async fn list_releases(&self, slug: &str) -> Result<ReleaseListResponse, DockyardError> {
+        self.request(
+            Method::GET,
+            &format!("/api/projects/{}/releases", slug),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn get_dockerfile(&self, slug: &str) -> Result<DockerfileResponse, DockyardError> {
+        self.request(
+            Method::GET,
+            &format!("/api/projects/{}/dockerfile", slug),
             None::<&()>,
         )
         .await
     }
 
-    pub async fn get_logs(&self, slug: &str, tail: u32) -> Result<LogsResponse, DockyardError> {
+    pub async fn set_dockerfile(
+        &self,
+        slug: &str,
+        content: Option<String>,
+    ) -> Result<SuccessResponse, DockyardError> {
+        self.request(
+            Method::PUT,
+            &format!("/api/projects/{}/dockerfile", slug),
+            Some(&DockerfileUpdateRequest { content }),
+        )
+        .await
+    }
+
+    pub async fn get_build_config(&self, slug: &str) -> Result<BuildConfigResponse, DockyardError> {
         self.request(
             Method::GET,
-            &format!("/api/projects/{}/logs?tail={}", slug, tail),
+            &format!("/api/projects/{}/build-config", slug),
             None::<&()>,
         )
         .await
     }
 
+    pub async fn update_build_config(
+        &self,
+        slug: &str,
+        req: &BuildConfigUpdateRequest,
+    ) -> Result<BuildConfigResponse, DockyardError> {
+        self.request(
+            Method::PUT,
+            &format!("/api/projects/{}/build-config", slug),
+            Some(req),
+        )
+        .await
+    }
+
+    pub async fn get_env(&self, slug: &str) -> Result<EnvResponse, DockyardError> {
+        self.request(
+            Method::GET,
+            &format!("/api/projects/{}/env", slug),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn update_env(
+        &self,
+        slug: &str,
+        req: &EnvUpdateRequest,
+    ) -> Result<EnvUpdateResponse, DockyardError> {
+        self.request(
+            Method::PUT,
+            &format!("/api/projects/{}/env", slug),
+            Some(req),
+        )
+        .await
+    }
+
+    pub async fn scale(&self, slug: &str, replicas: u32) -> Result<ScaleResponse, DockyardError> {
+        self.request(
+            Method::POST,
+            &format!("/api/projects/{}/scale", slug),
+            Some(&ScaleRequest { replicas }),
+        )
+        .await
+    }
+
+    pub async fn set_process(
+        &self,
+        slug: &str,
+        name: &str,
+        req: &ProcessUpdateRequest,
+    ) -> Result<ProcessUpdateResponse, DockyardError> {
+        self.request(
+            Method::PUT,
+            &format!("/api/projects/{}/processes/{}", slug, name),
+            Some(req),
+        )
+        .await
+    }
+
+    pub async fn remove_process(
+        &self,
+        slug: &str,
+        name: &str,
+    ) -> Result<SuccessResponse, DockyardError> {
+        self.request(
+            Method::DELETE,
+            &format!("/api/projects/{}/processes/{}", slug, name),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn get_resources(&self, slug: &str) -> Result<ResourceLimits, DockyardError> {
+        self.request(
+            Method::GET,
+            &format!("/api/projects/{}/resources", slug),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn update_resources(
+        &self,
+        slug: &str,
+        limits: &ResourceLimits,
+    ) -> Result<ResourcesUpdateResponse, DockyardError> {
+        self.request(
+            Method::PUT,
+            &format!("/api/projects/{}/resources", slug),
+            Some(limits),
+        )
+        .await
+    }
+
+    pub async fn get_links(&self, slug: &str) -> Result<LinksResponse, DockyardError> {
+        self.request(
+            Method::GET,
+            &format!("/api/projects/{}/links", slug),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn get_webhook_secret(
+        &self,
+        slug: &str,
+    ) -> Result<WebhookSecretResponse, DockyardError> {
+        self.request(
+            Method::GET,
+            &format!("/api/projects/{}/webhook-secret", slug),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn rotate_webhook_secret(
+        &self,
+        slug: &str,
+    ) -> Result<WebhookSecretResponse, DockyardError> {
+        self.request(
+            Method::POST,
+            &format!("/api/projects/{}/webhook-secret/rotate", slug),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn update_settings(
+        &self,
+        slug: &str,
+        req: &ProjectSettingsRequest,
+    ) -> Result<SuccessResponse, DockyardError> {
+        self.request(
+            Method::PUT,
+            &format!("/api/projects/{}/settings", slug),
+            Some(req),
+        )
+        .await
+    }
+
+    pub async fn update_links(
+        &self,
+        slug: &str,
+        req: &LinksUpdateRequest,
+    ) -> Result<SuccessResponse, DockyardError> {
+        self.request(
+            Method::PUT,
+            &format!("/api/projects/{}/links", slug),
+            Some(req),
+        )
+        .await
+    }
+
+    pub async fn list_backups(&self, slug: &str) -> Result<BackupListResponse, DockyardError> {
+        self.request(
+            Method::GET,
+            &format!("/api/projects/{}/backups", slug),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn run_backup(&self, slug: &str) -> Result<Backup, DockyardError> {
+        self.request(
+            Method::POST,
+            &format!("/api/projects/{}/backups", slug),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn restore_backup(&self, slug: &str, id: u32) -> Result<SuccessResponse, DockyardError> {
+        self.request(
+            Method::POST,
+            &format!("/api/projects/{}/backups/{}/restore", slug, id),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn list_cron_jobs(&self, slug: &str) -> Result<CronListResponse, DockyardError> {
+        self.request(
+            Method::GET,
+            &format!("/api/projects/{}/cron", slug),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn set_cron_job(
+        &self,
+        slug: &str,
+        name: &str,
+        req: &CronJobRequest,
+    ) -> Result<SuccessResponse, DockyardError> {
+        self.request(
+            Method::PUT,
+            &format!("/api/projects/{}/cron/jobs/{}", slug, name),
+            Some(req),
+        )
+        .await
+    }
+
+    pub async fn remove_cron_job(
+        &self,
+        slug: &str,
+        name: &str,
+    ) -> Result<SuccessResponse, DockyardError> {
+        self.request(
+            Method::DELETE,
+            &format!("/api/projects/{}/cron/jobs/{}", slug, name),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn run_cron_job(&self, slug: &str, name: &str) -> Result<CronRun, DockyardError> {
+        self.request(
+            Method::POST,
+            &format!("/api/projects/{}/cron/jobs/{}/run", slug, name),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn cron_history(
+        &self,
+        slug: &str,
+        job: Option<&str>,
+    ) -> Result<CronHistoryResponse, DockyardError> {
+        let query = job.map(|job| format!("?job={}", job)).unwrap_or_default();
+        self.request(
+            Method::GET,
+            &format!("/api/projects/{}/cron/runs{}", slug, query),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn get_cron_run(
+        &self,
+        slug: &str,
+        id: u32,
+    ) -> Result<CronRunResponse, DockyardError> {
+        self.request(
+            Method::GET,
+            &format!("/api/projects/{}/cron/runs/{}", slug, id),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn set_backup_schedule(
+        &self,
+        slug: &str,
+        req: &BackupScheduleRequest,
+    ) -> Result<SuccessResponse, DockyardError> {
+        self.request(
+            Method::PUT,
+            &format!("/api/projects/{}/backups/schedule", slug),
+            Some(req),
+        )
+        .await
+    }
+
+    pub async fn list_addons(&self) -> Result<Vec<AddonInfo>, DockyardError> {
+        self.request(Method::GET, "/api/addons", None::<&()>).await
+    }
+
+    pub async fn create_addon(
+        &self,
+        req: &AddonCreateRequest,
+    ) -> Result<AddonResponse, DockyardError> {
+        self.request(Method::POST, "/api/addons", Some(req)).await
+    }
+
+    pub async fn attach_addon(
+        &self,
+        name: &str,
+        req: &AddonAttachRequest,
+    ) -> Result<AddonResponse, DockyardError> {
+        self.request(
+            Method::POST,
+            &format!("/api/addons/{}/attach", name),
+            Some(req),
+        )
+        .await
+    }
+
+    pub async fn detach_addon(&self, name: &str) -> Result<AddonResponse, DockyardError> {
+        self.request(
+            Method::POST,
+            &format!("/api/addons/{}/detach", name),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn destroy_addon(
+        &self,
+        name: &str,
+        keep_data: bool,
+    ) -> Result<SuccessResponse, DockyardError> {
+        self.request(
+            Method::DELETE,
+            &format!("/api/addons/{}?keep_data={}", name, keep_data),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn list_volumes(&self) -> Result<Vec<VolumesResponse>, DockyardError> {
+        self.request(Method::GET, "/api/volumes", None::<&()>).await
+    }
+
+    pub async fn get_volumes(&self, slug: &str) -> Result<VolumesResponse, DockyardError> {
+        self.request(
+            Method::GET,
+            &format!("/api/projects/{}/volumes", slug),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn update_volumes(
+        &self,
+        slug: &str,
+        req: &VolumesUpdateRequest,
+    ) -> Result<SuccessResponse, DockyardError> {
+        self.request(
+            Method::PUT,
+            &format!("/api/projects/{}/volumes", slug),
+            Some(req),
+        )
+        .await
+    }
+
+    pub async fn rollback(
+        &self,
+        slug: &str,
+        release: Option<u32>,
+    ) -> Result<JobResponse, DockyardError> {
+        self.request(
+            Method::POST,
+            &format!("/api/projects/{}/releases/rollback", slug),
+            Some(&RollbackRequest { release }),
+        )
+        .await
+    }
+
     pub async fn get_config(&self) -> Result<ConfigResponse, DockyardError> {
         self.request(Method::GET, "/api/config", None::<&()>).await
     }
@@ -160,4 +751,41 @@ impl IpcClient {
     ) -> Result<SuccessResponse, DockyardError> {
         self.request(Method::PUT, "/api/config", Some(req)).await
     }
+
+    pub async fn rotate_master_key(&self) -> Result<SuccessResponse, DockyardError> {
+        self.request(Method::POST, "/api/config/rotate-key", None::<&()>)
+            .await
+    }
+}
+
+fn logs_query(
+    source: &LogSource,
+    tail: Option<u32>,
+    since: Option<i64>,
+    until: Option<i64>,
+) -> String {
+    let mut params = Vec::new();
+    if let Some(process) = &source.process {
+        params.push(format!("process={}", process));
+    }
+    if let Some(replica) = source.replica {
+        params.push(format!("replica={}", replica));
+    }
+    if let Some(tail) = tail {
+        params.push(format!("tail={}", tail));
+    }
+    if let Some(since) = since {
+        params.push(format!("since={}", since));
+    }
+    if let Some(until) = until {
+        params.push(format!("until={}", until));
+    }
+    params.join("&")
+}
+
+fn error_from_body(status: StatusCode, body: &[u8]) -> DockyardError {
+    if let Ok(err) = serde_json::from_slice::<ErrorResponse>(body) {
+        return DockyardError::Ipc(err.error);
+    }
+    DockyardError::Ipc(format!("HTTP {}", status))
 }
@@ -1,15 +1,70 @@
-use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
-use axum::response::Json;
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use futures_util::StreamExt;
 use serde::Deserialize;
 use std::sync::Arc;
 
+use crate::config::backup::{Backup, BackupTrigger};
+use crate::config::cron::{CronRun, CronTrigger};
+use crate::config::project::ResourceLimits;
+use crate::daemon::{build_log, session};
+use crate::docker::client::LogFilter;
 use crate::daemon::DaemonState;
 use crate::ipc::protocol::*;
+use crate::ipc::session::UPGRADE_PROTOCOL;
+use crate::models::build::BuildSummary;
 
 #[derive(Debug, Deserialize)]
 pub struct LogsQuery {
     pub tail: Option<u32>,
+    /// Unix timestamp; only return lines written at or after it
+    pub since: Option<i64>,
+    /// Unix timestamp; only return lines written before it
+    pub until: Option<i64>,
+    pub follow: Option<bool>,
+    /// Process type; `web` when unset
+    pub process: Option<String>,
+    /// Replica of the process, from 1
+    pub replica: Option<u32>,
+}
+
+impl LogsQuery {
+    fn source(&self) -> LogSource {
+        LogSource {
+            process: self.process.clone(),
+            replica: self.replica,
+        }
+    }
+
+    fn filter(&self, default_tail: Option<u32>) -> LogFilter {
+        LogFilter {
+            tail: self.tail.or(default_tail),
+            since: self.since,
+            until: self.until,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteQuery {
+    pub purge_volumes: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DestroyAddonQuery {
+    pub keep_data: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CronHistoryQuery {
+    pub job: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BuildLogQuery {
+    pub follow: Option<bool>,
 }
 
 pub async fn health(State(state): State<Arc<DaemonState>>) -> Json<HealthResponse> {
@@ -51,6 +106,36 @@ pub async fn get_project(
     }
 }
 
+pub async fn get_webhook_secret(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<WebhookSecretResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.get_webhook_secret(&slug).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn rotate_webhook_secret(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<WebhookSecretResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.rotate_webhook_secret(&slug).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
 pub async fn deploy_project(
     State(state): State<Arc<DaemonState>>,
     Json(req): Json<DeployRequest>,
@@ -69,10 +154,12 @@ pub async fn deploy_project(
 pub async fn rebuild_project(
     State(state): State<Arc<DaemonState>>,
     Path(slug): Path<String>,
-) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
-    match state.rebuild_project(&slug).await {
-        Ok(()) => Ok(Json(SuccessResponse {
+    Json(req): Json<RebuildRequest>,
+) -> Result<Json<JobResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.rebuild_project(&slug, req).await {
+        Ok(build_id) => Ok(Json(JobResponse {
             message: format!("Rebuild started for '{}'", slug),
+            build_id,
         })),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -120,8 +207,12 @@ pub async fn stop_project(
 pub async fn delete_project(
     State(state): State<Arc<DaemonState>>,
     Path(slug): Path<String>,
+    Query(query): Query<DeleteQuery>,
 ) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
-    match state.delete_project(&slug).await {
+    match state
+        .delete_project(&slug, query.purge_volumes.unwrap_or(false))
+        .await
+    {
         Ok(()) => Ok(Json(SuccessResponse {
             message: format!("Project '{}' deleted", slug),
         })),
@@ -139,8 +230,10 @@ pub async fn get_logs(
     Path(slug): Path<String>,
     Query(query): Query<LogsQuery>,
 ) -> Result<Json<LogsResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let tail = query.tail.unwrap_or(100);
-    match state.get_project_logs(&slug, tail).await {
+    match state
+        .get_project_logs(&slug, &query.source(), &query.filter(Some(100)))
+        .await
+    {
         Ok(logs) => Ok(Json(LogsResponse { logs })),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -151,6 +244,132 @@ pub async fn get_logs(
     }
 }
 
+pub async fn list_releases(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<ReleaseListResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.list_releases(&slug).await {
+        Ok(releases) => Ok(Json(releases)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn rollback_project(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    Json(req): Json<RollbackRequest>,
+) -> Result<Json<JobResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.rollback_project(&slug, req.release).await {
+        Ok((release, build_id)) => Ok(Json(JobResponse {
+            message: format!("Rollback to v{} started for '{}'", release, slug),
+            build_id,
+        })),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// Stream container logs as newline-delimited JSON `LogEntry` objects
+pub async fn stream_logs(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    Query(query): Query<LogsQuery>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let follow = query.follow.unwrap_or(false);
+    match state
+        .stream_project_logs(&slug, &query.source(), &query.filter(None), follow)
+        .await
+    {
+        Ok(stream) => {
+            let body = stream.map(|entry| {
+                let entry = entry.map_err(|e| std::io::Error::other(e.to_string()))?;
+                let mut line = serde_json::to_vec(&entry)?;
+                line.push(b'\n');
+                Ok::<_, std::io::Error>(Bytes::from(line))
+            });
+            Ok((
+                [(header::CONTENT_TYPE, "application/x-ndjson")],
+                Body::from_stream(body),
+            )
+                .into_response())
+        }
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn list_builds(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<BuildListResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.list_builds(&slug).await {
+        Ok(builds) => Ok(Json(BuildListResponse { builds })),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn get_build(
+    State(state): State<Arc<DaemonState>>,
+    Path((slug, build)): Path<(String, String)>,
+) -> Result<Json<BuildSummary>, (StatusCode, Json<ErrorResponse>)> {
+    let result = match state.resolve_build(&slug, &build).await {
+        Ok(id) => build_log::summary(&slug, id),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(summary) => Ok(Json(summary)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// Stream a build log as plain text; `?follow=true` keeps it open until the build ends
+pub async fn get_build_log(
+    State(state): State<Arc<DaemonState>>,
+    Path((slug, build)): Path<(String, String)>,
+    Query(query): Query<BuildLogQuery>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let result = match state.resolve_build(&slug, &build).await {
+        Ok(id) => build_log::stream(&slug, id, query.follow.unwrap_or(false)).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(stream) => Ok((
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            Body::from_stream(stream),
+        )
+            .into_response()),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
 pub async fn get_config(
     State(state): State<Arc<DaemonState>>,
 ) -> Result<Json<ConfigResponse>, (StatusCode, Json<ErrorResponse>)> {
@@ -181,3 +400,585 @@ pub async fn update_config(
         )),
     }
 }
+
+pub async fn rotate_master_key(
+    State(state): State<Arc<DaemonState>>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.rotate_master_key().await {
+        Ok((key_id, count)) => Ok(Json(SuccessResponse {
+            message: format!(
+                "Master key rotated to {} ({} secret(s) re-encrypted)",
+                key_id, count
+            ),
+        })),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn get_dockerfile(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<DockerfileResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.get_dockerfile(&slug).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn set_dockerfile(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    Json(req): Json<DockerfileUpdateRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let message = if req.content.is_some() {
+        format!("Dockerfile override saved for '{}'", slug)
+    } else {
+        format!("Dockerfile override removed for '{}'", slug)
+    };
+    match state.set_dockerfile(&slug, req.content).await {
+        Ok(()) => Ok(Json(SuccessResponse { message })),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn get_build_config(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<BuildConfigResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.get_build_config(&slug).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn update_build_config(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    Json(req): Json<BuildConfigUpdateRequest>,
+) -> Result<Json<BuildConfigResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.update_build_config(&slug, req).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn get_env(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<EnvResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.get_env(&slug).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn update_env(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    Json(req): Json<EnvUpdateRequest>,
+) -> Result<Json<EnvUpdateResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.update_env(&slug, req).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn scale_project(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    Json(req): Json<ScaleRequest>,
+) -> Result<Json<ScaleResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.scale_project(&slug, req.replicas).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn set_process(
+    State(state): State<Arc<DaemonState>>,
+    Path((slug, name)): Path<(String, String)>,
+    Json(req): Json<ProcessUpdateRequest>,
+) -> Result<Json<ProcessUpdateResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.set_process(&slug, &name, req).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn remove_process(
+    State(state): State<Arc<DaemonState>>,
+    Path((slug, name)): Path<(String, String)>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.remove_process(&slug, &name).await {
+        Ok(message) => Ok(Json(SuccessResponse { message })),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn get_resources(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<ResourceLimits>, (StatusCode, Json<ErrorResponse>)> {
+    match state.get_resources(&slug).await {
+        Ok(limits) => Ok(Json(limits)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn update_resources(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    Json(limits): Json<ResourceLimits>,
+) -> Result<Json<ResourcesUpdateResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.update_resources(&slug, limits).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn list_volumes(
+    State(state): State<Arc<DaemonState>>,
+) -> Result<Json<Vec<VolumesResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.list_volumes().await {
+        Ok(volumes) => Ok(Json(volumes)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn get_volumes(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<VolumesResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.get_volumes(&slug).await {
+        Ok(volumes) => Ok(Json(volumes)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn update_volumes(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    Json(req): Json<VolumesUpdateRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.update_volumes(&slug, req).await {
+        Ok(message) => Ok(Json(SuccessResponse { message })),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn get_links(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<LinksResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.get_links(&slug).await {
+        Ok(links) => Ok(Json(links)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn update_settings(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    Json(req): Json<ProjectSettingsRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.update_settings(&slug, req).await {
+        Ok(message) => Ok(Json(SuccessResponse { message })),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn update_links(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    Json(req): Json<LinksUpdateRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.update_links(&slug, req).await {
+        Ok(message) => Ok(Json(SuccessResponse { message })),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn list_addons(
+    State(state): State<Arc<DaemonState>>,
+) -> Result<Json<Vec<AddonInfo>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.list_addons().await {
+        Ok(addons) => Ok(Json(addons)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn create_addon(
+    State(state): State<Arc<DaemonState>>,
+    Json(req): Json<AddonCreateRequest>,
+) -> Result<Json<AddonResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.create_addon(req).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn attach_addon(
+    State(state): State<Arc<DaemonState>>,
+    Path(name): Path<String>,
+    Json(req): Json<AddonAttachRequest>,
+) -> Result<Json<AddonResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.attach_addon(&name, req).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn detach_addon(
+    State(state): State<Arc<DaemonState>>,
+    Path(name): Path<String>,
+) -> Result<Json<AddonResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.detach_addon(&name).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn destroy_addon(
+    State(state): State<Arc<DaemonState>>,
+    Path(name): Path<String>,
+    Query(query): Query<DestroyAddonQuery>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state
+        .destroy_addon(&name, query.keep_data.unwrap_or(false))
+        .await
+    {
+        Ok(message) => Ok(Json(SuccessResponse { message })),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn list_backups(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<BackupListResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.list_backups(&slug).await {
+        Ok(backups) => Ok(Json(backups)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn run_backup(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<Backup>, (StatusCode, Json<ErrorResponse>)> {
+    match state.backup_project(&slug, BackupTrigger::Manual).await {
+        Ok(backup) => Ok(Json(backup)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn list_cron_jobs(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+) -> Result<Json<CronListResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.list_cron_jobs(&slug).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn set_cron_job(
+    State(state): State<Arc<DaemonState>>,
+    Path((slug, name)): Path<(String, String)>,
+    Json(req): Json<CronJobRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.set_cron_job(&slug, &name, req).await {
+        Ok(message) => Ok(Json(SuccessResponse { message })),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn remove_cron_job(
+    State(state): State<Arc<DaemonState>>,
+    Path((slug, name)): Path<(String, String)>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.remove_cron_job(&slug, &name).await {
+        Ok(message) => Ok(Json(SuccessResponse { message })),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn run_cron_job(
+    State(state): State<Arc<DaemonState>>,
+    Path((slug, name)): Path<(String, String)>,
+) -> Result<Json<CronRun>, (StatusCode, Json<ErrorResponse>)> {
+    match state.run_cron_job(&slug, &name, CronTrigger::Manual).await {
+        Ok(run) => Ok(Json(run)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn cron_history(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    Query(query): Query<CronHistoryQuery>,
+) -> Result<Json<CronHistoryResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.cron_history(&slug, query.job.as_deref()).await {
+        Ok(runs) => Ok(Json(CronHistoryResponse { slug, runs })),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn get_cron_run(
+    State(state): State<Arc<DaemonState>>,
+    Path((slug, id)): Path<(String, u32)>,
+) -> Result<Json<CronRunResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.cron_run(&slug, id).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn restore_backup(
+    State(state): State<Arc<DaemonState>>,
+    Path((slug, id)): Path<(String, u32)>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.restore_backup(&slug, id).await {
+        Ok(message) => Ok(Json(SuccessResponse { message })),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn set_backup_schedule(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    Json(req): Json<BackupScheduleRequest>,
+) -> Result<Json<SuccessResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.set_backup_schedule(&slug, req).await {
+        Ok(message) => Ok(Json(SuccessResponse { message })),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+pub async fn exec_session(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    req: Request,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    upgrade_session(state, slug, req, false).await
+}
+
+pub async fn run_session(
+    State(state): State<Arc<DaemonState>>,
+    Path(slug): Path<String>,
+    req: Request,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    upgrade_session(state, slug, req, true).await
+}
+
+/// Resolve the session, then switch the connection to session frames and run it
+async fn upgrade_session(
+    state: Arc<DaemonState>,
+    slug: String,
+    req: Request,
+    one_off: bool,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }));
+
+    let (mut parts, body) = req.into_parts();
+    let on_upgrade = parts
+        .extensions
+        .remove::<hyper::upgrade::OnUpgrade>()
+        .ok_or_else(|| bad_request(format!("expected an upgrade to {}", UPGRADE_PROTOCOL)))?;
+    let body = axum::body::to_bytes(body, 64 * 1024)
+        .await
+        .map_err(|e| bad_request(e.to_string()))?;
+    let session_req: SessionRequest =
+        serde_json::from_slice(&body).map_err(|e| bad_request(e.to_string()))?;
+
+    let plan = if one_off {
+        state.plan_run(&slug, &session_req).await
+    } else {
+        state.plan_exec(&slug).await
+    }
+    .map_err(|e| bad_request(e.to_string()))?;
+
+    tokio::spawn(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => {
+                let stream = hyper_util::rt::TokioIo::new(upgraded);
+                session::serve(state, plan, session_req, stream).await;
+            }
+            Err(e) => tracing::warn!("[{}] Session upgrade failed: {}", slug, e),
+        }
+    });
+
+    Ok((
+        StatusCode::SWITCHING_PROTOCOLS,
+        [
+            (header::CONNECTION, "upgrade"),
+            (header::UPGRADE, UPGRADE_PROTOCOL),
+        ],
+    )
+        .into_response())
+}
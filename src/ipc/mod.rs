@@ -1,5 +1,6 @@
 pub mod client;
 pub mod handlers;
 pub mod protocol;
+pub mod session;
 
 pub use client::IpcClient;
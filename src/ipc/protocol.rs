@@ -1,8 +1,15 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::config::project::NetworkMode;
-use crate::models::project::ProjectStatus;
+use crate::config::addon::AddonKind;
+use crate::config::backup::Backup;
+use crate::config::cron::CronRun;
+use crate::config::project::{EnvUpdateMode, GitConfig, NetworkMode, ResourceLimits, VolumeMount};
+use crate::config::release::Release;
+use crate::models::build::BuildSummary;
+use crate::models::log::LogEntry;
+use crate::models::project::{ProjectState, ProjectStatus};
 
 // ---- Requests ----
 
@@ -12,9 +19,21 @@ pub struct DeployRequest {
     pub branch: String,
     pub network_mode: NetworkMode,
     pub hostname: Option<String>,
-    pub container_port: u16,
+    /// Port the app listens on; detected from the build when omitted
+    #[serde(default)]
+    pub container_port: Option<u16>,
     #[serde(default)]
     pub env_vars: HashMap<String, String>,
+    #[serde(default)]
+    pub resources: ResourceLimits,
+    #[serde(default)]
+    pub volumes: Vec<VolumeMount>,
+    /// Projects this one may call by slug
+    #[serde(default)]
+    pub links: Vec<String>,
+    /// Containers to run; one when omitted
+    #[serde(default)]
+    pub replicas: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,6 +44,12 @@ pub struct ConfigUpdateRequest {
     pub cloudflare_enabled: Option<bool>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RollbackRequest {
+    /// Release to redeploy; defaults to the one before the current release
+    pub release: Option<u32>,
+}
+
 // ---- Responses ----
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,7 +58,16 @@ pub struct DeployResponse {
     pub name: String,
     pub url: Option<String>,
     pub webhook_url: String,
-    pub host_port: u16,
+    /// None for internal projects
+    pub host_port: Option<u16>,
+    pub build_id: u32,
+}
+
+/// Returned when a request queues a build/deploy job
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobResponse {
+    pub message: String,
+    pub build_id: u32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -46,12 +80,41 @@ pub struct ProjectDetailResponse {
     pub status: ProjectStatus,
     pub repo_url: String,
     pub branch: String,
+    #[serde(default)]
+    pub git: GitConfig,
+    /// Always redacted; see [`WebhookSecretResponse`]
     pub webhook_secret: String,
 }
 
+/// Where GitHub sends push events for a project and the secret that signs them
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookSecretResponse {
+    pub webhook_url: String,
+    pub secret: String,
+}
+
+/// Which container logs are read from: replica `replica` (from 1) of a process type,
+/// the first `web` replica when unset
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LogSource {
+    pub process: Option<String>,
+    pub replica: Option<u32>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LogsResponse {
-    pub logs: Vec<String>,
+    pub logs: Vec<LogEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReleaseListResponse {
+    pub current: Option<u32>,
+    pub releases: Vec<Release>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuildListResponse {
+    pub builds: Vec<BuildSummary>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -71,6 +134,328 @@ pub struct ConfigResponse {
     pub socket_path: String,
 }
 
+/// The Dockerfile the next build of a project would use
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DockerfileResponse {
+    /// "repository", "override" or "generated (<runtime>)"
+    pub source: String,
+    pub path: String,
+    pub container_port: Option<u16>,
+    pub content: String,
+}
+
+/// Store a Dockerfile override, or remove it when `content` is `None`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DockerfileUpdateRequest {
+    pub content: Option<String>,
+}
+
+/// Build options of a project; secret values are never sent back
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuildConfigResponse {
+    pub args: HashMap<String, String>,
+    pub target: Option<String>,
+    pub secrets: Vec<String>,
+    #[serde(default)]
+    pub release_command: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct BuildConfigUpdateRequest {
+    #[serde(default)]
+    pub set_args: HashMap<String, String>,
+    #[serde(default)]
+    pub unset_args: Vec<String>,
+    #[serde(default)]
+    pub set_secrets: HashMap<String, String>,
+    #[serde(default)]
+    pub unset_secrets: Vec<String>,
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub clear_target: bool,
+    #[serde(default)]
+    pub release_command: Option<String>,
+    #[serde(default)]
+    pub clear_release_command: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnvResponse {
+    /// Secret values are redacted
+    pub env_vars: HashMap<String, String>,
+    /// Names of the variables stored encrypted
+    #[serde(default)]
+    pub secrets: Vec<String>,
+    pub update_mode: EnvUpdateMode,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct EnvUpdateRequest {
+    #[serde(default)]
+    pub set: HashMap<String, String>,
+    #[serde(default)]
+    pub unset: Vec<String>,
+    /// Store the values in `set` encrypted and redact them from responses
+    #[serde(default)]
+    pub secret: bool,
+    /// Remove every variable not in `set`
+    #[serde(default)]
+    pub replace: bool,
+    /// Apply with this mode instead of the project's configured one
+    #[serde(default)]
+    pub mode: Option<EnvUpdateMode>,
+    /// Only store the change; the running container keeps its current environment
+    #[serde(default)]
+    pub no_apply: bool,
+    /// Change the mode used for this and future updates
+    #[serde(default)]
+    pub default_mode: Option<EnvUpdateMode>,
+}
+
+/// Result of an env change; `build_id` is set when a container update was queued
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnvUpdateResponse {
+    pub message: String,
+    pub build_id: Option<u32>,
+}
+
+/// Rebuild from a given commit or ref instead of the branch's latest commit
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct RebuildRequest {
+    #[serde(default)]
+    pub commit: Option<String>,
+    #[serde(default)]
+    pub git_ref: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScaleRequest {
+    pub replicas: u32,
+}
+
+/// `build_id` is set when containers are being added or removed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScaleResponse {
+    pub message: String,
+    pub build_id: Option<u32>,
+}
+
+/// Changes to one process type; unset fields are kept
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ProcessUpdateRequest {
+    pub command: Option<String>,
+    /// Fall back to the Procfile's command
+    #[serde(default)]
+    pub clear_command: bool,
+    pub replicas: Option<u32>,
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub clear_port: bool,
+}
+
+/// `build_id` is set when containers are being started
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessUpdateResponse {
+    pub message: String,
+    pub build_id: Option<u32>,
+}
+
+/// Limits after an update; `message` says what reached the running container
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResourcesUpdateResponse {
+    pub message: String,
+    pub limits: ResourceLimits,
+}
+
+/// A declared mount, or a project volume that is no longer mounted
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VolumeInfo {
+    pub mount: Option<VolumeMount>,
+    /// Docker volume name; None for bind mounts
+    pub volume: Option<String>,
+    pub size_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VolumesResponse {
+    pub slug: String,
+    pub volumes: Vec<VolumeInfo>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VolumesUpdateRequest {
+    #[serde(default)]
+    pub add: Vec<VolumeMount>,
+    /// Container paths to unmount; the data is kept
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinkInfo {
+    pub slug: String,
+    /// Address the linking container reaches the project at
+    pub url: String,
+    pub internal: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinksResponse {
+    pub slug: String,
+    pub links: Vec<LinkInfo>,
+    /// Projects that link to this one
+    pub linked_by: Vec<String>,
+}
+
+/// Project settings to change; unset fields are left as they are
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ProjectSettingsRequest {
+    #[serde(default)]
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub submodules: Option<bool>,
+    #[serde(default)]
+    pub lfs: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LinksUpdateRequest {
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddonCreateRequest {
+    pub kind: AddonKind,
+    /// Image tag; the engine's current major version when omitted
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Defaults to `<project>-<kind>`, or the kind alone without a project
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Project to attach the new add-on to
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub env_var: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddonAttachRequest {
+    pub project: String,
+    /// Variable the connection URL is injected as; the kind's default when omitted
+    #[serde(default)]
+    pub env_var: Option<String>,
+}
+
+/// An add-on and the state of its container; credentials are never sent
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddonInfo {
+    pub name: String,
+    pub kind: AddonKind,
+    pub version: String,
+    pub project: Option<String>,
+    pub env_var: String,
+    /// `host:port` on `dockyard-network`
+    pub address: String,
+    pub state: ProjectState,
+}
+
+/// Result of an add-on change; `build_id` is set when the project's container is
+/// being updated with the new variables
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddonResponse {
+    pub message: String,
+    pub project: Option<String>,
+    pub build_id: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupListResponse {
+    pub slug: String,
+    pub schedule: Option<String>,
+    pub next_run: Option<DateTime<Utc>>,
+    pub keep: usize,
+    /// Where new backups go: a local directory or an `s3://` location
+    pub storage: String,
+    /// Newest first
+    pub backups: Vec<Backup>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BackupScheduleRequest {
+    /// Cron expression, 5 fields in UTC
+    #[serde(default)]
+    pub schedule: Option<String>,
+    /// Turn scheduled backups off
+    #[serde(default)]
+    pub clear: bool,
+    #[serde(default)]
+    pub keep: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CronJobInfo {
+    pub name: String,
+    pub schedule: String,
+    pub command: String,
+    pub timeout_secs: u64,
+    pub next_run: Option<DateTime<Utc>>,
+    pub last_run: Option<CronRun>,
+    pub running: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CronListResponse {
+    pub slug: String,
+    pub jobs: Vec<CronJobInfo>,
+}
+
+/// Declares a job or changes it; a new job needs a schedule and a command
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CronJobRequest {
+    /// Cron expression, 5 fields in UTC
+    #[serde(default)]
+    pub schedule: Option<String>,
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CronHistoryResponse {
+    pub slug: String,
+    /// Newest first
+    pub runs: Vec<CronRun>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CronRunResponse {
+    pub run: CronRun,
+    /// Captured stdout and stderr; stderr lines are prefixed with `[stderr]`
+    pub output: String,
+}
+
+/// Command for `dockyard exec` or `dockyard run`; the connection then switches to session
+/// frames
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionRequest {
+    /// A shell when empty
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub tty: bool,
+    /// Initial terminal size
+    #[serde(default)]
+    pub rows: Option<u16>,
+    #[serde(default)]
+    pub cols: Option<u16>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
@@ -0,0 +1,176 @@
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Protocol named in the `Upgrade` header of exec and run requests
+pub const UPGRADE_PROTOCOL: &str = "dockyard-session";
+
+/// Largest payload accepted in a frame
+const MAX_FRAME: usize = 1024 * 1024;
+
+/// Message on an upgraded exec or run connection: a type byte, a big-endian u32 length and
+/// the payload. The CLI sends stdin and resizes; the daemon sends output and the exit code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    Stdin(Vec<u8>),
+    /// The CLI's stdin reached end of file
+    StdinClosed,
+    Resize {
+        rows: u16,
+        cols: u16,
+    },
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+    /// Last frame of a session
+    Exit(i64),
+}
+
+impl Frame {
+    fn kind(&self) -> u8 {
+        match self {
+            Frame::Stdin(_) => 0,
+            Frame::StdinClosed => 1,
+            Frame::Resize { .. } => 2,
+            Frame::Stdout(_) => 3,
+            Frame::Stderr(_) => 4,
+            Frame::Exit(_) => 5,
+        }
+    }
+
+    pub async fn write<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> std::io::Result<()> {
+        let payload = match self {
+            Frame::Stdin(data) | Frame::Stdout(data) | Frame::Stderr(data) => data.clone(),
+            Frame::StdinClosed => Vec::new(),
+            Frame::Resize { rows, cols } => [rows.to_be_bytes(), cols.to_be_bytes()].concat(),
+            Frame::Exit(code) => code.to_be_bytes().to_vec(),
+        };
+        let mut buf = Vec::with_capacity(payload.len() + 5);
+        buf.push(self.kind());
+        buf.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&payload);
+        writer.write_all(&buf).await?;
+        writer.flush().await
+    }
+
+    /// Next frame, or None once the other side has closed the connection
+    pub async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<Option<Frame>> {
+        let mut header = [0u8; 5];
+        match reader.read_exact(&mut header).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        if len > MAX_FRAME {
+            return Err(invalid(format!("frame of {} bytes is too large", len)));
+        }
+        let mut payload = vec![0u8; len];
+        reader.read_exact(&mut payload).await?;
+
+        let frame = match header[0] {
+            0 => Frame::Stdin(payload),
+            1 => Frame::StdinClosed,
+            2 if len == 4 => Frame::Resize {
+                rows: u16::from_be_bytes([payload[0], payload[1]]),
+                cols: u16::from_be_bytes([payload[2], payload[3]]),
+            },
+            3 => Frame::Stdout(payload),
+            4 => Frame::Stderr(payload),
+            5 if len == 8 => {
+                let mut code = [0u8; 8];
+                code.copy_from_slice(&payload);
+                Frame::Exit(i64::from_be_bytes(code))
+            }
+            kind => return Err(invalid(format!("unknown frame type {}", kind))),
+        };
+        Ok(Some(frame))
+    }
+}
+
+fn invalid(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn encode(frames: &[Frame]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for frame in frames {
+            frame.write(&mut buf).await.unwrap();
+        }
+        buf
+    }
+
+    fn header(kind: u8, len: usize) -> Vec<u8> {
+        let mut buf = vec![kind];
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+        buf
+    }
+
+    #[tokio::test]
+    async fn frames_round_trip() {
+        let frames = vec![
+            Frame::Stdin(b"ls -la\n".to_vec()),
+            Frame::StdinClosed,
+            Frame::Resize {
+                rows: 40,
+                cols: 120,
+            },
+            Frame::Stdout(b"total 0\r\n".to_vec()),
+            Frame::Stderr(Vec::new()),
+            Frame::Exit(-1),
+            Frame::Exit(137),
+        ];
+        let buf = encode(&frames).await;
+
+        let mut reader = buf.as_slice();
+        for frame in &frames {
+            assert_eq!(
+                Frame::read(&mut reader).await.unwrap().as_ref(),
+                Some(frame)
+            );
+        }
+        // The other side closed the connection
+        assert_eq!(Frame::read(&mut reader).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn frames_up_to_the_limit_are_accepted() {
+        let frame = Frame::Stdout(vec![7; MAX_FRAME]);
+        let buf = encode(std::slice::from_ref(&frame)).await;
+        assert_eq!(Frame::read(&mut buf.as_slice()).await.unwrap(), Some(frame));
+    }
+
+    #[tokio::test]
+    async fn oversized_frames_are_rejected() {
+        let mut buf = header(3, MAX_FRAME + 1);
+        buf.resize(buf.len() + MAX_FRAME + 1, 0);
+        let err = Frame::read(&mut buf.as_slice()).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("too large"), "{}", err);
+
+        // Rejected from the header alone, before a payload is read
+        let err = Frame::read(&mut header(0, u32::MAX as usize).as_slice())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn unknown_frame_types_are_rejected() {
+        for kind in [6, 9, 255] {
+            let mut buf = header(kind, 2);
+            buf.extend_from_slice(b"hi");
+            let err = Frame::read(&mut buf.as_slice()).await.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            assert!(err.to_string().contains("unknown frame type"), "{}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn truncated_frames_are_errors() {
+        let buf = encode(&[Frame::Stdout(b"partial".to_vec())]).await;
+        let err = Frame::read(&mut &buf[..buf.len() - 2]).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,28 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl std::fmt::Display for BuildStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildStatus::Running => write!(f, "Running"),
+            BuildStatus::Succeeded => write!(f, "Succeeded"),
+            BuildStatus::Failed => write!(f, "Failed"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BuildSummary {
+    pub id: u32,
+    pub status: BuildStatus,
+    pub started_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
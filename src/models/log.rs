@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+/// A single line of container output
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LogEntry {
+    pub stream: LogStream,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub message: String,
+}
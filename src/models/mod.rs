@@ -1,4 +1,6 @@
+pub mod build;
 pub mod events;
+pub mod log;
 pub mod project;
 
 pub use project::{ProjectState, ProjectStatus};
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::config::project::ResourceLimits;
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum ProjectState {
@@ -27,19 +29,80 @@ impl std::fmt::Display for ProjectState {
     }
 }
 
+/// Step of a running deploy, rebuild or rollback
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeployPhase {
+    Cloning,
+    Building,
+    /// The release command is running against the new image
+    Releasing,
+    Starting,
+    Switching,
+    /// Volumes are being restored from a backup
+    Restoring,
+}
+
+impl std::fmt::Display for DeployPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeployPhase::Cloning => write!(f, "cloning"),
+            DeployPhase::Building => write!(f, "building"),
+            DeployPhase::Releasing => write!(f, "releasing"),
+            DeployPhase::Starting => write!(f, "starting"),
+            DeployPhase::Switching => write!(f, "switching"),
+            DeployPhase::Restoring => write!(f, "restoring"),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ProjectStatus {
     pub slug: String,
     pub name: String,
     pub state: ProjectState,
+    #[serde(default)]
+    pub phase: Option<DeployPhase>,
     pub container_id: Option<String>,
     pub uptime_secs: Option<u64>,
     pub memory_usage_mb: Option<f64>,
     pub cpu_percent: Option<f64>,
+    /// Processes and threads running in the container
+    #[serde(default)]
+    pub pids: Option<u64>,
+    /// Containers serving the project
+    #[serde(default = "default_replicas")]
+    pub replicas: u32,
+    /// `web` and the other process types, each with its own containers
+    #[serde(default)]
+    pub processes: Vec<ProcessStatus>,
+    #[serde(default)]
+    pub resources: ResourceLimits,
     pub url: Option<String>,
-    pub host_port: u16,
+    /// None for internal projects, which publish no port
+    pub host_port: Option<u16>,
     pub container_port: u16,
     pub network_mode: String,
     pub last_deploy: Option<DateTime<Utc>>,
+    /// Commit of the release currently deployed
+    #[serde(default)]
+    pub commit_sha: Option<String>,
     pub last_error: Option<String>,
 }
+
+/// One process type of a project; usage is summed over its containers
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProcessStatus {
+    pub name: String,
+    /// None when the image's default command runs
+    pub command: Option<String>,
+    pub replicas: u32,
+    pub running: u32,
+    pub port: Option<u16>,
+    pub memory_usage_mb: Option<f64>,
+    pub cpu_percent: Option<f64>,
+}
+
+fn default_replicas() -> u32 {
+    1
+}
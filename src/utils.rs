@@ -2,6 +2,7 @@ use std::net::TcpListener;
 use std::path::Path;
 use tokio::process::Command;
 
+use crate::config::project::GitConfig;
 use crate::error::DockyardError;
 
 /// Generate a URL-safe slug from a project name
@@ -24,6 +25,287 @@ pub fn repo_name(url: &str) -> String {
         .to_string()
 }
 
+/// Split a `KEY=VALUE` argument; the value may itself contain `=`
+pub fn parse_key_value(pair: &str) -> Result<(String, String), DockyardError> {
+    match pair.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(DockyardError::Config(format!(
+            "Expected KEY=VALUE, got '{}'",
+            pair
+        ))),
+    }
+}
+
+/// Parse a size such as `512m`, `1.5g` or `256` (MiB when no unit is given) into MiB
+pub fn parse_size_mb(value: &str) -> Result<u64, DockyardError> {
+    let lower = value.trim().to_lowercase();
+    let number = lower.trim_end_matches('b');
+    let (number, factor) = match number.chars().last() {
+        Some('k') => (&number[..number.len() - 1], 1.0 / 1024.0),
+        Some('m') => (&number[..number.len() - 1], 1.0),
+        Some('g') => (&number[..number.len() - 1], 1024.0),
+        Some('t') => (&number[..number.len() - 1], 1024.0 * 1024.0),
+        _ => (number, 1.0),
+    };
+    match number.parse::<f64>() {
+        Ok(n) if n > 0.0 && (n * factor) >= 1.0 => Ok((n * factor).round() as u64),
+        _ => Err(DockyardError::Config(format!(
+            "Invalid size '{}' (expected e.g. 512m or 2g, at least 1m)",
+            value
+        ))),
+    }
+}
+
+/// Parse a `.env` file: `KEY=VALUE` lines with optional `export ` prefix, `#` comments,
+/// single-quoted literal values and double-quoted values with `\n`, `\"` and `\\` escapes
+pub fn parse_dotenv(content: &str) -> Result<Vec<(String, String)>, DockyardError> {
+    let mut vars = Vec::new();
+    let mut lines = content.lines().enumerate();
+
+    while let Some((n, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, rest)) = line.split_once('=') else {
+            return Err(DockyardError::Config(format!(
+                "line {}: expected KEY=VALUE",
+                n + 1
+            )));
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(DockyardError::Config(format!(
+                "line {}: invalid variable name '{}'",
+                n + 1,
+                key
+            )));
+        }
+
+        let rest = rest.trim_start();
+        let value = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                // Quoted values may span lines until the closing quote
+                let mut raw = rest[1..].to_string();
+                let end = loop {
+                    if let Some(end) = closing_quote(&raw, quote) {
+                        break end;
+                    }
+                    match lines.next() {
+                        Some((_, next)) => {
+                            raw.push('\n');
+                            raw.push_str(next);
+                        }
+                        None => {
+                            return Err(DockyardError::Config(format!(
+                                "line {}: unterminated {} quote",
+                                n + 1,
+                                quote
+                            )))
+                        }
+                    }
+                };
+                let inner = &raw[..end];
+                if quote == '"' {
+                    unescape_double_quoted(inner)
+                } else {
+                    inner.to_string()
+                }
+            }
+            _ => {
+                // Unquoted: a ` #` starts a comment
+                let value = match rest.find(" #") {
+                    Some(i) => &rest[..i],
+                    None => rest,
+                };
+                value.trim_end().to_string()
+            }
+        };
+        vars.push((key.to_string(), value));
+    }
+
+    Ok(vars)
+}
+
+/// Byte offset of the first unescaped `quote` in `s`
+fn closing_quote(s: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '\\' if quote == '"' && !escaped => escaped = true,
+            c if c == quote && !escaped => return Some(i),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn unescape_double_quoted(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Render variables as a `.env` file that [`parse_dotenv`] reads back unchanged
+pub fn format_dotenv<'a>(vars: impl IntoIterator<Item = (&'a String, &'a String)>) -> String {
+    let mut vars: Vec<_> = vars.into_iter().collect();
+    vars.sort();
+
+    let mut out = String::new();
+    for (key, value) in vars {
+        let plain = !value.is_empty()
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_./:@,+".contains(c));
+        if plain {
+            out.push_str(&format!("{}={}\n", key, value));
+        } else {
+            let escaped = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+                .replace('\t', "\\t");
+            out.push_str(&format!("{}=\"{}\"\n", key, escaped));
+        }
+    }
+    out
+}
+
+/// Parse a log time filter: RFC 3339, a date (YYYY-MM-DD) or a relative
+/// duration such as `30s`, `10m`, `2h` or `3d` before now. Returns unix seconds.
+pub fn parse_time_filter(value: &str) -> Result<i64, DockyardError> {
+    let value = value.trim();
+
+    if let Ok(t) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(t.timestamp());
+    }
+    if let Ok(d) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        if let Some(t) = d.and_hms_opt(0, 0, 0) {
+            return Ok(t.and_utc().timestamp());
+        }
+    }
+
+    let invalid = || DockyardError::Config(format!("Invalid time: '{}'", value));
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let secs = match unit {
+        "s" => amount,
+        "m" => amount * 60,
+        "h" => amount * 3600,
+        "d" => amount * 86400,
+        _ => return Err(invalid()),
+    };
+
+    Ok(chrono::Utc::now().timestamp() - secs)
+}
+
+/// Parse a cron expression: the usual 5 fields (minute to day of week, 0 or 7 being
+/// Sunday), or 6-7 fields starting with seconds, where day of week runs from 1 (Sunday)
+/// to 7 as in the `cron` crate
+pub fn parse_cron(expr: &str) -> Result<cron::Schedule, DockyardError> {
+    let expr = expr.trim();
+    let invalid = |e: String| DockyardError::Config(format!("Invalid schedule '{}': {}", expr, e));
+    let fields: Vec<&str> = expr.split_whitespace().collect();
+    let full = match fields.as_slice() {
+        [minute, hour, day, month, weekday] => format!(
+            "0 {} {} {} {} {}",
+            minute,
+            hour,
+            day,
+            month,
+            unix_weekdays(weekday).map_err(invalid)?
+        ),
+        _ => expr.to_string(),
+    };
+    full.parse()
+        .map_err(|e: cron::error::Error| invalid(e.to_string()))
+}
+
+/// Translate a Unix day-of-week field (0-7, 0 and 7 being Sunday) to the `cron` crate's
+/// numbering (1-7 from Sunday), as an explicit list of days
+fn unix_weekdays(field: &str) -> Result<String, String> {
+    const NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+    let day = |value: &str| -> Result<u32, String> {
+        let lower = value.to_lowercase();
+        if let Some(index) = NAMES.iter().position(|n| lower.starts_with(n)) {
+            return Ok(index as u32);
+        }
+        match value.parse::<u32>() {
+            Ok(day) if day <= 7 => Ok(day),
+            _ => Err(format!("'{}' is not a day of the week (0-7)", value)),
+        }
+    };
+
+    if field == "*" || field == "?" {
+        return Ok("*".to_string());
+    }
+    let mut days = [false; 7];
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("'{}' is not a valid step", step)),
+            },
+            None => (item, 1),
+        };
+        let (first, last) = match range {
+            "*" | "?" => (0, 6),
+            _ => match range.split_once('-') {
+                Some((first, last)) => (day(first)?, day(last)?),
+                // `5/2` runs from 5 to the end of the week
+                None if item.contains('/') => (day(range)?, 6),
+                None => (day(range)?, day(range)?),
+            },
+        };
+        if first > last {
+            return Err(format!("'{}' is not a valid range", range));
+        }
+        for weekday in (first..=last).step_by(step as usize) {
+            days[weekday as usize % 7] = true;
+        }
+    }
+    Ok(days
+        .iter()
+        .enumerate()
+        .filter(|(_, on)| **on)
+        .map(|(index, _)| (index + 1).to_string())
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
+/// Total size of the files under a directory, without following symlinks
+pub fn dir_size(path: &Path) -> Option<u64> {
+    let meta = std::fs::symlink_metadata(path).ok()?;
+    if !meta.is_dir() {
+        return Some(meta.len());
+    }
+    let mut total = 0;
+    for entry in std::fs::read_dir(path).ok()?.flatten() {
+        total += dir_size(&entry.path()).unwrap_or(0);
+    }
+    Some(total)
+}
+
 /// Find an available port in the ephemeral range
 pub fn find_available_port() -> Result<u16, DockyardError> {
     let listener = TcpListener::bind("127.0.0.1:0")
@@ -32,45 +314,235 @@ pub fn find_available_port() -> Result<u16, DockyardError> {
     Ok(port)
 }
 
-/// Clone a git repository to a destination path
-pub async fn git_clone(repo_url: &str, dest: &Path, branch: &str) -> Result<(), DockyardError> {
-    let output = Command::new("git")
-        .args([
+/// Bring the checkout at `repo_path` to the latest commit of `branch` on `repo_url`, or to
+/// `revision` when given. Force-pushes, a changed branch or remote and local changes are
+/// all overwritten; a corrupt checkout is cloned again. Fetch and checkout failures are
+/// returned as they are, keeping the checkout. Returns the SHA checked out.
+pub async fn git_sync(
+    repo_url: &str,
+    repo_path: &Path,
+    branch: &str,
+    revision: Option<&str>,
+    options: &GitConfig,
+) -> Result<String, DockyardError> {
+    let usable = repo_path.join(".git").exists() && !is_corrupt(repo_path).await;
+    if usable {
+        reset_to_branch(repo_url, repo_path, branch).await?;
+    } else {
+        if repo_path.exists() {
+            tracing::warn!(
+                "{} is not a usable checkout, cloning again",
+                repo_path.display()
+            );
+            std::fs::remove_dir_all(repo_path)?;
+        }
+        git_clone(repo_url, repo_path, branch).await?;
+    }
+
+    if let Some(revision) = revision {
+        git_checkout_revision(repo_path, branch, revision).await?;
+    }
+    sync_submodules(repo_path, options.submodules).await?;
+    if options.lfs {
+        git(repo_path, &["lfs", "install", "--local"], "git lfs install").await?;
+        git(repo_path, &["lfs", "pull"], "git lfs pull").await?;
+    }
+    git_head_sha(repo_path).await
+}
+
+/// Clone a single branch, without history, to a destination path
+async fn git_clone(repo_url: &str, dest: &Path, branch: &str) -> Result<(), DockyardError> {
+    let parent = dest.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(parent)?;
+    let dest = dest.to_string_lossy();
+    git(
+        parent,
+        &[
             "clone",
             "--branch",
             branch,
             "--single-branch",
             "--depth",
             "1",
+            "--",
             repo_url,
-        ])
-        .arg(dest)
-        .output()
+            &dest,
+        ],
+        "git clone",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Whether git can no longer read the checkout's repository or objects
+async fn is_corrupt(repo_path: &Path) -> bool {
+    git(repo_path, &["rev-parse", "--git-dir"], "git rev-parse")
         .await
-        .map_err(|e| DockyardError::Git(e.to_string()))?;
+        .is_err()
+        || git(repo_path, &["fsck", "--connectivity-only"], "git fsck")
+            .await
+            .is_err()
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(DockyardError::Git(format!("git clone failed: {}", stderr)));
-    }
+/// Fetch the tip of `branch` and make the local branch, index and working tree match it
+async fn reset_to_branch(
+    repo_url: &str,
+    repo_path: &Path,
+    branch: &str,
+) -> Result<(), DockyardError> {
+    let remote_ref = format!("refs/remotes/origin/{}", branch);
+    let refspec = format!("+refs/heads/{}:{}", branch, remote_ref);
+    git(
+        repo_path,
+        &["remote", "set-url", "--", "origin", repo_url],
+        "git remote",
+    )
+    .await?;
+    git(
+        repo_path,
+        &["fetch", "--depth", "1", "--force", "origin", &refspec],
+        "git fetch",
+    )
+    .await?;
+    // Resets the branch, index and working tree like `reset --hard`, and switches to the
+    // branch when it changed
+    git(
+        repo_path,
+        &["checkout", "--force", "-B", branch, &remote_ref],
+        "git checkout",
+    )
+    .await?;
+    git(repo_path, &["clean", "-ffdx"], "git clean").await?;
+    Ok(())
+}
 
+/// Check out the submodules recorded in the current commit, or drop them when disabled
+async fn sync_submodules(repo_path: &Path, enabled: bool) -> Result<(), DockyardError> {
+    if !repo_path.join(".gitmodules").exists() {
+        return Ok(());
+    }
+    if !enabled {
+        git(
+            repo_path,
+            &["submodule", "deinit", "--all", "--force"],
+            "git submodule deinit",
+        )
+        .await?;
+        return Ok(());
+    }
+    git(
+        repo_path,
+        &["submodule", "sync", "--recursive"],
+        "git submodule sync",
+    )
+    .await?;
+    git(
+        repo_path,
+        &["submodule", "update", "--init", "--recursive", "--force"],
+        "git submodule update",
+    )
+    .await?;
     Ok(())
 }
 
-/// Pull latest changes in a git repository
-pub async fn git_pull(repo_path: &Path, branch: &str) -> Result<String, DockyardError> {
+/// Fetch a commit SHA, tag or branch from origin and check it out (detached); returns the
+/// SHA checked out
+async fn git_checkout_revision(
+    repo_path: &Path,
+    branch: &str,
+    revision: &str,
+) -> Result<String, DockyardError> {
+    let fetched = git(
+        repo_path,
+        &[
+            "fetch",
+            "--depth",
+            "1",
+            "--end-of-options",
+            "origin",
+            revision,
+        ],
+        "git fetch",
+    )
+    .await;
+    let target = match fetched {
+        Ok(_) => "FETCH_HEAD^{commit}".to_string(),
+        // Servers that refuse to send a commit by SHA, and short SHAs, need the
+        // branch's full history
+        Err(_) => {
+            let mut args = vec!["fetch", "--tags", "--end-of-options", "origin", branch];
+            if repo_path.join(".git").join("shallow").exists() {
+                args.insert(1, "--unshallow");
+            }
+            git(repo_path, &args, "git fetch").await?;
+            format!("{}^{{commit}}", revision)
+        }
+    };
+    // `checkout` has no `--end-of-options`, so it only ever sees the resolved SHA
+    let commit = git(
+        repo_path,
+        &["rev-parse", "--verify", "--end-of-options", &target],
+        "git rev-parse",
+    )
+    .await?;
+    git(
+        repo_path,
+        &["checkout", "--force", "--detach", &commit],
+        "git checkout",
+    )
+    .await?;
+
+    let sha = git_head_sha(repo_path).await?;
+    // A branch or tag named like a SHA must not stand in for the commit
+    if is_commit_sha(revision) && !sha.starts_with(&revision.to_lowercase()) {
+        return Err(DockyardError::Git(format!(
+            "{} resolved to commit {}, not to the requested commit",
+            revision, sha
+        )));
+    }
+    Ok(sha)
+}
+
+/// Whether `value` looks like a full or abbreviated commit SHA
+pub fn is_commit_sha(value: &str) -> bool {
+    (7..=40).contains(&value.len()) && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Whether `value` can be passed to git as a branch or tag name
+pub fn is_valid_ref(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with('-')
+        && !value.contains("..")
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c))
+}
+
+/// Run git in `dir`; `what` names the command in errors. LFS files are left as pointers
+/// unless pulled explicitly, and git never waits for credentials.
+async fn git(dir: &Path, args: &[&str], what: &str) -> Result<String, DockyardError> {
     let output = Command::new("git")
-        .args(["pull", "origin", branch])
-        .current_dir(repo_path)
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_LFS_SKIP_SMUDGE", "1")
+        .env("GIT_TERMINAL_PROMPT", "0")
         .output()
         .await
         .map_err(|e| DockyardError::Git(e.to_string()))?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(DockyardError::Git(format!("git pull failed: {}", stderr)));
+        return Err(DockyardError::Git(format!(
+            "{} failed: {}",
+            what,
+            stderr.trim()
+        )));
     }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
 
+/// Get the commit SHA currently checked out in a git repository
+pub async fn git_head_sha(repo_path: &Path) -> Result<String, DockyardError> {
     let sha_output = Command::new("git")
         .args(["rev-parse", "HEAD"])
         .current_dir(repo_path)
@@ -83,7 +555,165 @@ pub async fn git_pull(repo_path: &Path, branch: &str) -> Result<String, Dockyard
         .to_string())
 }
 
+/// Generate a random password made of hex digits, safe to embed in connection URLs
+pub fn generate_password() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
 /// Generate a random webhook secret
 pub fn generate_webhook_secret() -> String {
     uuid::Uuid::new_v4().to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, TimeZone, Utc, Weekday};
+
+    /// Weekdays of the next runs after Sunday 2024-06-02
+    fn next_weekdays(expr: &str, count: usize) -> Vec<Weekday> {
+        let start = Utc.with_ymd_and_hms(2024, 6, 2, 12, 0, 0).unwrap();
+        parse_cron(expr)
+            .unwrap()
+            .after(&start)
+            .take(count)
+            .map(|t| t.weekday())
+            .collect()
+    }
+
+    #[test]
+    fn weekday_range_is_monday_to_friday() {
+        use Weekday::*;
+        assert_eq!(
+            next_weekdays("0 3 * * 1-5", 7),
+            [Mon, Tue, Wed, Thu, Fri, Mon, Tue]
+        );
+        assert_eq!(
+            next_weekdays("0 3 * * mon-fri", 5),
+            [Mon, Tue, Wed, Thu, Fri]
+        );
+    }
+
+    #[test]
+    fn zero_and_seven_are_sunday() {
+        assert_eq!(next_weekdays("0 0 * * 0", 2), [Weekday::Sun, Weekday::Sun]);
+        assert_eq!(next_weekdays("0 0 * * 7", 2), [Weekday::Sun, Weekday::Sun]);
+        assert_eq!(next_weekdays("0 0 * * sun", 1), [Weekday::Sun]);
+    }
+
+    #[test]
+    fn ranges_lists_and_steps() {
+        use Weekday::*;
+        assert_eq!(next_weekdays("0 0 * * 5-7", 3), [Fri, Sat, Sun]);
+        assert_eq!(next_weekdays("0 0 * * 1,3", 3), [Mon, Wed, Mon]);
+        assert_eq!(next_weekdays("0 0 * * */2", 4), [Tue, Thu, Sat, Sun]);
+        assert_eq!(next_weekdays("0 0 * * *", 2), [Mon, Tue]);
+    }
+
+    #[test]
+    fn invalid_weekdays_are_rejected() {
+        assert!(parse_cron("0 0 * * 8").is_err());
+        assert!(parse_cron("0 0 * * 5-1").is_err());
+        assert!(parse_cron("0 0 * * 1/0").is_err());
+    }
+
+    #[test]
+    fn six_fields_keep_the_crate_numbering() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 2, 12, 0, 0).unwrap();
+        let next = parse_cron("0 0 0 * * 1")
+            .unwrap()
+            .after(&start)
+            .next()
+            .unwrap();
+        assert_eq!(next.weekday(), Weekday::Sun);
+    }
+
+    fn dotenv(content: &str) -> Vec<(String, String)> {
+        parse_dotenv(content).unwrap()
+    }
+
+    fn pairs(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn dotenv_reads_quotes_exports_and_comments() {
+        let content = "\
+# database
+export DATABASE_URL=postgres://app@db/app?sslmode=disable
+SECRET='literal $HOME \\n'
+GREETING=\"say \\\"hi\\\"\\nthen leave\"
+TOKEN=abc=def==  # trailing comment
+EMPTY=
+QUOTED_EMPTY=\"\"
+HASH=a#b
+MULTI=\"one
+two\"
+";
+        assert_eq!(
+            dotenv(content),
+            pairs(&[
+                ("DATABASE_URL", "postgres://app@db/app?sslmode=disable"),
+                ("SECRET", "literal $HOME \\n"),
+                ("GREETING", "say \"hi\"\nthen leave"),
+                ("TOKEN", "abc=def=="),
+                ("EMPTY", ""),
+                ("QUOTED_EMPTY", ""),
+                ("HASH", "a#b"),
+                ("MULTI", "one\ntwo"),
+            ])
+        );
+    }
+
+    #[test]
+    fn dotenv_rejects_malformed_lines() {
+        assert!(parse_dotenv("NO_VALUE\n").is_err());
+        assert!(parse_dotenv("=value\n").is_err());
+        assert!(parse_dotenv("TWO WORDS=value\n").is_err());
+        assert!(parse_dotenv("OPEN=\"never closed\n").is_err());
+    }
+
+    #[test]
+    fn dotenv_round_trips() {
+        let vars: std::collections::BTreeMap<String, String> = pairs(&[
+            ("PLAIN", "value-1.2_3"),
+            ("URL", "https://example.com/?a=b&c=d"),
+            ("SPACES", "  padded value  "),
+            ("QUOTES", "'single' and \"double\""),
+            ("BACKSLASH", "C:\\path\\n"),
+            ("LINES", "one\ntwo\r\n\tthree"),
+            ("COMMENT", "value # not a comment"),
+            ("EMPTY", ""),
+        ])
+        .into_iter()
+        .collect();
+
+        let content = format_dotenv(&vars);
+        let parsed: std::collections::BTreeMap<String, String> =
+            dotenv(&content).into_iter().collect();
+        assert_eq!(parsed, vars);
+    }
+
+    #[tokio::test]
+    async fn option_like_repository_urls_are_not_run() {
+        let dir = std::env::temp_dir().join(format!("dockyard-clone-{}", uuid::Uuid::new_v4()));
+        let marker = dir.join("pwned");
+        let url = format!("--upload-pack=touch {}", marker.display());
+        // Read as an option, the URL would leave the destination to be cloned from
+        let dest = dir.join("repo.git");
+        std::fs::create_dir_all(&dest).unwrap();
+        git(&dest, &["init", "--bare", "--quiet"], "git init")
+            .await
+            .unwrap();
+
+        assert!(git_clone(&url, &dest, "main").await.is_err());
+        assert!(!marker.exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}